@@ -0,0 +1,106 @@
+//! Compara `Mutex<Calculator>` contra `RwLock<Calculator>` bajo una carga de lectura:
+//! 8 hilos que hacen 90% de `GET` (leer la acumulación) y 10% de `OP + 1`.
+//!
+//! Usa la misma `Calculator` que el servidor, compilada desde sus fuentes.
+//!
+//! ```sh
+//! cargo bench --bench lock_comparison
+//! ```
+use std::{
+    hint::black_box,
+    sync::{Arc, Barrier, Mutex, RwLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+
+// El benchmark usa solo una parte de la API y se compila sin los `#[test]` del módulo.
+#[allow(dead_code, unused_imports)]
+#[path = "../src/bin/server/calculator_error.rs"]
+mod calculator_error;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/bin/server/operation.rs"]
+mod operation;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/bin/server/calculator.rs"]
+mod calculator;
+
+use calculator::Calculator;
+use operation::Operation;
+
+/// Hilos que comparten la calculadora.
+const THREADS: usize = 8;
+/// Operaciones que hace cada hilo por iteración.
+const OPS_PER_THREAD: usize = 1000;
+/// Una de cada `WRITE_EVERY` operaciones es una escritura; el resto son lecturas.
+const WRITE_EVERY: usize = 10;
+
+/// Calculadora compartida detrás de algún lock.
+trait SharedLock: Send + Sync + 'static {
+    fn get(&self) -> i64;
+    fn add_one(&self);
+}
+
+impl SharedLock for Mutex<Calculator> {
+    fn get(&self) -> i64 {
+        self.lock().unwrap().accumulation()
+    }
+
+    fn add_one(&self) {
+        self.lock().unwrap().apply(Operation::Add(1)).unwrap();
+    }
+}
+
+impl SharedLock for RwLock<Calculator> {
+    fn get(&self) -> i64 {
+        self.read().unwrap().accumulation()
+    }
+
+    fn add_one(&self) {
+        self.write().unwrap().apply(Operation::Add(1)).unwrap();
+    }
+}
+
+/// Corre `iters` iteraciones de la carga en `THREADS` hilos que arrancan a la vez con una
+/// `Barrier`. Devuelve el tiempo desde que se libera la barrera hasta que termina el último hilo.
+fn run_workload<L: SharedLock>(calculator: Arc<L>, iters: u64) -> Duration {
+    let barrier = Arc::new(Barrier::new(THREADS + 1));
+    let handles: Vec<_> = (0..THREADS)
+        .map(|_| {
+            let calculator = Arc::clone(&calculator);
+            let barrier = Arc::clone(&barrier);
+            thread::spawn(move || {
+                barrier.wait();
+                for i in 0..iters as usize * OPS_PER_THREAD {
+                    if i % WRITE_EVERY == 0 {
+                        calculator.add_one();
+                    } else {
+                        black_box(calculator.get());
+                    }
+                }
+            })
+        })
+        .collect();
+    barrier.wait();
+    let start = Instant::now();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    start.elapsed()
+}
+
+fn lock_comparison(c: &mut Criterion) {
+    let mut group = c.benchmark_group("read_heavy_8_threads");
+    group.throughput(Throughput::Elements((THREADS * OPS_PER_THREAD) as u64));
+    group.bench_function("mutex", |b| {
+        b.iter_custom(|iters| run_workload(Arc::new(Mutex::new(Calculator::new())), iters))
+    });
+    group.bench_function("rwlock", |b| {
+        b.iter_custom(|iters| run_workload(Arc::new(RwLock::new(Calculator::new())), iters))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, lock_comparison);
+criterion_main!(benches);
@@ -0,0 +1,48 @@
+//! Costo de serializar mensajes con `Protocol::to_bytes` (un `Vec<u8>` nuevo por mensaje)
+//! contra `Protocol::to_bytes_copy_free` (`bytes::Bytes`, sin reservar memoria para los
+//! mensajes fijos).
+//!
+//! ```sh
+//! cargo bench --bench protocol_bytes --features bytes
+//! ```
+use std::hint::black_box;
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use distributed_calculator::protocol::Protocol;
+
+/// Cantidad de mensajes serializados por iteración.
+const ITERATIONS: u64 = 100_000;
+
+/// Respuestas que el servidor envía con más frecuencia, fijas y con contenido variable.
+fn messages() -> Vec<Protocol> {
+    vec![
+        Protocol::Ok,
+        Protocol::Get,
+        Protocol::Value("42".to_string()),
+        Protocol::ErrorOperation("division by zero".to_string()),
+    ]
+}
+
+fn serialization(c: &mut Criterion) {
+    let messages = messages();
+    let mut group = c.benchmark_group("protocol_bytes");
+    group.throughput(Throughput::Elements(ITERATIONS));
+    group.bench_function("to_bytes", |b| {
+        b.iter(|| {
+            for i in 0..ITERATIONS as usize {
+                black_box(messages[i % messages.len()].to_bytes());
+            }
+        })
+    });
+    group.bench_function("to_bytes_copy_free", |b| {
+        b.iter(|| {
+            for i in 0..ITERATIONS as usize {
+                black_box(messages[i % messages.len()].to_bytes_copy_free());
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, serialization);
+criterion_main!(benches);
@@ -0,0 +1,131 @@
+//! Throughput del servidor medido sobre conexiones TCP reales por loopback.
+//!
+//! Levanta el binario `server` en un puerto libre y mide round-trips de `OP + 1`:
+//! un cliente esperando cada respuesta, 10 clientes concurrentes y un cliente que envía
+//! lotes sin esperar respuesta (pipelining).
+//!
+//! ```sh
+//! cargo bench --bench throughput
+//! ```
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    process::{Child, Command},
+    thread,
+    time::{Duration, Instant},
+};
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use distributed_calculator::{client::CalculatorClient, operation::Operation};
+
+/// Cantidad de mensajes por lote en el benchmark de pipelining.
+const BATCH_SIZE: u64 = 100;
+/// Cantidad de clientes del benchmark concurrente.
+const CLIENTS: u64 = 10;
+
+/// Proceso del servidor; se mata al descartarlo.
+struct ServerProcess {
+    child: Child,
+    addr: SocketAddr,
+}
+
+impl ServerProcess {
+    /// Levanta el servidor en un puerto libre y espera a que acepte conexiones.
+    fn start() -> Self {
+        let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let logs = std::env::temp_dir().join(format!("calc_bench_{}", std::process::id()));
+        std::fs::create_dir_all(&logs).unwrap();
+        let child = Command::new(env!("CARGO_BIN_EXE_server"))
+            .arg(addr.to_string())
+            .env("CALC_LOG_FILE", logs.join("server.log"))
+            .env("CALC_AUDIT_FILE", logs.join("audit.log"))
+            .spawn()
+            .expect("failed to start server");
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while TcpStream::connect(addr).is_err() {
+            assert!(Instant::now() < deadline, "server did not start");
+            thread::sleep(Duration::from_millis(20));
+        }
+        Self { child, addr }
+    }
+}
+
+impl Drop for ServerProcess {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Un cliente que espera la respuesta de cada operación antes de enviar la siguiente.
+fn single_client(c: &mut Criterion, server: &ServerProcess) {
+    let mut client = CalculatorClient::connect(server.addr).unwrap();
+    let mut group = c.benchmark_group("single_client");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("op_round_trip", |b| b.iter(|| client.apply(Operation::Add(1)).unwrap()));
+    group.finish();
+}
+
+/// `CLIENTS` clientes con su propia conexión, cada uno esperando sus respuestas.
+/// Se mide el tiempo hasta que terminan todos.
+fn concurrent_clients(c: &mut Criterion, server: &ServerProcess) {
+    let addr = server.addr;
+    let mut group = c.benchmark_group("concurrent_clients");
+    group.throughput(Throughput::Elements(CLIENTS));
+    group.bench_function("10_clients_op_round_trip", |b| {
+        b.iter_custom(|iters| {
+            let clients: Vec<_> = (0..CLIENTS).map(|_| CalculatorClient::connect(addr).unwrap()).collect();
+            let start = Instant::now();
+            let handles: Vec<_> = clients
+                .into_iter()
+                .map(|mut client| {
+                    thread::spawn(move || {
+                        for _ in 0..iters {
+                            client.apply(Operation::Add(1)).unwrap();
+                        }
+                    })
+                })
+                .collect();
+            for handle in handles {
+                handle.join().unwrap();
+            }
+            start.elapsed()
+        })
+    });
+    group.finish();
+}
+
+/// Un cliente que envía `BATCH_SIZE` operaciones seguidas y después lee todas las respuestas.
+fn batch(c: &mut Criterion, server: &ServerProcess) {
+    let stream = TcpStream::connect(server.addr).unwrap();
+    stream.set_nodelay(true).unwrap();
+    let mut writer = stream.try_clone().unwrap();
+    let mut reader = BufReader::new(stream);
+    let batch = b"OP + 1\n".repeat(BATCH_SIZE as usize);
+    let mut line = String::new();
+    // Se descarta el `HELLO` con el que el servidor abre la conexión.
+    reader.read_line(&mut line).unwrap();
+
+    let mut group = c.benchmark_group("batch");
+    group.throughput(Throughput::Elements(BATCH_SIZE));
+    group.bench_function("pipelined_ops", |b| {
+        b.iter(|| {
+            writer.write_all(&batch).unwrap();
+            for _ in 0..BATCH_SIZE {
+                line.clear();
+                reader.read_line(&mut line).unwrap();
+            }
+        })
+    });
+    group.finish();
+}
+
+fn throughput(c: &mut Criterion) {
+    let server = ServerProcess::start();
+    single_client(c, &server);
+    concurrent_clients(c, &server);
+    batch(c, &server);
+}
+
+criterion_group!(benches, throughput);
+criterion_main!(benches);
@@ -0,0 +1,13 @@
+//! `Operation::from_str` debe devolver un error, nunca entrar en pánico, ante cualquier texto.
+#![no_main]
+
+use std::str::FromStr;
+
+use distributed_calculator::operation::Operation;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: &str| {
+    if let Ok(operation) = Operation::from_str(input) {
+        let _ = operation.to_string();
+    }
+});
@@ -0,0 +1,12 @@
+//! Cualquier secuencia de bytes que llegue por la red debe parsearse sin entrar en pánico.
+#![no_main]
+
+use distributed_calculator::protocol::Protocol;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(result) = Protocol::from_bytes(data) {
+        let _ = format!("{}", result);
+        let _ = result.to_bytes();
+    }
+});
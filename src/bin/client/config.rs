@@ -0,0 +1,243 @@
+//! Opciones del cliente que se indican con flags después de la dirección y el archivo.
+
+use std::{net::SocketAddr, path::PathBuf, time::Duration};
+
+use crate::{client_error::ClientError, output::OutputFormat};
+
+/// Opciones con las que corre el cliente.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ClientConfig {
+    /// Cantidad de mensajes que se envían antes de leer sus respuestas (`--pipeline <N>`).
+    /// Con 1 se espera la respuesta de cada mensaje antes de enviar el siguiente.
+    pub pipeline_depth: usize,
+    /// Formato en el que se imprime el resultado (`--format <plain|json|csv>`).
+    pub format: OutputFormat,
+    /// Si es `true`, mide la latencia de cada mensaje e imprime un resumen al final (`--timing`).
+    pub timing: bool,
+    /// Directorio cuyos archivos `*.calc` se procesan en lugar de un único archivo (`--directory <path>`).
+    pub directory: Option<PathBuf>,
+    /// Si es `true`, imprime el nombre de cada archivo antes de enviar sus operaciones (`--verbose`).
+    pub verbose: bool,
+    /// Si es `true`, corta ante el primer error del servidor en lugar de seguir (`--strict`).
+    pub strict: bool,
+    /// Si es `true`, solo valida el archivo sin conectarse al servidor (`--dry-run`).
+    pub dry_run: bool,
+    /// Si es `true`, imprime cuántas operaciones se enviaron y cuántas fallaron (`--count`).
+    pub count: bool,
+    /// Si es `true`, no imprime el resultado; con `--count` solo se imprime la cuenta (`--quiet`).
+    pub quiet: bool,
+    /// Si es `false`, no se envía el `GET` final ni se imprime el valor (`--no-get`).
+    pub send_final_get: bool,
+    /// Archivo donde se escribe el valor final en lugar de imprimir el resultado (`--output <path>`).
+    pub output: Option<PathBuf>,
+    /// Cantidad de veces que se reenvía una operación que falló por un error transitorio del
+    /// servidor antes de darla por fallida (`--retry-ops <N>`). Con 0 no se reintenta.
+    pub retry_ops: usize,
+    /// Cantidad de conexiones por las que se reparten las líneas del archivo, enviadas a la vez
+    /// desde hilos separados (`--parallel <N>`). Implica `--no-get`. Con `None` se usa una sola.
+    pub parallel: Option<usize>,
+    /// Tiempo máximo que se espera al conectarse y en cada lectura o escritura (`--timeout <SECS>`).
+    /// Si es `None`, se espera indefinidamente.
+    pub timeout: Option<Duration>,
+    /// Réplicas a las que también se envía cada línea, además de la dirección del servidor
+    /// (`--replicas <IP:PORT,...>`). Si está vacío, se usa un solo servidor.
+    pub replicas: Vec<SocketAddr>,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            pipeline_depth: 1,
+            format: OutputFormat::Plain,
+            timing: false,
+            directory: None,
+            verbose: false,
+            strict: false,
+            dry_run: false,
+            count: false,
+            quiet: false,
+            send_final_get: true,
+            output: None,
+            retry_ops: 0,
+            parallel: None,
+            timeout: None,
+            replicas: Vec::new(),
+        }
+    }
+}
+
+/// Parsea los flags opcionales del cliente.
+/// Recibe los argumentos que siguen a la dirección y al archivo de entrada (o a la dirección,
+/// si se usa `--directory`).
+///
+/// #Errores
+/// 'MissingArgument' si un flag no tiene su valor.
+/// 'InvalidArgument' si el flag es desconocido o su valor no es válido.
+pub fn parse_options<I: IntoIterator<Item = String>>(options: I) -> Result<ClientConfig, ClientError> {
+    let mut config = ClientConfig::default();
+    let mut iter = options.into_iter();
+    while let Some(option) = iter.next() {
+        match option.as_str() {
+            "--pipeline" => {
+                let value = iter.next().ok_or(ClientError::MissingArgument)?;
+                config.pipeline_depth = match value.parse::<usize>() {
+                    Ok(depth) if depth > 0 => depth,
+                    _ => return Err(ClientError::InvalidArgument),
+                };
+            }
+            "--format" => {
+                config.format = iter.next().ok_or(ClientError::MissingArgument)?.parse()?;
+            }
+            "--timing" => config.timing = true,
+            "--directory" => {
+                config.directory = Some(PathBuf::from(iter.next().ok_or(ClientError::MissingArgument)?));
+            }
+            "--verbose" => config.verbose = true,
+            "--strict" => config.strict = true,
+            "--dry-run" => config.dry_run = true,
+            "--no-get" => config.send_final_get = false,
+            "--count" => config.count = true,
+            "--quiet" => config.quiet = true,
+            "--output" => {
+                config.output = Some(PathBuf::from(iter.next().ok_or(ClientError::MissingArgument)?));
+            }
+            "--retry-ops" => {
+                let value = iter.next().ok_or(ClientError::MissingArgument)?;
+                config.retry_ops = value.parse().map_err(|_| ClientError::InvalidArgument)?;
+            }
+            "--parallel" => {
+                let value = iter.next().ok_or(ClientError::MissingArgument)?;
+                config.parallel = match value.parse::<usize>() {
+                    Ok(parallel) if parallel > 0 => Some(parallel),
+                    _ => return Err(ClientError::InvalidArgument),
+                };
+                // Las partes se envían sin orden entre sí, así que el valor final no significa nada.
+                config.send_final_get = false;
+            }
+            "--timeout" => {
+                let value = iter.next().ok_or(ClientError::MissingArgument)?;
+                config.timeout = match value.parse::<u64>() {
+                    Ok(secs) if secs > 0 => Some(Duration::from_secs(secs)),
+                    _ => return Err(ClientError::InvalidArgument),
+                };
+            }
+            "--replicas" => {
+                let value = iter.next().ok_or(ClientError::MissingArgument)?;
+                config.replicas = value
+                    .split(',')
+                    .map(|addr| addr.parse().map_err(|_| ClientError::InvalidArgument))
+                    .collect::<Result<_, _>>()?;
+            }
+            _ => return Err(ClientError::InvalidArgument),
+        }
+    }
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        client_error::ClientError,
+        config::{ClientConfig, parse_options},
+        output::OutputFormat,
+    };
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn no_options_uses_defaults() {
+        assert_eq!(parse_options(args(&[])).unwrap(), ClientConfig::default());
+    }
+
+    #[test]
+    fn pipeline_option_sets_depth() {
+        let config = parse_options(args(&["--pipeline", "16"])).unwrap();
+        assert_eq!(config.pipeline_depth, 16);
+    }
+
+    #[test]
+    fn pipeline_option_rejects_invalid_values() {
+        assert!(matches!(parse_options(args(&["--pipeline"])), Err(ClientError::MissingArgument)));
+        assert!(matches!(parse_options(args(&["--pipeline", "0"])), Err(ClientError::InvalidArgument)));
+        assert!(matches!(parse_options(args(&["--pipeline", "x"])), Err(ClientError::InvalidArgument)));
+        assert!(matches!(parse_options(args(&["--unknown"])), Err(ClientError::InvalidArgument)));
+    }
+
+    #[test]
+    fn format_option_sets_output_format() {
+        assert_eq!(parse_options(args(&["--format", "json"])).unwrap().format, OutputFormat::Json);
+        assert!(matches!(parse_options(args(&["--format"])), Err(ClientError::MissingArgument)));
+        assert!(matches!(parse_options(args(&["--format", "xml"])), Err(ClientError::InvalidArgument)));
+    }
+
+    #[test]
+    fn timing_option_enables_timing() {
+        assert!(parse_options(args(&["--timing"])).unwrap().timing);
+        assert!(!parse_options(args(&[])).unwrap().timing);
+    }
+
+    #[test]
+    fn directory_option_sets_path() {
+        let config = parse_options(args(&["--directory", "inputs", "--verbose"])).unwrap();
+        assert_eq!(config.directory.as_deref(), Some(std::path::Path::new("inputs")));
+        assert!(config.verbose);
+        assert!(matches!(parse_options(args(&["--directory"])), Err(ClientError::MissingArgument)));
+    }
+
+    #[test]
+    fn output_option_sets_path() {
+        let config = parse_options(args(&["--output", "value.txt"])).unwrap();
+        assert_eq!(config.output.as_deref(), Some(std::path::Path::new("value.txt")));
+        assert!(matches!(parse_options(args(&["--output"])), Err(ClientError::MissingArgument)));
+    }
+
+    #[test]
+    fn no_get_option_disables_the_final_get() {
+        assert!(!parse_options(args(&["--no-get"])).unwrap().send_final_get);
+        assert!(parse_options(args(&[])).unwrap().send_final_get);
+    }
+
+    #[test]
+    fn count_and_quiet_options() {
+        let config = parse_options(args(&["--count", "--quiet"])).unwrap();
+        assert!(config.count);
+        assert!(config.quiet);
+        assert!(!parse_options(args(&[])).unwrap().count);
+    }
+
+    #[test]
+    fn retry_ops_option_sets_the_retries() {
+        assert_eq!(parse_options(args(&["--retry-ops", "3"])).unwrap().retry_ops, 3);
+        assert_eq!(parse_options(args(&[])).unwrap().retry_ops, 0);
+        assert!(matches!(parse_options(args(&["--retry-ops"])), Err(ClientError::MissingArgument)));
+        assert!(matches!(parse_options(args(&["--retry-ops", "-1"])), Err(ClientError::InvalidArgument)));
+    }
+
+    #[test]
+    fn parallel_option_sets_connections_and_disables_the_final_get() {
+        let config = parse_options(args(&["--parallel", "4"])).unwrap();
+        assert_eq!(config.parallel, Some(4));
+        assert!(!config.send_final_get);
+        assert!(matches!(parse_options(args(&["--parallel"])), Err(ClientError::MissingArgument)));
+        assert!(matches!(parse_options(args(&["--parallel", "0"])), Err(ClientError::InvalidArgument)));
+    }
+
+    #[test]
+    fn timeout_option_sets_the_duration() {
+        let config = parse_options(args(&["--timeout", "5"])).unwrap();
+        assert_eq!(config.timeout, Some(std::time::Duration::from_secs(5)));
+        assert!(matches!(parse_options(args(&["--timeout"])), Err(ClientError::MissingArgument)));
+        assert!(matches!(parse_options(args(&["--timeout", "0"])), Err(ClientError::InvalidArgument)));
+    }
+
+    #[test]
+    fn replicas_option_sets_the_addresses() {
+        let config = parse_options(args(&["--replicas", "127.0.0.1:5001,[::1]:5002"])).unwrap();
+        assert_eq!(config.replicas, vec!["127.0.0.1:5001".parse().unwrap(), "[::1]:5002".parse().unwrap()]);
+        assert!(parse_options(args(&[])).unwrap().replicas.is_empty());
+        assert!(matches!(parse_options(args(&["--replicas"])), Err(ClientError::MissingArgument)));
+        assert!(matches!(parse_options(args(&["--replicas", "127.0.0.1:5001,"])), Err(ClientError::InvalidArgument)));
+    }
+}
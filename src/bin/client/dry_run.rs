@@ -0,0 +1,99 @@
+//! Validación de un archivo de operaciones sin conectarse al servidor (`--dry-run`).
+
+use std::{fmt, io::BufRead, str::FromStr};
+
+use distributed_calculator::{operation::Operation, protocol::Protocol};
+
+use crate::{
+    client_error::ClientError,
+    utils::{is_comment_or_blank, parse_from_file},
+};
+
+/// Resultado de validar un archivo.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct DryRunReport {
+    /// Cantidad de operaciones válidas
+    pub valid_count: usize,
+    /// Líneas inválidas: número de línea (desde 1), texto original y error
+    pub error_lines: Vec<(usize, String, String)>,
+}
+
+impl fmt::Display for DryRunReport {
+    /// Ejemplo:
+    /// ```text
+    /// valid operations: 2, errors: 1
+    /// line 3: "/ 0": division by zero
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "valid operations: {}, errors: {}", self.valid_count, self.error_lines.len())?;
+        for (number, line, error) in &self.error_lines {
+            write!(f, "\nline {}: \"{}\": {}", number, line, error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Lee todo el archivo y valida cada línea con `Operation::from_str`, sin enviar nada.
+/// Las líneas vacías, los comentarios y los comandos del protocolo que no son operaciones (`GET`, `HISTORY`, ...)
+/// no se cuentan ni se validan.
+///
+/// #Errores
+/// 'FailToReadLine' si no se puede leer una línea del archivo.
+pub fn dry_run<R: BufRead>(reader: R) -> Result<DryRunReport, ClientError> {
+    let mut report = DryRunReport::default();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.map_err(|_| ClientError::FailToReadLine)?;
+        if is_comment_or_blank(&line) {
+            continue;
+        }
+        let result = match Protocol::from_bytes(parse_from_file(&line).trim_end().as_bytes()) {
+            Ok(Protocol::Operation(args)) => Operation::from_str(&args).map(|_| ()),
+            Ok(Protocol::SynthaxError(error)) => Err(error),
+            Err(error) => Err(error.to_string()),
+            _ => continue,
+        };
+        match result {
+            Ok(()) => report.valid_count += 1,
+            Err(error) => report.error_lines.push((index + 1, line.trim_end().to_string(), error)),
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::dry_run::{DryRunReport, dry_run};
+
+    #[test]
+    fn dry_run_counts_valid_operations() {
+        let report = dry_run(Cursor::new("+ 1\n* 3\n\nADD 2\nGET\n")).unwrap();
+        assert_eq!(report, DryRunReport { valid_count: 3, error_lines: vec![] });
+    }
+
+    #[test]
+    fn dry_run_skips_comments() {
+        let report = dry_run(Cursor::new("# seed\n+ 1\n  # / 0\n")).unwrap();
+        assert_eq!(report, DryRunReport { valid_count: 1, error_lines: vec![] });
+    }
+
+    #[test]
+    fn dry_run_reports_invalid_lines_with_their_number() {
+        let report = dry_run(Cursor::new("+ 1\n/ 0\n% 2\n+ x\n")).unwrap();
+
+        assert_eq!(report.valid_count, 1);
+        let numbers: Vec<usize> = report.error_lines.iter().map(|(number, _, _)| *number).collect();
+        assert_eq!(numbers, vec![2, 3, 4]);
+        assert_eq!(report.error_lines[0], (2, "/ 0".to_string(), "division by zero".to_string()));
+    }
+
+    #[test]
+    fn report_display_lists_errors() {
+        let report = DryRunReport {
+            valid_count: 2,
+            error_lines: vec![(3, "/ 0".to_string(), "division by zero".to_string())],
+        };
+        assert_eq!(report.to_string(), "valid operations: 2, errors: 1\nline 3: \"/ 0\": division by zero");
+    }
+}
@@ -1,17 +1,312 @@
-use std::{fs::File, io::BufReader};
+use std::{
+    fs::File,
+    io::BufReader,
+    net::SocketAddr,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
 
-use crate::{client_error::ClientError, utils::parse_address, utils::process_files};
+use crate::{
+    client_error::ClientError,
+    config::{ClientConfig, parse_options},
+    dry_run::dry_run,
+    utils::{parse_address, process_directory, process_files, process_parallel, process_replicated},
+};
 
 mod client_error;
+mod config;
+mod dry_run;
+mod output;
+mod stats;
 mod utils;
 
+/// Código de salida cuando el usuario corta el envío con Ctrl-C (128 + `SIGINT`).
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+/// Argumentos de línea de comandos del cliente.
+#[derive(Debug, PartialEq, Eq)]
+struct ClientArgs {
+    /// Dirección del servidor (`IP:PUERTO`).
+    addr: SocketAddr,
+    /// Archivo de operaciones a enviar; `None` con `--directory`.
+    file_path: Option<String>,
+    /// Flags opcionales.
+    config: ClientConfig,
+}
+
+/// Lo que pide la línea de comandos: mostrar la ayuda, mostrar la versión o correr el cliente.
+#[derive(Debug, PartialEq, Eq)]
+enum ParseResult {
+    Help,
+    Version,
+    Run(ClientArgs),
+}
+
+/// Termina con código 1 si el servidor respondió con algún error. Con `--strict` corta en el
+/// primer error, que se devuelve como `Err` y también termina con código 1.
+/// Con `--dry-run` termina con código 1 si alguna línea del archivo es inválida.
+/// Con `--help` imprime el uso y con `--version` la versión, y termina con código 0.
+/// Si el usuario aprieta Ctrl-C deja de enviar operaciones, pide e imprime el valor actual,
+/// cierra la conexión y termina con código 130, como un proceso cortado por `SIGINT`. Un segundo
+/// Ctrl-C termina en el momento, por si el servidor dejó de responder.
 fn main() -> Result<(), ClientError> {
-    let addr = parse_address(std::env::args())?;
-    let file_path = std::env::args()
-        .nth(2)
-        .ok_or(ClientError::MissingArgument)?;
-    let file = File::open(file_path).map_err(|_| ClientError::InvalidArgument)?;
-    let reader = BufReader::new(file);
-    process_files(addr, reader)?;
+    let env = |name: &str| std::env::var(name).ok();
+    let ClientArgs { addr, file_path, config } = match parse_arguments(std::env::args(), env)? {
+        ParseResult::Help => {
+            print_usage(&std::env::args().next().unwrap_or_else(|| "client".to_string()));
+            return Ok(());
+        }
+        ParseResult::Version => {
+            println!("{}", version());
+            return Ok(());
+        }
+        ParseResult::Run(args) => args,
+    };
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let flag = Arc::clone(&interrupted);
+    let handler = move || {
+        if flag.swap(true, Ordering::SeqCst) {
+            std::process::exit(INTERRUPTED_EXIT_CODE);
+        }
+    };
+    if let Err(e) = ctrlc::set_handler(handler) {
+        eprintln!("cannot install the Ctrl-C handler: {}", e);
+    }
+    let result = match (&config.directory, file_path) {
+        (Some(_), Some(_)) => Err(ClientError::InvalidArgument),
+        (Some(_), None) if config.dry_run || config.parallel.is_some() || !config.replicas.is_empty() => {
+            Err(ClientError::InvalidArgument)
+        }
+        (None, Some(_)) if config.parallel.is_some() && !config.replicas.is_empty() => Err(ClientError::InvalidArgument),
+        (Some(dir_path), None) => process_directory(addr, dir_path, &config, &interrupted),
+        (None, Some(file_path)) => {
+            let file = File::open(file_path).map_err(|_| ClientError::InvalidArgument)?;
+            if config.dry_run {
+                let report = dry_run(BufReader::new(file))?;
+                println!("{}", report);
+                Ok(!report.error_lines.is_empty())
+            } else if !config.replicas.is_empty() {
+                process_replicated(addr, BufReader::new(file), &config, &interrupted)
+            } else if let Some(parallel) = config.parallel {
+                process_parallel(addr, BufReader::new(file), parallel, &config, &interrupted)
+            } else {
+                process_files(addr, BufReader::new(file), &config, &interrupted)
+            }
+        }
+        (None, None) => Err(ClientError::MissingArgument),
+    };
+    let had_errors = match result {
+        Err(ClientError::Interrupted) => {
+            eprintln!("{}", ClientError::Interrupted);
+            std::process::exit(INTERRUPTED_EXIT_CODE);
+        }
+        // Cada línea fallida ya se informó en stderr con su número de línea.
+        Err(ClientError::LineErrors(_)) => true,
+        result => result?,
+    };
+    if had_errors {
+        std::process::exit(1);
+    }
     Ok(())
 }
+
+/// Parsea la dirección del servidor, el archivo de entrada opcional y los flags.
+/// Los argumentos de la línea de comandos tienen prioridad; si falta la dirección se usa
+/// `CALC_SERVER_ADDR` y, si falta el archivo y no se usa `--directory`, `CALC_INPUT_FILE`.
+/// `env` devuelve el valor de una variable de entorno, o `None` si no está definida.
+/// Si aparece `--help` / `-h` o `--version` / `-V` en cualquier posición, el resto de los
+/// argumentos se ignora; `--help` tiene prioridad.
+///
+/// #Errores
+/// `MissingArgument` si no se indica la dirección o un flag no tiene su valor.
+/// `InvalidArgument` si la dirección no es válida o algún flag es desconocido.
+fn parse_arguments<I, E>(inputs: I, env: E) -> Result<ParseResult, ClientError>
+where
+    I: IntoIterator<Item = String>,
+    E: Fn(&str) -> Option<String>,
+{
+    let args: Vec<String> = inputs.into_iter().collect();
+    if args.iter().skip(1).any(|arg| arg == "--help" || arg == "-h") {
+        return Ok(ParseResult::Help);
+    }
+    if args.iter().skip(1).any(|arg| arg == "--version" || arg == "-V") {
+        return Ok(ParseResult::Version);
+    }
+    let addr = parse_address(args.iter().cloned(), env("CALC_SERVER_ADDR"))?;
+    let addr_given = args.get(1).is_some_and(|arg| !arg.starts_with("--"));
+    let mut rest: Vec<String> = args.into_iter().skip(if addr_given { 2 } else { 1 }).collect();
+    let file_path = match rest.first() {
+        Some(first) if !first.starts_with("--") => Some(rest.remove(0)),
+        _ => None,
+    };
+    let config = parse_options(rest)?;
+    let file_path = match file_path {
+        Some(path) => Some(path),
+        None if config.directory.is_none() => env("CALC_INPUT_FILE"),
+        None => None,
+    };
+    Ok(ParseResult::Run(ClientArgs { addr, file_path, config }))
+}
+
+/// Texto de ayuda del cliente, con `program_name` como nombre del ejecutable.
+fn usage(program_name: &str) -> String {
+    format!(
+        "Usage: {program} <IP:PORT> [FILE] [OPTIONS]
+
+Sends the operations in FILE (one per line) to the calculator server at IP:PORT
+and prints the resulting value.
+
+Arguments:
+  <IP:PORT>               Server address, e.g. 127.0.0.1:5000 or [::1]:5000
+  [FILE]                  File with the operations to send (omit with --directory)
+
+Options:
+  --pipeline <N>          Send up to N operations before waiting for the replies (default 1)
+  --format <FORMAT>       Output format: plain, json or csv (default plain)
+  --timing                Measure the latency of each message and print a summary at the end
+  --directory <PATH>      Send every *.calc file in PATH instead of a single FILE
+  --verbose               Print the name of each file before sending its operations
+  --strict                Stop at the first error reported by the server
+  --dry-run               Validate FILE without connecting to the server
+  --no-get                Do not request nor print the final value
+  --count                 Print how many operations were sent and how many failed
+  --quiet                 Do not print the result (with --count, print only the count)
+  --output <PATH>         Write the final value to PATH instead of printing the result
+  --retry-ops <N>         Resend an operation up to N more times on transient server errors
+  --parallel <N>          Split FILE in N parts sent at once over N connections (implies --no-get)
+  --timeout <SECS>        Give up if the server takes more than SECS to connect, read or write
+  --replicas <ADDRS>      Also send every operation to these comma-separated IP:PORT replicas
+                          and print the value only if all of them agree
+  -h, --help              Print this help and exit
+  -V, --version           Print the version and exit
+
+Environment:
+  CALC_SERVER_ADDR        Server address used when <IP:PORT> is not given
+  CALC_INPUT_FILE         Operations file used when FILE is not given
+
+Example:
+  {program} 127.0.0.1:5000 operations.txt --pipeline 8 --format json
+",
+        program = program_name
+    )
+}
+
+/// Línea que imprime `--version`, con la versión del crate embebida al compilar.
+fn version() -> String {
+    format!("distributed-calculator {}", env!("CARGO_PKG_VERSION"))
+}
+
+/// Imprime el texto de ayuda en stdout.
+fn print_usage(program_name: &str) {
+    print!("{}", usage(program_name));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{ClientArgs, ParseResult, client_error::ClientError, config::ClientConfig, parse_arguments, usage, version};
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    /// Entorno sin variables definidas.
+    fn no_env(_: &str) -> Option<String> {
+        None
+    }
+
+    /// Entorno con solo las variables de `vars`.
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    fn run(addr: &str, file_path: Option<&str>) -> ParseResult {
+        ParseResult::Run(ClientArgs {
+            addr: addr.parse().unwrap(),
+            file_path: file_path.map(str::to_string),
+            config: ClientConfig::default(),
+        })
+    }
+
+    #[test]
+    fn parse_arguments_detects_help() {
+        assert_eq!(parse_arguments(args(&["client", "--help"]), no_env).unwrap(), ParseResult::Help);
+        assert_eq!(parse_arguments(args(&["client", "-h"]), no_env).unwrap(), ParseResult::Help);
+        assert_eq!(
+            parse_arguments(args(&["client", "127.0.0.1:5000", "ops.txt", "--help"]), no_env).unwrap(),
+            ParseResult::Help
+        );
+    }
+
+    #[test]
+    fn parse_arguments_reads_address_file_and_flags() {
+        let result = parse_arguments(args(&["client", "127.0.0.1:5000", "ops.txt", "--strict"]), no_env).unwrap();
+        let expected = ClientArgs {
+            addr: "127.0.0.1:5000".parse().unwrap(),
+            file_path: Some("ops.txt".to_string()),
+            config: ClientConfig {
+                strict: true,
+                ..ClientConfig::default()
+            },
+        };
+        assert_eq!(result, ParseResult::Run(expected));
+        assert!(matches!(parse_arguments(args(&["client"]), no_env), Err(ClientError::MissingArgument)));
+    }
+
+    #[test]
+    fn parse_arguments_detects_version() {
+        assert_eq!(parse_arguments(args(&["client", "--version"]), no_env).unwrap(), ParseResult::Version);
+        assert_eq!(parse_arguments(args(&["client", "127.0.0.1:5000", "-V"]), no_env).unwrap(), ParseResult::Version);
+        assert_eq!(parse_arguments(args(&["client", "--version", "--help"]), no_env).unwrap(), ParseResult::Help);
+        assert_eq!(version(), format!("distributed-calculator {}", env!("CARGO_PKG_VERSION")));
+    }
+
+    #[test]
+    fn parse_arguments_falls_back_to_env_vars() {
+        let vars = env(&[("CALC_SERVER_ADDR", "127.0.0.1:9000"), ("CALC_INPUT_FILE", "env.txt")]);
+
+        assert_eq!(parse_arguments(args(&["client"]), &vars).unwrap(), run("127.0.0.1:9000", Some("env.txt")));
+        assert_eq!(
+            parse_arguments(args(&["client", "127.0.0.1:5000"]), &vars).unwrap(),
+            run("127.0.0.1:5000", Some("env.txt"))
+        );
+        assert_eq!(
+            parse_arguments(args(&["client", "127.0.0.1:5000", "ops.txt"]), &vars).unwrap(),
+            run("127.0.0.1:5000", Some("ops.txt"))
+        );
+        let Ok(ParseResult::Run(with_directory)) = parse_arguments(args(&["client", "--directory", "ops"]), &vars) else {
+            panic!("expected a run configuration");
+        };
+        assert_eq!(with_directory.addr, "127.0.0.1:9000".parse().unwrap());
+        assert_eq!(with_directory.file_path, None);
+    }
+
+    #[test]
+    fn parse_arguments_fails_without_address_or_env_var() {
+        let vars = env(&[("CALC_INPUT_FILE", "env.txt")]);
+        assert!(matches!(parse_arguments(args(&["client"]), &vars), Err(ClientError::MissingArgument)));
+        assert_eq!(
+            parse_arguments(args(&["client", "127.0.0.1:5000"]), no_env).unwrap(),
+            run("127.0.0.1:5000", None)
+        );
+    }
+
+    #[test]
+    fn usage_documents_flags_and_example() {
+        let text = usage("client");
+        let flags = [
+            "--pipeline", "--format", "--timing", "--directory", "--verbose", "--strict", "--dry-run", "--no-get",
+            "--count", "--quiet", "--output", "--retry-ops", "--parallel", "--timeout", "--replicas", "--help",
+            "--version",
+        ];
+        for flag in flags {
+            assert!(text.contains(flag), "missing {}", flag);
+        }
+        assert!(text.contains("<IP:PORT>"));
+        assert!(text.contains("client 127.0.0.1:5000"));
+    }
+}
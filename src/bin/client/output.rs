@@ -0,0 +1,149 @@
+//! Formatos en los que el cliente imprime el resultado de procesar un archivo.
+
+use std::str::FromStr;
+
+use crate::client_error::ClientError;
+
+/// Formato de salida elegido con `--format`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Solo el valor final, como hasta ahora
+    #[default]
+    Plain,
+    /// Un objeto JSON con el valor final y la cantidad de operaciones enviadas
+    Json,
+    /// Una fila `operation,response` por cada mensaje enviado
+    Csv,
+}
+
+impl OutputFormat {
+    /// Devuelve el formateador que corresponde a este formato.
+    pub fn formatter(&self) -> Box<dyn Formatter> {
+        match self {
+            OutputFormat::Plain => Box::new(PlainFormatter),
+            OutputFormat::Json => Box::new(JsonFormatter),
+            OutputFormat::Csv => Box::new(CsvFormatter),
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = ClientError;
+
+    /// Parsea el valor de `--format`: `plain`, `json` o `csv`.
+    ///
+    /// #Errores
+    /// 'InvalidArgument' si el formato es desconocido.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(OutputFormat::Plain),
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            _ => Err(ClientError::InvalidArgument),
+        }
+    }
+}
+
+/// Arma el texto que imprime el cliente.
+/// `header` y `format_operation` se usan antes del resultado final, una vez y por cada mensaje
+/// respectivamente; devuelven `None` si el formato no imprime nada en ese punto.
+pub trait Formatter {
+    /// Línea que se imprime antes de cualquier otra.
+    fn header(&self) -> Option<String> {
+        None
+    }
+
+    /// Línea que se imprime por cada mensaje enviado junto con la respuesta del servidor.
+    fn format_operation(&self, _operation: &str, _response: &str) -> Option<String> {
+        None
+    }
+
+    /// Recibe el valor final de la calculadora y la cantidad de mensajes enviados.
+    /// Devuelve la línea con el resultado.
+    fn format_result(&self, value: i64, ops: usize) -> String;
+}
+
+/// Imprime solo el valor final.
+pub struct PlainFormatter;
+
+impl Formatter for PlainFormatter {
+    fn format_result(&self, value: i64, _ops: usize) -> String {
+        value.to_string()
+    }
+}
+
+/// Imprime `{"value": 42, "operations_sent": 10}`.
+pub struct JsonFormatter;
+
+impl Formatter for JsonFormatter {
+    fn format_result(&self, value: i64, ops: usize) -> String {
+        format!("{{\"value\": {}, \"operations_sent\": {}}}", value, ops)
+    }
+}
+
+/// Imprime una fila por mensaje y, al final, la del `GET` con el valor final.
+pub struct CsvFormatter;
+
+impl Formatter for CsvFormatter {
+    fn header(&self) -> Option<String> {
+        Some("operation,response".to_string())
+    }
+
+    fn format_operation(&self, operation: &str, response: &str) -> Option<String> {
+        Some(format!("{},{}", csv_field(operation), csv_field(response)))
+    }
+
+    fn format_result(&self, value: i64, _ops: usize) -> String {
+        format!("GET,VALUE {}", value)
+    }
+}
+
+/// Escapa un campo CSV: si tiene comas, comillas o saltos de línea lo encierra entre comillas
+/// y duplica las comillas internas.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::{CsvFormatter, Formatter, JsonFormatter, OutputFormat, PlainFormatter};
+
+    #[test]
+    fn plain_formatter_prints_bare_value() {
+        let formatter = PlainFormatter;
+        assert_eq!(formatter.header(), None);
+        assert_eq!(formatter.format_operation("OP + 1", "OK"), None);
+        assert_eq!(formatter.format_result(42, 10), "42");
+    }
+
+    #[test]
+    fn json_formatter_prints_value_and_count() {
+        let formatter = JsonFormatter;
+        assert_eq!(formatter.format_operation("OP + 1", "OK"), None);
+        assert_eq!(formatter.format_result(-7, 3), "{\"value\": -7, \"operations_sent\": 3}");
+    }
+
+    #[test]
+    fn csv_formatter_prints_one_row_per_operation() {
+        let formatter = CsvFormatter;
+        assert_eq!(formatter.header().as_deref(), Some("operation,response"));
+        assert_eq!(formatter.format_operation("OP + 1", "OK").as_deref(), Some("OP + 1,OK"));
+        assert_eq!(
+            formatter.format_operation("OP / 0", "ERROR \"division by zero\"").as_deref(),
+            Some("OP / 0,\"ERROR \"\"division by zero\"\"\"")
+        );
+        assert_eq!(formatter.format_result(5, 1), "GET,VALUE 5");
+    }
+
+    #[test]
+    fn output_format_parses_known_names() {
+        assert_eq!("plain".parse::<OutputFormat>().unwrap(), OutputFormat::Plain);
+        assert_eq!("json".parse::<OutputFormat>().unwrap(), OutputFormat::Json);
+        assert_eq!("csv".parse::<OutputFormat>().unwrap(), OutputFormat::Csv);
+        assert!("xml".parse::<OutputFormat>().is_err());
+    }
+}
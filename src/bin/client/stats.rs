@@ -0,0 +1,148 @@
+//! Estadísticas de los mensajes enviados al servidor: latencias (`--timing`) y cantidad de
+//! operaciones y errores (`--count`).
+
+use std::{fmt, time::Duration};
+
+use distributed_calculator::protocol::Protocol;
+
+use crate::utils::Exchange;
+
+/// Resumen de las latencias de ida y vuelta medidas por el cliente.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct TimingStats {
+    /// Latencia más baja
+    pub min: Duration,
+    /// Latencia más alta
+    pub max: Duration,
+    /// Promedio de las latencias
+    pub mean: Duration,
+    /// Percentil 95, calculado por rango más cercano
+    pub p95: Duration,
+}
+
+impl TimingStats {
+    /// Recibe las latencias medidas, en cualquier orden.
+    /// Devuelve sus estadísticas, o todas en cero si no hay muestras.
+    pub fn compute(samples: &[Duration]) -> TimingStats {
+        if samples.is_empty() {
+            return TimingStats::default();
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort();
+        let total: Duration = sorted.iter().sum();
+        let p95_rank = (sorted.len() * 95).div_ceil(100);
+        TimingStats {
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            mean: total / sorted.len() as u32,
+            p95: sorted[p95_rank - 1],
+        }
+    }
+}
+
+impl fmt::Display for TimingStats {
+    /// Ejemplo: `min=1.2ms max=3.4ms mean=2.1ms p95=3.3ms`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "min={:?} max={:?} mean={:?} p95={:?}",
+            self.min, self.max, self.mean, self.p95
+        )
+    }
+}
+
+/// Cantidad de mensajes enviados y de respuestas de error, sin contar el `GET` final.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ProcessingStats {
+    /// Mensajes enviados al servidor
+    pub sent_count: usize,
+    /// Mensajes que el servidor respondió con `ERROR`
+    pub error_count: usize,
+}
+
+impl ProcessingStats {
+    /// Cuenta los mensajes enviados y los que recibieron un error.
+    pub fn compute(exchanges: &[Exchange]) -> ProcessingStats {
+        ProcessingStats {
+            sent_count: exchanges.len(),
+            error_count: exchanges
+                .iter()
+                .filter(|exchange| {
+                    matches!(Protocol::from_bytes(exchange.response.as_bytes()), Ok(Protocol::ErrorOperation(_)))
+                })
+                .count(),
+        }
+    }
+}
+
+impl fmt::Display for ProcessingStats {
+    /// Ejemplo: `Sent: 3 operations, 1 errors`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Sent: {} operations, {} errors", self.sent_count, self.error_count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        stats::{ProcessingStats, TimingStats},
+        utils::Exchange,
+    };
+
+    fn millis(values: &[u64]) -> Vec<Duration> {
+        values.iter().map(|v| Duration::from_millis(*v)).collect()
+    }
+
+    #[test]
+    fn compute_with_known_samples() {
+        let samples = millis(&[5, 1, 4, 2, 3, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20]);
+        let stats = TimingStats::compute(&samples);
+
+        assert_eq!(stats.min, Duration::from_millis(1));
+        assert_eq!(stats.max, Duration::from_millis(20));
+        assert_eq!(stats.mean, Duration::from_micros(10_500));
+        assert_eq!(stats.p95, Duration::from_millis(19));
+    }
+
+    #[test]
+    fn compute_with_single_sample() {
+        let stats = TimingStats::compute(&millis(&[7]));
+        assert_eq!(stats.min, Duration::from_millis(7));
+        assert_eq!(stats.max, Duration::from_millis(7));
+        assert_eq!(stats.mean, Duration::from_millis(7));
+        assert_eq!(stats.p95, Duration::from_millis(7));
+    }
+
+    #[test]
+    fn compute_without_samples_is_zero() {
+        assert_eq!(TimingStats::compute(&[]), TimingStats::default());
+    }
+
+    #[test]
+    fn display_lists_every_statistic() {
+        let stats = TimingStats::compute(&millis(&[1, 3]));
+        assert_eq!(stats.to_string(), "min=1ms max=3ms mean=2ms p95=3ms");
+    }
+
+    #[test]
+    fn processing_stats_count_errors() {
+        let exchange = |operation: &str, response: &str| Exchange {
+            operation: operation.to_string(),
+            response: response.to_string(),
+        };
+        let exchanges = vec![
+            exchange("OP + 1", "OK"),
+            exchange("OP / 0", "ERROR \"division by zero\""),
+            exchange("NOPE", "ERROR \"parsing error: unknown operation\""),
+            exchange("OP * 2", "OK"),
+        ];
+
+        let stats = ProcessingStats::compute(&exchanges);
+
+        assert_eq!(stats, ProcessingStats { sent_count: 4, error_count: 2 });
+        assert_eq!(stats.to_string(), "Sent: 4 operations, 2 errors");
+        assert_eq!(ProcessingStats::compute(&[]), ProcessingStats::default());
+    }
+}
@@ -3,119 +3,765 @@
 //! y manejar la comunicación con el servidor.
 
 use std::{
+    collections::VecDeque,
+    fs::{self, File},
     io::{BufRead, BufReader, Read, Write},
     net::{SocketAddr, TcpStream},
+    path::{Path, PathBuf},
     str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
 };
 
-use distributed_calculator::protocol::Protocol;
+use distributed_calculator::{
+    capabilities::ServerCapabilities,
+    protocol::{PROTOCOL_VERSION, Protocol, is_transient_error},
+};
+
+use crate::{
+    client_error::{ClientError, LineError},
+    config::ClientConfig,
+    output::Formatter,
+    stats::{ProcessingStats, TimingStats},
+};
+
+/// Espera antes del primer reenvío con `--retry-ops`; cada reenvío siguiente espera un múltiplo más.
+pub const RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Un mensaje enviado al servidor junto con la respuesta que recibió.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Exchange {
+    /// Mensaje enviado, sin el salto de línea final
+    pub operation: String,
+    /// Respuesta del servidor, sin el salto de línea final
+    pub response: String,
+}
+
+/// Resultado de procesar un archivo: cada mensaje con su respuesta y el valor final.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RunSummary {
+    /// Mensajes enviados en orden, sin contar el `HELLO` inicial ni el `GET` final
+    pub exchanges: Vec<Exchange>,
+    /// Valor final de la calculadora. Es `None` si el servidor respondió el `GET` con un error
+    /// o si no se envió el `GET` final.
+    pub value: Option<i64>,
+    /// Si es `true`, no se envió el `GET` final (`--no-get`) y por eso no hay valor.
+    pub skipped_get: bool,
+    /// Latencia de ida y vuelta de cada mensaje. Solo se mide con `--timing`.
+    pub latencies: Vec<Duration>,
+    /// Si es `true`, el usuario interrumpió el envío con Ctrl-C y quedaron líneas sin enviar.
+    pub interrupted: bool,
+    /// Líneas del archivo a las que el servidor respondió con un error, en orden
+    pub line_errors: Vec<LineError>,
+}
+
+impl RunSummary {
+    /// Indica si el servidor respondió con un error a algún mensaje, incluido el `GET` final.
+    pub fn had_errors(&self) -> bool {
+        (!self.skipped_get && self.value.is_none()) || self.stats().error_count > 0
+    }
 
-use crate::client_error::ClientError;
+    /// Cantidad de mensajes enviados y de errores, para `--count`.
+    pub fn stats(&self) -> ProcessingStats {
+        ProcessingStats::compute(&self.exchanges)
+    }
+}
+
+/// Arma las líneas a imprimir para un resultado según el formateador elegido.
+pub fn render_summary(summary: &RunSummary, formatter: &dyn Formatter) -> Vec<String> {
+    let mut lines: Vec<String> = formatter.header().into_iter().collect();
+    lines.extend(
+        summary
+            .exchanges
+            .iter()
+            .filter_map(|exchange| formatter.format_operation(&exchange.operation, &exchange.response)),
+    );
+    if let Some(value) = summary.value {
+        lines.push(formatter.format_result(value, summary.exchanges.len()));
+    }
+    lines
+}
 
 ///
 ///
 /// Parsea la dirección IP y puerto desde los argumentos de entrada.
 /// Recibe un iterador de strings (normalmente los argumentos de línea de comandos).
 /// El primer argumento es ignorado (nombre del programa).
-/// El segundo argumento debe ser la dirección en formato "IP:PUERTO".
+/// El segundo argumento debe ser la dirección en formato "IP:PUERTO". Si falta (o es un flag),
+/// se usa `fallback`, que normalmente viene de `CALC_SERVER_ADDR`.
 /// Devuelve un `SocketAddr` si el parseo es exitoso, o un `ClientError` en caso de error.
 ///
 /// #Errores
-/// 'MissingArgument' si no se proporciona la dirección.
+/// 'MissingArgument' si no se proporciona la dirección ni hay `fallback`.
 /// 'InvalidArgument' si la dirección no es válida.
-pub fn parse_address<I: IntoIterator<Item = String>>(inputs: I) -> Result<SocketAddr, ClientError> {
+pub fn parse_address<I: IntoIterator<Item = String>>(
+    inputs: I,
+    fallback: Option<String>,
+) -> Result<SocketAddr, ClientError> {
     let mut iter = inputs.into_iter();
     iter.next();
-    let ip_str = iter.next().ok_or(ClientError::MissingArgument)?;
+    let ip_str = match iter.next() {
+        Some(arg) if !arg.starts_with("--") => arg,
+        _ => fallback.ok_or(ClientError::MissingArgument)?,
+    };
     let addr = SocketAddr::from_str(&ip_str).map_err(|_| ClientError::InvalidArgument)?;
     Ok(addr)
 }
 
-/// Es un wrapper que conecta al servidor y llama a `process_files_with_stream`.
-/// Recibe la dirección del servidor y un lector de archivos.
+/// Es un wrapper que conecta al servidor, llama a `process_files_with_stream` e imprime el
+/// resultado en el formato elegido (o escribe el valor final en el archivo de `--output`).
+/// Recibe la dirección del servidor, un lector de archivos y la configuración del cliente.
+/// Devuelve `true` si el servidor respondió con un error al `GET` final.
+/// Si `interrupted` se activa (Ctrl-C) deja de enviar líneas, imprime el valor que quedó y
+/// devuelve `Interrupted`.
+///
+/// #Errores
+/// 'FailedConnection' si no se puede conectar al servidor.
+/// 'Timeout' si se vence el tiempo de `--timeout` esperando al servidor.
+/// 'ServerErrorMessage' con `strict` activado, ante el primer error del servidor.
+/// 'LineErrors' sin `strict`, si el servidor respondió con un error a alguna línea del archivo.
+/// 'InvalidArgument' si no se puede escribir el archivo de `--output`.
+/// 'Interrupted' si el usuario interrumpió el envío.
+pub fn process_files<R: BufRead>(
+    addr: SocketAddr,
+    file_reader: R,
+    config: &ClientConfig,
+    interrupted: &AtomicBool,
+) -> Result<bool, ClientError> {
+    let summary =
+        over_connection(addr, config, |stream| process_files_with_stream(file_reader, stream, config, interrupted))?;
+    finish(&summary, config)
+}
+
+/// Procesa todos los archivos `*.calc` de un directorio, en orden lexicográfico, por una única
+/// conexión al servidor, e imprime el resultado en el formato elegido.
+/// Con `verbose` activado imprime el nombre de cada archivo antes de enviar sus operaciones.
+/// Los números de línea de los errores se cuentan desde el principio de cada archivo.
+/// Devuelve `true` si el servidor respondió con un error al `GET` final.
+///
+/// #Errores
+/// 'InvalidArgument' si no se puede leer el directorio o alguno de sus archivos.
+/// Los mismos que `process_files`.
+pub fn process_directory(
+    addr: SocketAddr,
+    dir_path: &Path,
+    config: &ClientConfig,
+    interrupted: &AtomicBool,
+) -> Result<bool, ClientError> {
+    let mut sources = Vec::new();
+    for path in calc_files_in(dir_path)? {
+        let file = File::open(&path).map_err(|_| ClientError::InvalidArgument)?;
+        let name = path.file_name().map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+        sources.push((Some(name), BufReader::new(file)));
+    }
+    let summary =
+        over_connection(addr, config, |stream| process_sources_with_stream(sources, stream, 0, config, interrupted))?;
+    finish(&summary, config)
+}
+
+/// Abre una conexión al servidor y corre `run` sobre ella. Con `timeout` configurado, la
+/// conexión y cada lectura o escritura esperan como máximo ese tiempo.
+///
+/// #Errores
+/// 'FailedConnection' si no se puede conectar al servidor.
+/// 'Timeout' si se vence `timeout` esperando al servidor.
+/// Los que devuelva `run`.
+fn over_connection<T>(
+    addr: SocketAddr,
+    config: &ClientConfig,
+    run: impl FnOnce(TcpStream) -> Result<T, ClientError>,
+) -> Result<T, ClientError> {
+    connect(addr, config.timeout).and_then(run).map_err(|e| e.with_timeout(config.timeout))
+}
+
+/// Se conecta a `addr` sin el algoritmo de Nagle. Con `timeout`, la conexión y cada lectura o
+/// escritura esperan como máximo ese tiempo.
+///
+/// #Errores
+/// 'FailedConnection' si no se puede conectar o configurar el socket.
+fn connect(addr: SocketAddr, timeout: Option<Duration>) -> Result<TcpStream, ClientError> {
+    let stream = match timeout {
+        Some(timeout) => TcpStream::connect_timeout(&addr, timeout),
+        None => TcpStream::connect(addr),
+    }
+    .map_err(ClientError::FailedConnection)?;
+    stream.set_nodelay(true).map_err(ClientError::FailedConnection)?;
+    stream.set_read_timeout(timeout).map_err(ClientError::FailedConnection)?;
+    stream.set_write_timeout(timeout).map_err(ClientError::FailedConnection)?;
+    Ok(stream)
+}
+
+/// Reparte las líneas de `file_reader` en `parallel` partes contiguas y envía cada una por su
+/// propia conexión desde su propio hilo (`--parallel <N>`); después imprime el resultado.
+/// Las partes se procesan a la vez, así que no hay orden entre operaciones de partes distintas:
+/// solo tiene sentido si son independientes, por ejemplo porque cada parte usa su propio
+/// namespace de calculadora. Por eso no se pide el valor final (`--parallel` implica `--no-get`).
+///
+/// #Errores
+/// 'FailedConnection' si no se puede conectar al servidor.
+/// Los mismos que `process_files`.
+pub fn process_parallel<R: BufRead>(
+    addr: SocketAddr,
+    file_reader: R,
+    parallel: usize,
+    config: &ClientConfig,
+    interrupted: &AtomicBool,
+) -> Result<bool, ClientError> {
+    let mut lines = Vec::new();
+    for line in file_reader.lines() {
+        match line {
+            Ok(line) => lines.push(format!("{}\n", line)),
+            Err(_) => eprintln!("{}", ClientError::FailToReadLine),
+        }
+    }
+    let summary = run_parallel(addr, &lines, parallel, config, interrupted)?;
+    finish(&summary, config)
+}
+
+/// Envía cada línea de `file_reader` a `addr` y a todas las réplicas de `--replicas`, de a un
+/// mensaje, para que apliquen las mismas operaciones en el mismo orden; después imprime el
+/// resultado. Una línea cuenta como fallida si alguna réplica responde con un error, y el
+/// valor final solo se imprime si todas las réplicas coinciden.
+///
+/// #Errores
+/// 'FailedConnection' si no se puede conectar a alguna réplica.
+/// 'Timeout' si se vence `--timeout` esperando a alguna réplica.
+/// Los mismos que `ConnectionPool::apply` y `ConnectionPool::get`.
+/// 'ServerErrorMessage' con `strict` activado, ante el primer error de alguna réplica.
+pub fn process_replicated<R: BufRead>(
+    addr: SocketAddr,
+    file_reader: R,
+    config: &ClientConfig,
+    interrupted: &AtomicBool,
+) -> Result<bool, ClientError> {
+    let mut addrs = vec![addr];
+    addrs.extend(&config.replicas);
+    let summary = ConnectionPool::new(addrs, config.timeout)
+        .and_then(|mut pool| process_with_pool(file_reader, &mut pool, config, interrupted))
+        .map_err(|e| e.with_timeout(config.timeout))?;
+    finish(&summary, config)
+}
+
+/// Envía las líneas de `file_reader` por `pool` y arma el resumen con la respuesta de la
+/// primera réplica, o con el primer error si alguna réplica falló.
+///
+/// #Errores
+/// Los mismos que `process_replicated`.
+fn process_with_pool<R: BufRead>(
+    file_reader: R,
+    pool: &mut ConnectionPool,
+    config: &ClientConfig,
+    interrupted: &AtomicBool,
+) -> Result<RunSummary, ClientError> {
+    let mut summary = RunSummary::default();
+    let mut server_buf = String::new();
+    for (index, line) in file_reader.lines().enumerate() {
+        if interrupted.load(Ordering::SeqCst) {
+            summary.interrupted = true;
+            break;
+        }
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => {
+                eprintln!("{}", ClientError::FailToReadLine);
+                continue;
+            }
+        };
+        if is_comment_or_blank(&line) {
+            continue;
+        }
+        let message = parse_from_file(&line);
+        let sent_at = config.timing.then(Instant::now);
+        let responses = pool.apply(message.as_bytes())?;
+        let response = responses
+            .iter()
+            .find(|response| matches!(response, Protocol::ErrorOperation(_)))
+            .unwrap_or(&responses[0]);
+        server_buf.push_str(&response.to_string());
+        let sent = (message.trim_end().to_string(), sent_at, index + 1);
+        record_response(sent, &mut server_buf, &mut summary, config.strict)?;
+    }
+    if config.send_final_get {
+        summary.value = Some(pool.get()?);
+    } else {
+        summary.skipped_get = true;
+    }
+    Ok(summary)
+}
+
+/// Envía cada parte de `lines` por una conexión distinta, en hilos separados, y junta los
+/// resultados en el orden del archivo.
 ///
 /// #Errores
 /// 'FailedConnection' si no se puede conectar al servidor.
-pub fn process_files<R: BufRead>(addr: SocketAddr, file_reader: R) -> Result<(), ClientError> {
-    let stream = TcpStream::connect(addr).map_err(|_| ClientError::FailedConnection)?;
-    process_files_with_stream(file_reader, stream)
+/// Los mismos que `process_files_with_stream`; si fallan varias partes se devuelve el error de la primera.
+fn run_parallel(
+    addr: SocketAddr,
+    lines: &[String],
+    parallel: usize,
+    config: &ClientConfig,
+    interrupted: &AtomicBool,
+) -> Result<RunSummary, ClientError> {
+    let chunk_size = lines.len().div_ceil(parallel.max(1)).max(1);
+    let results: Vec<Result<RunSummary, ClientError>> = thread::scope(|scope| {
+        let handles: Vec<_> = lines
+            .chunks(chunk_size)
+            .enumerate()
+            .map(|(index, chunk)| {
+                scope.spawn(move || {
+                    over_connection(addr, config, |stream| {
+                        // Cada parte numera sus líneas a partir de donde empieza en el archivo.
+                        let contents = chunk.concat();
+                        let sources = vec![(None, contents.as_bytes())];
+                        process_sources_with_stream(sources, stream, index * chunk_size, config, interrupted)
+                    })
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+            .collect()
+    });
+
+    let mut summary = RunSummary {
+        skipped_get: true,
+        ..RunSummary::default()
+    };
+    for result in results {
+        let part = result?;
+        summary.exchanges.extend(part.exchanges);
+        summary.latencies.extend(part.latencies);
+        summary.interrupted |= part.interrupted;
+        summary.line_errors.extend(part.line_errors);
+    }
+    Ok(summary)
+}
+
+/// Imprime el resultado y devuelve `true` si el servidor respondió con un error al `GET` final.
+///
+/// #Errores
+/// 'InvalidArgument' si no se puede escribir el archivo de `--output`.
+/// 'Interrupted' si el envío se cortó con Ctrl-C; el resultado se imprime igual.
+/// 'LineErrors' si el servidor respondió con un error a alguna línea; el resultado se imprime igual.
+fn finish(summary: &RunSummary, config: &ClientConfig) -> Result<bool, ClientError> {
+    print_summary(summary, config)?;
+    if summary.interrupted {
+        return Err(ClientError::Interrupted);
+    }
+    if !summary.line_errors.is_empty() {
+        return Err(ClientError::LineErrors(summary.line_errors.clone()));
+    }
+    Ok(summary.had_errors())
+}
+
+/// Devuelve las rutas de los archivos con extensión `calc` de un directorio, ordenadas.
+///
+/// #Errores
+/// 'InvalidArgument' si no se puede leer el directorio.
+fn calc_files_in(dir_path: &Path) -> Result<Vec<PathBuf>, ClientError> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir_path)
+        .map_err(|_| ClientError::InvalidArgument)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "calc"))
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Imprime el resultado en el formato elegido y, con `timing` activado, el resumen de latencias.
+/// Con `output` configurado, en lugar de imprimir el resultado escribe el valor final en ese archivo
+/// (si no se envió el `GET` final, el archivo no se toca). Con `quiet` no imprime el resultado
+/// y con `count` imprime además cuántas operaciones se enviaron y cuántas fallaron. Con `verbose`
+/// imprime al final la lista de líneas a las que el servidor respondió con un error.
+///
+/// #Errores
+/// 'InvalidArgument' si no se puede escribir el archivo de `output`.
+fn print_summary(summary: &RunSummary, config: &ClientConfig) -> Result<(), ClientError> {
+    match &config.output {
+        Some(_) if summary.skipped_get => {}
+        Some(path) => write_value(path, summary.value)?,
+        None if config.quiet => {}
+        None => {
+            for line in render_summary(summary, config.format.formatter().as_ref()) {
+                println!("{}", line);
+            }
+        }
+    }
+    if config.count {
+        println!("{}", summary.stats());
+    }
+    if config.timing {
+        // Va a stderr para no mezclarse con la salida en JSON o CSV.
+        eprintln!("latency {}", TimingStats::compute(&summary.latencies));
+    }
+    if config.verbose && !summary.line_errors.is_empty() {
+        eprintln!("{} lines failed:", summary.line_errors.len());
+        for error in &summary.line_errors {
+            eprintln!("  {}", error);
+        }
+    }
+    Ok(())
+}
+
+/// Escribe `value` en `path`, sin salto de línea final para que otras herramientas lo lean tal
+/// cual. Si el servidor no devolvió un valor, el archivo queda vacío.
+///
+/// #Errores
+/// 'InvalidArgument' si no se puede abrir o escribir el archivo.
+pub fn write_value(path: &Path, value: Option<i64>) -> Result<(), ClientError> {
+    let contents = value.map(|value| value.to_string()).unwrap_or_default();
+    fs::write(path, contents).map_err(|_| ClientError::InvalidArgument)
 }
 
 /// Procesa las líneas del archivo y las envía al servidor a través del stream.
 /// Recibe un lector de archivos y un stream (implementando `Write` y `Read`).
-/// Lee cada línea del archivo, la envía al servidor, y espera una respuesta.
-/// Al final, envía una solicitud para obtener el valor final de la calculadora.
-/// Maneja errores de lectura/escritura y respuestas del servidor.
+/// Primero responde el `HELLO` del servidor para acordar sus capacidades; las líneas que
+/// usan una capacidad que el servidor no acepta se saltean con un aviso en lugar de enviarse,
+/// salvo las operaciones con varios operandos, que se envían de a un operando.
+/// Lee cada línea del archivo y la envía al servidor. Envía hasta `pipeline_depth` mensajes
+/// seguidos antes de leer sus respuestas, que el servidor devuelve en el mismo orden.
+/// Las líneas vacías y los comentarios (ver `is_comment_or_blank`) no se envían.
+/// Cada error del servidor se imprime con el número de la línea que lo causó, contando las
+/// líneas vacías, los comentarios y las salteadas, y se agrega a `line_errors` del resumen.
+/// Al final, salvo que `send_final_get` sea `false` (`--no-get`), envía una solicitud para
+/// obtener el valor final de la calculadora.
+/// Devuelve cada mensaje enviado con su respuesta y el valor final. Con `timing` activado
+/// mide además el tiempo entre que se envía cada mensaje y se lee su respuesta.
+/// Antes de cada línea revisa `interrupted`: si está activo no envía más líneas, lee las
+/// respuestas pendientes, pide el valor final igual que al terminar y marca el resumen como
+/// interrumpido.
 ///
 /// #Errores
 /// 'FailToReadLine' si no se puede leer una línea del archivo.
+/// 'ServerErrorMessage' con `strict` activado, ante el primer error del servidor.
 fn process_files_with_stream<R: BufRead, W: Write + Read>(
-    mut file_reader: R,
+    file_reader: R,
     stream: W,
-) -> Result<(), ClientError> {
+    config: &ClientConfig,
+    interrupted: &AtomicBool,
+) -> Result<RunSummary, ClientError> {
+    process_sources_with_stream(vec![(None, file_reader)], stream, 0, config, interrupted)
+}
+
+/// Igual que `process_files_with_stream`, pero envía las líneas de varias fuentes, una detrás de
+/// otra, por la misma conexión. Cada fuente puede tener un nombre, que se imprime antes de
+/// enviar sus líneas si `verbose` está activado. Las líneas de cada fuente se numeran a partir
+/// de `first_line + 1`.
+///
+/// #Errores
+/// Los mismos que `process_files_with_stream`.
+fn process_sources_with_stream<R: BufRead, W: Write + Read>(
+    sources: Vec<(Option<String>, R)>,
+    stream: W,
+    first_line: usize,
+    config: &ClientConfig,
+    interrupted: &AtomicBool,
+) -> Result<RunSummary, ClientError> {
     let mut reader = BufReader::new(stream);
     let mut line_buf = String::new();
     let mut server_buf = String::new();
+    let mut in_flight = VecDeque::new();
+    let mut summary = RunSummary::default();
+    let capabilities = negotiate_capabilities(&mut reader, &mut server_buf)?;
 
-    loop {
-        line_buf.clear();
-        let bytes_read_result: Result<usize, std::io::Error> = file_reader.read_line(&mut line_buf);
-        match bytes_read_result {
-            Ok(n) => {
-                if n == 0 {
-                    break;
-                }
+    'sources: for (name, mut file_reader) in sources {
+        if let Some(name) = name
+            && config.verbose
+        {
+            println!("==> {} <==", name);
+        }
+        let mut line_number = first_line;
+        loop {
+            if interrupted.load(Ordering::SeqCst) {
+                summary.interrupted = true;
+                break 'sources;
             }
-            Err(_) => {
-                eprintln!("{}", ClientError::FailToReadLine);
+            line_buf.clear();
+            let bytes_read_result: Result<usize, std::io::Error> = file_reader.read_line(&mut line_buf);
+            match bytes_read_result {
+                Ok(n) => {
+                    if n == 0 {
+                        break;
+                    }
+                    line_number += 1;
+                }
+                Err(_) => {
+                    line_number += 1;
+                    eprintln!("{}", ClientError::FailToReadLine);
+                    continue;
+                }
+            };
+            if is_comment_or_blank(&line_buf) {
                 continue;
             }
-        };
 
-        let line = parse_from_file(&line_buf);
-        let bytes = line.as_bytes();
-
-        write_to_addr(reader.get_mut(), bytes)?;
-        receive_response(&mut reader, &mut server_buf)?;
+            let line = parse_from_file(&line_buf);
+            let messages = match required_capability(&line) {
+                Some(required) if !capabilities.contains(required) => {
+                    match split_batch(&line).filter(|_| required == ServerCapabilities::BATCH) {
+                        Some(messages) => messages,
+                        None => {
+                            eprintln!("skipping \"{}\": server does not support {}", line.trim_end(), required);
+                            continue;
+                        }
+                    }
+                }
+                _ => vec![line],
+            };
 
-        server_buf.clear();
+            for message in messages {
+                let bytes = message.as_bytes();
+                let sent_at = config.timing.then(Instant::now);
+                if config.retry_ops > 0 {
+                    // Para reenviar una operación hay que leer su respuesta antes de enviar la siguiente,
+                    // así que con `--retry-ops` no se acumulan mensajes sin respuesta.
+                    send_with_retry(&mut reader, &mut server_buf, bytes, config.retry_ops + 1)?;
+                    let sent = (message.trim_end().to_string(), sent_at, line_number);
+                    record_response(sent, &mut server_buf, &mut summary, config.strict)?;
+                    continue;
+                }
+                write_to_addr(reader.get_mut(), bytes)?;
+                in_flight.push_back((message.trim_end().to_string(), sent_at, line_number));
+                if in_flight.len() >= config.pipeline_depth {
+                    receive_responses(&mut reader, &mut server_buf, &mut in_flight, &mut summary, config.strict)?;
+                }
+            }
+        }
+    }
+    receive_responses(&mut reader, &mut server_buf, &mut in_flight, &mut summary, config.strict)?;
+    if !config.send_final_get {
+        summary.skipped_get = true;
+        return Ok(summary);
     }
     write_to_addr(reader.get_mut(), &Protocol::Get.to_bytes())?;
-    last_value_of_calculator(&mut reader, &mut server_buf)?;
+    summary.value = last_value_of_calculator(&mut reader, &mut server_buf)?;
+
+    Ok(summary)
+}
+
+/// Lee el `HELLO` con el que el servidor abre la conexión y lo responde eligiendo, de las
+/// capacidades que ofrece, todas las que conoce el cliente; devuelve las elegidas.
+/// Descarta el `SESSION_ID` con el que el servidor responde.
+///
+/// #Errores
+/// 'ProtocolError' si el primer mensaje del servidor no es un `HELLO`.
+/// 'FailedWrite' si no se puede enviar la respuesta.
+/// 'FailedConnection' si no se puede leer del servidor o cierra la conexión.
+fn negotiate_capabilities<S: Read + Write>(
+    reader: &mut BufReader<S>,
+    server_buf: &mut String,
+) -> Result<ServerCapabilities, ClientError> {
+    let chosen = read_greeting(reader, server_buf)? & ServerCapabilities::all();
+    write_to_addr(reader.get_mut(), &Protocol::Hello { version: PROTOCOL_VERSION, capabilities: chosen.bits() }.to_bytes())?;
+    // El cliente no retoma sesiones, pero tiene que leer el `SESSION_ID` con el que responde el servidor.
+    server_buf.clear();
+    match reader.read_line(server_buf) {
+        Ok(0) => return Err(ClientError::connection_closed()),
+        Err(e) => return Err(ClientError::FailedConnection(e)),
+        Ok(_) => {}
+    }
+    server_buf.clear();
+    Ok(chosen)
+}
 
+/// Lee el `HELLO` con el que el servidor abre la conexión y devuelve las capacidades que ofrece.
+///
+/// #Errores
+/// 'ProtocolError' si el primer mensaje del servidor no es un `HELLO`.
+/// 'FailedConnection' si no se puede leer del servidor o cierra la conexión.
+fn read_greeting<R: BufRead>(reader: &mut R, server_buf: &mut String) -> Result<ServerCapabilities, ClientError> {
+    server_buf.clear();
+    match reader.read_line(server_buf) {
+        Ok(0) => return Err(ClientError::connection_closed()),
+        Err(e) => return Err(ClientError::FailedConnection(e)),
+        Ok(_) => {}
+    }
+    match Protocol::from_bytes(server_buf.trim_end().as_bytes()) {
+        Ok(Protocol::Hello { capabilities, .. }) => Ok(ServerCapabilities::from_bits(capabilities)),
+        _ => Err(ClientError::ProtocolError {
+            expected: "HELLO".to_string(),
+            got: server_buf.trim_end().to_string(),
+        }),
+    }
+}
+
+/// Devuelve la capacidad del servidor que necesita un mensaje, si necesita alguna.
+fn required_capability(line: &str) -> Option<ServerCapabilities> {
+    Protocol::from_bytes(line.trim_end().as_bytes()).ok().as_ref().and_then(ServerCapabilities::required_by)
+}
+
+/// Divide una operación con varios operandos en una operación por operando, para los servidores
+/// que no aceptan `BATCH`. Solo se puede con `+`, `-` y `*`: `+ 1 2` equivale a `+ 1` y luego `+ 2`.
+/// Devuelve `None` si la línea no es una operación que se pueda dividir.
+fn split_batch(line: &str) -> Option<Vec<String>> {
+    match line.split_whitespace().collect::<Vec<&str>>().as_slice() {
+        ["OP", operator, operands @ ..]
+            if matches!(operator.to_ascii_uppercase().as_str(), "+" | "-" | "*" | "ADD" | "SUB" | "MUL") =>
+        {
+            Some(operands.iter().map(|operand| format!("OP {} {}\n", operator, operand)).collect())
+        }
+        _ => None,
+    }
+}
+
+/// Lee las respuestas de los mensajes enviados sin esperar respuesta, vaciando `in_flight`.
+/// Cada mensaje se agrega al resumen junto con su respuesta y, si se anotó cuándo se envió,
+/// su latencia. Cada mensaje de `in_flight` guarda también el número de la línea de la que salió.
+///
+/// #Errores
+/// Los mismos que `receive_response`.
+/// 'ServerErrorMessage' si `strict` es `true` y el servidor responde con un error.
+fn receive_responses<R: BufRead>(
+    reader: &mut R,
+    server_buf: &mut String,
+    in_flight: &mut VecDeque<(String, Option<Instant>, usize)>,
+    summary: &mut RunSummary,
+    strict: bool,
+) -> Result<(), ClientError> {
+    while let Some(sent) = in_flight.pop_front() {
+        receive_response(reader, server_buf, sent.0.as_bytes())?;
+        record_response(sent, server_buf, summary, strict)?;
+    }
     Ok(())
 }
 
+/// Agrega al resumen el mensaje enviado con la respuesta que quedó en `server_buf` y, si se
+/// anotó cuándo se envió, su latencia. `sent` es el mensaje, cuándo se envió y el número de la
+/// línea de la que salió. Si la respuesta es un error lo imprime con ese número de línea y lo
+/// agrega a `line_errors`. Deja `server_buf` vacío.
+///
+/// #Errores
+/// 'ServerErrorMessage' si `strict` es `true` y el servidor respondió con un error.
+fn record_response(
+    (operation, sent_at, line): (String, Option<Instant>, usize),
+    server_buf: &mut String,
+    summary: &mut RunSummary,
+    strict: bool,
+) -> Result<(), ClientError> {
+    if let Ok(Protocol::ErrorOperation(message)) = Protocol::from_bytes(server_buf.trim_end().as_bytes()) {
+        let error = LineError { line, content: operation.clone(), error: message };
+        eprintln!("{}", error);
+        if strict {
+            return Err(ClientError::ServerErrorMessage(error.error));
+        }
+        summary.line_errors.push(error);
+    }
+    if let Some(sent_at) = sent_at {
+        summary.latencies.push(sent_at.elapsed());
+    }
+    summary.exchanges.push(Exchange {
+        operation,
+        response: server_buf.trim_end().to_string(),
+    });
+    server_buf.clear();
+    Ok(())
+}
+
+/// Envía `bytes` y lee su respuesta en `server_buf`. Si el servidor responde con un error
+/// transitorio (ver `is_transient_error`) reenvía el mensaje, hasta completar `max_attempts`
+/// intentos en total, esperando `RETRY_BACKOFF * intento` antes de cada reenvío.
+/// Al terminar, `server_buf` tiene la respuesta del último intento.
+///
+/// #Errores
+/// 'FailedWrite' si no se puede enviar el mensaje.
+/// 'FailedConnection' si no se puede leer la respuesta o el servidor cierra la conexión.
+pub fn send_with_retry<S: Read + Write>(
+    reader: &mut BufReader<S>,
+    server_buf: &mut String,
+    bytes: &[u8],
+    max_attempts: usize,
+) -> Result<(), ClientError> {
+    let mut attempt = 1;
+    loop {
+        write_to_addr(reader.get_mut(), bytes)?;
+        receive_response(reader, server_buf, bytes)?;
+        let transient = matches!(
+            Protocol::from_bytes(server_buf.trim_end().as_bytes()),
+            Ok(Protocol::ErrorOperation(message)) if is_transient_error(&message)
+        );
+        if !transient || attempt >= max_attempts {
+            return Ok(());
+        }
+        server_buf.clear();
+        thread::sleep(RETRY_BACKOFF * attempt as u32);
+        attempt += 1;
+    }
+}
+
 /// Lee una línea de respuesta del servidor y la procesa.
-/// Recibe un lector (implementando `BufRead`) y un buffer de string para almacenar la respuesta.
-/// Si la respuesta es un error de nuestra parte que comunica el Servidor, imprime el mensaje de error.
+/// Recibe un lector (implementando `BufRead`), un buffer de string para almacenar la respuesta y
+/// el mensaje que se envió, para verificar que la respuesta sea la que le corresponde.
+/// Un error del servidor se acepta como respuesta a cualquier mensaje; no lo imprime, porque
+/// quien llama lo informa junto con la línea que lo causó.
 ///
 /// #Errores
 /// 'FailedConnection' si no se puede leer la respuesta o si el servidor cierra la conexión.
-/// 'ServerErrorMessage' si el servidor responde con un mensaje de error.
+/// 'ProtocolError' si la respuesta no es ni un error ni la que corresponde al mensaje enviado.
 fn receive_response<R: BufRead>(
     reader: &mut R,
     server_buf: &mut String,
+    sent: &[u8],
 ) -> Result<(), ClientError> {
     let response_bytes_result = reader.read_line(server_buf);
     match response_bytes_result {
         Ok(n) => {
             if n == 0 {
-                return Err(ClientError::FailedConnection);
+                return Err(ClientError::connection_closed());
             }
         }
-        Err(_) => {
-            return Err(ClientError::FailedConnection);
+        Err(e) => {
+            return Err(ClientError::FailedConnection(e));
         }
     };
+    if server_buf.trim_end() == "HISTORY_START" {
+        receive_history_response(reader, server_buf)?;
+    }
 
-    let protocol: Protocol = Protocol::from_bytes(server_buf.trim_end().as_bytes());
-    if let Protocol::ErrorOperation(message) = protocol {
-        eprintln!("{}", ClientError::ServerErrorMessage(message));
+    let response = server_buf.trim_end();
+    let is_error = matches!(Protocol::from_bytes(response.as_bytes()), Ok(Protocol::ErrorOperation(_)));
+    if !is_error
+        && let Some(expected) = expected_response(sent)
+        && response.split_whitespace().next() != Some(expected)
+    {
+        return Err(ClientError::ProtocolError { expected: expected.to_string(), got: response.to_string() });
     }
     Ok(())
 }
 
+/// Sigue leyendo la respuesta de `GET_HISTORY` después de su primera línea (`HISTORY_START`),
+/// que ya está en `server_buf`, y agrega cada línea hasta `HISTORY_END` inclusive.
+///
+/// #Errores
+/// 'FailedConnection' si no se puede leer una línea o si el servidor cierra la conexión antes
+/// de `HISTORY_END`.
+fn receive_history_response<R: BufRead>(reader: &mut R, server_buf: &mut String) -> Result<(), ClientError> {
+    loop {
+        let start = server_buf.len();
+        match reader.read_line(server_buf) {
+            Ok(0) => return Err(ClientError::connection_closed()),
+            Err(e) => return Err(ClientError::FailedConnection(e)),
+            Ok(_) => {}
+        }
+        if server_buf[start..].trim_end() == "HISTORY_END" {
+            return Ok(());
+        }
+    }
+}
+
+/// Devuelve el comando con el que el servidor responde a `sent` cuando no hay un error, si el
+/// mensaje tiene una única respuesta posible: `OK` para `OP`, `VALUE` para `GET` y
+/// `HISTORY_START` para `GET_HISTORY`.
+fn expected_response(sent: &[u8]) -> Option<&'static str> {
+    match Protocol::from_bytes(sent.strip_suffix(b"\n").unwrap_or(sent)) {
+        Ok(Protocol::Operation(_)) => Some("OK"),
+        Ok(Protocol::Get) => Some("VALUE"),
+        Ok(Protocol::GetHistory) => Some("HISTORY_START"),
+        _ => None,
+    }
+}
+
 /// Escribe los bytes en el stream y fuerza el envío.
 /// Recibe un escritor (implementando `Write`) y un slice de bytes.
 ///
@@ -124,54 +770,66 @@ fn receive_response<R: BufRead>(
 fn write_to_addr<W: Write>(writer: &mut W, bytes: &[u8]) -> Result<(), ClientError> {
     writer
         .write_all(bytes)
-        .map_err(|_| ClientError::FailedWrite)?;
-    writer.flush().map_err(|_| ClientError::FailedWrite)?;
+        .map_err(ClientError::FailedWrite)?;
+    writer.flush().map_err(ClientError::FailedWrite)?;
     Ok(())
 }
 
 /// Lee la última respuesta del servidor, que debe ser el valor actual de la calculadora.
 /// Recibe un lector (implementando `BufRead`) y un buffer de string para almacenar la respuesta.
-/// Si la respuesta es un valor, lo imprime. Si es un error, imprime el mensaje de error.
+/// Si la respuesta es un valor, lo devuelve. Si es un error, imprime el mensaje de error y devuelve `None`.
 ///
 /// #Errores
 /// 'FailedConnection' si no se puede leer la respuesta o si el servidor cierra la conexión.
-/// 'ErrorMessage' si la respuesta no es ni un valor ni un mensaje de error (no es la esperada).
+/// 'ProtocolError' si la respuesta no es ni un valor ni un mensaje de error (no es la esperada).
+/// 'ErrorMessage' si el valor no es un entero.
 fn last_value_of_calculator<R: BufRead>(
     reader: &mut R,
     server_buf: &mut String,
-) -> Result<(), ClientError> {
+) -> Result<Option<i64>, ClientError> {
     let response_bytes_result = reader.read_line(server_buf);
     match response_bytes_result {
         Ok(n) => {
             if n == 0 {
-                return Err(ClientError::FailedConnection);
+                return Err(ClientError::connection_closed());
             }
         }
-        Err(_) => {
-            return Err(ClientError::FailedConnection);
+        Err(e) => {
+            return Err(ClientError::FailedConnection(e));
         }
     };
 
-    let protocol = Protocol::from_bytes(server_buf.trim_end().as_bytes());
+    let protocol = Protocol::from_bytes(server_buf.trim_end().as_bytes()).map_err(|_| ClientError::ErrorMessage)?;
 
     match protocol {
-        Protocol::Value(val) => {
-            println!("{}", val);
-        }
+        Protocol::Value(val) => val.parse().map(Some).map_err(|_| ClientError::ErrorMessage),
         Protocol::ErrorOperation(message) => {
             eprintln!("{}", ClientError::ServerErrorMessage(message));
+            Ok(None)
         }
-        _ => {
-            return Err(ClientError::ErrorMessage);
-        }
+        _ => Err(ClientError::ProtocolError { expected: "VALUE".to_string(), got: server_buf.trim_end().to_string() }),
     }
-    Ok(())
 }
 
+/// Indica si una línea del archivo de entrada no tiene nada que enviar: está vacía o es un
+/// comentario, es decir que su primer carácter que no es un espacio es `#`.
+pub fn is_comment_or_blank(line: &str) -> bool {
+    let line = line.trim();
+    line.is_empty() || line.starts_with('#')
+}
+
+/// Convierte una línea del archivo de entrada en un mensaje del protocolo.
+/// Las líneas de la forma `<operador> <valor> [<valor> ...]` se envían como `OP <operador> <valor> ...`.
+/// El operador puede ser un símbolo (`+ 5`) o su alias (`ADD 5`); el servidor normaliza ambos.
+/// Las líneas que ya son un comando del protocolo (`SET_REGISTER A 1`, `GET A`, ...) se envían sin cambios.
 pub fn parse_from_file(line: &str) -> String {
     let vector: Vec<&str> = line.split_whitespace().collect();
+    let is_command = !matches!(
+        Protocol::from_bytes(line.trim_end().as_bytes()),
+        Ok(Protocol::SynthaxError(_))
+    );
 
-    let vector_with_op = if vector.len() == 2 {
+    let vector_with_op = if vector.len() >= 2 && !is_command {
         let mut v = vec!["OP"];
         v.extend(&vector);
         v
@@ -184,23 +842,122 @@ pub fn parse_from_file(line: &str) -> String {
     result
 }
 
+/// Conexiones abiertas a cada réplica de la calculadora. Cada mensaje se envía a todas para
+/// que apliquen las mismas operaciones en el mismo orden.
+pub struct ConnectionPool {
+    connections: Vec<BufReader<TcpStream>>,
+}
+
+impl ConnectionPool {
+    /// Se conecta a cada una de las direcciones de `addrs` y lee el `HELLO` con el que abre cada
+    /// réplica. No lo responde: sin handshake las réplicas aceptan todo lo que tengan habilitado.
+    /// Con `timeout`, cada conexión y cada lectura o escritura esperan como máximo ese tiempo.
+    ///
+    /// #Errores
+    /// 'InvalidArgument' si `addrs` está vacío.
+    /// 'FailedConnection' si no se puede conectar a alguna de las réplicas.
+    /// 'ProtocolError' si alguna réplica no abre la conexión con `HELLO`.
+    pub fn new(addrs: Vec<SocketAddr>, timeout: Option<Duration>) -> Result<ConnectionPool, ClientError> {
+        if addrs.is_empty() {
+            return Err(ClientError::InvalidArgument);
+        }
+        let mut connections = Vec::with_capacity(addrs.len());
+        for addr in addrs {
+            let mut connection = BufReader::new(connect(addr, timeout)?);
+            read_greeting(&mut connection, &mut String::new())?;
+            connections.push(connection);
+        }
+        Ok(ConnectionPool { connections })
+    }
+
+    /// Envía `op` a todas las réplicas y devuelve sus respuestas, en el orden de las direcciones.
+    /// Primero envía a todas y después lee, así las réplicas procesan el mensaje a la vez.
+    ///
+    /// #Errores
+    /// 'FailedWrite' si no se puede enviar el mensaje a alguna réplica.
+    /// 'FailedConnection' si alguna réplica cierra la conexión.
+    /// 'ErrorMessage' si alguna respuesta no es un mensaje válido del protocolo.
+    pub fn apply(&mut self, op: &[u8]) -> Result<Vec<Protocol>, ClientError> {
+        for connection in &mut self.connections {
+            write_to_addr(connection.get_mut(), op)?;
+        }
+        let mut responses = Vec::with_capacity(self.connections.len());
+        let mut server_buf = String::new();
+        for connection in &mut self.connections {
+            server_buf.clear();
+            match connection.read_line(&mut server_buf) {
+                Ok(0) => return Err(ClientError::connection_closed()),
+                Err(e) => return Err(ClientError::FailedConnection(e)),
+                Ok(_) => {}
+            }
+            if server_buf.trim_end() == "HISTORY_START" {
+                receive_history_response(connection, &mut server_buf)?;
+            }
+            let response =
+                Protocol::from_bytes(server_buf.trim_end().as_bytes()).map_err(|_| ClientError::ErrorMessage)?;
+            responses.push(response);
+        }
+        Ok(responses)
+    }
+
+    /// Pide el valor a todas las réplicas y lo devuelve si todas coinciden.
+    ///
+    /// #Errores
+    /// Los mismos que `apply`.
+    /// 'ServerErrorMessage' si alguna réplica responde con un error o si no todas tienen el mismo valor.
+    pub fn get(&mut self) -> Result<i64, ClientError> {
+        let mut values = Vec::with_capacity(self.connections.len());
+        for response in self.apply(&Protocol::Get.to_bytes())? {
+            match response {
+                Protocol::Value(value) => values.push(value.parse::<i64>().map_err(|_| ClientError::ErrorMessage)?),
+                Protocol::ErrorOperation(message) => return Err(ClientError::ServerErrorMessage(message)),
+                _ => return Err(ClientError::ErrorMessage),
+            }
+        }
+        if values.windows(2).any(|pair| pair[0] != pair[1]) {
+            let values: Vec<String> = values.iter().map(i64::to_string).collect();
+            return Err(ClientError::ServerErrorMessage(format!(
+                "replicas disagree on the value: {}",
+                values.join(", ")
+            )));
+        }
+        Ok(values[0])
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::{
-        io::{BufReader, BufWriter, Cursor, Write},
-        net::SocketAddr,
+        io::{BufRead, BufReader, BufWriter, Cursor, Read, Write},
+        net::{SocketAddr, TcpListener},
+        sync::atomic::{AtomicBool, Ordering},
+        thread,
+        time::Duration,
     };
 
     use distributed_calculator::protocol::Protocol;
 
     use crate::{
-        client_error::ClientError,
+        client_error::{ClientError, LineError},
+        config::ClientConfig,
+        output::{CsvFormatter, JsonFormatter},
         utils::{
-            last_value_of_calculator, parse_address, parse_from_file, receive_response,
-            write_to_addr,
+            ConnectionPool, Exchange, RunSummary, finish, last_value_of_calculator, parse_address, parse_from_file,
+            process_directory, process_files_with_stream, process_replicated, process_with_pool, over_connection, receive_response, render_summary, run_parallel,
+            send_with_retry, write_to_addr, write_value,
         },
     };
 
+    /// Bandera de Ctrl-C que nunca se activa.
+    static NOT_INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+    fn with_depth(pipeline_depth: usize) -> ClientConfig {
+        ClientConfig {
+            pipeline_depth,
+            ..ClientConfig::default()
+        }
+    }
+
     #[test]
     fn test_parse_from_client() {
         let input = "+ 5\n";
@@ -208,19 +965,48 @@ mod tests {
         assert_eq!(parse_from_file(input), expected);
     }
 
+    #[test]
+    fn test_parse_from_client_keyword_and_symbol_forms() {
+        assert_eq!(parse_from_file("ADD 5\n"), "OP ADD 5\n");
+        assert_eq!(parse_from_file("sub 3\n"), "OP sub 3\n");
+        assert_eq!(parse_from_file("- 3\n"), "OP - 3\n");
+    }
+
+    #[test]
+    fn test_parse_from_client_keeps_protocol_commands() {
+        assert_eq!(parse_from_file("SET_REGISTER A 1\n"), "SET_REGISTER A 1\n");
+        assert_eq!(parse_from_file("GET A"), "GET A\n");
+        assert_eq!(parse_from_file("OP + 1"), "OP + 1\n");
+    }
+
+    #[test]
+    fn test_parse_from_client_multiple_operands() {
+        assert_eq!(parse_from_file("+ 1 2 3\n"), "OP + 1 2 3\n");
+    }
+
     #[test]
     fn parsing_address_successfully() {
         let args = vec!["program".to_string(), "127.0.0.1:8080".to_string()];
 
-        let addr = parse_address(args).unwrap();
+        let addr = parse_address(args, None).unwrap();
         assert_eq!(addr, "127.0.0.1:8080".parse::<SocketAddr>().unwrap());
     }
 
+    #[test]
+    fn parse_address_prefers_the_argument_over_the_fallback() {
+        let fallback = Some("127.0.0.1:9000".to_string());
+        let args = vec!["program".to_string(), "127.0.0.1:8080".to_string()];
+        assert_eq!(parse_address(args, fallback.clone()).unwrap(), "127.0.0.1:8080".parse::<SocketAddr>().unwrap());
+
+        let args = vec!["program".to_string(), "--strict".to_string()];
+        assert_eq!(parse_address(args, fallback).unwrap(), "127.0.0.1:9000".parse::<SocketAddr>().unwrap());
+    }
+
     #[test]
     fn parse_fails_missing_argument() {
         let args = vec!["program".to_string()]; // sin IP
 
-        let err = parse_address(args).unwrap_err();
+        let err = parse_address(args, None).unwrap_err();
         matches!(err, ClientError::MissingArgument);
     }
 
@@ -228,7 +1014,7 @@ mod tests {
     fn parse_fails_invalid_address() {
         let args = vec!["program".to_string(), "not_an_ip".to_string()];
 
-        let err = parse_address(args).unwrap_err();
+        let err = parse_address(args, None).unwrap_err();
         matches!(err, ClientError::InvalidArgument);
     }
 
@@ -242,7 +1028,7 @@ mod tests {
 
         let result = last_value_of_calculator(&mut reader, &mut buf);
 
-        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), Some(42));
         assert_eq!(buf.as_bytes(), server_response.as_slice());
     }
 
@@ -269,7 +1055,7 @@ mod tests {
 
         let result = last_value_of_calculator(&mut reader, &mut buf).unwrap_err();
 
-        assert!(matches!(result, ClientError::FailedConnection));
+        assert!(matches!(result, ClientError::FailedConnection(_)));
     }
 
     #[test]
@@ -292,7 +1078,7 @@ mod tests {
         let mut reader = BufReader::new(cursor);
         let mut buf = String::new();
 
-        let result = receive_response(&mut reader, &mut buf);
+        let result = receive_response(&mut reader, &mut buf, b"OP + 1\n");
         assert!(result.is_ok());
         let data_str = String::from_utf8(data).unwrap();
         assert_eq!(buf, data_str);
@@ -303,7 +1089,685 @@ mod tests {
         let cursor = Cursor::new(Vec::new());
         let mut reader = BufReader::new(cursor);
         let mut buf = String::new();
-        let result = receive_response(&mut reader, &mut buf).unwrap_err();
-        assert!(matches!(result, ClientError::FailedConnection));
+        let result = receive_response(&mut reader, &mut buf, b"OP + 1\n").unwrap_err();
+        assert!(matches!(result, ClientError::FailedConnection(_)));
+    }
+
+    struct FakeServer {
+        responses: Cursor<Vec<u8>>,
+        received: Vec<u8>,
+    }
+
+    impl Read for FakeServer {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.responses.read(buf)
+        }
+    }
+
+    impl Write for FakeServer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.received.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn process_files_with_pipeline_reads_every_response() {
+        let input = Cursor::new(b"+ 1\n* 3\n- 1\n".to_vec());
+        let responses = b"HELLO 1 0\nSESSION_ID 3fa2\nOK\nOK\nOK\nVALUE 2\n".to_vec();
+        let total = responses.len() as u64;
+        let mut server = FakeServer {
+            responses: Cursor::new(responses),
+            received: Vec::new(),
+        };
+
+        process_files_with_stream(input, &mut server, &with_depth(2), &NOT_INTERRUPTED).unwrap();
+
+        assert_eq!(
+            String::from_utf8(server.received).unwrap(),
+            "HELLO 1 0\nOP + 1\nOP * 3\nOP - 1\nGET\n"
+        );
+        assert_eq!(server.responses.position(), total);
+    }
+
+    #[test]
+    fn process_files_skips_commands_without_capability() {
+        let input = Cursor::new(b"+ 1\nHISTORY\nSET_REGISTER A 1\n".to_vec());
+        let mut server = FakeServer {
+            responses: Cursor::new(b"HELLO 1 8\nSESSION_ID 3fa2\nOK\nOK\nVALUE 1\n".to_vec()),
+            received: Vec::new(),
+        };
+
+        process_files_with_stream(input, &mut server, &with_depth(1), &NOT_INTERRUPTED).unwrap();
+
+        assert_eq!(
+            String::from_utf8(server.received).unwrap(),
+            "HELLO 1 8\nOP + 1\nSET_REGISTER A 1\nGET\n"
+        );
+    }
+
+    #[test]
+    fn process_files_splits_batches_when_the_server_does_not_accept_batch() {
+        let input = Cursor::new(b"+ 1 2 3\n/ 2 2\n* 2 5\n".to_vec());
+        let mut server = FakeServer {
+            // El servidor ofrece todas las capacidades menos BATCH y el cliente las elige todas.
+            responses: Cursor::new(b"HELLO 1 1d\nSESSION_ID 3fa2\nOK\nOK\nOK\nOK\nOK\nVALUE 60\n".to_vec()),
+            received: Vec::new(),
+        };
+
+        let summary = process_files_with_stream(input, &mut server, &with_depth(2), &NOT_INTERRUPTED).unwrap();
+
+        assert_eq!(
+            String::from_utf8(server.received).unwrap(),
+            "HELLO 1 1d\nOP + 1\nOP + 2\nOP + 3\nOP * 2\nOP * 5\nGET\n"
+        );
+        assert_eq!(summary.value, Some(60));
+    }
+
+    #[test]
+    fn process_files_fails_when_the_server_does_not_open_with_hello() {
+        let input = Cursor::new(b"+ 2\n".to_vec());
+        let mut server = FakeServer {
+            responses: Cursor::new(b"OK\nVALUE 2\n".to_vec()),
+            received: Vec::new(),
+        };
+
+        let result = process_files_with_stream(input, &mut server, &with_depth(1), &NOT_INTERRUPTED);
+
+        assert!(matches!(result, Err(ClientError::ProtocolError { expected, got }) if expected == "HELLO" && got == "OK"));
+        assert!(server.received.is_empty());
+    }
+
+    #[test]
+    fn process_files_pairs_each_operation_with_its_response() {
+        let input = Cursor::new(b"+ 4\n/ 0\n".to_vec());
+        let mut server = FakeServer {
+            responses: Cursor::new(b"HELLO 1 0\nSESSION_ID 3fa2\nOK\nERROR \"division by zero\"\nVALUE 4\n".to_vec()),
+            received: Vec::new(),
+        };
+
+        let summary = process_files_with_stream(input, &mut server, &with_depth(2), &NOT_INTERRUPTED).unwrap();
+
+        assert_eq!(summary.value, Some(4));
+        assert!(summary.latencies.is_empty());
+        assert_eq!(
+            summary.exchanges,
+            vec![
+                Exchange { operation: "OP + 4".to_string(), response: "OK".to_string() },
+                Exchange { operation: "OP / 0".to_string(), response: "ERROR \"division by zero\"".to_string() },
+            ]
+        );
+    }
+
+    #[test]
+    fn render_summary_uses_formatter() {
+        let summary = RunSummary {
+            exchanges: vec![Exchange { operation: "OP + 1".to_string(), response: "OK".to_string() }],
+            value: Some(1),
+            ..RunSummary::default()
+        };
+
+        assert_eq!(render_summary(&summary, &JsonFormatter), vec!["{\"value\": 1, \"operations_sent\": 1}"]);
+        assert_eq!(
+            render_summary(&summary, &CsvFormatter),
+            vec!["operation,response", "OP + 1,OK", "GET,VALUE 1"]
+        );
+    }
+
+    #[test]
+    fn process_files_with_timing_measures_every_operation() {
+        let input = Cursor::new(b"+ 1\n+ 2\n+ 3\n".to_vec());
+        let mut server = FakeServer {
+            responses: Cursor::new(b"HELLO 1 0\nSESSION_ID 3fa2\nOK\nOK\nOK\nVALUE 6\n".to_vec()),
+            received: Vec::new(),
+        };
+        let config = ClientConfig {
+            timing: true,
+            ..with_depth(2)
+        };
+
+        let summary = process_files_with_stream(input, &mut server, &config, &NOT_INTERRUPTED).unwrap();
+
+        assert_eq!(summary.latencies.len(), 3);
+    }
+
+    #[test]
+    fn process_directory_sends_every_calc_file_over_one_connection() {
+        let dir = std::env::temp_dir().join(format!("calc_dir_test_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("b.calc"), "* 3\n").unwrap();
+        std::fs::write(dir.join("a.calc"), "+ 2\n").unwrap();
+        std::fs::write(dir.join("notes.txt"), "+ 100\n").unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            writer.write_all(b"HELLO 1 0\n").unwrap();
+            let mut received = Vec::new();
+            for line in BufReader::new(stream).lines() {
+                let line = line.unwrap();
+                let response = match line.as_str() {
+                    "HELLO 1 0" => "SESSION_ID 3fa2\n",
+                    "GET" => "VALUE 6\n",
+                    _ => "OK\n",
+                };
+                writer.write_all(response.as_bytes()).unwrap();
+                received.push(line.clone());
+                if line == "GET" {
+                    break;
+                }
+            }
+            received
+        });
+
+        process_directory(addr, &dir, &ClientConfig::default(), &NOT_INTERRUPTED).unwrap();
+
+        assert_eq!(server.join().unwrap(), vec!["HELLO 1 0", "OP + 2", "OP * 3", "GET"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn had_errors_detects_error_responses() {
+        let ok = Exchange { operation: "OP + 1".to_string(), response: "OK".to_string() };
+        let error = Exchange { operation: "OP / 0".to_string(), response: "ERROR \"division by zero\"".to_string() };
+
+        assert!(!RunSummary { exchanges: vec![ok], value: Some(1), ..RunSummary::default() }.had_errors());
+        assert!(RunSummary { exchanges: vec![error], value: Some(1), ..RunSummary::default() }.had_errors());
+        assert!(RunSummary { value: None, ..RunSummary::default() }.had_errors());
+    }
+
+    #[test]
+    fn process_files_strict_stops_at_first_error() {
+        let input = Cursor::new(b"/ 0\n+ 1\n".to_vec());
+        let mut server = FakeServer {
+            responses: Cursor::new(b"HELLO 1 0\nSESSION_ID 3fa2\nERROR \"division by zero\"\nOK\nVALUE 1\n".to_vec()),
+            received: Vec::new(),
+        };
+        let config = ClientConfig {
+            strict: true,
+            ..ClientConfig::default()
+        };
+
+        let result = process_files_with_stream(input, &mut server, &config, &NOT_INTERRUPTED);
+
+        assert!(matches!(result, Err(ClientError::ServerErrorMessage(msg)) if msg == "division by zero"));
+        assert_eq!(String::from_utf8(server.received).unwrap(), "HELLO 1 0\nOP / 0\n");
+    }
+
+    #[test]
+    fn process_files_with_only_comments_sends_nothing_but_the_get() {
+        let input = Cursor::new(b"# This adds the initial seed value\n\n   # indented\n".to_vec());
+        let mut server = FakeServer {
+            responses: Cursor::new(b"HELLO 1 0\nSESSION_ID 3fa2\nVALUE 0\n".to_vec()),
+            received: Vec::new(),
+        };
+
+        let summary = process_files_with_stream(input, &mut server, &with_depth(1), &NOT_INTERRUPTED).unwrap();
+
+        assert_eq!(String::from_utf8(server.received).unwrap(), "HELLO 1 0\nGET\n");
+        assert!(summary.exchanges.is_empty());
+        assert_eq!(summary.value, Some(0));
+        assert!(!summary.had_errors());
+    }
+
+    #[test]
+    fn process_files_skips_comments_between_operations() {
+        let input = Cursor::new(b"# This adds the initial seed value\n+ 100\n\n# then divides by zero\n/ 0\n".to_vec());
+        let mut server = FakeServer {
+            responses: Cursor::new(b"HELLO 1 0\nSESSION_ID 3fa2\nOK\nERROR \"division by zero\"\nVALUE 100\n".to_vec()),
+            received: Vec::new(),
+        };
+
+        let summary = process_files_with_stream(input, &mut server, &with_depth(2), &NOT_INTERRUPTED).unwrap();
+
+        assert_eq!(String::from_utf8(server.received).unwrap(), "HELLO 1 0\nOP + 100\nOP / 0\nGET\n");
+        assert_eq!(summary.value, Some(100));
+        // Los comentarios y las líneas vacías cuentan para el número de línea.
+        assert_eq!(summary.line_errors[0].line, 5);
+    }
+
+    #[test]
+    fn process_files_records_the_line_number_of_each_error() {
+        // La línea 2 se saltea (el servidor no acepta HISTORY) y la 5 se envía de a un operando.
+        let input = Cursor::new(b"+ 1\nCLEAR_HISTORY\n/ 0\n+ 2\n* 1 2\n".to_vec());
+        let mut server = FakeServer {
+            responses: Cursor::new(
+                b"HELLO 1 0\nSESSION_ID 3fa2\nOK\nERROR \"division by zero\"\nOK\nOK\nERROR \"overflow\"\nVALUE 6\n"
+                    .to_vec(),
+            ),
+            received: Vec::new(),
+        };
+
+        let summary = process_files_with_stream(input, &mut server, &with_depth(2), &NOT_INTERRUPTED).unwrap();
+
+        assert_eq!(
+            summary.line_errors,
+            vec![
+                LineError { line: 3, content: "OP / 0".to_string(), error: "division by zero".to_string() },
+                LineError { line: 5, content: "OP * 2".to_string(), error: "overflow".to_string() },
+            ]
+        );
+        assert_eq!(summary.line_errors[0].to_string(), "Line 3: ERROR \"division by zero\"");
+    }
+
+    #[test]
+    fn finish_reports_line_errors_outside_strict_mode() {
+        let error = LineError { line: 42, content: "OP / 0".to_string(), error: "division by zero".to_string() };
+        let summary = RunSummary { value: Some(1), line_errors: vec![error.clone()], ..RunSummary::default() };
+        let config = ClientConfig { quiet: true, ..ClientConfig::default() };
+
+        assert!(matches!(finish(&summary, &config), Err(ClientError::LineErrors(errors)) if errors == vec![error]));
+        assert!(!finish(&RunSummary { value: Some(1), ..RunSummary::default() }, &config).unwrap());
+    }
+
+    #[test]
+    fn write_value_writes_without_trailing_newline() {
+        let path = std::env::temp_dir().join(format!("client_write_value_{}.txt", std::process::id()));
+
+        write_value(&path, Some(-15)).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "-15");
+        write_value(&path, None).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+        std::fs::remove_file(&path).unwrap();
+
+        let missing_dir = std::env::temp_dir().join("client_write_value_missing").join("value.txt");
+        assert!(matches!(write_value(&missing_dir, Some(1)), Err(ClientError::InvalidArgument)));
+    }
+
+    #[test]
+    fn no_get_skips_the_final_get() {
+        let input = Cursor::new("OP + 1\nOP + 2\n");
+        let mut server = FakeServer {
+            responses: Cursor::new(b"HELLO 1 0\nSESSION_ID 3fa2\nOK\nOK\n".to_vec()),
+            received: Vec::new(),
+        };
+        let config = ClientConfig {
+            send_final_get: false,
+            ..ClientConfig::default()
+        };
+
+        let summary = process_files_with_stream(input, &mut server, &config, &NOT_INTERRUPTED).unwrap();
+
+        assert_eq!(String::from_utf8(server.received).unwrap(), "HELLO 1 0\nOP + 1\nOP + 2\n");
+        assert_eq!(summary.value, None);
+        assert!(summary.skipped_get);
+        assert!(!summary.had_errors());
+    }
+
+    #[test]
+    fn send_with_retry_resends_after_transient_errors() {
+        let mut reader = BufReader::new(FakeServer {
+            responses: Cursor::new(b"ERROR \"internal error: busy\"\nERROR \"internal error: busy\"\nOK\n".to_vec()),
+            received: Vec::new(),
+        });
+        let mut buf = String::new();
+
+        send_with_retry(&mut reader, &mut buf, b"OP + 1\n", 3).unwrap();
+
+        assert_eq!(buf, "OK\n");
+        assert_eq!(String::from_utf8(reader.into_inner().received).unwrap(), "OP + 1\n".repeat(3));
+    }
+
+    #[test]
+    fn send_with_retry_gives_up_after_max_attempts() {
+        let mut reader = BufReader::new(FakeServer {
+            responses: Cursor::new(b"ERROR \"internal error: busy\"\nERROR \"internal error: busy\"\nOK\n".to_vec()),
+            received: Vec::new(),
+        });
+        let mut buf = String::new();
+
+        send_with_retry(&mut reader, &mut buf, b"OP + 1\n", 2).unwrap();
+
+        assert_eq!(buf, "ERROR \"internal error: busy\"\n");
+        assert_eq!(String::from_utf8(reader.into_inner().received).unwrap(), "OP + 1\n".repeat(2));
+    }
+
+    #[test]
+    fn send_with_retry_does_not_resend_permanent_errors() {
+        let mut reader = BufReader::new(FakeServer {
+            responses: Cursor::new(b"ERROR \"parsing error: unknown operation\"\nOK\n".to_vec()),
+            received: Vec::new(),
+        });
+        let mut buf = String::new();
+
+        send_with_retry(&mut reader, &mut buf, b"OP % 1\n", 3).unwrap();
+
+        assert_eq!(buf, "ERROR \"parsing error: unknown operation\"\n");
+        assert_eq!(String::from_utf8(reader.into_inner().received).unwrap(), "OP % 1\n");
+    }
+
+    #[test]
+    fn retry_ops_records_only_the_last_response() {
+        let input = Cursor::new("+ 1\n+ 2\n");
+        let mut server = FakeServer {
+            responses: Cursor::new(b"HELLO 1 0\nSESSION_ID 3fa2\nERROR \"internal error: busy\"\nOK\nOK\nVALUE 3\n".to_vec()),
+            received: Vec::new(),
+        };
+        let config = ClientConfig {
+            retry_ops: 1,
+            ..ClientConfig::default()
+        };
+
+        let summary = process_files_with_stream(input, &mut server, &config, &NOT_INTERRUPTED).unwrap();
+
+        assert_eq!(
+            String::from_utf8(server.received).unwrap(),
+            "HELLO 1 0\nOP + 1\nOP + 1\nOP + 2\nGET\n"
+        );
+        assert_eq!(summary.exchanges.len(), 2);
+        assert_eq!(summary.value, Some(3));
+        assert!(!summary.had_errors());
+    }
+
+    #[test]
+    fn interrupted_stops_sending_and_still_requests_the_value() {
+        let input = Cursor::new("+ 1\n+ 2\n");
+        let mut server = FakeServer {
+            responses: Cursor::new(b"HELLO 1 0\nSESSION_ID 3fa2\nVALUE 7\n".to_vec()),
+            received: Vec::new(),
+        };
+        let interrupted = AtomicBool::new(true);
+
+        let summary = process_files_with_stream(input, &mut server, &ClientConfig::default(), &interrupted).unwrap();
+
+        assert_eq!(String::from_utf8(server.received).unwrap(), "HELLO 1 0\nGET\n");
+        assert!(summary.interrupted);
+        assert!(summary.exchanges.is_empty());
+        assert_eq!(summary.value, Some(7));
+    }
+
+    /// Entrega una línea por lectura y activa `interrupted` al entregar la línea `interrupt_at`.
+    struct InterruptingInput<'a> {
+        lines: Vec<&'static str>,
+        delivered: usize,
+        interrupt_at: usize,
+        interrupted: &'a AtomicBool,
+    }
+
+    impl Read for InterruptingInput<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let Some(line) = self.lines.get(self.delivered) else {
+                return Ok(0);
+            };
+            self.delivered += 1;
+            if self.delivered == self.interrupt_at {
+                self.interrupted.store(true, Ordering::SeqCst);
+            }
+            buf[..line.len()].copy_from_slice(line.as_bytes());
+            Ok(line.len())
+        }
+    }
+
+    #[test]
+    fn interrupted_reads_the_pending_responses_first() {
+        let interrupted = AtomicBool::new(false);
+        let input = BufReader::new(InterruptingInput {
+            lines: vec!["+ 1\n", "+ 2\n", "+ 3\n"],
+            delivered: 0,
+            interrupt_at: 2,
+            interrupted: &interrupted,
+        });
+        let mut server = FakeServer {
+            responses: Cursor::new(b"HELLO 1 0\nSESSION_ID 3fa2\nOK\nOK\nVALUE 3\n".to_vec()),
+            received: Vec::new(),
+        };
+
+        let summary = process_files_with_stream(input, &mut server, &with_depth(8), &interrupted).unwrap();
+
+        assert_eq!(String::from_utf8(server.received).unwrap(), "HELLO 1 0\nOP + 1\nOP + 2\nGET\n");
+        assert!(summary.interrupted);
+        assert_eq!(summary.exchanges.len(), 2);
+        assert_eq!(summary.value, Some(3));
+    }
+
+    /// Réplica falsa que contesta los `OP` con `OK` y `GET` con `VALUE value`.
+    /// Devuelve su dirección y un hilo que termina con los mensajes recibidos.
+    fn replica(value: i64) -> (SocketAddr, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            writer.write_all(b"HELLO 1 1f\n").unwrap();
+            let mut received = Vec::new();
+            for line in BufReader::new(stream).lines() {
+                let line = line.unwrap();
+                let response = if line == "GET" { format!("VALUE {}\n", value) } else { "OK\n".to_string() };
+                writer.write_all(response.as_bytes()).unwrap();
+                received.push(line);
+            }
+            received
+        });
+        (addr, handle)
+    }
+
+    #[test]
+    fn connection_pool_sends_every_message_to_all_replicas() {
+        let (first, first_handle) = replica(3);
+        let (second, second_handle) = replica(3);
+        let mut pool = ConnectionPool::new(vec![first, second], None).unwrap();
+
+        let responses = pool.apply(&Protocol::Operation("+ 3".to_string()).to_bytes()).unwrap();
+        let value = pool.get().unwrap();
+        drop(pool);
+
+        assert_eq!(responses, vec![Protocol::Ok, Protocol::Ok]);
+        assert_eq!(value, 3);
+        assert_eq!(first_handle.join().unwrap(), vec!["OP + 3", "GET"]);
+        assert_eq!(second_handle.join().unwrap(), vec!["OP + 3", "GET"]);
+    }
+
+    #[test]
+    fn connection_pool_get_fails_when_replicas_disagree() {
+        let (first, _) = replica(3);
+        let (second, _) = replica(4);
+        let mut pool = ConnectionPool::new(vec![first, second], None).unwrap();
+
+        let result = pool.get();
+
+        assert!(
+            matches!(result, Err(ClientError::ServerErrorMessage(message)) if message == "replicas disagree on the value: 3, 4")
+        );
+    }
+
+    #[test]
+    fn process_with_pool_sends_each_line_to_every_replica() {
+        let (first, first_handle) = replica(3);
+        let (second, second_handle) = replica(3);
+        let mut pool = ConnectionPool::new(vec![first, second], None).unwrap();
+        let input = Cursor::new("# suma\n+ 3\n\n+ 0\n");
+
+        let summary = process_with_pool(input, &mut pool, &ClientConfig::default(), &NOT_INTERRUPTED).unwrap();
+        drop(pool);
+
+        assert_eq!(summary.value, Some(3));
+        assert_eq!(summary.exchanges, vec![
+                Exchange { operation: "OP + 3".to_string(), response: "OK".to_string() },
+                Exchange { operation: "OP + 0".to_string(), response: "OK".to_string() },
+            ]);
+        assert_eq!(first_handle.join().unwrap(), vec!["OP + 3", "OP + 0", "GET"]);
+        assert_eq!(second_handle.join().unwrap(), vec!["OP + 3", "OP + 0", "GET"]);
+    }
+
+    #[test]
+    fn process_replicated_gives_up_on_a_replica_that_does_not_answer() {
+        let (first, _) = replica(3);
+        let hung = TcpListener::bind("127.0.0.1:0").unwrap();
+        let config = ClientConfig {
+            replicas: vec![hung.local_addr().unwrap()],
+            timeout: Some(Duration::from_millis(100)),
+            ..ClientConfig::default()
+        };
+
+        let result = process_replicated(first, Cursor::new("+ 1\n"), &config, &NOT_INTERRUPTED);
+
+        assert!(matches!(result, Err(ClientError::Timeout { after }) if after == Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn connection_pool_requires_a_replica() {
+        assert!(matches!(ConnectionPool::new(Vec::new(), None), Err(ClientError::InvalidArgument)));
+    }
+
+    #[test]
+    fn run_parallel_sends_each_chunk_over_its_own_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let mut connections = Vec::new();
+            for _ in 0..3 {
+                let (stream, _) = listener.accept().unwrap();
+                connections.push(thread::spawn(move || {
+                    let mut writer = stream.try_clone().unwrap();
+                    writer.write_all(b"HELLO 1 0\n").unwrap();
+                    let mut received = Vec::new();
+                    for line in BufReader::new(stream).lines() {
+                        let line = line.unwrap();
+                        let response = if line.starts_with("HELLO") { "SESSION_ID 3fa2\n" } else { "OK\n" };
+                        writer.write_all(response.as_bytes()).unwrap();
+                        received.push(line);
+                    }
+                    received
+                }));
+            }
+            let mut received: Vec<Vec<String>> = connections.into_iter().map(|c| c.join().unwrap()).collect();
+            received.sort();
+            received
+        });
+        let lines: Vec<String> = (1..=5).map(|n| format!("+ {}\n", n)).collect();
+        let config = ClientConfig {
+            send_final_get: false,
+            ..ClientConfig::default()
+        };
+
+        let summary = run_parallel(addr, &lines, 3, &config, &NOT_INTERRUPTED).unwrap();
+
+        let operations: Vec<&str> = summary.exchanges.iter().map(|e| e.operation.as_str()).collect();
+        assert_eq!(operations, vec!["OP + 1", "OP + 2", "OP + 3", "OP + 4", "OP + 5"]);
+        assert!(summary.skipped_get);
+        assert!(!summary.had_errors());
+        assert_eq!(
+            server.join().unwrap(),
+            vec![
+                vec!["HELLO 1 0", "OP + 1", "OP + 2"],
+                vec!["HELLO 1 0", "OP + 3", "OP + 4"],
+                vec!["HELLO 1 0", "OP + 5"],
+            ]
+        );
+    }
+
+    #[test]
+    fn run_parallel_numbers_lines_from_the_start_of_the_file() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let connections: Vec<_> = (0..2)
+                .map(|_| {
+                    let (stream, _) = listener.accept().unwrap();
+                    thread::spawn(move || {
+                        let mut writer = stream.try_clone().unwrap();
+                        writer.write_all(b"HELLO 1 0\n").unwrap();
+                        for line in BufReader::new(stream).lines() {
+                            let response = match line.unwrap().as_str() {
+                                "HELLO 1 0" => "SESSION_ID 3fa2\n",
+                                "OP / 0" => "ERROR \"division by zero\"\n",
+                                _ => "OK\n",
+                            };
+                            writer.write_all(response.as_bytes()).unwrap();
+                        }
+                    })
+                })
+                .collect();
+            connections.into_iter().for_each(|c| c.join().unwrap());
+        });
+        let lines: Vec<String> = ["+ 1", "/ 0", "+ 2", "+ 3", "/ 0"].iter().map(|l| format!("{}\n", l)).collect();
+        let config = ClientConfig {
+            send_final_get: false,
+            ..ClientConfig::default()
+        };
+
+        let summary = run_parallel(addr, &lines, 2, &config, &NOT_INTERRUPTED).unwrap();
+        server.join().unwrap();
+
+        let failed: Vec<usize> = summary.line_errors.iter().map(|e| e.line).collect();
+        assert_eq!(failed, vec![2, 5]);
+    }
+
+    #[test]
+    fn over_connection_times_out_when_the_server_does_not_answer() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        // Acepta la conexión pero nunca envía el `HELLO`.
+        let server = thread::spawn(move || listener.accept().unwrap());
+        let timeout = Duration::from_millis(50);
+        let config = ClientConfig {
+            timeout: Some(timeout),
+            ..ClientConfig::default()
+        };
+
+        let result = over_connection(addr, &config, |stream| {
+            process_files_with_stream(Cursor::new("+ 1\n"), stream, &config, &NOT_INTERRUPTED)
+        });
+
+        assert!(matches!(result, Err(ClientError::Timeout { after }) if after == timeout));
+        drop(server.join().unwrap());
+    }
+
+    #[test]
+    fn receive_response_rejects_unexpected_messages() {
+        let mut reader = BufReader::new(Cursor::new(b"VALUE 3\n".to_vec()));
+        let mut buf = String::new();
+
+        let result = receive_response(&mut reader, &mut buf, b"OP + 1\n");
+
+        assert!(matches!(result, Err(ClientError::ProtocolError { expected, got }) if expected == "OK" && got == "VALUE 3"));
+    }
+
+    #[test]
+    fn receive_response_accepts_errors_and_unchecked_messages() {
+        let mut reader = BufReader::new(Cursor::new(b"ERROR \"division by zero\"\nVALUE 3\n".to_vec()));
+        let mut buf = String::new();
+
+        receive_response(&mut reader, &mut buf, b"OP / 0\n").unwrap();
+        buf.clear();
+        receive_response(&mut reader, &mut buf, b"GET_REGISTER A\n").unwrap();
+        assert_eq!(buf, "VALUE 3\n");
+    }
+
+    #[test]
+    fn receive_response_reads_the_whole_history_response() {
+        let mut reader = BufReader::new(Cursor::new(b"HISTORY_START\n+ 5\n* 3\nHISTORY_END\nVALUE 15\n".to_vec()));
+        let mut buf = String::new();
+
+        receive_response(&mut reader, &mut buf, b"GET_HISTORY\n").unwrap();
+        assert_eq!(
+            Protocol::from_bytes(buf.as_bytes()).unwrap(),
+            Protocol::HistoryResponse(vec!["+ 5".to_string(), "* 3".to_string()])
+        );
+        buf.clear();
+        receive_response(&mut reader, &mut buf, b"GET\n").unwrap();
+        assert_eq!(buf, "VALUE 15\n");
+
+        let mut truncated = BufReader::new(Cursor::new(b"HISTORY_START\n+ 5\n".to_vec()));
+        buf.clear();
+        let result = receive_response(&mut truncated, &mut buf, b"GET_HISTORY\n");
+        assert!(matches!(result, Err(ClientError::FailedConnection(_))));
+    }
+
+    #[test]
+    fn last_value_rejects_responses_other_than_value() {
+        let mut reader = BufReader::new(Cursor::new(b"OK\n".to_vec()));
+        let mut buf = String::new();
+
+        let result = last_value_of_calculator(&mut reader, &mut buf);
+
+        let error = result.unwrap_err();
+        assert_eq!(error.to_string(), "ERROR \"protocol error: expected VALUE, got OK\"");
     }
 }
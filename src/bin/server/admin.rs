@@ -0,0 +1,127 @@
+//! Puerto de administración del servidor.
+//! Separa el tráfico de gestión del de datos: por este puerto solo se aceptan `AUTH`, `STATUS`,
+//! `LIST_CLIENTS`, `KILL <id>` y `SHUTDOWN`, y todos salvo `AUTH` requieren haberse autenticado
+//! con el token de administración.
+use std::{
+    io::{BufRead, BufReader, Cursor, Read, Write},
+    net::TcpListener,
+    thread,
+};
+
+use distributed_calculator::protocol::Protocol;
+
+use crate::{
+    handle_client::{handle_auth_message, handle_list_clients_message, handle_status_message, redacted, send_protocol},
+    logger::{LogSender, log_error, log_info, log_warn},
+    server_error::ServerError,
+    server_state::ServerState,
+};
+
+/// Acepta conexiones en el puerto de administración y atiende cada una en un hilo propio.
+/// Termina cuando se pide apagar el servidor.
+pub fn run_admin_listener(listener: TcpListener, shared_state: ServerState, sender: LogSender) {
+    for stream in listener.incoming() {
+        if shared_state.is_shutting_down() {
+            break;
+        }
+        match stream {
+            Ok(stream) => {
+                let state = shared_state.clone();
+                let sender = sender.clone();
+                let peer_addr = stream.peer_addr().map_or("unknown".to_string(), |p| p.to_string());
+                let _ = log_info!(sender, format!("New admin connection from {}", peer_addr));
+                thread::spawn(move || {
+                    if let Err(e) = handle_admin_connection(stream, state, sender.clone(), peer_addr.clone()) {
+                        eprintln!("{}", e);
+                    }
+                    let _ = log_info!(sender, format!("Admin connection from {} closed", peer_addr));
+                });
+            }
+            Err(_) => {
+                let _ = log_error!(sender, ServerError::FailedConnection);
+            }
+        }
+    }
+}
+
+/// Maneja una conexión al puerto de administración.
+/// Lee comandos hasta que el cliente cierra la conexión o pide `SHUTDOWN`.
+///
+/// # Errores
+/// - `ServerError::ReadFailed`: Si falla la lectura del stream.
+/// - `ServerError::WriteFailed`: Si falla la escritura de una respuesta.
+fn handle_admin_connection<RW: Read + Write>(
+    mut stream: RW,
+    state: ServerState,
+    sender: LogSender,
+    peer_addr: String,
+) -> Result<(), ServerError> {
+    let mut is_admin = false;
+    let mut buf = String::new();
+    let mut reader = BufReader::new(&mut stream);
+
+    loop {
+        buf.clear();
+        match reader.read_line(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(_) => {}
+            Err(e) => {
+                let error = ServerError::ReadFailed(e);
+                let _ = log_error!(sender, format!("[admin {}] {}", peer_addr, error));
+                return Err(error);
+            }
+        }
+
+        let protocol = Protocol::from_bytes(buf.trim_end().as_bytes()).map_err(ServerError::invalid_message)?;
+        let _ = log_info!(sender, format!("From [admin {}] received: {}", peer_addr, redacted(&protocol)));
+
+        let shutdown = matches!(protocol, Protocol::Shutdown) && is_admin;
+        let mut response = Cursor::new(Vec::new());
+        let result = match protocol {
+            Protocol::Auth(token) => {
+                is_admin = state.config.admin_token.as_deref() == Some(token.as_str());
+                handle_auth_message(is_admin, &mut response)
+            }
+            _ if !is_admin => send_protocol(
+                Protocol::ErrorOperation("admin authentication required".to_string()),
+                &mut response,
+            ),
+            Protocol::Status => handle_status_message(&state, &mut response),
+            Protocol::ListClients => handle_list_clients_message(&state.registry, is_admin, &mut response),
+            Protocol::Kill(id) => handle_kill_message(&state, &id, &mut response),
+            Protocol::Shutdown => send_protocol(Protocol::Ok, &mut response),
+            _ => send_protocol(
+                Protocol::ErrorOperation(format!("unexpected message: {}", protocol.to_string().trim_end())),
+                &mut response,
+            ),
+        };
+
+        result.and_then(|_| {
+            reader
+                .get_mut()
+                .write_all(response.get_ref())
+                .map_err(ServerError::WriteFailed)
+        })?;
+
+        if shutdown {
+            let _ = log_warn!(sender, format!("[admin {}] Shutdown requested", peer_addr));
+            state.request_shutdown();
+            return Ok(());
+        }
+    }
+}
+
+/// Cierra la conexión de datos con el identificador recibido.
+///
+/// #Errores
+/// `Error::PosionError` - En el caso de que se envenene el lock del registro.
+fn handle_kill_message<RW: Read + Write>(state: &ServerState, id: &str, stream: &mut RW) -> Result<(), ServerError> {
+    let Ok(id) = id.parse::<u64>() else {
+        return send_protocol(Protocol::ErrorOperation(format!("invalid connection id: {}", id)), stream);
+    };
+    if state.registry.kill(id)? {
+        send_protocol(Protocol::Ok, stream)
+    } else {
+        send_protocol(Protocol::ErrorOperation(format!("unknown connection: {}", id)), stream)
+    }
+}
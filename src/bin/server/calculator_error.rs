@@ -0,0 +1,2 @@
+//! Los errores de la calculadora viven en la biblioteca porque `ServerError` los envuelve.
+pub use distributed_calculator::calculator_error::CalculatorError;
@@ -0,0 +1,148 @@
+//! Configuración del servidor.
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use crate::logger::{DEFAULT_LOG_CAPACITY, LogFormat, LogLevel};
+
+/// Intervalo por defecto entre dos `heartbeat` del log.
+pub const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Tiempo por defecto durante el que se puede retomar una sesión después de desconectarse.
+pub const DEFAULT_SESSION_TTL: Duration = Duration::from_secs(300);
+
+/// Forma de delimitar los mensajes en una conexión.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Framing {
+    /// Cada mensaje termina en `\n`
+    #[default]
+    Newline,
+    /// Cada mensaje va precedido por su largo como `u32` big-endian
+    LengthPrefixed,
+}
+
+/// Parámetros con los que corre el servidor.
+/// Los valores por defecto son los que usa el binario si no se indica otra cosa.
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    /// Ruta del archivo de log general. Si es vacía, el log general va solo a stderr
+    pub log_file: String,
+    /// Si es `true`, el log general también se escribe en stderr
+    pub log_stderr: bool,
+    /// Formato del log general: texto o una línea JSON por evento
+    pub log_format: LogFormat,
+    /// Nivel mínimo del log general para los módulos sin filtro propio
+    pub log_level: LogLevel,
+    /// Nivel mínimo del log general por módulo (por ejemplo `handle_client`)
+    pub log_module_filters: HashMap<String, LogLevel>,
+    /// Si es `true`, los mensajes idénticos consecutivos del log general se escriben una sola vez
+    pub log_dedup: bool,
+    /// Si es `true`, el log general se archiva con la fecha en el nombre cada vez que cambia el día
+    pub log_rolling: bool,
+    /// Si es `true`, los logs archivados se comprimen con gzip
+    pub log_compress_rotated: bool,
+    /// Ruta del archivo de auditoría de operaciones
+    pub audit_file: String,
+    /// Ruta del archivo de métricas por conexión
+    pub metrics_file: String,
+    /// Cantidad máxima de eventos encolados para el logger. Si se llena, los eventos se descartan.
+    pub log_channel_capacity: usize,
+    /// Token que habilita los comandos de administración. Si es `None`, nadie puede usarlos.
+    pub admin_token: Option<String>,
+    /// Dirección del puerto de administración. Si es `None`, no se abre.
+    pub admin_address: Option<SocketAddr>,
+    /// Dirección del endpoint HTTP de métricas de Prometheus. Si es `None`, no se abre.
+    #[cfg(feature = "prometheus")]
+    pub metrics_address: Option<SocketAddr>,
+    /// Archivo donde se persiste el estado de la calculadora. Si es `None`, no se persiste.
+    pub state_file: Option<String>,
+    /// Archivo donde se escribe el PID del servidor al arrancar; se borra al apagarlo.
+    /// Si es `None`, no se escribe.
+    pub pid_file: Option<PathBuf>,
+    /// Tiempo de inactividad tras el cual se envían sondas TCP keepalive. Si es `None`, no se configura.
+    pub tcp_keepalive: Option<Duration>,
+    /// Tiempo máximo que una lectura o escritura en una conexión de datos puede quedar
+    /// bloqueada; al vencerse se cierra la conexión con `ServerError::Timeout`. Si es `None`,
+    /// no hay límite.
+    pub io_timeout: Option<Duration>,
+    /// Tiempo durante el que se puede retomar con `RESUME` la sesión de una conexión cerrada.
+    pub session_ttl: Duration,
+    /// Cada cuánto se loguea `heartbeat: server alive` para saber que el servidor sigue vivo
+    /// aunque no tenga actividad. Si es `None`, no se loguea.
+    pub heartbeat_interval: Option<Duration>,
+    /// Si es `true`, desactiva el algoritmo de Nagle en cada conexión para reducir la latencia
+    /// de mensajes chicos. Se puede desactivar para cargas masivas.
+    pub tcp_nodelay: bool,
+    /// Cantidad máxima de conexiones simultáneas. Si es `None`, no hay límite.
+    pub max_connections: Option<usize>,
+    /// Cantidad máxima de conexiones que se atienden a la vez. Las que lleguen de más esperan
+    /// en el backlog del sistema operativo hasta que termine alguna. Si es `None`, no hay límite.
+    pub max_in_flight: Option<usize>,
+    /// Cantidad de hilos que atienden conexiones. Si es `None`, se usa un hilo por conexión.
+    pub thread_pool_size: Option<usize>,
+    /// Cantidad máxima de respuestas que se encolan antes de enviarlas cuando el cliente manda
+    /// varios mensajes sin esperar respuesta (pipelining).
+    pub pipeline_depth: usize,
+    /// Framing de los mensajes en las conexiones de datos.
+    pub framing: Framing,
+    /// Acumulación con la que arranca la calculadora si no hay un archivo de estado que restaurar.
+    pub initial_accumulation: i64,
+    /// Si es `false`, `HISTORY` y `CLEAR_HISTORY` responden con un error.
+    pub history: bool,
+    /// Si es `false`, los comandos de registros responden con un error.
+    pub registers: bool,
+    /// Versión que el servidor informa con `VERSION`. Por defecto es la versión del crate.
+    pub version: String,
+    /// Momento en que arrancó el servidor. `Server::run` lo actualiza al iniciar.
+    pub start_time: Instant,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            log_file: "./logs/server.log".to_string(),
+            log_stderr: false,
+            log_format: LogFormat::Text,
+            log_level: LogLevel::Debug,
+            log_module_filters: HashMap::new(),
+            log_dedup: false,
+            log_rolling: false,
+            log_compress_rotated: true,
+            audit_file: "./logs/audit.log".to_string(),
+            metrics_file: "./logs/metrics.log".to_string(),
+            log_channel_capacity: DEFAULT_LOG_CAPACITY,
+            admin_token: None,
+            admin_address: None,
+            #[cfg(feature = "prometheus")]
+            metrics_address: None,
+            state_file: None,
+            pid_file: None,
+            tcp_keepalive: None,
+            io_timeout: None,
+            session_ttl: DEFAULT_SESSION_TTL,
+            heartbeat_interval: Some(DEFAULT_HEARTBEAT_INTERVAL),
+            tcp_nodelay: true,
+            max_connections: None,
+            max_in_flight: None,
+            thread_pool_size: None,
+            pipeline_depth: 32,
+            framing: Framing::Newline,
+            initial_accumulation: 0,
+            history: true,
+            registers: true,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            start_time: Instant::now(),
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Devuelve `true` si no hace falta más que la acumulación: sin historial ni registros
+    /// el servidor usa una `LockFreeCalculator` en lugar de lockear la calculadora completa.
+    pub fn lock_free(&self) -> bool {
+        !self.history && !self.registers
+    }
+}
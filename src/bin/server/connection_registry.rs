@@ -0,0 +1,203 @@
+//! Registro de las conexiones activas del servidor.
+//! Cada conexión recibe un identificador `u64` al ser aceptada y se mantiene en el registro
+//! hasta que se cierra. Se usa para los comandos de administración `LIST_CLIENTS` y `KILL`.
+use std::{
+    collections::HashMap,
+    net::{Shutdown, TcpStream},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use crate::server_error::ServerError;
+
+/// Datos de una conexión activa.
+#[derive(Clone, Debug)]
+pub struct ConnectionInfo {
+    /// Dirección del cliente
+    pub peer_addr: String,
+    /// Momento en que se aceptó la conexión
+    pub connected_at: SystemTime,
+    /// Cantidad de operaciones enviadas por el cliente
+    pub ops_count: u64,
+}
+
+/// Registro compartido de conexiones. Clonarlo es barato: todas las copias comparten el mismo mapa.
+#[derive(Clone, Default)]
+pub struct ConnectionRegistry {
+    connections: Arc<Mutex<HashMap<u64, ConnectionInfo>>>,
+    streams: Arc<Mutex<HashMap<u64, TcpStream>>>,
+    next_id: Arc<Mutex<u64>>,
+}
+
+impl ConnectionRegistry {
+    /// Crea un registro vacío.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registra una nueva conexión y devuelve el identificador asignado.
+    ///
+    /// #Errores
+    /// `Error::PosionError` - En el caso de que se envenene el lock.
+    pub fn register(&self, peer_addr: &str) -> Result<u64, ServerError> {
+        let id = {
+            let mut next_id = self.next_id.lock().map_err(|_| ServerError::PoisonError)?;
+            *next_id += 1;
+            *next_id
+        };
+        let info = ConnectionInfo {
+            peer_addr: peer_addr.to_string(),
+            connected_at: SystemTime::now(),
+            ops_count: 0,
+        };
+        self.connections
+            .lock()
+            .map_err(|_| ServerError::PoisonError)?
+            .insert(id, info);
+        Ok(id)
+    }
+
+    /// Guarda una copia del stream de la conexión `id` para poder cerrarla con `kill`.
+    ///
+    /// #Errores
+    /// `Error::PosionError` - En el caso de que se envenene el lock.
+    pub fn attach_stream(&self, id: u64, stream: TcpStream) -> Result<(), ServerError> {
+        self.streams
+            .lock()
+            .map_err(|_| ServerError::PoisonError)?
+            .insert(id, stream);
+        Ok(())
+    }
+
+    /// Cierra el stream de la conexión `id`. El hilo que la atiende ve el cierre y la quita del registro.
+    /// Devuelve `false` si no hay una conexión con ese identificador.
+    ///
+    /// #Errores
+    /// `Error::PosionError` - En el caso de que se envenene el lock.
+    pub fn kill(&self, id: u64) -> Result<bool, ServerError> {
+        let streams = self.streams.lock().map_err(|_| ServerError::PoisonError)?;
+        match streams.get(&id) {
+            Some(stream) => {
+                let _ = stream.shutdown(Shutdown::Both);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Incrementa la cantidad de operaciones de la conexión `id`, si sigue registrada.
+    ///
+    /// #Errores
+    /// `Error::PosionError` - En el caso de que se envenene el lock.
+    pub fn increment_ops(&self, id: u64) -> Result<(), ServerError> {
+        let mut connections = self.connections.lock().map_err(|_| ServerError::PoisonError)?;
+        if let Some(info) = connections.get_mut(&id) {
+            info.ops_count += 1;
+        }
+        Ok(())
+    }
+
+    /// Quita la conexión `id` del registro.
+    ///
+    /// #Errores
+    /// `Error::PosionError` - En el caso de que se envenene el lock.
+    pub fn remove(&self, id: u64) -> Result<(), ServerError> {
+        self.connections
+            .lock()
+            .map_err(|_| ServerError::PoisonError)?
+            .remove(&id);
+        self.streams
+            .lock()
+            .map_err(|_| ServerError::PoisonError)?
+            .remove(&id);
+        Ok(())
+    }
+
+    /// Devuelve la cantidad de conexiones activas.
+    ///
+    /// #Errores
+    /// `Error::PosionError` - En el caso de que se envenene el lock.
+    pub fn count(&self) -> Result<usize, ServerError> {
+        Ok(self.connections.lock().map_err(|_| ServerError::PoisonError)?.len())
+    }
+
+    /// Devuelve las conexiones activas ordenadas por identificador.
+    ///
+    /// #Errores
+    /// `Error::PosionError` - En el caso de que se envenene el lock.
+    pub fn list(&self) -> Result<Vec<(u64, ConnectionInfo)>, ServerError> {
+        let connections = self.connections.lock().map_err(|_| ServerError::PoisonError)?;
+        let mut list: Vec<(u64, ConnectionInfo)> = connections
+            .iter()
+            .map(|(id, info)| (*id, info.clone()))
+            .collect();
+        list.sort_by_key(|(id, _)| *id);
+        Ok(list)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Read,
+        net::{TcpListener, TcpStream},
+    };
+
+    use crate::connection_registry::ConnectionRegistry;
+
+    #[test]
+    fn register_assigns_increasing_ids() {
+        let registry = ConnectionRegistry::new();
+        let first = registry.register("127.0.0.1:1000").unwrap();
+        let second = registry.register("127.0.0.1:2000").unwrap();
+
+        assert!(second > first);
+        let list = registry.list().unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].1.peer_addr, "127.0.0.1:1000");
+        assert_eq!(list[1].1.ops_count, 0);
+    }
+
+    #[test]
+    fn increment_ops_updates_only_that_connection() {
+        let registry = ConnectionRegistry::new();
+        let first = registry.register("127.0.0.1:1000").unwrap();
+        let second = registry.register("127.0.0.1:2000").unwrap();
+
+        registry.increment_ops(first).unwrap();
+        registry.increment_ops(first).unwrap();
+        registry.increment_ops(second).unwrap();
+
+        let list = registry.list().unwrap();
+        assert_eq!(list[0].1.ops_count, 2);
+        assert_eq!(list[1].1.ops_count, 1);
+    }
+
+    #[test]
+    fn remove_drops_connection() {
+        let registry = ConnectionRegistry::new();
+        let id = registry.register("127.0.0.1:1000").unwrap();
+        let clone = registry.clone();
+
+        clone.remove(id).unwrap();
+        registry.increment_ops(id).unwrap();
+
+        assert!(registry.list().unwrap().is_empty());
+        assert_eq!(registry.count().unwrap(), 0);
+    }
+
+    #[test]
+    fn kill_closes_attached_stream() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let registry = ConnectionRegistry::new();
+        let id = registry.register("127.0.0.1:1000").unwrap();
+        registry.attach_stream(id, stream).unwrap();
+
+        assert!(registry.kill(id).unwrap());
+        assert!(!registry.kill(id + 1).unwrap());
+        let mut buf = [0; 1];
+        assert_eq!(client.read(&mut buf).unwrap(), 0);
+    }
+}
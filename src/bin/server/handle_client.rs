@@ -1,67 +1,591 @@
 //! Modulo de manejo de clientes conectados al servidor.
 use std::{
-    io::{BufRead, BufReader, Read, Write}, net::TcpStream, str::FromStr, sync::{mpsc::Sender, Arc}
+    collections::{HashMap, VecDeque}, io::{self, BufRead, BufReader, Cursor, Read, Write}, str::FromStr, sync::mpsc, time::{Duration, Instant, SystemTime}
 };
 
-use distributed_calculator::protocol::Protocol;
-use crate::{calculator::Calculator, logger::LogEvent, operation::Operation, server_error::ServerError};
+use distributed_calculator::{
+    capabilities::ServerCapabilities,
+    protocol::{PROTOCOL_VERSION, Protocol, read_frame, transient_error, write_frame},
+};
+use crate::{
+    calculator::{Calculator, CalculatorState, LockFreeCalculator},
+    config::Framing,
+    connection_registry::ConnectionRegistry,
+    logger::{LogEvent, LogSender, log_error, log_info, log_warn},
+    namespaces::DEFAULT_NAMESPACE,
+    operation::{Operation, parse_operand},
+    peer_stream::PeerStream,
+    server_error::ServerError,
+    server_state::ServerState,
+    sessions::{SessionState, SessionStore},
+    shared_calculator::SharedCalculator,
+    server_stats::ServerStats,
+    subscribers::spawn_push_thread,
+};
 
 /// Maneja la conexión con un cliente.
 /// Lee mensajes del cliente, los procesa y envía respuestas.
-/// Recibe un stream de lectura/escritura y una referencia al calculadora compartida.
+/// Recibe el stream del cliente (que conoce su dirección), el estado compartido del servidor,
+/// el canal del logger y el identificador de la conexión en el registro.
+/// Cada mensaje recibido, cada respuesta enviada y cada error se loguean con la dirección del cliente.
+/// Los mensajes con bytes de control se rechazan con un error y un aviso en el log, sin loguear su contenido.
+/// Los comandos de administración solo se aceptan después de un `AUTH` con el token correcto.
+/// Entre `BEGIN` y `COMMIT` las operaciones se encolan en la conexión y se responden con `OK`;
+/// `COMMIT` las aplica todas juntas y `ROLLBACK` las descarta.
+/// `DRAIN` espera a que terminen los mensajes que modifican el estado en las demás conexiones y
+/// los frena hasta que la misma conexión envíe `RELEASE` o se cierre.
+/// Al aceptar la conexión el servidor envía `HELLO` con sus capacidades; el cliente puede
+/// responder con otro `HELLO` con las que elige de entre ellas, que quedan en el
+/// `ConnectionState` y limitan los comandos que se aceptan. A ese `HELLO` se responde con el
+/// identificador de la sesión; al cerrarse la conexión se guarda su estado para que otra lo
+/// retome con `RESUME` antes de `session_ttl`.
+/// Las respuestas se encolan mientras queden mensajes completos ya recibidos (pipelining) y se
+/// envían en orden, juntas, cuando no hay más mensajes pendientes o la cola llega a `pipeline_depth`.
+/// Al terminar la conexión se envían al logger sus métricas de latencia y throughput.
 /// Devuelve un resultado indicando éxito o error.
 ///
 /// # Errores
 /// - `ServerError::ReadFailed`: Si falla la lectura del stream.
-pub fn handle_connection(
-    mut stream: TcpStream,
-    calculator: Arc<std::sync::Mutex<Calculator>>,
-    sender: Sender<LogEvent>
+/// - `ServerError::WriteFailed`: Si falla la escritura de una respuesta.
+/// - `ServerError::Timeout`: Si se vence `io_timeout` esperando una lectura o una escritura.
+pub fn handle_connection<RW: Read + Write>(
+    stream: PeerStream<RW>,
+    state: ServerState,
+    sender: LogSender,
+    connection_id: u64,
 ) -> Result<(), ServerError> {
-  
-    let peer_addr = match stream.peer_addr() {
-        Ok(addr) => addr.to_string(),
-        Err(_) => "unknown".to_string(),
-    };
-
+    let io_timeout = state.config.io_timeout;
+    serve_connection(stream, state, sender, connection_id).map_err(|e| e.with_timeout(io_timeout))
+}
 
-    let mut buf = String::new();
+/// Atiende la conexión como se describe en `handle_connection`, sin distinguir los tiempos de
+/// espera vencidos del resto de los errores de E/S.
+fn serve_connection<RW: Read + Write>(
+    mut stream: PeerStream<RW>,
+    state: ServerState,
+    sender: LogSender,
+    connection_id: u64,
+) -> Result<(), ServerError> {
+    let peer_addr = stream.peer_addr().to_string();
+    // `SELECT` cambia la calculadora de la conexión; la acumulación sin locks solo reemplaza
+    // a la de `default`.
+    let mut calculator = state.calculator.clone();
+    let mut lock_free = state.lock_free.clone();
+    let mut namespace = DEFAULT_NAMESPACE.to_string();
+    let mut transaction: Option<Vec<Operation>> = None;
+    let mut is_admin = false;
+    let mut connection = ConnectionState::default();
+    // Lock de escritura de `state.drain` tomado con `DRAIN`; se suelta con `RELEASE` o al cerrar la conexión.
+    let mut drain_guard = None;
+    let mut buf = Vec::new();
+    let mut pending: VecDeque<Vec<u8>> = VecDeque::with_capacity(state.config.pipeline_depth);
     let mut reader = BufReader::new(&mut stream);
+    let mut metrics = ConnectionMetrics::new();
+    let mut bytes_read = 0;
+    let mut op_count = 0;
 
-    loop {
-        buf.clear();
+    let framing = state.config.framing;
+    #[cfg(feature = "otel")]
+    let trace = crate::telemetry::ConnectionTrace::start(&peer_addr);
 
-        let bytes_read_result = reader.read_line(&mut buf);
+    // El servidor abre el handshake ofreciendo sus capacidades; el cliente responde con las que elige.
+    let offered = server_capabilities(&state, reader.get_ref().supports_push());
+    pending.push_back(Protocol::Hello { version: PROTOCOL_VERSION, capabilities: offered.bits() }.to_bytes());
+    flush_responses(reader.get_mut(), &mut pending, framing, &sender, &peer_addr)?;
 
-        match bytes_read_result {
-            Ok(n) => {
-                if n == 0 {
-                    // acá se podría logear
-                    let _ = sender.send(LogEvent::Info(format!("[{}] Connection closed by client", peer_addr)));
-                    return Ok(());
-                }
+    loop {
+        let received = match read_message(&mut reader, framing, &mut buf) {
+            Ok(Some((received, size))) => {
+                bytes_read += size;
+                received
+            }
+            Ok(None) => {
+                metrics.send(&sender, &peer_addr, connection_id);
+                let session = SessionState { namespace, transaction, negotiated: connection.capabilities };
+                save_session(&state, connection.session_id, session, &sender, &peer_addr);
+                flush_responses(reader.get_mut(), &mut pending, framing, &sender, &peer_addr)?;
+                let _ = log_info!(
+                    sender,
+                    "Connection closed by client",
+                    peer_addr => peer_addr,
+                    bytes_read => bytes_read,
+                    op_count => op_count
+                );
+                return Ok(());
+            }
+            Err(e) => {
+                let e = e.with_timeout(state.config.io_timeout);
+                metrics.send(&sender, &peer_addr, connection_id);
+                let session = SessionState { namespace, transaction, negotiated: connection.capabilities };
+                save_session(&state, connection.session_id, session, &sender, &peer_addr);
+                let _ = log_error!(sender, format!( "[{}] {}",peer_addr, e));
+                return Err(e);
             }
-            Err(_) => {
-                let _ = sender.send(LogEvent::Error(format!( "[{}] {}",peer_addr, ServerError::ReadFailed.to_string())));
-                return Err(ServerError::ReadFailed);
+        };
+        let received_at = Instant::now();
+
+        let protocol = match received {
+            Received::Message(protocol) => protocol,
+            // Se rechaza sin loguear su contenido, para que los bytes de control no lleguen al log.
+            Received::NonPrintable => {
+                let _ = log_warn!(sender, format!("[{}] Rejected a message with non-printable bytes", peer_addr));
+                let response = Protocol::ErrorOperation("non-printable bytes in message".to_string()).to_bytes();
+                metrics.record(received_at.elapsed());
+                queue_response(&mut reader, &mut pending, response, &state, &sender, &peer_addr)?;
+                continue;
             }
         };
+        let _ = log_info!(sender, format!("From [{}] received: {}", peer_addr, redacted(&protocol)));
+
+        let mutates_state = matches!(
+            protocol,
+            Protocol::Operation(_)
+                | Protocol::ClearHistory
+                | Protocol::SetRegister(_, _)
+                | Protocol::Swap(_, _)
+                | Protocol::Rename { .. }
+                | Protocol::Restore(_)
+                | Protocol::Copy { .. }
+                | Protocol::Commit
+                | Protocol::RollbackTo(_)
+        );
 
-        let protocol: Protocol = Protocol::from_bytes(buf.trim_end().as_bytes());
+        let is_operation = matches!(protocol, Protocol::Operation(_));
+        let queued = is_operation && transaction.is_some();
+        if is_operation {
+            op_count += 1;
+        }
+        let is_commit = matches!(protocol, Protocol::Commit);
+        // El archivo de estado guarda solo `default`: las operaciones de otros namespaces y las
+        // que quedan encoladas en una transacción no lo cambian.
+        let persists = mutates_state
+            && match &protocol {
+                Protocol::Restore(_) => true,
+                Protocol::Copy { to, .. } => to == DEFAULT_NAMESPACE,
+                _ => namespace == DEFAULT_NAMESPACE && !queued,
+            };
+        #[cfg(feature = "otel")]
+        let span = trace.message_span(&protocol);
+
+        // La conexión que hizo `DRAIN` ya tiene el lock de escritura: sus mensajes pasan sin esperar.
+        let _in_flight = if mutates_state && drain_guard.is_none() {
+            Some(state.drain.read().map_err(|_| ServerError::PoisonError)?)
+        } else {
+            None
+        };
 
-        let _ = sender.send(LogEvent::Info(format!("From [{}] received: {}", peer_addr, protocol)));
+        let not_negotiated = connection
+            .capabilities
+            .and_then(|caps| ServerCapabilities::required_by(&protocol).filter(|required| !caps.contains(*required)))
+            .map(|capability| format!("{} was not negotiated", capability));
 
-        match protocol {
-            Protocol::Operation(args) => {
-                handle_operation_message(&calculator, reader.get_mut(), args)
+        // La respuesta se arma en memoria para poder loguearla antes de enviarla.
+        let mut response = Cursor::new(Vec::new());
+        let result = match protocol {
+            _ if not_negotiated.is_some() => {
+                send_protocol(Protocol::ErrorOperation(not_negotiated.unwrap_or_default()), &mut response)
             }
-            Protocol::Get => handle_get_message(&calculator, reader.get_mut()),
+            Protocol::Operation(args) if queued => state
+                .registry
+                .increment_ops(connection_id)
+                .and_then(|_| match Operation::from_str(&args) {
+                    Ok(op) => {
+                        transaction.get_or_insert_with(Vec::new).push(op);
+                        send_protocol(Protocol::Ok, &mut response)
+                    }
+                    Err(e) => send_protocol(Protocol::ErrorOperation(e.to_string()), &mut response),
+                }),
+            Protocol::Operation(args) => state
+                .registry
+                .increment_ops(connection_id)
+                .and_then(|_| match &lock_free {
+                    Some(fast) => handle_lock_free_operation_message(fast, &mut response, args, &sender, &peer_addr),
+                    None => handle_operation_message(&calculator, &mut response, args, &sender, &peer_addr),
+                }),
+            Protocol::Get => match &lock_free {
+                Some(fast) => send_protocol(Protocol::Value(fast.accumulation().to_string()), &mut response),
+                None => handle_get_message(&calculator, &mut response),
+            },
+            Protocol::History | Protocol::ClearHistory | Protocol::GetHistory | Protocol::RollbackTo(_)
+                if !state.config.history =>
+            {
+                send_protocol(Protocol::ErrorOperation("history is disabled".to_string()), &mut response)
+            }
+            Protocol::SetRegister(_, _)
+            | Protocol::GetRegister(_)
+            | Protocol::Swap(_, _)
+            | Protocol::Rename { .. }
+            | Protocol::ListRegisters
+            | Protocol::MultiGet(_)
+                if !state.config.registers =>
+            {
+                send_protocol(Protocol::ErrorOperation("registers are disabled".to_string()), &mut response)
+            }
+            Protocol::History => handle_history_message(&calculator, &mut response),
+            Protocol::GetHistory => handle_get_history_message(&calculator, &mut response),
+            Protocol::RollbackTo(step) => handle_rollback_to_message(&calculator, &mut response, step),
+            Protocol::ClearHistory => handle_clear_history_message(&calculator, &mut response),
+            Protocol::SetRegister(name, value) => {
+                handle_set_register_message(&calculator, &mut response, &name, &value)
+            }
+            Protocol::GetRegister(name) => {
+                handle_get_register_message(&calculator, &mut response, &name)
+            }
+            Protocol::MultiGet(names) => handle_multi_get_message(&calculator, &mut response, &names),
+            Protocol::Swap(a, b) => handle_swap_message(&calculator, &mut response, &a, &b),
+            Protocol::Rename { from, to } => handle_rename_message(&calculator, &mut response, &from, &to),
+            Protocol::ListRegisters => calculator
+                .read(Calculator::register_names)
+                .and_then(|names| send_protocol(Protocol::RegisterList(names), &mut response)),
+            Protocol::Select(name) => match state.namespaces.get_or_create(&name) {
+                Ok(selected) => {
+                    calculator = selected;
+                    lock_free = state.lock_free.clone().filter(|_| name == DEFAULT_NAMESPACE);
+                    namespace = name;
+                    send_protocol(Protocol::Ok, &mut response)
+                }
+                Err(e) => Err(e),
+            },
+            Protocol::Copy { from, to } => handle_copy_message(&state, &from, &to, &mut response),
+            Protocol::Begin if transaction.is_some() => {
+                send_protocol(Protocol::ErrorOperation("transaction already open".to_string()), &mut response)
+            }
+            Protocol::Begin => {
+                transaction = Some(Vec::new());
+                send_protocol(Protocol::Ok, &mut response)
+            }
+            Protocol::Commit => match transaction.take() {
+                Some(operations) => handle_commit_message(
+                    &calculator,
+                    lock_free.as_deref(),
+                    &operations,
+                    &mut response,
+                    &sender,
+                    &peer_addr,
+                ),
+                None => send_protocol(Protocol::ErrorOperation("no transaction open".to_string()), &mut response),
+            },
+            Protocol::Rollback => match transaction.take() {
+                Some(_) => send_protocol(Protocol::Ok, &mut response),
+                None => send_protocol(Protocol::ErrorOperation("no transaction open".to_string()), &mut response),
+            },
+            // Se responde sin llegar a la calculadora: ni la acumulación ni el historial cambian.
+            Protocol::Noop => send_protocol(Protocol::Ok, &mut response),
+            Protocol::Echo(payload) => send_protocol(Protocol::EchoReply(payload), &mut response),
+            Protocol::Drain if drain_guard.is_some() => {
+                send_protocol(Protocol::ErrorOperation("already draining".to_string()), &mut response)
+            }
+            Protocol::Drain => match state.drain.write() {
+                Ok(guard) => {
+                    drain_guard = Some(guard);
+                    send_protocol(Protocol::Ok, &mut response)
+                }
+                Err(_) => Err(ServerError::PoisonError),
+            },
+            Protocol::Release => match drain_guard.take() {
+                Some(_) => send_protocol(Protocol::Ok, &mut response),
+                None => send_protocol(Protocol::ErrorOperation("not draining".to_string()), &mut response),
+            },
+            Protocol::Subscribe => {
+                handle_subscribe_message(&state, reader.get_ref(), connection_id, &namespace, &mut response)
+            }
+            Protocol::Unsubscribe => state
+                .subscribers
+                .unsubscribe(connection_id)
+                .and_then(|_| send_protocol(Protocol::Ok, &mut response)),
+            Protocol::Auth(token) => {
+                is_admin = state.config.admin_token.as_deref() == Some(token.as_str());
+                handle_auth_message(is_admin, &mut response)
+            }
+            // Con un puerto de administración aparte, estos comandos solo se atienden ahí.
+            Protocol::ListClients if state.config.admin_address.is_none() => {
+                handle_list_clients_message(&state.registry, is_admin, &mut response)
+            }
+            Protocol::Status if state.config.admin_address.is_none() => handle_status_message(&state, &mut response),
+            Protocol::Snapshot => handle_snapshot_message(&state, &mut response),
+            Protocol::Restore(id) => handle_restore_message(&state, &id, &mut response),
+            Protocol::Hello { capabilities, .. } => {
+                // Se descarta lo que el cliente elija fuera de lo ofrecido.
+                connection.capabilities = Some(ServerCapabilities::from_bits(capabilities) & offered);
+                let id = connection.session_id.get_or_insert_with(SessionStore::new_id).clone();
+                send_protocol(Protocol::SessionId(id), &mut response)
+            }
+            Protocol::Resume(id) => match state.sessions.resume(&id, state.config.session_ttl) {
+                Ok(Some(session)) => state.namespaces.get_or_create(&session.namespace).and_then(|selected| {
+                    calculator = selected;
+                    lock_free = state.lock_free.clone().filter(|_| session.namespace == DEFAULT_NAMESPACE);
+                    namespace = session.namespace;
+                    transaction = session.transaction;
+                    connection = ConnectionState { capabilities: session.negotiated, session_id: Some(id) };
+                    send_protocol(Protocol::Ok, &mut response)
+                }),
+                Ok(None) => send_protocol(Protocol::ErrorOperation("unknown session".to_string()), &mut response),
+                Err(e) => Err(e),
+            },
+            Protocol::Version => handle_version_message(&state, &mut response),
             _ => send_protocol(
-                Protocol::ErrorOperation(format!("unexpected message: {}", protocol).to_string()),
-                reader.get_mut(),
+                Protocol::ErrorOperation(format!("unexpected message: {}", protocol.to_string().trim_end())),
+                &mut response,
             ),
-        }?;
+        };
+
+        #[cfg(feature = "otel")]
+        if let Some(span) = span {
+            crate::telemetry::finish_message_span(span, response.get_ref());
+        }
+
+        #[cfg(feature = "prometheus")]
+        if is_operation && !queued && result.is_ok() {
+            state.metrics.operation_applied(!response.get_ref().starts_with(b"ERROR"));
+        }
+
+        if (is_operation && !queued || is_commit) && result.is_ok() && response.get_ref().starts_with(b"OK") {
+            let value = match &lock_free {
+                Some(fast) => Ok(fast.accumulation()),
+                None => calculator.accumulation(),
+            };
+            if let Err(e) = value.and_then(|value| state.subscribers.publish(&namespace, value)) {
+                let _ = log_error!(sender, format!("[{}] {}", peer_addr, e));
+            }
+        }
+
+        if result.is_ok() && persists && let Err(e) = persist_state(&state) {
+            let _ = log_error!(sender, format!("[{}] {}", peer_addr, e));
+        }
+
+        metrics.record(received_at.elapsed());
+        if let Err(e) = result {
+            metrics.send(&sender, &peer_addr, connection_id);
+            flush_responses(reader.get_mut(), &mut pending, framing, &sender, &peer_addr)?;
+            let _ = log_error!(sender, format!("[{}] {}", peer_addr, e));
+            return Err(e);
+        }
+
+        queue_response(&mut reader, &mut pending, response.into_inner(), &state, &sender, &peer_addr)?;
+    }
+}
+
+/// Encola la respuesta a un mensaje y envía las pendientes si no quedan más pedidos completos en
+/// el buffer o si la cola llegó a `pipeline_depth`.
+///
+/// # Errores
+/// - `ServerError::WriteFailed`: Si falla la escritura en el stream.
+fn queue_response<RW: Read + Write>(
+    reader: &mut BufReader<RW>,
+    pending: &mut VecDeque<Vec<u8>>,
+    response: Vec<u8>,
+    state: &ServerState,
+    sender: &LogSender,
+    peer_addr: &str,
+) -> Result<(), ServerError> {
+    let framing = state.config.framing;
+    pending.push_back(response);
+    let more_requests_buffered = match framing {
+        Framing::Newline => reader.buffer().contains(&b'\n'),
+        Framing::LengthPrefixed => !reader.buffer().is_empty(),
+    };
+    if !more_requests_buffered || pending.len() >= state.config.pipeline_depth {
+        flush_responses(reader.get_mut(), pending, framing, sender, peer_addr)?;
+    }
+    Ok(())
+}
+
+/// Texto con el que se loguea un mensaje recibido: el mismo de su `Display`, salvo el token de
+/// `AUTH`, que se reemplaza por `***` para que el secreto no quede escrito en el log.
+pub fn redacted(protocol: &Protocol) -> String {
+    match protocol {
+        Protocol::Auth(_) => "AUTH ***\n".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Indica si el mensaje, tal como llegó, tiene bytes de control (menores a 0x20) que no sean tab
+/// ni salto de línea, o no es UTF-8 válido. Incluye los que el parser descartaría como espacios,
+/// como el tab vertical o el retorno de carro.
+fn has_non_printable_bytes(message: &[u8]) -> bool {
+    std::str::from_utf8(message).is_err() || message.iter().any(|&byte| byte < 0x20 && byte != b'\t' && byte != b'\n')
+}
+
+/// Lo que se acuerda con el cliente en el handshake `HELLO`, que empieza el servidor al aceptar
+/// la conexión.
+#[derive(Default)]
+struct ConnectionState {
+    /// Capacidades que el cliente eligió de entre las que ofreció el servidor. Sin handshake es
+    /// `None` y se acepta todo lo que el servidor tenga habilitado.
+    capabilities: Option<ServerCapabilities>,
+    /// Se asigna al responder `HELLO` y al retomar una sesión con `RESUME`.
+    session_id: Option<String>,
+}
+
+/// Guarda el estado de la conexión para que se pueda retomar con `RESUME`, si hizo `HELLO`.
+/// Un error al guardarla solo se loguea: la conexión ya se está cerrando.
+fn save_session(
+    state: &ServerState,
+    session_id: Option<String>,
+    session: SessionState,
+    sender: &LogSender,
+    peer_addr: &str,
+) {
+    if let Some(id) = session_id
+        && let Err(e) = state.sessions.save(id, session, state.config.session_ttl)
+    {
+        let _ = log_error!(sender, format!("[{}] {}", peer_addr, e));
+    }
+}
+
+/// Mensajes atendidos por una conexión y el tiempo que llevó responderlos, para las métricas
+/// que se envían al logger cuando la conexión termina.
+struct ConnectionMetrics {
+    started: Instant,
+    requests: u64,
+    busy: Duration,
+}
+
+impl ConnectionMetrics {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            requests: 0,
+            busy: Duration::ZERO,
+        }
+    }
+
+    /// Registra un mensaje cuya respuesta se armó en `elapsed`.
+    fn record(&mut self, elapsed: Duration) {
+        self.requests += 1;
+        self.busy += elapsed;
+    }
+
+    /// Envía como `LogEvent::Metric` la cantidad de mensajes, los mensajes por segundo desde que
+    /// se abrió la conexión y la latencia promedio, con el cliente y la conexión como tags.
+    fn send(&self, sender: &LogSender, peer_addr: &str, connection_id: u64) {
+        let tags = HashMap::from([
+            ("peer".to_string(), peer_addr.to_string()),
+            ("connection_id".to_string(), connection_id.to_string()),
+        ]);
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let average_latency = if self.requests == 0 { 0.0 } else { self.busy.as_secs_f64() / self.requests as f64 };
+        let timestamp = SystemTime::now();
+        for (name, value) in [
+            ("connection_requests_total", self.requests as f64),
+            ("connection_requests_per_second", if elapsed > 0.0 { self.requests as f64 / elapsed } else { 0.0 }),
+            ("connection_latency_avg_seconds", average_latency),
+        ] {
+            let _ = sender.send(LogEvent::Metric {
+                name: name.to_string(),
+                value,
+                tags: tags.clone(),
+                timestamp,
+            });
+        }
+    }
+}
+
+/// Mensaje leído del cliente.
+enum Received {
+    /// Un mensaje sin bytes de control, ya interpretado.
+    Message(Protocol),
+    /// Un mensaje con bytes de control o que no es UTF-8 válido: no se interpreta ni se loguea.
+    NonPrintable,
+}
+
+/// Lee el próximo mensaje del cliente según el framing configurado y devuelve también los bytes
+/// que ocupó en el stream. Los bytes se revisan tal como llegaron, antes de interpretarlos.
+/// Devuelve `None` si el cliente cerró la conexión.
+///
+/// # Errores
+/// - `ServerError::ReadFailed`: Si falla la lectura o el frame con framing por longitud es inválido.
+fn read_message<R: Read>(
+    reader: &mut BufReader<R>,
+    framing: Framing,
+    buf: &mut Vec<u8>,
+) -> Result<Option<(Received, usize)>, ServerError> {
+    let size = match framing {
+        Framing::Newline => {
+            buf.clear();
+            match reader.read_until(b'\n', buf) {
+                Ok(0) => return Ok(None),
+                Ok(n) => n,
+                Err(e) => return Err(ServerError::ReadFailed(e)),
+            }
+        }
+        Framing::LengthPrefixed => match read_frame(reader) {
+            Ok(payload) => {
+                *buf = payload;
+                // Prefijo de 4 bytes más el payload.
+                buf.len() + 4
+            }
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(ServerError::ReadFailed(e)),
+        },
+    };
+    let message = buf.strip_suffix(b"\n").unwrap_or(buf);
+    if has_non_printable_bytes(message) {
+        return Ok(Some((Received::NonPrintable, size)));
+    }
+    Protocol::from_bytes(message)
+        .map(|protocol| Some((Received::Message(protocol), size)))
+        .map_err(ServerError::invalid_message)
+}
+
+/// Envía en orden todas las respuestas encoladas con una única escritura y las loguea.
+/// Con framing por longitud, cada respuesta se envía como un frame sin el `\n` final.
+/// Recibe el stream, la cola de respuestas, el framing, el canal del logger y la dirección del cliente.
+///
+/// # Errores
+/// - `ServerError::WriteFailed`: Si falla la escritura en el stream.
+fn flush_responses<W: Write>(
+    stream: &mut W,
+    pending: &mut VecDeque<Vec<u8>>,
+    framing: Framing,
+    sender: &LogSender,
+    peer_addr: &str,
+) -> Result<(), ServerError> {
+    if pending.is_empty() {
+        return Ok(());
+    }
+    let mut batch: Vec<u8> = Vec::new();
+    for response in pending.iter() {
+        match framing {
+            Framing::Newline => batch.extend_from_slice(response),
+            Framing::LengthPrefixed => {
+                let payload = response.strip_suffix(b"\n").unwrap_or(response);
+                write_frame(&mut batch, payload).map_err(ServerError::WriteFailed)?;
+            }
+        }
+    }
+    if let Err(e) = stream.write_all(&batch).map_err(ServerError::WriteFailed) {
+        let _ = log_error!(sender, format!("[{}] {}", peer_addr, e));
+        return Err(e);
+    }
+    for response in pending.drain(..) {
+        let _ = log_info!(
+            sender,
+            format!("To [{}] sent: {}", peer_addr, String::from_utf8_lossy(&response).trim_end())
+        );
     }
+    Ok(())
+}
+
+/// Guarda el estado de la calculadora en el archivo configurado, si lo hay.
+/// Recibe el estado compartido del servidor. Toma `state.persist` mientras escribe, así las
+/// conexiones guardan de a una.
+///
+/// #Errores
+/// `Error::PosionError` - En el caso de que se envenene el lock.
+/// `Error::StateFileFailed` - Si no se puede escribir el archivo de estado.
+fn persist_state(state: &ServerState) -> Result<(), ServerError> {
+    let Some(path) = &state.config.state_file else {
+        return Ok(());
+    };
+    let _persisting = state.persist.lock().map_err(|_| ServerError::PoisonError)?;
+    if let Some(fast) = &state.lock_free {
+        // Sin historial ni registros solo hace falta guardar la acumulación.
+        let mut calc = Calculator::new();
+        calc.restore(CalculatorState { accumulation: fast.accumulation(), base: fast.accumulation(), history: Vec::new() });
+        return calc.save(path).map_err(|_| ServerError::StateFileFailed);
+    }
+    state.calculator.read(|calc| calc.save(path))?.map_err(|_| ServerError::StateFileFailed)
 }
 
 /// Envía un mensaje de protocolo al cliente a través del stream.
@@ -70,25 +594,30 @@ pub fn handle_connection(
 ///
 /// # Errores
 /// - `ServerError::WriteFailed`: Si falla la escritura en el stream.
-fn send_protocol<RW: Read + Write>(protocol: Protocol, stream: &mut RW) -> Result<(), ServerError> {
+pub fn send_protocol<RW: Read + Write>(protocol: Protocol, stream: &mut RW) -> Result<(), ServerError> {
     let response = protocol.to_bytes();
     stream
         .write_all(&response)
-        .map_err(|_| ServerError::WriteFailed)?;
+        .map_err(ServerError::WriteFailed)?;
     Ok(())
 }
 
 /// Maneja un mensaje de operación recibido del cliente.
 /// Parsea la operación, la aplica a la calculadora y envía una respuesta.
-/// Recibe la calculadora compartida, el stream y los argumentos de la operación.
+/// Recibe la calculadora compartida, el stream, los argumentos de la operación,
+/// el canal del logger y la dirección del cliente (para la auditoría).
 /// Devuelve un resultado indicando éxito o error.
+/// Si el lock de la calculadora está envenenado lo limpia y responde con un error transitorio
+/// (ver `transient_error`), así el cliente puede reenviar la operación con `--retry-ops`.
 ///
 /// #Errores
 /// Asociados a el parseo de la Operacion o a la aplicación de la Operación.
 fn handle_operation_message<RW: Read + Write>(
-    calculator: &Arc<std::sync::Mutex<Calculator>>,
+    calculator: &SharedCalculator,
     stream: &mut RW,
     args: String,
+    sender: &LogSender,
+    peer_addr: &str,
 ) -> Result<(), ServerError> {
     let op = match Operation::from_str(&args) {
         Ok(op) => op,
@@ -96,28 +625,113 @@ fn handle_operation_message<RW: Read + Write>(
             return send_protocol(Protocol::ErrorOperation(e.to_string()), stream);
         }
     };
-    apply_operation(calculator, op)?;
-    send_protocol(Protocol::Ok, stream)?;
-    Ok(())
+    match apply_operation(calculator, op, sender, peer_addr) {
+        Ok(()) => send_protocol(Protocol::Ok, stream),
+        Err(ServerError::OperationFailed(e)) => {
+            send_protocol(Protocol::ErrorOperation(e.message().to_string()), stream)
+        }
+        // La operación no llegó a aplicarse: se limpia el lock y el cliente puede reenviarla.
+        Err(ServerError::PoisonError) => {
+            let _ = log_error!(sender, format!("[{}] {}", peer_addr, ServerError::PoisonError));
+            calculator.clear_poison();
+            send_protocol(transient_error("calculator lock poisoned"), stream)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Maneja un mensaje de operación cuando el servidor usa la calculadora sin locks.
+/// Igual que `handle_operation_message`, pero sin historial y sin lockear la calculadora.
+///
+/// #Errores
+/// - `ServerError::WriteFailed`: Si falla la escritura en el stream.
+fn handle_lock_free_operation_message<RW: Read + Write>(
+    calculator: &LockFreeCalculator,
+    stream: &mut RW,
+    args: String,
+    sender: &LogSender,
+    peer_addr: &str,
+) -> Result<(), ServerError> {
+    let op = match Operation::from_str(&args) {
+        Ok(op) => op,
+        Err(e) => return send_protocol(Protocol::ErrorOperation(e.to_string()), stream),
+    };
+    let description = op.to_string();
+    match calculator.apply(op) {
+        Ok(accumulation) => {
+            let _ = sender.send(LogEvent::Audit {
+                peer_addr: peer_addr.to_string(),
+                operation: description,
+                result_accumulation: accumulation,
+                timestamp: SystemTime::now(),
+            });
+            send_protocol(Protocol::Ok, stream)
+        }
+        Err(e) => send_protocol(Protocol::ErrorOperation(e.message().to_string()), stream),
+    }
+}
+
+/// Maneja un `COMMIT`: aplica las operaciones encoladas desde `BEGIN` sin soltar el lock de la
+/// calculadora (o con un único `compare_exchange` si se usa la calculadora sin locks) y responde `OK`.
+/// Si alguna falla no se aplica ninguna y se responde con el mensaje de error.
+/// Envía un único evento de auditoría con todas las operaciones y la acumulación resultante.
+///
+/// #Errores
+/// `Error::PosionError` - En el caso de que se envenene el lock, se termina la conexión.
+/// `Error::WriteFailed` - Si falla la escritura en el stream.
+fn handle_commit_message<RW: Read + Write>(
+    calculator: &SharedCalculator,
+    lock_free: Option<&LockFreeCalculator>,
+    operations: &[Operation],
+    stream: &mut RW,
+    sender: &LogSender,
+    peer_addr: &str,
+) -> Result<(), ServerError> {
+    let result = match lock_free {
+        Some(fast) => fast.apply_all(operations).map_err(ServerError::OperationFailed),
+        None => calculator.apply_all(operations),
+    };
+    match result {
+        Ok(accumulation) => {
+            let description: Vec<String> = operations.iter().map(|op| op.to_string()).collect();
+            let _ = sender.send(LogEvent::Audit {
+                peer_addr: peer_addr.to_string(),
+                operation: description.join("; "),
+                result_accumulation: accumulation,
+                timestamp: SystemTime::now(),
+            });
+            send_protocol(Protocol::Ok, stream)
+        }
+        Err(ServerError::OperationFailed(e)) => {
+            send_protocol(Protocol::ErrorOperation(e.message().to_string()), stream)
+        }
+        Err(e) => Err(e),
+    }
 }
 
 /// Aplica operación a una calculadora.
-/// Recibe la calculadra y la operación.
+/// Recibe la calculadra, la operación, el canal del logger y la dirección del cliente.
+/// Si la operación se aplica, envía un evento de auditoría con la acumulación resultante.
 /// Devuelve un resultado indicando éxito o error.
 ///
 /// #Errores
 /// `Error::PosionError` - En el caso de que se envenene el lock, se termina la conexión.
+/// `Error::OperationFailed` - Si la calculadora rechaza la operación.
 fn apply_operation(
-    calculator: &Arc<std::sync::Mutex<Calculator>>,
+    calculator: &SharedCalculator,
     operation: Operation,
+    sender: &LogSender,
+    peer_addr: &str,
 ) -> Result<(), ServerError> {
-    match calculator.lock() {
-        Ok(mut calc) => {
-            calc.apply(operation);
-            Ok(())
-        }
-        Err(_) => Err(ServerError::PoisonError),
-    }
+    let description = operation.to_string();
+    let accumulation = calculator.apply(operation)?;
+    let _ = sender.send(LogEvent::Audit {
+        peer_addr: peer_addr.to_string(),
+        operation: description,
+        result_accumulation: accumulation,
+        timestamp: SystemTime::now(),
+    });
+    Ok(())
 }
 
 /// Calcula el valor actual de la calculadora y envia el protocolo de get al cliente .
@@ -127,7 +741,7 @@ fn apply_operation(
 /// #Errores
 /// Asociados a la aplicación de las funciones.
 fn handle_get_message<RW: Read + Write>(
-    calculator: &Arc<std::sync::Mutex<Calculator>>,
+    calculator: &SharedCalculator,
     stream: &mut RW,
 ) -> Result<(), ServerError> {
     let value = get_value(calculator)?;
@@ -136,283 +750,1593 @@ fn handle_get_message<RW: Read + Write>(
 }
 
 ///Aplica la operación de pedirle la acumulación a la calculadora
-/// Recibe la calculadora y la lockea para poder acceder a sus datos.
+/// Recibe la calculadora y toma su lock de lectura, así varios `GET` simultáneos no se bloquean entre sí.
+/// Devuelve un resultado indicando éxito o error.
+///
+/// #Errores
+/// `Error::PosionError` - En el caso de que se envenene el lock, se termina la conexión.
+fn get_value(calculator: &SharedCalculator) -> Result<i64, ServerError> {
+    calculator.accumulation()
+}
+
+/// Envía al cliente el historial de operaciones aplicadas a la calculadora.
+/// Recibe la calculadora y el stream.
 /// Devuelve un resultado indicando éxito o error.
 ///
 /// #Errores
 /// `Error::PosionError` - En el caso de que se envenene el lock, se termina la conexión.
-fn get_value(calculator: &Arc<std::sync::Mutex<Calculator>>) -> Result<u8, ServerError> {
-    match calculator.lock() {
-        Ok(calc) => Ok(calc.accumulation()),
-        Err(_) => Err(ServerError::PoisonError),
+fn handle_history_message<RW: Read + Write>(
+    calculator: &SharedCalculator,
+    stream: &mut RW,
+) -> Result<(), ServerError> {
+    let history = calculator.read(|calc| calc.into_iter().map(|op| op.to_string()).collect())?;
+    send_protocol(Protocol::HistoryValue(history), stream)
+}
+
+/// Envía al cliente el historial de operaciones aplicadas a la calculadora, una por línea entre
+/// `HISTORY_START` y `HISTORY_END`. A diferencia de `HISTORY`, las operaciones no se separan
+/// con `;`, así que cada una se lee tal cual.
+///
+/// #Errores
+/// `Error::PosionError` - En el caso de que se envenene el lock, se termina la conexión.
+fn handle_get_history_message<RW: Read + Write>(
+    calculator: &SharedCalculator,
+    stream: &mut RW,
+) -> Result<(), ServerError> {
+    let history = calculator.read(|calc| calc.into_iter().map(|op| op.to_string()).collect())?;
+    send_protocol(Protocol::HistoryResponse(history), stream)
+}
+
+/// Vuelve la calculadora al paso `step` de su historial y responde `OK`.
+/// Si el historial no llega a ese paso responde con un mensaje de error.
+///
+/// #Errores
+/// `Error::PosionError` - En el caso de que se envenene el lock, se termina la conexión.
+fn handle_rollback_to_message<RW: Read + Write>(
+    calculator: &SharedCalculator,
+    stream: &mut RW,
+    step: usize,
+) -> Result<(), ServerError> {
+    if calculator.write(|calc| calc.rollback_to(step))? {
+        send_protocol(Protocol::Ok, stream)
+    } else {
+        send_protocol(Protocol::ErrorOperation("step out of range".to_string()), stream)
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use std::{
-        io::{BufRead, BufReader, Cursor, Read, Write},
-        net::{TcpListener, TcpStream},
-        sync::{mpsc::channel, Arc, Mutex},
-        thread,
+/// Vacía el historial de la calculadora sin modificar su acumulación y responde `OK`.
+/// Recibe la calculadora y el stream.
+/// Devuelve un resultado indicando éxito o error.
+///
+/// #Errores
+/// `Error::PosionError` - En el caso de que se envenene el lock, se termina la conexión.
+fn handle_clear_history_message<RW: Read + Write>(
+    calculator: &SharedCalculator,
+    stream: &mut RW,
+) -> Result<(), ServerError> {
+    calculator.write(Calculator::clear_history)?;
+    send_protocol(Protocol::Ok, stream)
+}
+
+/// Asigna un valor a un registro con nombre y responde `OK`.
+/// Recibe la calculadora, el stream, el nombre del registro y el valor sin parsear.
+/// Si el valor no es un entero válido responde con un mensaje de error.
+///
+/// #Errores
+/// `Error::PosionError` - En el caso de que se envenene el lock, se termina la conexión.
+fn handle_set_register_message<RW: Read + Write>(
+    calculator: &SharedCalculator,
+    stream: &mut RW,
+    name: &str,
+    value: &str,
+) -> Result<(), ServerError> {
+    let value = match parse_operand(value) {
+        Ok(value) => value,
+        Err(e) => return send_protocol(Protocol::ErrorOperation(e), stream),
     };
+    calculator.write(|calc| calc.set_register(name, value))?;
+    send_protocol(Protocol::Ok, stream)
+}
 
-    use distributed_calculator::protocol::Protocol;
+/// Envía al cliente el valor de un registro con nombre.
+/// Recibe la calculadora, el stream y el nombre del registro.
+/// Si el registro no existe responde con un mensaje de error.
+///
+/// #Errores
+/// `Error::PosionError` - En el caso de que se envenene el lock, se termina la conexión.
+fn handle_get_register_message<RW: Read + Write>(
+    calculator: &SharedCalculator,
+    stream: &mut RW,
+    name: &str,
+) -> Result<(), ServerError> {
+    let value = calculator.read(|calc| calc.register(name))?;
+    match value {
+        Some(value) => send_protocol(Protocol::Value(value.to_string()), stream),
+        None => send_protocol(
+            Protocol::ErrorOperation(format!("unknown register: {}", name)),
+            stream,
+        ),
+    }
+}
 
-    use crate::{
-        calculator::Calculator,
-        handle_client::{
-            apply_operation, get_value, handle_connection, handle_get_message,
-            handle_operation_message, send_protocol,
-        }, logger::LogEvent,
+/// Envía al cliente los valores de varios registros con nombre en un único `MVALUE`.
+/// Recibe la calculadora, el stream y los nombres de los registros. Los valores se leen con un
+/// único lock, así que son todos del mismo momento.
+/// Si algún registro no existe responde con un mensaje de error por el primero que falte.
+///
+/// #Errores
+/// `Error::PosionError` - En el caso de que se envenene el lock, se termina la conexión.
+fn handle_multi_get_message<RW: Read + Write>(
+    calculator: &SharedCalculator,
+    stream: &mut RW,
+    names: &[String],
+) -> Result<(), ServerError> {
+    let values: Vec<(&String, Option<i64>)> =
+        calculator.read(|calc| names.iter().map(|name| (name, calc.register(name))).collect())?;
+    match values.iter().find(|(_, value)| value.is_none()) {
+        Some((name, _)) => send_protocol(Protocol::ErrorOperation(format!("unknown register: {}", name)), stream),
+        None => {
+            let values = values
+                .into_iter()
+                .filter_map(|(name, value)| value.map(|value| (name.clone(), value.to_string())))
+                .collect();
+            send_protocol(Protocol::MultiValue(values), stream)
+        }
+    }
+}
+
+/// Intercambia los valores de dos registros con nombre y responde `OK`.
+/// Recibe la calculadora, el stream y los nombres de ambos registros.
+///
+/// #Errores
+/// `Error::PosionError` - En el caso de que se envenene el lock, se termina la conexión.
+fn handle_swap_message<RW: Read + Write>(
+    calculator: &SharedCalculator,
+    stream: &mut RW,
+    a: &str,
+    b: &str,
+) -> Result<(), ServerError> {
+    calculator.write(|calc| calc.swap_registers(a, b))?;
+    send_protocol(Protocol::Ok, stream)
+}
+
+/// Cambia el nombre de un registro y responde `OK`.
+/// Recibe la calculadora, el stream y los nombres actual y nuevo del registro.
+/// Si el registro no existe o ya hay uno con el nombre nuevo responde con un mensaje de error.
+///
+/// #Errores
+/// `Error::PosionError` - En el caso de que se envenene el lock, se termina la conexión.
+fn handle_rename_message<RW: Read + Write>(
+    calculator: &SharedCalculator,
+    stream: &mut RW,
+    from: &str,
+    to: &str,
+) -> Result<(), ServerError> {
+    match calculator.write(|calc| calc.rename_register(from, to))? {
+        Ok(()) => send_protocol(Protocol::Ok, stream),
+        Err(message) => send_protocol(Protocol::ErrorOperation(message.to_string()), stream),
+    }
+}
+
+/// Responde a un intento de autenticación como administrador.
+/// Recibe si el token fue aceptado y el stream.
+///
+/// #Errores
+/// - `ServerError::WriteFailed`: Si falla la escritura en el stream.
+pub fn handle_auth_message<RW: Read + Write>(accepted: bool, stream: &mut RW) -> Result<(), ServerError> {
+    if accepted {
+        send_protocol(Protocol::Ok, stream)
+    } else {
+        send_protocol(Protocol::ErrorOperation("authentication failed".to_string()), stream)
+    }
+}
+
+/// Envía la lista de clientes conectados. Es un comando de administración.
+/// Recibe el registro de conexiones, si la conexión está autenticada como administrador y el stream.
+/// Cada cliente se describe como `id=<id> peer=<dirección> connected_at=<segundos unix> ops=<cantidad>`.
+///
+/// #Errores
+/// `Error::PosionError` - En el caso de que se envenene el lock del registro.
+pub fn handle_list_clients_message<RW: Read + Write>(
+    registry: &ConnectionRegistry,
+    is_admin: bool,
+    stream: &mut RW,
+) -> Result<(), ServerError> {
+    if !is_admin {
+        return send_protocol(
+            Protocol::ErrorOperation("admin authentication required".to_string()),
+            stream,
+        );
+    }
+    let clients = registry
+        .list()?
+        .into_iter()
+        .map(|(id, info)| {
+            let connected_at = info
+                .connected_at
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            format!(
+                "id={} peer={} connected_at={} ops={}",
+                id, info.peer_addr, connected_at, info.ops_count
+            )
+        })
+        .collect();
+    send_protocol(Protocol::ClientList(clients), stream)
+}
+
+/// Envía las estadísticas del servidor (tiempo en marcha, conexiones activas y actividad de la calculadora).
+/// Recibe el estado compartido y el stream.
+///
+/// #Errores
+/// `Error::PosionError` - En el caso de que se envenene el lock del registro.
+pub fn handle_status_message<RW: Read + Write>(state: &ServerState, stream: &mut RW) -> Result<(), ServerError> {
+    let calculator = match &state.lock_free {
+        Some(fast) => fast.stats(),
+        None => state.calculator.read(Calculator::stats)?,
     };
+    let stats = ServerStats::new(state.config.start_time, state.registry.count()?, calculator);
+    send_protocol(Protocol::StatusInfo(stats.to_string()), stream)
+}
 
-    #[test]
-    fn get_value_of_calculator() {
-        let calculator = Arc::new(std::sync::Mutex::new(Calculator::new()));
-        let value = get_value(&calculator).unwrap();
+/// Guarda una foto de la acumulación y el historial y responde con su identificador.
+///
+/// #Errores
+/// `Error::PosionError` - En el caso de que se envenene algún lock.
+fn handle_snapshot_message<RW: Read + Write>(state: &ServerState, stream: &mut RW) -> Result<(), ServerError> {
+    let snapshot = match &state.lock_free {
+        Some(fast) => CalculatorState { accumulation: fast.accumulation(), base: fast.accumulation(), history: Vec::new() },
+        None => state.calculator.read(Calculator::snapshot)?,
+    };
+    let id = state.snapshots.save(snapshot)?;
+    send_protocol(Protocol::SnapshotId(id), stream)
+}
 
-        assert_eq!(value, 0);
+/// Vuelve la calculadora a la foto `id` y responde `OK`.
+/// Si no existe una foto con ese identificador responde con un mensaje de error.
+///
+/// #Errores
+/// `Error::PosionError` - En el caso de que se envenene algún lock.
+fn handle_restore_message<RW: Read + Write>(state: &ServerState, id: &str, stream: &mut RW) -> Result<(), ServerError> {
+    let Some(snapshot) = state.snapshots.get(id)? else {
+        return send_protocol(Protocol::ErrorOperation(format!("unknown snapshot: {}", id)), stream);
+    };
+    if let Some(fast) = &state.lock_free {
+        fast.set_accumulation(snapshot.accumulation);
+        return send_protocol(Protocol::Ok, stream);
     }
+    state.calculator.write(|calc| calc.restore(snapshot))?;
+    send_protocol(Protocol::Ok, stream)
+}
 
-    #[test]
-    fn send_get_message() {
-        let response = Protocol::Value("0".to_string()).to_string();
-        let calculator = Arc::new(std::sync::Mutex::new(Calculator::new()));
-        let mut cursor = Cursor::new(Vec::new());
-        let mut output = String::new();
+/// Suscribe la conexión a los cambios de la calculadora `namespace` y responde `OK`.
+/// Un hilo aparte le envía al cliente un `VALUE` cada vez que alguien aplica una operación.
+/// Si el stream no permite escribir desde otro hilo responde con un mensaje de error.
+///
+/// #Errores
+/// `Error::PosionError` - En el caso de que se envenene el lock de las suscripciones.
+fn handle_subscribe_message<RW, W: Read + Write>(
+    state: &ServerState,
+    stream: &PeerStream<RW>,
+    connection_id: u64,
+    namespace: &str,
+    response: &mut W,
+) -> Result<(), ServerError> {
+    let Some(writer) = stream.push_writer() else {
+        return send_protocol(Protocol::ErrorOperation("subscriptions are not supported".to_string()), response);
+    };
+    let (sender, receiver) = mpsc::channel();
+    state.subscribers.subscribe(connection_id, namespace, sender)?;
+    spawn_push_thread(writer, state.config.framing, receiver);
+    send_protocol(Protocol::Ok, response)
+}
 
-        handle_get_message(&calculator, &mut cursor).unwrap();
-        cursor.set_position(0);
-        cursor.read_to_string(&mut output).unwrap();
+/// Copia la acumulación y el historial de la calculadora `from` en `to`, creándola si no existe,
+/// y responde `OK`. Copiar una calculadora en sí misma no cambia nada.
+/// Si `from` no existe responde con un mensaje de error.
+///
+/// #Errores
+/// `Error::PosionError` - En el caso de que se envenene algún lock.
+fn handle_copy_message<RW: Read + Write>(
+    state: &ServerState,
+    from: &str,
+    to: &str,
+    stream: &mut RW,
+) -> Result<(), ServerError> {
+    let Some(source) = state.namespaces.get(from)? else {
+        return send_protocol(Protocol::ErrorOperation("source not found".to_string()), stream);
+    };
+    if from == to {
+        return send_protocol(Protocol::Ok, stream);
+    }
+    // Con la calculadora sin locks, la acumulación de `default` vive fuera de su `Calculator`.
+    let default_lock_free = |name: &str| state.lock_free.as_ref().filter(|_| name == DEFAULT_NAMESPACE);
+    let snapshot = match default_lock_free(from) {
+        Some(fast) => CalculatorState { accumulation: fast.accumulation(), base: fast.accumulation(), history: Vec::new() },
+        None => source.read(Calculator::snapshot)?,
+    };
+    if let Some(fast) = default_lock_free(to) {
+        fast.set_accumulation(snapshot.accumulation);
+    }
+    state.namespaces.get_or_create(to)?.write(|calc| calc.restore(snapshot))?;
+    send_protocol(Protocol::Ok, stream)
+}
+
+/// Devuelve las capacidades que el servidor puede acordar en `HELLO`; no incluye los comandos
+/// deshabilitados. `SUBSCRIBE` depende de que el stream permita escribir desde otro hilo.
+fn server_capabilities(state: &ServerState, supports_push: bool) -> ServerCapabilities {
+    let mut capabilities = ServerCapabilities::AUTH | ServerCapabilities::BATCH;
+    if state.config.history {
+        capabilities = capabilities | ServerCapabilities::HISTORY;
+    }
+    if state.config.registers {
+        capabilities = capabilities | ServerCapabilities::REGISTERS;
+    }
+    if supports_push {
+        capabilities = capabilities | ServerCapabilities::SUBSCRIBE;
+    }
+    capabilities
+}
+
+/// Envía la versión del servidor y la del protocolo.
+/// Ejemplo: `VERSION_INFO crate=0.1.0 protocol=1`
+///
+/// #Errores
+/// - `ServerError::WriteFailed`: Si falla la escritura en el stream.
+fn handle_version_message<RW: Read + Write>(state: &ServerState, stream: &mut RW) -> Result<(), ServerError> {
+    let info = format!("crate={} protocol={}", state.config.version, PROTOCOL_VERSION);
+    send_protocol(Protocol::VersionInfo(info), stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        io::{BufRead, BufReader, Cursor, Read, Write},
+        net::{TcpListener, TcpStream},
+        thread,
+        time::Duration,
+    };
+
+    use distributed_calculator::protocol::{Protocol, is_transient_error, write_frame};
+
+    use crate::{
+        calculator::Calculator,
+        config::{Framing, ServerConfig},
+        handle_client::{
+            apply_operation, get_value, handle_clear_history_message, handle_connection,
+            handle_get_message, handle_get_register_message, handle_history_message, handle_multi_get_message,
+            handle_operation_message, handle_swap_message, persist_state, send_protocol,
+        }, logger::{DEFAULT_LOG_CAPACITY, LogEvent, LogSink, LoggerConfig, format_fields, log_channel, start_logger}, peer_stream::PeerStream, server_error::ServerError, server_state::ServerState,
+        shared_calculator::SharedCalculator,
+    };
+
+    #[test]
+    fn get_value_of_calculator() {
+        let calculator = SharedCalculator::new(Calculator::new());
+        let value = get_value(&calculator).unwrap();
+
+        assert_eq!(value, 0);
+    }
+
+    #[test]
+    fn concurrent_gets_do_not_deadlock_while_an_operation_is_in_progress() {
+        let calculator = SharedCalculator::new(Calculator::new());
+        let (done, finished) = std::sync::mpsc::channel();
+        let spawn_gets = |done: &std::sync::mpsc::Sender<i64>| {
+            for _ in 0..10 {
+                let calculator = calculator.clone();
+                let done = done.clone();
+                thread::spawn(move || done.send(get_value(&calculator).unwrap()).unwrap());
+            }
+        };
+
+        // Con un lector retenido los GET avanzan igual: no se excluyen entre sí.
+        calculator
+            .read(|_| {
+                spawn_gets(&done);
+                for _ in 0..10 {
+                    assert_eq!(finished.recv_timeout(std::time::Duration::from_secs(5)).unwrap(), 0);
+                }
+            })
+            .unwrap();
+
+        // Mientras una operación tiene el lock de escritura los GET esperan y ven su resultado.
+        calculator
+            .write(|calc| {
+                spawn_gets(&done);
+                thread::sleep(std::time::Duration::from_millis(20));
+                calc.apply(crate::operation::Operation::Add(5)).unwrap();
+            })
+            .unwrap();
+        for _ in 0..10 {
+            assert_eq!(finished.recv_timeout(std::time::Duration::from_secs(5)).unwrap(), 5);
+        }
+    }
+
+    #[test]
+    fn send_get_message() {
+        let response = Protocol::Value("0".to_string()).to_string();
+        let calculator = SharedCalculator::new(Calculator::new());
+        let mut cursor = Cursor::new(Vec::new());
+        let mut output = String::new();
+
+        handle_get_message(&calculator, &mut cursor).unwrap();
+        cursor.set_position(0);
+        cursor.read_to_string(&mut output).unwrap();
+
+        assert_eq!(output, response);
+    }
+
+    #[test]
+    fn apply_operation_success() {
+        let calculator = SharedCalculator::new(Calculator::new());
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let op = crate::operation::Operation::Add(5);
+
+        apply_operation(&calculator, op, &sender, "peer").unwrap();
+
+        assert_eq!(calculator.accumulation().unwrap(), 5);
+    }
+
+    #[test]
+    fn handle_operation_message_ok() {
+        let calculator = SharedCalculator::new(Calculator::new());
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let mut cursor = Cursor::new(Vec::new());
+        let args = "+ 5".to_string();
+        let response = Protocol::Ok;
+
+        handle_operation_message(&calculator, &mut cursor, args, &sender, "peer").unwrap();
+        cursor.set_position(0);
+        let mut output = String::new();
+        cursor.read_to_string(&mut output).unwrap();
+
+        assert_eq!(output, response.to_string());
+        assert_eq!(calculator.accumulation().unwrap(), 5);
+    }
+
+    #[test]
+    fn handle_operation_message_error() {
+        let calculator = SharedCalculator::new(Calculator::new());
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let mut cursor = Cursor::new(Vec::new());
+        let args = "% 5".to_string();
+        let response =
+            Protocol::ErrorOperation(("parsing error: unknown operation: %").to_string())
+                .to_string();
+
+        handle_operation_message(&calculator, &mut cursor, args, &sender, "peer").unwrap();
+        cursor.set_position(0);
+        let mut output = String::new();
+        cursor.read_to_string(&mut output).unwrap();
+
+        assert_eq!(output, response);
+    }
+
+    #[test]
+    fn handle_operation_message_multiple_operands() {
+        let calculator = SharedCalculator::new(Calculator::new());
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let mut cursor = Cursor::new(Vec::new());
+
+        handle_operation_message(&calculator, &mut cursor, "+ 1 2 3".to_string(), &sender, "peer").unwrap();
+        assert_eq!(get_value(&calculator).unwrap(), 6);
+
+        handle_operation_message(&calculator, &mut cursor, "* 2 3".to_string(), &sender, "peer").unwrap();
+        assert_eq!(get_value(&calculator).unwrap(), 36);
+    }
+
+    #[test]
+    fn handle_operation_message_shift_overflow() {
+        let calculator = SharedCalculator::new(Calculator::new());
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let mut cursor = Cursor::new(Vec::new());
+        let response = Protocol::ErrorOperation(
+            "shift overflow: shift amount must be at most 63".to_string(),
+        )
+        .to_string();
+
+        handle_operation_message(&calculator, &mut cursor, "<< 64".to_string(), &sender, "peer").unwrap();
+        cursor.set_position(0);
+        let mut output = String::new();
+        cursor.read_to_string(&mut output).unwrap();
+
+        assert_eq!(output, response);
+        assert!(calculator.read(|calc| calc.history().is_empty()).unwrap());
+    }
+
+    #[test]
+    fn test_send_protocol() {
+        let mut cursor = Cursor::new(Vec::new());
+        let protocol = Protocol::Ok;
+        let response = Protocol::Ok.to_string();
+
+        send_protocol(protocol, &mut cursor).unwrap();
+        cursor.set_position(0);
+        let mut output = String::new();
+        cursor.read_to_string(&mut output).unwrap();
+
+        assert_eq!(output, response);
+    }
+
+    #[test]
+    fn integration_test_handle_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let calculator = SharedCalculator::new(Calculator::new());
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        thread::spawn(move || {
+            let (stream, addr) = listener.accept().unwrap();
+            handle_connection(PeerStream::new(stream, addr.to_string()), ServerState::new(calculator, ServerConfig::default()), sender, 0).unwrap();
+        });
+
+        let mut client = TcpStream::connect(addr).unwrap();
+        client.write_all(b"OP + 1\nGET\n").unwrap();
+        client.flush().unwrap();
+
+        let mut reader = BufReader::new(client);
+        read_greeting(&mut reader);
+        let mut buf = String::new();
+        reader.read_line(&mut buf).unwrap();
+
+        assert!(buf.contains("OK"));
+
+        buf.clear();
+        reader.read_line(&mut buf).unwrap();
+
+        assert!(buf.contains("VALUE 1"));
+    }
+
+    #[test]
+    fn poisoned_lock_answers_a_transient_error_and_the_retry_succeeds() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let calculator = SharedCalculator::new(Calculator::new());
+        let poisoner = calculator.clone();
+        let _ = thread::spawn(move || poisoner.write(|_| panic!("poison the lock"))).join();
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        thread::spawn(move || {
+            let (stream, addr) = listener.accept().unwrap();
+            handle_connection(PeerStream::new(stream, addr.to_string()), ServerState::new(calculator, ServerConfig::default()), sender, 0).unwrap();
+        });
+
+        let mut reader = BufReader::new(TcpStream::connect(addr).unwrap());
+        read_greeting(&mut reader);
+        let mut responses = Vec::new();
+        for _ in 0..3 {
+            reader.get_mut().write_all(b"OP + 1\n").unwrap();
+            let mut buf = String::new();
+            reader.read_line(&mut buf).unwrap();
+            let transient = matches!(
+                Protocol::from_bytes(buf.trim_end().as_bytes()),
+                Ok(Protocol::ErrorOperation(message)) if is_transient_error(&message)
+            );
+            responses.push(buf);
+            if !transient {
+                break;
+            }
+        }
+        reader.get_mut().write_all(b"GET\n").unwrap();
+        let mut value = String::new();
+        reader.read_line(&mut value).unwrap();
+
+        assert_eq!(responses, vec!["ERROR \"internal error: calculator lock poisoned\"\n", "OK\n"]);
+        assert_eq!(value, "VALUE 1\n");
+    }
+
+    #[test]
+    fn handle_connection_sends_connection_metrics_on_close() {
+        let stream = FakeStream {
+            input: Cursor::new(b"OP + 1\nGET\nGET\n".to_vec()),
+            output: Vec::new(),
+        };
+        let (sender, receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let state = ServerState::new(SharedCalculator::new(Calculator::new()), ServerConfig::default());
+        handle_connection(PeerStream::new(stream, "10.0.0.1:4000"), state, sender, 7).unwrap();
+
+        let metrics: Vec<(String, f64, HashMap<String, String>)> = receiver
+            .try_iter()
+            .filter_map(|event| match event {
+                LogEvent::Metric { name, value, tags, .. } => Some((name, value, tags)),
+                _ => None,
+            })
+            .collect();
+        let names: Vec<&str> = metrics.iter().map(|(name, _, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["connection_requests_total", "connection_requests_per_second", "connection_latency_avg_seconds"]
+        );
+        assert_eq!(metrics[0].1, 3.0);
+        assert!(metrics.iter().all(|(_, value, _)| *value >= 0.0));
+        assert_eq!(metrics[0].2["peer"], "10.0.0.1:4000");
+        assert_eq!(metrics[0].2["connection_id"], "7");
+    }
+
+    #[test]
+    fn handle_connection_times_out_waiting_for_a_read() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let _client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, addr) = listener.accept().unwrap();
+        let timeout = Duration::from_millis(50);
+        stream.set_read_timeout(Some(timeout)).unwrap();
+        let config = ServerConfig {
+            io_timeout: Some(timeout),
+            ..ServerConfig::default()
+        };
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+
+        let result = handle_connection(
+            PeerStream::new(stream, addr.to_string()),
+            ServerState::new(SharedCalculator::new(Calculator::new()), config),
+            sender,
+            0,
+        );
+
+        assert!(matches!(result, Err(ServerError::Timeout { operation: "read", after }) if after == timeout));
+    }
+
+    #[test]
+    fn integration_test_handle_connection_unexpected_message() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let calculator = SharedCalculator::new(Calculator::new());
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+
+        thread::spawn(move || {
+            let (stream, addr) = listener.accept().unwrap();
+            handle_connection(PeerStream::new(stream, addr.to_string()), ServerState::new(calculator, ServerConfig::default()), sender, 0).unwrap();
+        });
+
+        let mut client = TcpStream::connect(addr).unwrap();
+        client.write_all(b"hola\nGET\n").unwrap();
+        client.flush().unwrap();
+
+        let mut reader = BufReader::new(client);
+        read_greeting(&mut reader);
+        let mut buf = String::new();
+        reader.read_line(&mut buf).unwrap();
+
+        assert!(buf.contains("ERROR \"unexpected message: hola\""));
+
+        buf.clear();
+        reader.read_line(&mut buf).unwrap();
+
+        assert!(buf.contains("VALUE 0"));
+    }
+
+    #[test]
+    fn integration_test_handle_connection_unknown_operation() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let calculator = SharedCalculator::new(Calculator::new());
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+
+        thread::spawn(move || {
+            let (stream, addr) = listener.accept().unwrap();
+            handle_connection(PeerStream::new(stream, addr.to_string()), ServerState::new(calculator, ServerConfig::default()), sender, 0).unwrap();
+        });
+
+        let mut client = TcpStream::connect(addr).unwrap();
+        client.write_all(b"OP 8 8\nGET\n").unwrap();
+        client.flush().unwrap();
+
+        let mut reader = BufReader::new(client);
+        read_greeting(&mut reader);
+        let mut buf = String::new();
+        reader.read_line(&mut buf).unwrap();
+        println!("buf: {}", buf);
+
+        assert!(buf.contains("ERROR \"parsing error: unknown operation: 8\""));
+
+        buf.clear();
+        reader.read_line(&mut buf).unwrap();
+
+        assert!(buf.contains("VALUE 0"));
+    }
+
+    #[test]
+    fn integration_test_handle_connection_too_large_integer_operation() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let calculator = SharedCalculator::new(Calculator::new());
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+
+        thread::spawn(move || {
+            let (stream, addr) = listener.accept().unwrap();
+            handle_connection(PeerStream::new(stream, addr.to_string()), ServerState::new(calculator, ServerConfig::default()), sender, 0).unwrap();
+        });
+
+        let mut client = TcpStream::connect(addr).unwrap();
+        client.write_all(b"OP + 9223372036854775808\nGET\n").unwrap();
+        client.flush().unwrap();
+
+        let mut reader = BufReader::new(client);
+        read_greeting(&mut reader);
+        let mut buf = String::new();
+        reader.read_line(&mut buf).unwrap();
+        println!("buf: {}", buf);
+
+        assert!(buf.contains(
+            "ERROR \"parsing error: invalid integer: number too large to fit in target type\""
+        ));
+
+        buf.clear();
+        reader.read_line(&mut buf).unwrap();
+
+        assert!(buf.contains("VALUE 0"));
+    }
+
+    #[test]
+    fn integration_test_handle_connection_invalid_digit_operation() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let calculator = SharedCalculator::new(Calculator::new());
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        thread::spawn(move || {
+            let (stream, addr) = listener.accept().unwrap();
+            handle_connection(PeerStream::new(stream, addr.to_string()), ServerState::new(calculator, ServerConfig::default()), sender, 0).unwrap();
+        });
+
+        let mut client = TcpStream::connect(addr).unwrap();
+        client.write_all(b"OP + cinco\nGET\n").unwrap();
+        client.flush().unwrap();
+
+        let mut reader = BufReader::new(client);
+        read_greeting(&mut reader);
+        let mut buf = String::new();
+        reader.read_line(&mut buf).unwrap();
+        println!("buf: {}", buf);
+
+        assert!(
+            buf.contains("ERROR \"parsing error: invalid integer: invalid digit found in string\"")
+        );
+
+        buf.clear();
+        reader.read_line(&mut buf).unwrap();
+
+        assert!(buf.contains("VALUE 0"));
+    }
+
+    #[test]
+    fn test_client_disconnects() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let calculator = SharedCalculator::new(Calculator::new());
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+
+        let handle = std::thread::spawn(move || {
+            let (stream, addr) = listener.accept().unwrap();
+            handle_connection(PeerStream::new(stream, addr.to_string()), ServerState::new(calculator, ServerConfig::default()), sender, 0)
+        });
+
+        let client = TcpStream::connect(addr).unwrap();
+        drop(client);
+
+        let result = handle.join().unwrap();
+        assert!(matches!(result, Ok(())));
+    }
+
+    #[test]
+    fn clear_history_keeps_accumulation() {
+        let calculator = SharedCalculator::new(Calculator::new());
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        apply_operation(&calculator, crate::operation::Operation::Add(5), &sender, "peer").unwrap();
+        let mut cursor = Cursor::new(Vec::new());
+
+        handle_clear_history_message(&calculator, &mut cursor).unwrap();
+        handle_history_message(&calculator, &mut cursor).unwrap();
+        cursor.set_position(0);
+        let mut output = String::new();
+        cursor.read_to_string(&mut output).unwrap();
+
+        assert_eq!(output, "OK\nHISTORY_VALUE \n");
+        assert_eq!(get_value(&calculator).unwrap(), 5);
+    }
+
+    #[test]
+    fn integration_test_handle_connection_clear_history() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let calculator = SharedCalculator::new(Calculator::new());
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+
+        thread::spawn(move || {
+            let (stream, addr) = listener.accept().unwrap();
+            handle_connection(PeerStream::new(stream, addr.to_string()), ServerState::new(calculator, ServerConfig::default()), sender, 0).unwrap();
+        });
+
+        let mut client = TcpStream::connect(addr).unwrap();
+        client
+            .write_all(b"OP + 5\nHISTORY\nCLEAR_HISTORY\nHISTORY\nGET\n")
+            .unwrap();
+        client.flush().unwrap();
+
+        let mut reader = BufReader::new(client);
+        read_greeting(&mut reader);
+        let mut buf = String::new();
+        for expected in ["OK", "HISTORY_VALUE + 5", "OK", "HISTORY_VALUE", "VALUE 5"] {
+            buf.clear();
+            reader.read_line(&mut buf).unwrap();
+            assert_eq!(buf.trim_end(), expected);
+        }
+    }
+
+    #[test]
+    fn integration_test_handle_connection_noop() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let calculator = SharedCalculator::new(Calculator::new());
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+
+        thread::spawn(move || {
+            let (stream, addr) = listener.accept().unwrap();
+            handle_connection(PeerStream::new(stream, addr.to_string()), ServerState::new(calculator, ServerConfig::default()), sender, 0).unwrap();
+        });
+
+        let mut client = TcpStream::connect(addr).unwrap();
+        client.write_all(b"OP + 5\nNOOP\nGET\nNOOP\nNOOP\nHISTORY\nGET\n").unwrap();
+        client.flush().unwrap();
+
+        let mut reader = BufReader::new(client);
+        read_greeting(&mut reader);
+        let mut buf = String::new();
+        for expected in ["OK", "OK", "VALUE 5", "OK", "OK", "HISTORY_VALUE + 5", "VALUE 5"] {
+            buf.clear();
+            reader.read_line(&mut buf).unwrap();
+            assert_eq!(buf.trim_end(), expected);
+        }
+    }
+
+    #[test]
+    fn integration_test_handle_connection_echo() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let calculator = SharedCalculator::new(Calculator::new());
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+
+        thread::spawn(move || {
+            let (stream, addr) = listener.accept().unwrap();
+            handle_connection(PeerStream::new(stream, addr.to_string()), ServerState::new(calculator, ServerConfig::default()), sender, 0).unwrap();
+        });
+
+        let mut client = TcpStream::connect(addr).unwrap();
+        client.write_all(b"ECHO 1700000000 abc\nECHO_REPLY x\nGET\n").unwrap();
+        client.flush().unwrap();
+
+        let mut reader = BufReader::new(client);
+        read_greeting(&mut reader);
+        let mut buf = String::new();
+        for expected in ["ECHO_REPLY 1700000000 abc", "ERROR \"unexpected message: ECHO_REPLY x\"", "VALUE 0"] {
+            buf.clear();
+            reader.read_line(&mut buf).unwrap();
+            assert_eq!(buf.trim_end(), expected);
+        }
+    }
+
+    #[test]
+    fn integration_test_handle_connection_set() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let calculator = SharedCalculator::new(Calculator::new());
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+
+        thread::spawn(move || {
+            let (stream, addr) = listener.accept().unwrap();
+            handle_connection(PeerStream::new(stream, addr.to_string()), ServerState::new(calculator, ServerConfig::default()), sender, 0).unwrap();
+        });
+
+        let mut client = TcpStream::connect(addr).unwrap();
+        client
+            .write_all(b"OP = 100\nGET\nOP = 0\nGET\nOP = -42\nGET\n")
+            .unwrap();
+        client.flush().unwrap();
 
-        assert_eq!(output, response);
+        let mut reader = BufReader::new(client);
+        read_greeting(&mut reader);
+        let mut buf = String::new();
+        for expected in ["OK", "VALUE 100", "OK", "VALUE 0", "OK", "VALUE -42"] {
+            buf.clear();
+            reader.read_line(&mut buf).unwrap();
+            assert_eq!(buf.trim_end(), expected);
+        }
     }
 
     #[test]
-    fn apply_operation_success() {
-        let calculator = Arc::new(std::sync::Mutex::new(Calculator::new()));
-        let op = crate::operation::Operation::Add(5);
+    fn swap_message_exchanges_registers() {
+        let calculator = SharedCalculator::new(Calculator::new());
+        calculator.write(|calc| calc.set_register("A", 10)).unwrap();
+        calculator.write(|calc| calc.set_register("B", 20)).unwrap();
+        let mut cursor = Cursor::new(Vec::new());
 
-        apply_operation(&calculator, op).unwrap();
+        handle_swap_message(&calculator, &mut cursor, "A", "B").unwrap();
+        handle_get_register_message(&calculator, &mut cursor, "A").unwrap();
+        handle_get_register_message(&calculator, &mut cursor, "B").unwrap();
+        cursor.set_position(0);
+        let mut output = String::new();
+        cursor.read_to_string(&mut output).unwrap();
 
-        assert_eq!(calculator.lock().unwrap().accumulation(), 5);
+        assert_eq!(output, "OK\nVALUE 20\nVALUE 10\n");
     }
 
     #[test]
-    fn handle_operation_message_ok() {
-        let calculator = Arc::new(std::sync::Mutex::new(Calculator::new()));
+    fn get_unknown_register() {
+        let calculator = SharedCalculator::new(Calculator::new());
         let mut cursor = Cursor::new(Vec::new());
-        let args = "+ 5".to_string();
-        let response = Protocol::Ok;
 
-        handle_operation_message(&calculator, &mut cursor, args).unwrap();
+        handle_get_register_message(&calculator, &mut cursor, "A").unwrap();
         cursor.set_position(0);
         let mut output = String::new();
         cursor.read_to_string(&mut output).unwrap();
 
-        assert_eq!(output, response.to_string());
-        assert_eq!(calculator.lock().unwrap().accumulation(), 5);
+        assert_eq!(output, "ERROR \"unknown register: A\"\n");
     }
 
     #[test]
-    fn handle_operation_message_error() {
-        let calculator = Arc::new(std::sync::Mutex::new(Calculator::new()));
+    fn multi_get_returns_every_register_in_one_message() {
+        let calculator = SharedCalculator::new(Calculator::new());
+        calculator.write(|calc| calc.set_register("A", 10)).unwrap();
+        calculator.write(|calc| calc.set_register("B", -2)).unwrap();
         let mut cursor = Cursor::new(Vec::new());
-        let args = "% 5".to_string();
-        let response =
-            Protocol::ErrorOperation(("parsing error: unknown operation: %").to_string())
-                .to_string();
 
-        handle_operation_message(&calculator, &mut cursor, args).unwrap();
+        handle_multi_get_message(&calculator, &mut cursor, &["B".to_string(), "A".to_string()]).unwrap();
         cursor.set_position(0);
         let mut output = String::new();
         cursor.read_to_string(&mut output).unwrap();
 
-        assert_eq!(output, response);
+        assert_eq!(output, "MVALUE A=10 B=-2\n");
     }
 
     #[test]
-    fn test_send_protocol() {
+    fn multi_get_with_an_unknown_register() {
+        let calculator = SharedCalculator::new(Calculator::new());
+        calculator.write(|calc| calc.set_register("A", 10)).unwrap();
         let mut cursor = Cursor::new(Vec::new());
-        let protocol = Protocol::Ok;
-        let response = Protocol::Ok.to_string();
 
-        send_protocol(protocol, &mut cursor).unwrap();
+        handle_multi_get_message(&calculator, &mut cursor, &["A".to_string(), "C".to_string()]).unwrap();
         cursor.set_position(0);
         let mut output = String::new();
         cursor.read_to_string(&mut output).unwrap();
 
-        assert_eq!(output, response);
+        assert_eq!(output, "ERROR \"unknown register: C\"\n");
     }
 
     #[test]
-    fn integration_test_handle_connection() {
+    fn integration_test_handle_connection_swap() {
         let listener = TcpListener::bind("127.0.0.1:0").unwrap();
         let addr = listener.local_addr().unwrap();
 
-        let calculator = Arc::new(Mutex::new(Calculator::new()));
-        let (sender, _receiver) = channel::<LogEvent>();
+        let calculator = SharedCalculator::new(Calculator::new());
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+
         thread::spawn(move || {
-            let (stream, _) = listener.accept().unwrap();
-            handle_connection(stream, calculator,sender).unwrap();
+            let (stream, addr) = listener.accept().unwrap();
+            handle_connection(PeerStream::new(stream, addr.to_string()), ServerState::new(calculator, ServerConfig::default()), sender, 0).unwrap();
         });
 
         let mut client = TcpStream::connect(addr).unwrap();
-        client.write_all(b"OP + 1\nGET\n").unwrap();
+        client
+            .write_all(b"SET_REGISTER A 10\nSET_REGISTER B 20\nSWAP A B\nGET A\nGET B\nGET\n")
+            .unwrap();
         client.flush().unwrap();
 
         let mut reader = BufReader::new(client);
+        read_greeting(&mut reader);
         let mut buf = String::new();
-        reader.read_line(&mut buf).unwrap();
+        for expected in ["OK", "OK", "OK", "VALUE 20", "VALUE 10", "VALUE 0"] {
+            buf.clear();
+            reader.read_line(&mut buf).unwrap();
+            assert_eq!(buf.trim_end(), expected);
+        }
+    }
 
-        assert!(buf.contains("OK"));
+    #[test]
+    fn snapshot_and_restore_round_trip() {
+        let calculator = SharedCalculator::new(Calculator::new());
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let mut stream = FakeStream {
+            input: Cursor::new(
+                b"OP + 5\nSNAPSHOT\nOP * 3\nGET\nRESTORE snap-1\nGET\nHISTORY\nRESTORE snap-9\n".to_vec(),
+            ),
+            output: Vec::new(),
+        };
 
-        buf.clear();
-        reader.read_line(&mut buf).unwrap();
+        handle_connection(PeerStream::new(&mut stream, "peer".to_string()), ServerState::new(calculator, ServerConfig::default()), sender, 0).unwrap();
 
-        assert!(buf.contains("VALUE 1"));
+        assert_eq!(
+            String::from_utf8(stream.output).unwrap(),
+            "HELLO 1 f\nOK\nSNAPSHOT_ID snap-1\nOK\nVALUE 15\nOK\nVALUE 5\nHISTORY_VALUE + 5\nERROR \"unknown snapshot: snap-9\"\n"
+        );
     }
 
     #[test]
-    fn integration_test_handle_connection_unexpected_message() {
-        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
-        let addr = listener.local_addr().unwrap();
+    fn server_opens_the_handshake_with_its_capabilities() {
+        let calculator = SharedCalculator::new(Calculator::new());
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let mut stream = FakeStream {
+            input: Cursor::new(b"HELLO 1 f\n".to_vec()),
+            output: Vec::new(),
+        };
 
-        let calculator = Arc::new(Mutex::new(Calculator::new()));
-        let (sender, _receiver) = channel::<LogEvent>();
+        handle_connection(PeerStream::new(&mut stream, "peer".to_string()), ServerState::new(calculator, ServerConfig::default()), sender, 0).unwrap();
 
-        thread::spawn(move || {
-            let (stream, _) = listener.accept().unwrap();
-            handle_connection(stream, calculator, sender).unwrap();
-        });
+        // AUTH, BATCH, HISTORY y REGISTERS; SUBSCRIBE no, porque el stream no permite escribir desde otro hilo.
+        // A la respuesta del cliente se contesta solo con el identificador de la sesión.
+        let output = String::from_utf8(stream.output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "HELLO 1 f");
+        assert!(matches!(
+            Protocol::from_bytes(lines[1].as_bytes()).unwrap(),
+            Protocol::SessionId(id) if id.len() == 32
+        ));
+        assert_eq!(lines.len(), 2);
+    }
 
-        let mut client = TcpStream::connect(addr).unwrap();
-        client.write_all(b"hola\nGET\n").unwrap();
-        client.flush().unwrap();
+    #[test]
+    fn hello_rejects_commands_that_were_not_negotiated() {
+        let calculator = SharedCalculator::new(Calculator::new());
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let mut stream = FakeStream {
+            input: Cursor::new(b"SET_REGISTER A 1\nHELLO 1 14\nHISTORY\nOP + 1 2\nSET_REGISTER A 1\nOP + 3\nGET\n".to_vec()),
+            output: Vec::new(),
+        };
 
-        let mut reader = BufReader::new(client);
-        let mut buf = String::new();
-        reader.read_line(&mut buf).unwrap();
+        handle_connection(PeerStream::new(&mut stream, "peer".to_string()), ServerState::new(calculator, ServerConfig::default()), sender, 0).unwrap();
+
+        // El cliente elige HISTORY y SUBSCRIBE; SUBSCRIBE no estaba entre las que ofreció el servidor.
+        let output = String::from_utf8(stream.output).unwrap();
+        let lines: Vec<&str> = output.lines().filter(|line| !line.starts_with("SESSION_ID")).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "HELLO 1 f",
+                "OK",
+                "HISTORY_VALUE ",
+                "ERROR \"BATCH was not negotiated\"",
+                "ERROR \"REGISTERS was not negotiated\"",
+                "OK",
+                "VALUE 3",
+            ]
+        );
+    }
 
-        assert!(buf.contains("ERROR \"unexpected message: hola\""));
+    /// Atiende con `state` una conexión que recibe `input` y devuelve las líneas que respondió,
+    /// sin el `HELLO` con el que el servidor abre la conexión.
+    fn serve(state: &ServerState, input: &[u8]) -> Vec<String> {
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let mut stream = FakeStream {
+            input: Cursor::new(input.to_vec()),
+            output: Vec::new(),
+        };
+        handle_connection(PeerStream::new(&mut stream, "peer".to_string()), state.clone(), sender, 0).unwrap();
+        let mut output = Cursor::new(stream.output);
+        read_greeting(&mut output);
+        output.lines().map(Result::unwrap).collect()
+    }
 
-        buf.clear();
-        reader.read_line(&mut buf).unwrap();
+    #[test]
+    fn get_history_lists_every_operation_on_its_own_line() {
+        let state = ServerState::new(SharedCalculator::new(Calculator::new()), ServerConfig::default());
 
-        assert!(buf.contains("VALUE 0"));
+        let output = serve(&state, b"OP + 5\nOP * 3\nGET_HISTORY\nGET\n");
+
+        assert_eq!(output, vec!["OK", "OK", "HISTORY_START", "+ 5", "* 3", "HISTORY_END", "VALUE 15"]);
     }
 
     #[test]
-    fn integration_test_handle_connection_unknown_operation() {
-        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
-        let addr = listener.local_addr().unwrap();
+    fn rollback_to_replays_the_history_up_to_the_step() {
+        let state = ServerState::new(SharedCalculator::new(Calculator::new()), ServerConfig::default());
 
-        let calculator = Arc::new(Mutex::new(Calculator::new()));
-        let (sender, _receiver) = channel::<LogEvent>();
+        let output = serve(&state, b"OP + 5\nOP * 3\nOP - 1\nROLLBACK_TO 1\nGET\nGET_HISTORY\n");
 
-        thread::spawn(move || {
-            let (stream, _) = listener.accept().unwrap();
-            handle_connection(stream, calculator,sender).unwrap();
-        });
+        assert_eq!(output, vec!["OK", "OK", "OK", "OK", "VALUE 15", "HISTORY_START", "+ 5", "* 3", "HISTORY_END"]);
+    }
 
-        let mut client = TcpStream::connect(addr).unwrap();
-        client.write_all(b"OP 8 8\nGET\n").unwrap();
-        client.flush().unwrap();
+    #[test]
+    fn rollback_to_the_first_and_last_steps() {
+        let state = ServerState::new(SharedCalculator::new(Calculator::new()), ServerConfig::default());
 
-        let mut reader = BufReader::new(client);
-        let mut buf = String::new();
-        reader.read_line(&mut buf).unwrap();
-        println!("buf: {}", buf);
+        let output = serve(&state, b"OP + 5\nOP * 3\nROLLBACK_TO 1\nGET\nROLLBACK_TO 0\nGET\nGET_HISTORY\n");
 
-        assert!(buf.contains("ERROR \"parsing error: unknown operation: 8\""));
+        assert_eq!(output, vec!["OK", "OK", "OK", "VALUE 15", "OK", "VALUE 5", "HISTORY_START", "+ 5", "HISTORY_END"]);
+    }
 
-        buf.clear();
-        reader.read_line(&mut buf).unwrap();
+    #[test]
+    fn rollback_to_starts_from_the_initial_accumulation() {
+        let state = ServerState::new(SharedCalculator::new(Calculator::with_initial(100)), ServerConfig::default());
 
-        assert!(buf.contains("VALUE 0"));
+        let output = serve(&state, b"OP + 5\nOP * 2\nOP - 10\nROLLBACK_TO 2\nGET\nROLLBACK_TO 1\nGET\nROLLBACK_TO 0\nGET\n");
+
+        assert_eq!(output, vec!["OK", "OK", "OK", "OK", "VALUE 200", "OK", "VALUE 210", "OK", "VALUE 105"]);
     }
 
     #[test]
-    fn integration_test_handle_connection_too_large_integer_operation() {
-        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
-        let addr = listener.local_addr().unwrap();
+    fn rollback_to_starts_from_the_accumulation_left_by_clear_history() {
+        let state = ServerState::new(SharedCalculator::new(Calculator::new()), ServerConfig::default());
 
-        let calculator = Arc::new(Mutex::new(Calculator::new()));
-        let (sender, _receiver) = channel::<LogEvent>();
+        let output = serve(&state, b"OP + 10\nCLEAR_HISTORY\nOP + 1\nROLLBACK_TO 0\nGET\n");
 
-        thread::spawn(move || {
-            let (stream, _) = listener.accept().unwrap();
-            handle_connection(stream, calculator, sender).unwrap();
-        });
+        assert_eq!(output, vec!["OK", "OK", "OK", "OK", "VALUE 11"]);
+    }
 
-        let mut client = TcpStream::connect(addr).unwrap();
-        client.write_all(b"OP + 300\nGET\n").unwrap();
-        client.flush().unwrap();
+    #[test]
+    fn rollback_to_a_step_beyond_the_history() {
+        let state = ServerState::new(SharedCalculator::new(Calculator::new()), ServerConfig::default());
 
-        let mut reader = BufReader::new(client);
-        let mut buf = String::new();
-        reader.read_line(&mut buf).unwrap();
-        println!("buf: {}", buf);
+        let output = serve(&state, b"ROLLBACK_TO 0\nOP + 5\nROLLBACK_TO 1\nGET\n");
 
-        assert!(buf.contains(
-            "ERROR \"parsing error: invalid integer: number too large to fit in target type\""
-        ));
+        assert_eq!(output, vec!["ERROR \"step out of range\"", "OK", "ERROR \"step out of range\"", "VALUE 5"]);
+    }
 
-        buf.clear();
-        reader.read_line(&mut buf).unwrap();
+    #[test]
+    fn get_history_is_rejected_when_history_is_disabled() {
+        let config = ServerConfig {
+            history: false,
+            ..ServerConfig::default()
+        };
+        let state = ServerState::new(SharedCalculator::new(Calculator::new()), config);
 
-        assert!(buf.contains("VALUE 0"));
+        assert_eq!(serve(&state, b"GET_HISTORY\n"), vec!["ERROR \"history is disabled\""]);
     }
 
     #[test]
-    fn integration_test_handle_connection_invalid_digit_operation() {
+    fn rename_moves_the_value_to_the_new_name() {
+        let state = ServerState::new(SharedCalculator::new(Calculator::new()), ServerConfig::default());
+
+        let output = serve(&state, b"SET_REGISTER A 10\nRENAME A B\nGET B\nGET A\n");
+
+        assert_eq!(output, vec!["OK", "OK", "VALUE 10", "ERROR \"unknown register: A\""]);
+    }
+
+    #[test]
+    fn rename_errors_leave_the_registers_unchanged() {
+        let state = ServerState::new(SharedCalculator::new(Calculator::new()), ServerConfig::default());
+
+        let output = serve(&state, b"SET_REGISTER A 1\nSET_REGISTER B 2\nRENAME A B\nRENAME C D\nMGET A B\n");
+
+        assert_eq!(
+            output,
+            vec!["OK", "OK", "ERROR \"target name already exists\"", "ERROR \"source not found\"", "MVALUE A=1 B=2"]
+        );
+    }
+
+    #[test]
+    fn list_registers_returns_the_sorted_names() {
+        let state = ServerState::new(SharedCalculator::new(Calculator::new()), ServerConfig::default());
+
+        assert_eq!(serve(&state, b"LIST_REGISTERS\n"), vec!["REGISTERS"]);
+        assert_eq!(serve(&state, b"SET_REGISTER total 1\nLIST_REGISTERS\n"), vec!["OK", "REGISTERS total"]);
+        assert_eq!(
+            serve(&state, b"SET_REGISTER B 2\nSET_REGISTER A 3\nLIST_REGISTERS\n"),
+            vec!["OK", "OK", "REGISTERS A B total"]
+        );
+    }
+
+    #[test]
+    fn register_names_cannot_contain_spaces() {
+        let state = ServerState::new(SharedCalculator::new(Calculator::new()), ServerConfig::default());
+
+        // El nombre se parsea como un único token, así que la lista nunca necesita comillas.
+        let output = serve(&state, b"SET_REGISTER my total 1\nLIST_REGISTERS\n");
+
+        assert_eq!(output, vec!["ERROR \"unexpected message: SET_REGISTER my total 1\"", "REGISTERS"]);
+    }
+
+    #[test]
+    fn messages_with_non_printable_bytes_are_rejected() {
+        let state = ServerState::new(SharedCalculator::new(Calculator::new()), ServerConfig::default());
+        let (sender, receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let mut stream = FakeStream {
+            input: Cursor::new(b"OP + 1\0\0\nSET_REGISTER A\x1b[2J 1\nOP + 2\nGET\n".to_vec()),
+            output: Vec::new(),
+        };
+
+        handle_connection(PeerStream::new(&mut stream, "peer".to_string()), state, sender, 0).unwrap();
+
+        let output: Vec<String> = String::from_utf8(stream.output).unwrap().lines().skip(1).map(str::to_string).collect();
+        let rejected = "ERROR \"non-printable bytes in message\"";
+        assert_eq!(output, vec![rejected, rejected, "OK", "VALUE 2"]);
+        let events: Vec<LogEvent> = receiver.try_iter().collect();
+        let warnings = events.iter().filter(|event| matches!(event, LogEvent::Warn { .. })).count();
+        assert_eq!(warnings, 2);
+        assert!(!events.iter().any(|event| matches!(event, LogEvent::Info { message, .. } if message.contains('\0'))));
+    }
+
+    #[test]
+    fn control_bytes_the_tokenizer_would_drop_are_rejected() {
+        let state = ServerState::new(SharedCalculator::new(Calculator::new()), ServerConfig::default());
+
+        let output = serve(&state, b"OP\x0b+ 1\nOP + 2\r\nOP +\x0c3\nECHO \x1bhola\nOP + 4\nGET\n");
+
+        let rejected = "ERROR \"non-printable bytes in message\"";
+        assert_eq!(output, vec![rejected, rejected, rejected, rejected, "OK", "VALUE 4"]);
+    }
+
+    #[test]
+    fn invalid_utf8_is_rejected_without_closing_the_connection() {
+        let state = ServerState::new(SharedCalculator::new(Calculator::new()), ServerConfig::default());
+
+        let output = serve(&state, b"OP + \xff\xfe\nOP + 1\nGET\n");
+
+        assert_eq!(output, vec!["ERROR \"non-printable bytes in message\"", "OK", "VALUE 1"]);
+    }
+
+    #[test]
+    fn framed_messages_with_control_bytes_are_rejected() {
+        let config = ServerConfig { framing: Framing::LengthPrefixed, ..ServerConfig::default() };
+        let state = ServerState::new(SharedCalculator::new(Calculator::new()), config);
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let mut input = Vec::new();
+        for payload in [&b"OP\x0b+ 1"[..], b"OP + 1", b"GET"] {
+            write_frame(&mut input, payload).unwrap();
+        }
+        let mut stream = FakeStream { input: Cursor::new(input), output: Vec::new() };
+
+        handle_connection(PeerStream::new(&mut stream, "peer".to_string()), state, sender, 0).unwrap();
+
+        let mut output = stream.output.as_slice();
+        assert!(matches!(Protocol::read_framed(&mut output).unwrap(), Protocol::Hello { .. }));
+        let responses: Vec<Protocol> = (0..3).map(|_| Protocol::read_framed(&mut output).unwrap()).collect();
+        assert_eq!(
+            responses,
+            vec![
+                Protocol::ErrorOperation("non-printable bytes in message".to_string()),
+                Protocol::Ok,
+                Protocol::Value("1".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn resume_restores_the_session_of_a_closed_connection() {
+        let state = ServerState::new(SharedCalculator::new(Calculator::new()), ServerConfig::default());
+
+        let first = serve(&state, b"HELLO 1 4\nSELECT foo\nOP + 2\nBEGIN\nOP * 5\n");
+        let Protocol::SessionId(id) = Protocol::from_bytes(first[0].as_bytes()).unwrap() else {
+            panic!("expected SESSION_ID, got {}", first[0]);
+        };
+
+        let resumed = serve(&state, format!("RESUME {}\nCOMMIT\nGET\nSWAP A B\n", id).as_bytes());
+        assert_eq!(resumed, vec!["OK", "OK", "VALUE 10", "ERROR \"REGISTERS was not negotiated\""]);
+
+        // La sesión se volvió a guardar al cerrarse la segunda conexión, y solo se retoma una vez.
+        assert_eq!(serve(&state, format!("RESUME {}\nGET\n", id).as_bytes()), vec!["OK", "VALUE 10"]);
+        assert_eq!(serve(&state, b"RESUME 0123\n"), vec!["ERROR \"unknown session\""]);
+    }
+
+    #[test]
+    fn sessions_expire_after_the_ttl() {
+        let config = ServerConfig {
+            session_ttl: Duration::from_millis(10),
+            ..ServerConfig::default()
+        };
+        let state = ServerState::new(SharedCalculator::new(Calculator::new()), config);
+
+        let first = serve(&state, b"HELLO 1 f\nSELECT foo\n");
+        let id = first[0].trim_start_matches("SESSION_ID ");
+        assert_eq!(id.len(), 32);
+        thread::sleep(Duration::from_millis(30));
+
+        assert_eq!(serve(&state, format!("RESUME {}\n", id).as_bytes()), vec!["ERROR \"unknown session\""]);
+    }
+
+    #[test]
+    fn drain_holds_back_other_connections_until_release() {
         let listener = TcpListener::bind("127.0.0.1:0").unwrap();
         let addr = listener.local_addr().unwrap();
+        let state = ServerState::new(SharedCalculator::new(Calculator::new()), ServerConfig::default());
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
 
-        let calculator = Arc::new(Mutex::new(Calculator::new()));
-        let (sender, _receiver) = channel::<LogEvent>();
+        let drain_state = state.clone();
         thread::spawn(move || {
-            let (stream, _) = listener.accept().unwrap();
-            handle_connection(stream, calculator, sender).unwrap();
+            let (stream, addr) = listener.accept().unwrap();
+            handle_connection(PeerStream::new(stream, addr.to_string()), drain_state, sender, 0).unwrap();
         });
+        let writers: Vec<_> = (0..4)
+            .map(|_| {
+                let state = state.clone();
+                thread::spawn(move || serve(&state, "OP + 1\n".repeat(500).as_bytes()))
+            })
+            .collect();
 
-        let mut client = TcpStream::connect(addr).unwrap();
-        client.write_all(b"OP + cinco\nGET\n").unwrap();
-        client.flush().unwrap();
+        let client = TcpStream::connect(addr).unwrap();
+        let mut reader = BufReader::new(client.try_clone().unwrap());
+        read_greeting(&mut reader);
+        let mut writer = client;
+        let mut request = |message: &[u8]| {
+            writer.write_all(message).unwrap();
+            let mut buf = String::new();
+            reader.read_line(&mut buf).unwrap();
+            buf.trim_end().to_string()
+        };
 
-        let mut reader = BufReader::new(client);
-        let mut buf = String::new();
-        reader.read_line(&mut buf).unwrap();
-        println!("buf: {}", buf);
+        assert_eq!(request(b"DRAIN\n"), "OK");
+        let drained = request(b"GET\n");
+        thread::sleep(Duration::from_millis(50));
+        // Mientras dura el `DRAIN` ninguna otra conexión cambia el valor, pero la que drena sí puede.
+        assert_eq!(request(b"GET\n"), drained);
+        assert_eq!(request(b"OP + 0\n"), "OK");
+        assert_eq!(request(b"DRAIN\n"), "ERROR \"already draining\"");
+        assert_eq!(request(b"RELEASE\n"), "OK");
+        assert_eq!(request(b"RELEASE\n"), "ERROR \"not draining\"");
+
+        for writer in writers {
+            assert!(writer.join().unwrap().iter().all(|line| line == "OK"));
+        }
+        assert_eq!(request(b"GET\n"), "VALUE 2000");
+    }
 
-        assert!(
-            buf.contains("ERROR \"parsing error: invalid integer: invalid digit found in string\"")
-        );
+    #[test]
+    fn handle_connection_with_length_prefixed_framing() {
+        let calculator = SharedCalculator::new(Calculator::new());
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let mut input = Vec::new();
+        Protocol::write_framed(&mut input, &Protocol::Operation("+ 7".to_string())).unwrap();
+        Protocol::write_framed(&mut input, &Protocol::Get).unwrap();
+        let mut stream = FakeStream {
+            input: Cursor::new(input),
+            output: Vec::new(),
+        };
+        let config = ServerConfig {
+            framing: Framing::LengthPrefixed,
+            ..ServerConfig::default()
+        };
 
-        buf.clear();
-        reader.read_line(&mut buf).unwrap();
+        handle_connection(PeerStream::new(&mut stream, "peer".to_string()), ServerState::new(calculator, config), sender, 0).unwrap();
 
-        assert!(buf.contains("VALUE 0"));
+        let mut output = stream.output.as_slice();
+        assert!(matches!(Protocol::read_framed(&mut output).unwrap(), Protocol::Hello { .. }));
+        assert!(matches!(Protocol::read_framed(&mut output).unwrap(), Protocol::Ok));
+        match Protocol::read_framed(&mut output).unwrap() {
+            Protocol::Value(value) => assert_eq!(value, "7"),
+            other => panic!("unexpected protocol: {}", other),
+        }
+        assert!(output.is_empty());
+    }
+
+    /// Lee el `HELLO` con el que el servidor abre cada conexión.
+    fn read_greeting<R: BufRead>(reader: &mut R) {
+        let mut greeting = String::new();
+        reader.read_line(&mut greeting).unwrap();
+        assert!(greeting.starts_with("HELLO "), "unexpected greeting: {}", greeting);
+    }
+
+    struct FakeStream {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for FakeStream {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for FakeStream {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn log_messages(receiver: std::sync::mpsc::Receiver<LogEvent>) -> Vec<String> {
+        receiver
+            .try_iter()
+            .filter_map(|event| match event {
+                LogEvent::Info { message, fields, .. } | LogEvent::Error { message, fields, .. } => {
+                    Some(format!("{}{}", message, format_fields(&fields)))
+                }
+                _ => None,
+            })
+            .collect()
     }
 
     #[test]
-    fn test_client_disconnects() {
-        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
-        let addr = listener.local_addr().unwrap();
+    fn handle_connection_logs_with_peer_address() {
+        let calculator = SharedCalculator::new(Calculator::new());
+        let (sender, receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let stream = FakeStream {
+            input: Cursor::new(b"OP + 1\nGET\n".to_vec()),
+            output: Vec::new(),
+        };
 
-        let calculator = Arc::new(Mutex::new(Calculator::new()));
-        let (sender, _receiver) = channel::<LogEvent>();
+        handle_connection(PeerStream::new(stream, "10.0.0.1:4000".to_string()), ServerState::new(calculator, ServerConfig::default()), sender, 0).unwrap();
 
-        let handle = std::thread::spawn(move || {
-            let (stream, _) = listener.accept().unwrap();
-            handle_connection(stream, calculator, sender)
+        let messages = log_messages(receiver);
+        assert!(messages.iter().any(|m| m.contains("[10.0.0.1:4000] received: OP + 1")));
+        assert!(messages.iter().any(|m| m.contains("[10.0.0.1:4000] sent: OK")));
+        assert!(messages.iter().any(|m| m.contains("[10.0.0.1:4000] sent: VALUE 1")));
+        assert!(messages.iter().any(|m| {
+            m == "Connection closed by client bytes_read=11 op_count=1 peer_addr=10.0.0.1:4000"
+        }));
+        assert!(messages.iter().all(|m| m.contains("[10.0.0.1:4000]") || m.contains("peer_addr=10.0.0.1:4000")));
+    }
+
+    #[test]
+    fn persisted_state_is_always_a_complete_file() {
+        let path = std::env::temp_dir().join(format!("handle_client_persist_{}.json", std::process::id()));
+        let config = ServerConfig {
+            state_file: Some(path.display().to_string()),
+            ..ServerConfig::default()
+        };
+        let state = ServerState::new(SharedCalculator::new(Calculator::with_initial(7)), config);
+        persist_state(&state).unwrap();
+
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..50 {
+                        persist_state(&state).unwrap();
+                        assert_eq!(Calculator::load(&path).unwrap().accumulation(), 7);
+                    }
+                });
+            }
         });
+        let _ = std::fs::remove_file(&path);
+    }
 
-        let client = TcpStream::connect(addr).unwrap();
-        drop(client);
+    #[test]
+    fn only_changes_to_default_rewrite_the_state_file() {
+        let path = std::env::temp_dir().join(format!("handle_client_namespaces_{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let config = ServerConfig {
+            state_file: Some(path.display().to_string()),
+            ..ServerConfig::default()
+        };
+        let state = ServerState::new(SharedCalculator::new(Calculator::new()), config);
 
-        let result = handle.join().unwrap();
-        assert!(matches!(result, Ok(())));
+        serve(&state, b"SELECT other\nOP + 1\nSELECT default\nBEGIN\nOP + 2\n");
+        assert!(!path.exists());
+
+        serve(&state, b"OP + 3\n");
+        let saved = Calculator::load(&path).unwrap().accumulation();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(saved, 3);
+    }
+
+    #[test]
+    fn auth_token_never_reaches_the_log_file() {
+        let dir = std::env::temp_dir();
+        let id = std::process::id();
+        let log_path = dir.join(format!("handle_client_auth_{}.log", id));
+        let unused = |name: &str| dir.join(format!("handle_client_auth_{}_{}.log", name, id)).display().to_string();
+        let _ = std::fs::remove_file(&log_path);
+        let (sender, handle) = start_logger(LoggerConfig {
+            sink: LogSink::File(log_path.clone()),
+            audit_file: unused("audit"),
+            metrics_file: unused("metrics"),
+            ..LoggerConfig::default()
+        });
+        let config = ServerConfig {
+            admin_token: Some("s3cr3t-token".to_string()),
+            ..ServerConfig::default()
+        };
+        let state = ServerState::new(SharedCalculator::new(Calculator::new()), config);
+        let stream = FakeStream {
+            input: Cursor::new(b"AUTH s3cr3t-token\nAUTH wrong-guess\n".to_vec()),
+            output: Vec::new(),
+        };
+
+        handle_connection(PeerStream::new(stream, "peer".to_string()), state, sender.clone(), 0).unwrap();
+        sender.send(LogEvent::CloseConnection).unwrap();
+        handle.join().unwrap();
+
+        let content = std::fs::read_to_string(&log_path).unwrap();
+        let _ = std::fs::remove_file(&log_path);
+        let _ = std::fs::remove_file(unused("audit"));
+        let _ = std::fs::remove_file(unused("metrics"));
+        assert!(content.contains("[peer] received: AUTH ***"));
+        assert!(!content.contains("s3cr3t-token"));
+        assert!(!content.contains("wrong-guess"));
+    }
+
+    struct BrokenWriter {
+        input: Cursor<Vec<u8>>,
+    }
+
+    impl Read for BrokenWriter {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for BrokenWriter {
+        fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("broken pipe"))
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn handle_connection_logs_errors_with_peer_address() {
+        let calculator = SharedCalculator::new(Calculator::new());
+        let (sender, receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let stream = BrokenWriter {
+            input: Cursor::new(b"GET\n".to_vec()),
+        };
+
+        let result = handle_connection(PeerStream::new(stream, "10.0.0.1:4000".to_string()), ServerState::new(calculator, ServerConfig::default()), sender, 0);
+
+        assert!(matches!(result, Err(ServerError::WriteFailed(_))));
+        let messages = log_messages(receiver);
+        assert!(messages.iter().any(|m| m.contains("[10.0.0.1:4000]") && m.contains("Failed to write")));
+    }
+
+    #[test]
+    fn apply_operation_sends_audit_events_in_order() {
+        let calculator = SharedCalculator::new(Calculator::new());
+        let (sender, receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+
+        for args in ["+ 5", "* 3", "<< 64", "- 1"] {
+            let mut cursor = Cursor::new(Vec::new());
+            handle_operation_message(&calculator, &mut cursor, args.to_string(), &sender, "10.0.0.1:4000")
+                .unwrap();
+        }
+
+        let audits: Vec<(String, String, i64)> = receiver
+            .try_iter()
+            .filter_map(|event| match event {
+                LogEvent::Audit { peer_addr, operation, result_accumulation, .. } => {
+                    Some((peer_addr, operation, result_accumulation))
+                }
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(
+            audits,
+            vec![
+                ("10.0.0.1:4000".to_string(), "+ 5".to_string(), 5),
+                ("10.0.0.1:4000".to_string(), "* 3".to_string(), 15),
+                ("10.0.0.1:4000".to_string(), "- 1".to_string(), 14),
+            ]
+        );
+    }
+
+    #[test]
+    fn list_clients_requires_admin_authentication() {
+        let calculator = SharedCalculator::new(Calculator::new());
+        let config = ServerConfig {
+            admin_token: Some("secret".to_string()),
+            ..ServerConfig::default()
+        };
+        let state = ServerState::new(calculator, config);
+        let id = state.registry.register("10.0.0.1:4000").unwrap();
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let mut stream = FakeStream {
+            input: Cursor::new(
+                b"ADMIN LIST_CLIENTS\nAUTH wrong\nADMIN LIST_CLIENTS\nOP + 1\nAUTH secret\nADMIN LIST_CLIENTS\n".to_vec(),
+            ),
+            output: Vec::new(),
+        };
+
+        handle_connection(PeerStream::new(&mut stream, "10.0.0.1:4000".to_string()), state, sender, id).unwrap();
+
+        let output = String::from_utf8(stream.output).unwrap();
+        let lines: Vec<&str> = output.lines().skip(1).collect();
+        assert_eq!(lines[0], "ERROR \"admin authentication required\"");
+        assert_eq!(lines[1], "ERROR \"authentication failed\"");
+        assert_eq!(lines[2], "ERROR \"admin authentication required\"");
+        assert_eq!(lines[3], "OK");
+        assert_eq!(lines[4], "OK");
+        assert!(lines[5].starts_with(&format!("CLIENTS id={} peer=10.0.0.1:4000 connected_at=", id)));
+        assert!(lines[5].ends_with("ops=1"));
     }
 }
@@ -1,81 +1,741 @@
 //! Modulo de Logger
 //! Este módulo proporciona un logger simple basado en hilos que escribe eventos de log en un archivo.
-//! Soporta eventos de tipo `Info`, `Error` y `CloseConnection`, y corre en un hilo dedicado.
-use std::{fs::OpenOptions, io::Write, sync::mpsc, thread, time::SystemTime};
+//! Soporta eventos de tipo `Debug`, `Info`, `Warn`, `Error`, `Audit`, `Metric`, `Flush` y `CloseConnection`, y corre en un hilo dedicado.
+//! Los eventos `Audit` se escriben en un archivo de auditoría separado del log general, y los `Metric`
+//! en un archivo de métricas con el formato de exposición de Prometheus.
+//! El log general puede ir a un archivo, a stderr o a ambos, según el [`LogSink`] configurado,
+//! como texto o como una línea JSON por evento, según el [`LogFormat`].
+//! `Info` y `Error` llevan además campos estructurados, que se arman con [`log_info!`] y [`log_error!`].
+//! Los eventos con nivel llevan el módulo que los generó y se filtran con el nivel mínimo de ese
+//! módulo en [`LoggerConfig::module_filters`], o con [`LoggerConfig::level`] si no tiene uno.
+//! El canal hacia el logger es acotado: si se llena, los eventos se descartan en lugar de
+//! bloquear a quien los envía, y se cuentan en [`LogSender::dropped_events`].
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, SendError, SyncSender, TrySendError},
+    },
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use flate2::{Compression, write::GzEncoder};
+
+/// Capacidad por defecto del canal del logger.
+pub const DEFAULT_LOG_CAPACITY: usize = 1000;
+
+/// Destino de los eventos del log general (`Debug`, `Info`, `Warn` y `Error`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LogSink {
+    /// Solo el archivo indicado. El archivo se borra al iniciar.
+    File(PathBuf),
+    /// Solo stderr.
+    Stderr,
+    /// El archivo indicado y, con las mismas líneas, stderr.
+    Both(PathBuf),
+}
+
+impl LogSink {
+    /// Devuelve el archivo en el que se escribe, si hay alguno.
+    pub fn file(&self) -> Option<&Path> {
+        match self {
+            LogSink::File(path) | LogSink::Both(path) => Some(path),
+            LogSink::Stderr => None,
+        }
+    }
+
+    /// Indica si las líneas se escriben en stderr.
+    pub fn writes_stderr(&self) -> bool {
+        matches!(self, LogSink::Stderr | LogSink::Both(_))
+    }
+}
+
+/// Formato de las líneas del log general.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    /// `[<timestamp>] NIVEL: mensaje clave=valor ...`
+    #[default]
+    Text,
+    /// Un objeto JSON por línea con `timestamp` (ISO 8601, como en texto), `level`, `message` y los campos del evento.
+    Json,
+}
+
+/// Nivel de un evento del log general, de menor a mayor severidad.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl FromStr for LogLevel {
+    type Err = ();
+
+    /// Acepta `debug`, `info`, `warn` y `error`, sin distinguir mayúsculas.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "debug" => Ok(LogLevel::Debug),
+            "info" => Ok(LogLevel::Info),
+            "warn" => Ok(LogLevel::Warn),
+            "error" => Ok(LogLevel::Error),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Archivos en los que escribe el logger y capacidad de su canal.
+#[derive(Clone, Debug)]
+pub struct LoggerConfig {
+    /// Destino del log general.
+    pub sink: LogSink,
+    /// Formato del log general.
+    pub format: LogFormat,
+    /// Nivel mínimo de los eventos que se escriben, para los módulos sin filtro propio.
+    pub level: LogLevel,
+    /// Nivel mínimo por módulo (por ejemplo `handle_client`), que reemplaza a `level`.
+    pub module_filters: HashMap<String, LogLevel>,
+    /// Si es `true`, los mensajes idénticos consecutivos se escriben una vez, seguidos de
+    /// `(repeated N times)` cuando llega otro mensaje.
+    pub dedup: bool,
+    /// Si es `true`, al cambiar el día se archiva el log como `server.<AAAA-MM-DD>.log` y se
+    /// empieza uno nuevo. La fecha es la del día que terminó, en UTC.
+    pub rolling: bool,
+    /// Si es `true`, cada log archivado se comprime en segundo plano a `<archivo>.gz` y se borra el
+    /// original. Si la compresión falla se conserva el archivo sin comprimir.
+    pub compress_rotated: bool,
+    /// Ruta del archivo de auditoría. Se abre en modo append con el primer evento `Audit`.
+    pub audit_file: String,
+    /// Ruta del archivo de métricas. Se abre en modo append con el primer evento `Metric`.
+    pub metrics_file: String,
+    /// Cantidad máxima de eventos encolados esperando al logger.
+    pub capacity: usize,
+}
+
+impl Default for LoggerConfig {
+    fn default() -> Self {
+        Self {
+            sink: LogSink::File(PathBuf::from("./logs/server.log")),
+            format: LogFormat::Text,
+            level: LogLevel::Debug,
+            module_filters: HashMap::new(),
+            dedup: false,
+            rolling: false,
+            compress_rotated: true,
+            audit_file: "./logs/audit.log".to_string(),
+            metrics_file: "./logs/metrics.log".to_string(),
+            capacity: DEFAULT_LOG_CAPACITY,
+        }
+    }
+}
+
+impl LoggerConfig {
+    /// Crea la configuración por defecto, para encadenar [`LoggerConfig::level`] y [`LoggerConfig::filter`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Nivel mínimo para los módulos sin filtro propio.
+    pub fn level(mut self, level: LogLevel) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Nivel mínimo para los eventos del módulo `module`.
+    pub fn filter(mut self, module: &str, level: LogLevel) -> Self {
+        self.module_filters.insert(module.to_string(), level);
+        self
+    }
+
+    /// Indica si se escribe un evento de nivel `level` generado en `module`.
+    fn enabled(&self, level: LogLevel, module: Option<&str>) -> bool {
+        let min = module.and_then(|m| self.module_filters.get(m)).unwrap_or(&self.level);
+        level >= *min
+    }
+}
+
+/// Extremo de envío del canal acotado del logger.
+/// Clonarlo es barato y todos los clones comparten el contador de eventos descartados.
+#[derive(Clone)]
+pub struct LogSender {
+    sender: SyncSender<LogEvent>,
+    dropped_events: Arc<AtomicU64>,
+}
+
+impl LogSender {
+    /// Encola `event` sin bloquear. Si el canal está lleno el evento se descarta y se cuenta
+    /// en [`LogSender::dropped_events`].
+    ///
+    /// #Errores
+    /// `TrySendError::Full` si se descartó el evento.
+    /// `TrySendError::Disconnected` si el logger ya terminó.
+    pub fn send(&self, event: LogEvent) -> Result<(), TrySendError<LogEvent>> {
+        let result = self.sender.try_send(event);
+        if let Err(TrySendError::Full(_)) = result {
+            self.dropped_events.fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+
+    /// Encola `event` esperando a que haya lugar en el canal. Se usa para los eventos de control
+    /// (`Flush`, `CloseConnection`), que no se pueden descartar.
+    ///
+    /// #Errores
+    /// `SendError` si el logger ya terminó.
+    pub fn send_blocking(&self, event: LogEvent) -> Result<(), SendError<LogEvent>> {
+        self.sender.send(event)
+    }
+
+    /// Devuelve la cantidad de eventos descartados porque el canal estaba lleno.
+    pub fn dropped_events(&self) -> u64 {
+        self.dropped_events.load(Ordering::Relaxed)
+    }
+}
+
+/// Crea el canal acotado del logger con lugar para `capacity` eventos.
+pub fn log_channel(capacity: usize) -> (LogSender, mpsc::Receiver<LogEvent>) {
+    let (sender, receiver) = mpsc::sync_channel(capacity);
+    let sender = LogSender {
+        sender,
+        dropped_events: Arc::new(AtomicU64::new(0)),
+    };
+    (sender, receiver)
+}
 
 /// Representa un evento de log que puede ser enviado al hilo del logger.
+/// `module` es el módulo que generó el evento; los macros `log_*!` lo completan solos.
 pub enum LogEvent{ 
-    /// Mensaje informativo
-    Info(String), 
-    /// Mensaje de error    
-    Error(String), 
+    /// Mensaje de depuración
+    Debug {
+        message: String,
+        module: Option<String>,
+    },
+    /// Mensaje informativo, con campos estructurados
+    Info {
+        message: String,
+        fields: HashMap<String, String>,
+        module: Option<String>,
+    },
+    /// Advertencia: algo no salió como se esperaba pero el servidor sigue funcionando
+    Warn {
+        message: String,
+        module: Option<String>,
+    },
+    /// Mensaje de error, con campos estructurados
+    Error {
+        message: String,
+        fields: HashMap<String, String>,
+        module: Option<String>,
+    },
+    /// Operación aplicada a la calculadora, para el log de auditoría
+    Audit {
+        peer_addr: String,
+        operation: String,
+        result_accumulation: i64,
+        timestamp: SystemTime,
+    },
+    /// Muestra de una serie temporal, para el archivo de métricas
+    Metric {
+        name: String,
+        value: f64,
+        tags: HashMap<String, String>,
+        timestamp: SystemTime,
+    },
+    /// Pide que todo lo escrito hasta ahora llegue al disco. El logger responde por el canal
+    /// cuando terminó; ver [`flush_logger`].
+    Flush(mpsc::Sender<()>),
     /// Señal para cerrar el hilo del logger de manera segura    
     CloseConnection
 }
 
+/// Reloj del logger. Se inyecta para poder simular el paso del tiempo en tests.
+pub type TimeProvider = Box<dyn Fn() -> SystemTime + Send>;
+
 /// Inicia un hilo de logger que escucha eventos `LogEvent` y los escribe en un archivo.
-/// Recibe: `file_path` - Ruta del archivo de log. El archivo se borra al iniciar.
-/// Recibe: `receiver` - Canal MPSC desde el que se recibirán los eventos de log.
+/// Recibe la configuración con el destino del log, las rutas de los archivos y la capacidad del canal.
 ///
-/// Devuelve un `JoinHandle` del hilo del logger. Se puede llamar a `.join()` para esperar a que termine.
+/// Devuelve el extremo de envío del canal y el `JoinHandle` del hilo del logger. Se puede llamar
+/// a `.join()` para esperar a que termine.
 ///
 /// #Comportamiento
 ///
-/// Borra el contenido del archivo de log al inicio.
+/// Borra el contenido del archivo de log al inicio, si el destino tiene uno.
 /// Añade nuevas entradas a medida que llegan eventos.
-/// Termina cuando recibe `LogEvent::CloseConnection`.
-pub fn start_logger(file_path: &str, reciever: mpsc::Receiver<LogEvent>) -> thread::JoinHandle<()> { 
-    let path = file_path.to_string(); 
-    
-    thread::spawn(move || { 
+/// Con `rolling`, archiva el log al escribir la primera línea de un día nuevo y, con `compress_rotated`,
+/// lo comprime en otro hilo. Antes de terminar espera a que terminen las compresiones pendientes.
+/// Con `LogEvent::Flush` sincroniza los archivos con el disco y avisa por el canal del evento.
+/// Termina cuando recibe `LogEvent::CloseConnection`, después de sincronizar los archivos con el disco.
+pub fn start_logger(config: LoggerConfig) -> (LogSender, thread::JoinHandle<()>) {
+    let (sender, reciever) = log_channel(config.capacity);
+    let handle = thread::spawn(move || run_logger(config, reciever, &mut io::stderr(), Box::new(SystemTime::now)));
+    (sender, handle)
+}
+
+/// Cuerpo del hilo del logger: procesa los eventos de `reciever` hasta `CloseConnection`.
+/// Las líneas que el destino manda a stderr se escriben en `stderr` y la hora se toma de `now`, para
+/// poder capturarlas y controlarla en tests.
+fn run_logger(config: LoggerConfig, reciever: mpsc::Receiver<LogEvent>, stderr: &mut dyn Write, now: TimeProvider) {
+    let audit_path = &config.audit_file;
+    let metrics_path = &config.metrics_file;
+    let echo = config.sink.writes_stderr();
+    let format = config.format;
 
-        if let Err(e) = OpenOptions::new().write(true).truncate(true).create(true).open(&path) {
+    let mut file: Option<File> = None;
+    if let Some(path) = config.sink.file() {
+        if let Err(e) = OpenOptions::new().write(true).truncate(true).create(true).open(path) {
             eprintln!("Failed to clear log file: {}", e);
             return;
         }
 
-        let mut file = match OpenOptions::new().create(true).append(true).open(&path) {
-            Ok(f) => f,
+        file = match OpenOptions::new().create(true).append(true).open(path) {
+            Ok(f) => Some(f),
             Err(e) => {
                 eprintln!("Failed to open log file: {}", e);
                 return;
             }
         };
+    }
+
+    let mut audit_file: Option<File> = None;
+    let mut metrics_file: Option<File> = None;
+    let mut last_message: Option<(LogLevel, String)> = None;
+    let mut repeat_count: usize = 0;
+    let mut last_date = date_of(now());
+    let mut compressions: Vec<thread::JoinHandle<()>> = Vec::new();
 
-        for event in reciever {
-            match event { 
-                LogEvent::Info(msg) => { 
-                    let line = format!("[{:?}] INFO: {}\n", SystemTime::now(), msg); 
-                    let _ = file.write_all(line.as_bytes());
-                    let _ = file.flush();
-                },
-                LogEvent::Error(msg) => { 
-                    let line = format!("[{:?}] ERROR: {}\n", SystemTime::now(), msg); 
-                    let _ = file.write_all(line.as_bytes());
-                    let _ = file.flush();
+    for event in reciever {
+        match event { 
+            LogEvent::Debug { .. } | LogEvent::Info { .. } | LogEvent::Warn { .. } | LogEvent::Error { .. } => {
+                if let Some((level, module)) = event_level(&event)
+                    && !config.enabled(level, module)
+                {
+                    continue;
+                }
+                let timestamp = now();
+                if config.dedup
+                    && let Some((level, key)) = dedup_key(&event)
+                {
+                    if last_message.as_ref().is_some_and(|(_, last)| *last == key) {
+                        repeat_count += 1;
+                        continue;
+                    }
+                    write_repeats(&mut repeat_count, last_message.as_ref(), format, timestamp, file.as_mut(), echo, stderr);
+                    last_message = Some((level, key));
+                }
+                if config.rolling
+                    && let Some(path) = config.sink.file()
+                    && let Some(archived) = roll_log_file(path, &mut file, &mut last_date, date_of(timestamp))
+                    && config.compress_rotated
+                {
+                    compressions.push(thread::spawn(move || {
+                        if let Err(e) = compress_file(&archived) {
+                            eprintln!("Failed to compress rotated log {}: {}", archived.display(), e);
+                        }
+                    }));
+                }
+                write_log_line(&format_log_line(&event, format, timestamp), file.as_mut(), echo, stderr);
+            }
+            LogEvent::Audit { peer_addr, operation, result_accumulation, timestamp } => {
+                if audit_file.is_none() {
+                    match OpenOptions::new().create(true).append(true).open(audit_path) {
+                        Ok(f) => audit_file = Some(f),
+                        Err(e) => {
+                            eprintln!("Failed to open audit file: {}", e);
+                            continue;
+                        }
+                    }
+                }
+                if let Some(audit) = audit_file.as_mut() {
+                    let line = format_audit_line(&peer_addr, &operation, result_accumulation, timestamp);
+                    let _ = audit.write_all(line.as_bytes());
+                    let _ = audit.flush();
+                }
+            }
+            LogEvent::Metric { name, value, tags, timestamp } => {
+                if metrics_file.is_none() {
+                    match OpenOptions::new().create(true).append(true).open(metrics_path) {
+                        Ok(f) => metrics_file = Some(f),
+                        Err(e) => {
+                            eprintln!("Failed to open metrics file: {}", e);
+                            continue;
+                        }
+                    }
+                }
+                if let Some(metrics) = metrics_file.as_mut() {
+                    let line = format_metric_line(&name, value, &tags, timestamp);
+                    let _ = metrics.write_all(line.as_bytes());
+                    let _ = metrics.flush();
                 }
-                LogEvent::CloseConnection => break,
             }
+            LogEvent::Flush(done) => {
+                write_repeats(&mut repeat_count, last_message.as_ref(), format, now(), file.as_mut(), echo, stderr);
+                let _ = stderr.flush();
+                for f in [file.as_mut(), audit_file.as_mut(), metrics_file.as_mut()].into_iter().flatten() {
+                    let _ = f.flush();
+                    let _ = f.sync_data();
+                }
+                let _ = done.send(());
+            }
+            LogEvent::CloseConnection => break,
+        }
+    }
+    write_repeats(&mut repeat_count, last_message.as_ref(), format, now(), file.as_mut(), echo, stderr);
+    for compression in compressions {
+        let _ = compression.join();
+    }
+
+    for f in [file.as_mut(), audit_file.as_mut(), metrics_file.as_mut()].into_iter().flatten() {
+        if let Err(e) = f.sync_all() {
+            eprintln!("Failed to sync log file: {}", e);
         }
-    })
+    }
+}
+
+/// Escribe una línea del log general en el archivo, si hay, y en stderr si `echo` es `true`.
+fn write_log_line(line: &str, file: Option<&mut File>, echo: bool, stderr: &mut dyn Write) {
+    if let Some(file) = file {
+        let _ = file.write_all(line.as_bytes());
+        let _ = file.flush();
+    }
+    if echo {
+        let _ = stderr.write_all(line.as_bytes());
+    }
+}
+
+/// Si `today` es un día posterior a `last_date`, archiva el log en [`dated_path`] con la fecha
+/// del día que terminó y abre uno nuevo en `path`. Devuelve la ruta del archivo archivado, si se archivó.
+fn roll_log_file(path: &Path, file: &mut Option<File>, last_date: &mut NaiveDate, today: NaiveDate) -> Option<PathBuf> {
+    if today <= *last_date {
+        return None;
+    }
+    if let Some(old) = file.take() {
+        let _ = old.sync_all();
+    }
+    let archived = dated_path(path, *last_date);
+    let archived = match fs::rename(path, &archived) {
+        Ok(()) => Some(archived),
+        Err(e) => {
+            eprintln!("Failed to rotate log file: {}", e);
+            None
+        }
+    };
+    *file = match OpenOptions::new().create(true).append(true).open(path) {
+        Ok(f) => Some(f),
+        Err(e) => {
+            eprintln!("Failed to open log file: {}", e);
+            None
+        }
+    };
+    *last_date = today;
+    archived
+}
+
+/// Comprime `path` con gzip en `<path>.gz` y borra el original. Devuelve la ruta del `.gz`.
+///
+/// #Errores
+/// Si falla la lectura, la escritura o el borrado. En ese caso se borra el `.gz` a medio escribir
+/// y se conserva el original.
+fn compress_file(path: &Path) -> io::Result<PathBuf> {
+    let mut gz_name = path.file_name().unwrap_or_default().to_os_string();
+    gz_name.push(".gz");
+    let gz_path = path.with_file_name(gz_name);
+    match write_gzip(path, &gz_path).and_then(|_| fs::remove_file(path)) {
+        Ok(()) => Ok(gz_path),
+        Err(e) => {
+            let _ = fs::remove_file(&gz_path);
+            Err(e)
+        }
+    }
+}
+
+/// Escribe en `gz_path` el contenido de `path` comprimido con gzip y lo sincroniza con el disco.
+fn write_gzip(path: &Path, gz_path: &Path) -> io::Result<()> {
+    let mut input = File::open(path)?;
+    let mut encoder = GzEncoder::new(File::create(gz_path)?, Compression::default());
+    io::copy(&mut input, &mut encoder)?;
+    encoder.finish()?.sync_all()
+}
+
+/// Si el último mensaje se repitió, escribe `(repeated N times)` con su mismo nivel y reinicia la cuenta.
+fn write_repeats(
+    repeat_count: &mut usize,
+    last_message: Option<&(LogLevel, String)>,
+    format: LogFormat,
+    timestamp: SystemTime,
+    file: Option<&mut File>,
+    echo: bool,
+    stderr: &mut dyn Write,
+) {
+    if *repeat_count == 0 {
+        return;
+    }
+    if let Some((level, _)) = last_message {
+        let event = event_with_level(*level, format!("(repeated {} times)", repeat_count));
+        write_log_line(&format_log_line(&event, format, timestamp), file, echo, stderr);
+    }
+    *repeat_count = 0;
+}
+
+/// Espera a que el logger que recibe de `sender` haya escrito en disco todos los eventos
+/// enviados antes. Devuelve `false` si el logger ya no está corriendo.
+pub fn flush_logger(sender: &LogSender) -> bool {
+    let (done, wait) = mpsc::channel();
+    sender.send_blocking(LogEvent::Flush(done)).is_ok() && wait.recv().is_ok()
+}
+
+/// Arma la línea del log general para `event` en el formato `format`, la misma que se escribe en el
+/// archivo y en stderr, con la hora `timestamp`. En texto los campos se agregan al final como `clave=valor`, ordenados por clave;
+/// en JSON van como claves extra del objeto, sin pisar `timestamp`, `level` ni `message`.
+/// Los eventos que no van al log general (`Audit`, `Metric`, `Flush`, `CloseConnection`) dan una cadena vacía.
+///
+/// Ejemplo: `[2024-01-15T12:34:56.789Z] INFO: Connection closed by client op_count=2 peer_addr=127.0.0.1:5000`
+fn format_log_line(event: &LogEvent, format: LogFormat, timestamp: SystemTime) -> String {
+    let no_fields = HashMap::new();
+    let Some((level, message, fields)) = log_parts(event) else {
+        return String::new();
+    };
+    let fields = fields.unwrap_or(&no_fields);
+    match format {
+        LogFormat::Text => format!("[{}] {}: {}{}\n", format_timestamp(timestamp), level, message, format_fields(fields)),
+        LogFormat::Json => {
+            let mut object: serde_json::Map<String, serde_json::Value> =
+                fields.iter().map(|(key, value)| (key.clone(), serde_json::Value::from(value.as_str()))).collect();
+            object.insert("timestamp".to_string(), serde_json::Value::from(format_timestamp(timestamp)));
+            object.insert("level".to_string(), serde_json::Value::from(level));
+            object.insert("message".to_string(), serde_json::Value::from(message));
+            format!("{}\n", serde_json::Value::Object(object))
+        }
+    }
+}
+
+/// Campos estructurados de un evento `Info` o `Error`.
+type LogFields = HashMap<String, String>;
+
+/// Devuelve el nombre del nivel, el mensaje y los campos (si tiene) de un evento del log general,
+/// o `None` para los demás eventos.
+fn log_parts(event: &LogEvent) -> Option<(&'static str, &str, Option<&LogFields>)> {
+    match event {
+        LogEvent::Debug { message, .. } => Some(("DEBUG", message, None)),
+        LogEvent::Info { message, fields, .. } => Some(("INFO", message, Some(fields))),
+        LogEvent::Warn { message, .. } => Some(("WARN", message, None)),
+        LogEvent::Error { message, fields, .. } => Some(("ERROR", message, Some(fields))),
+        _ => None,
+    }
+}
+
+/// Clave con la que se comparan dos eventos para deduplicarlos: nivel, mensaje y campos, sin el timestamp.
+fn dedup_key(event: &LogEvent) -> Option<(LogLevel, String)> {
+    let (level, _) = event_level(event)?;
+    let (name, message, fields) = log_parts(event)?;
+    Some((level, format!("{} {}{}", name, message, fields.map(format_fields).unwrap_or_default())))
+}
+
+/// Arma un evento de nivel `level` con `message` y sin módulo ni campos.
+fn event_with_level(level: LogLevel, message: String) -> LogEvent {
+    match level {
+        LogLevel::Debug => LogEvent::Debug { message, module: None },
+        LogLevel::Info => LogEvent::Info { message, fields: HashMap::new(), module: None },
+        LogLevel::Warn => LogEvent::Warn { message, module: None },
+        LogLevel::Error => LogEvent::Error { message, fields: HashMap::new(), module: None },
+    }
+}
+
+/// Formatea `timestamp` en UTC según ISO 8601 / RFC 3339, con milisegundos.
+///
+/// Ejemplo: `2024-01-15T12:34:56.789Z`
+fn format_timestamp(timestamp: SystemTime) -> String {
+    DateTime::<Utc>::from(timestamp).format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()
+}
+
+/// Devuelve la fecha UTC de `timestamp`, con la que se decide cuándo rotar el log.
+fn date_of(timestamp: SystemTime) -> NaiveDate {
+    DateTime::<Utc>::from(timestamp).date_naive()
+}
+
+/// Ruta con la que se archiva el log del día `date`: la fecha va antes de la extensión.
+///
+/// Ejemplo: `logs/server.log` -> `logs/server.2024-01-14.log`
+fn dated_path(path: &Path, date: NaiveDate) -> PathBuf {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let name = match path.extension() {
+        Some(extension) => format!("{}.{}.{}", stem, date.format("%Y-%m-%d"), extension.to_string_lossy()),
+        None => format!("{}.{}", stem, date.format("%Y-%m-%d")),
+    };
+    path.with_file_name(name)
+}
+
+/// Devuelve el nivel y el módulo de un evento del log general, o `None` para los demás eventos.
+fn event_level(event: &LogEvent) -> Option<(LogLevel, Option<&str>)> {
+    match event {
+        LogEvent::Debug { module, .. } => Some((LogLevel::Debug, module.as_deref())),
+        LogEvent::Info { module, .. } => Some((LogLevel::Info, module.as_deref())),
+        LogEvent::Warn { module, .. } => Some((LogLevel::Warn, module.as_deref())),
+        LogEvent::Error { module, .. } => Some((LogLevel::Error, module.as_deref())),
+        _ => None,
+    }
+}
+
+/// Nombre del módulo para los filtros: la ruta de `module_path!()` sin el nombre del binario,
+/// o `main` para la raíz.
+///
+/// Ejemplo: `server::handle_client` -> `handle_client`
+pub fn module_name(module_path: &str) -> String {
+    match module_path.split_once("::") {
+        Some((_, module)) => module.to_string(),
+        None => "main".to_string(),
+    }
+}
+
+/// Arma los campos de un evento como ` clave=valor`, ordenados por clave, para el log en texto.
+pub fn format_fields(fields: &HashMap<String, String>) -> String {
+    let mut fields: Vec<(&String, &String)> = fields.iter().collect();
+    fields.sort();
+    fields.into_iter().map(|(key, value)| format!(" {}={}", key, value)).collect()
+}
+
+/// Envía por `sender` un `LogEvent::Info` con `message` y los campos `clave => valor` indicados,
+/// con el módulo desde el que se llama. Las claves son identificadores y los valores, cualquier cosa
+/// que implemente `Display`. Devuelve el resultado de [`LogSender::send`].
+///
+/// Ejemplo: `log_info!(sender, "Connection closed by client", peer_addr => peer_addr, op_count => 2)`
+macro_rules! log_info {
+    ($sender:expr, $message:expr $(, $key:ident => $value:expr)* $(,)?) => {
+        $sender.send($crate::logger::LogEvent::Info {
+            message: ::std::string::ToString::to_string(&$message),
+            fields: $crate::logger::log_fields!($($key => $value),*),
+            module: Some($crate::logger::module_name(module_path!())),
+        })
+    };
+}
+
+/// Igual que [`log_info!`], pero envía un `LogEvent::Error`.
+macro_rules! log_error {
+    ($sender:expr, $message:expr $(, $key:ident => $value:expr)* $(,)?) => {
+        $sender.send($crate::logger::LogEvent::Error {
+            message: ::std::string::ToString::to_string(&$message),
+            fields: $crate::logger::log_fields!($($key => $value),*),
+            module: Some($crate::logger::module_name(module_path!())),
+        })
+    };
+}
+
+/// Envía por `sender` un `LogEvent::Debug` con `message` y el módulo desde el que se llama.
+macro_rules! log_debug {
+    ($sender:expr, $message:expr $(,)?) => {
+        $sender.send($crate::logger::LogEvent::Debug {
+            message: ::std::string::ToString::to_string(&$message),
+            module: Some($crate::logger::module_name(module_path!())),
+        })
+    };
+}
+
+/// Envía por `sender` un `LogEvent::Warn` con `message` y el módulo desde el que se llama.
+macro_rules! log_warn {
+    ($sender:expr, $message:expr $(,)?) => {
+        $sender.send($crate::logger::LogEvent::Warn {
+            message: ::std::string::ToString::to_string(&$message),
+            module: Some($crate::logger::module_name(module_path!())),
+        })
+    };
+}
+
+/// Arma el `HashMap` de campos de [`log_info!`] y [`log_error!`].
+macro_rules! log_fields {
+    ($($key:ident => $value:expr),* $(,)?) => {
+        ::std::collections::HashMap::<String, String>::from([
+            $((stringify!($key).to_string(), ::std::string::ToString::to_string(&$value))),*
+        ])
+    };
+}
+
+pub(crate) use {log_debug, log_error, log_fields, log_info, log_warn};
+
+/// Arma una línea del log de auditoría con formato `clave=valor`, fácil de parsear.
+/// El timestamp se expresa en milisegundos desde la época Unix.
+///
+/// Ejemplo: `timestamp=1700000000000 peer=127.0.0.1:5000 operation="+ 5" accumulation=5`
+fn format_audit_line(peer_addr: &str, operation: &str, result_accumulation: i64, timestamp: SystemTime) -> String {
+    let millis = timestamp
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    format!(
+        "timestamp={} peer={} operation=\"{}\" accumulation={}\n",
+        millis, peer_addr, operation, result_accumulation
+    )
+}
+
+/// Arma una línea del archivo de métricas con formato `<timestamp> <name>{<tags>} <value>`, como
+/// en la exposición de Prometheus. Los tags se ordenan por clave para que la línea sea estable y
+/// las llaves se omiten si no hay ninguno. El timestamp se expresa en milisegundos desde la época Unix.
+///
+/// Ejemplo: `1700000000000 connection_requests_total{peer="127.0.0.1:5000"} 3`
+fn format_metric_line(name: &str, value: f64, tags: &HashMap<String, String>, timestamp: SystemTime) -> String {
+    let millis = timestamp
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    let mut tags: Vec<(&String, &String)> = tags.iter().collect();
+    tags.sort();
+    let labels: Vec<String> = tags
+        .into_iter()
+        .map(|(key, value)| format!("{}=\"{}\"", key, value.replace('\\', "\\\\").replace('"', "\\\"")))
+        .collect();
+    let labels = if labels.is_empty() { String::new() } else { format!("{{{}}}", labels.join(",")) };
+    format!("{} {}{} {}\n", millis, name, labels, value)
 }
 
 #[cfg(test)]
 mod tests {
-    use std::{fs, sync::mpsc};
+    use std::{
+        collections::HashMap,
+        fs,
+        io::Read,
+        path::{Path, PathBuf},
+        sync::{
+            Arc,
+            atomic::{AtomicU64, Ordering},
+        },
+        thread,
+        time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    };
+
+    use chrono::NaiveDate;
+    use flate2::read::GzDecoder;
+
+    use crate::logger::{
+        compress_file, dated_path, flush_logger, format_audit_line, format_log_line, format_metric_line, format_timestamp, log_channel, module_name, run_logger,
+        start_logger, LogEvent, LogFormat, LogLevel, LogSink, LoggerConfig,
+    };
+
+    fn test_config(log_file: &str, audit_file: &str, metrics_file: &str) -> LoggerConfig {
+        LoggerConfig {
+            sink: LogSink::File(PathBuf::from(log_file)),
+            audit_file: audit_file.to_string(),
+            metrics_file: metrics_file.to_string(),
+            ..LoggerConfig::default()
+        }
+    }
 
-    use crate::logger::{start_logger, LogEvent};
     #[test]
     fn test_logger_receives_events() {
         let log_path = "logs/server_test_.log";
 
         let _ = fs::remove_file(log_path);
 
-        let (sender, receiver) = mpsc::channel();
-        let handle = start_logger(log_path, receiver);
+        let (sender, handle) = start_logger(test_config(log_path, "logs/audit_unused_test_.log", "logs/metrics_unused_test_.log"));
 
-        sender.send(LogEvent::Info("Test info".to_string())).unwrap();
-        sender.send(LogEvent::Error("Test error".to_string())).unwrap();
+        log_info!(sender, "Test info").unwrap();
+        log_error!(sender, "Test error").unwrap();
+        log_debug!(sender, "Test debug").unwrap();
+        log_warn!(sender, "Test warn").unwrap();
         sender.send(LogEvent::CloseConnection).unwrap(); 
 
         handle.join().unwrap();
@@ -83,7 +743,416 @@ mod tests {
         let content = fs::read_to_string(log_path).unwrap();
         assert!(content.contains("INFO: Test info"));
         assert!(content.contains("ERROR: Test error"));
+        assert!(content.contains("DEBUG: Test debug"));
+        assert!(content.contains("WARN: Test warn"));
+        for line in content.lines() {
+            assert!(line.starts_with('[') && line[25..].starts_with("] "), "unexpected line: {}", line);
+            assert!(is_iso_8601(&line[1..25]), "unexpected timestamp: {}", line);
+        }
+        let _ = fs::remove_file(log_path);
+    }
+
+    /// Indica si `s` tiene la forma `AAAA-MM-DDTHH:MM:SS.mmmZ`.
+    fn is_iso_8601(s: &str) -> bool {
+        let bytes = s.as_bytes();
+        bytes.len() == 24
+            && bytes.iter().enumerate().all(|(i, b)| match i {
+                4 | 7 => *b == b'-',
+                10 => *b == b'T',
+                13 | 16 => *b == b':',
+                19 => *b == b'.',
+                23 => *b == b'Z',
+                _ => b.is_ascii_digit(),
+            })
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        assert_eq!(format_timestamp(UNIX_EPOCH + Duration::from_millis(1_705_322_096_789)), "2024-01-15T12:34:56.789Z");
+        // 29 de febrero de un año bisiesto y fin de siglo.
+        assert_eq!(format_timestamp(UNIX_EPOCH + Duration::from_secs(951_782_400)), "2000-02-29T00:00:00.000Z");
+        assert_eq!(format_timestamp(UNIX_EPOCH + Duration::from_secs(4_107_542_399)), "2100-02-28T23:59:59.000Z");
+    }
+
+    #[test]
+    fn test_format_audit_line() {
+        let timestamp = UNIX_EPOCH + Duration::from_millis(1_500);
+        assert_eq!(
+            format_audit_line("127.0.0.1:5000", "+ 5", 5, timestamp),
+            "timestamp=1500 peer=127.0.0.1:5000 operation=\"+ 5\" accumulation=5\n"
+        );
+    }
+
+    #[test]
+    fn test_audit_events_go_to_audit_file_in_order() {
+        let log_path = "logs/server_audit_test_.log";
+        let audit_path = "logs/audit_test_.log";
         let _ = fs::remove_file(log_path);
+        let _ = fs::remove_file(audit_path);
+
+        let (sender, handle) = start_logger(test_config(log_path, audit_path, "logs/metrics_unused_test_.log"));
+
+        for (operation, result) in [("+ 5", 5), ("* 3", 15), ("- 1", 14)] {
+            sender
+                .send(LogEvent::Audit {
+                    peer_addr: "127.0.0.1:5000".to_string(),
+                    operation: operation.to_string(),
+                    result_accumulation: result,
+                    timestamp: UNIX_EPOCH,
+                })
+                .unwrap();
+        }
+        sender.send(LogEvent::CloseConnection).unwrap();
+        handle.join().unwrap();
+
+        let audit = fs::read_to_string(audit_path).unwrap();
+        let lines: Vec<&str> = audit.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "timestamp=0 peer=127.0.0.1:5000 operation=\"+ 5\" accumulation=5",
+                "timestamp=0 peer=127.0.0.1:5000 operation=\"* 3\" accumulation=15",
+                "timestamp=0 peer=127.0.0.1:5000 operation=\"- 1\" accumulation=14",
+            ]
+        );
+        assert!(!fs::read_to_string(log_path).unwrap().contains("accumulation="));
+        let _ = fs::remove_file(log_path);
+        let _ = fs::remove_file(audit_path);
+    }
+
+    #[test]
+    fn test_format_metric_line() {
+        let timestamp = UNIX_EPOCH + Duration::from_millis(1_500);
+        let tags = HashMap::from([
+            ("peer".to_string(), "127.0.0.1:5000".to_string()),
+            ("connection".to_string(), "3".to_string()),
+        ]);
+        assert_eq!(
+            format_metric_line("connection_requests_total", 3.0, &tags, timestamp),
+            "1500 connection_requests_total{connection=\"3\",peer=\"127.0.0.1:5000\"} 3\n"
+        );
+        assert_eq!(format_metric_line("uptime_seconds", 0.25, &HashMap::new(), UNIX_EPOCH), "0 uptime_seconds 0.25\n");
+        let quoted = HashMap::from([("msg".to_string(), "say \"hi\"".to_string())]);
+        assert_eq!(format_metric_line("m", 1.0, &quoted, UNIX_EPOCH), "0 m{msg=\"say \\\"hi\\\"\"} 1\n");
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_metric_events_go_to_metrics_file() {
+        let log_path = "logs/server_metrics_test_.log";
+        let metrics_path = "logs/metrics_test_.log";
+        let _ = fs::remove_file(log_path);
+        let _ = fs::remove_file(metrics_path);
+
+        let (sender, handle) = start_logger(test_config(log_path, "logs/audit_unused_test_.log", metrics_path));
+        sender
+            .send(LogEvent::Metric {
+                name: "connection_latency_seconds".to_string(),
+                value: 0.5,
+                tags: HashMap::from([("peer".to_string(), "p".to_string())]),
+                timestamp: UNIX_EPOCH,
+            })
+            .unwrap();
+        sender.send(LogEvent::CloseConnection).unwrap();
+        handle.join().unwrap();
+
+        assert_eq!(fs::read_to_string(metrics_path).unwrap(), "0 connection_latency_seconds{peer=\"p\"} 0.5\n");
+        assert!(fs::read_to_string(log_path).unwrap().is_empty());
+        let _ = fs::remove_file(log_path);
+        let _ = fs::remove_file(metrics_path);
+    }
+
+    #[test]
+    fn test_flush_waits_for_prior_events_to_be_written() {
+        let log_path = "logs/server_flush_test_.log";
+        let audit_path = "logs/audit_flush_test_.log";
+        let _ = fs::remove_file(log_path);
+        let _ = fs::remove_file(audit_path);
+
+        let (sender, handle) = start_logger(test_config(log_path, audit_path, "logs/metrics_unused_test_.log"));
+        for i in 0..100 {
+            log_info!(sender, format!("message {}", i)).unwrap();
+        }
+        sender
+            .send(LogEvent::Audit {
+                peer_addr: "p".to_string(),
+                operation: "+ 1".to_string(),
+                result_accumulation: 1,
+                timestamp: UNIX_EPOCH,
+            })
+            .unwrap();
+
+        // El logger sigue corriendo: lo leído es lo que el flush garantizó.
+        assert!(flush_logger(&sender));
+        let content = fs::read_to_string(log_path).unwrap();
+        assert_eq!(content.lines().count(), 100);
+        assert!(content.ends_with("INFO: message 99\n"));
+        assert_eq!(fs::read_to_string(audit_path).unwrap().lines().count(), 1);
+
+        sender.send(LogEvent::CloseConnection).unwrap();
+        handle.join().unwrap();
+        assert!(!flush_logger(&sender));
+        let _ = fs::remove_file(log_path);
+        let _ = fs::remove_file(audit_path);
+    }
+
+    #[test]
+    fn test_format_log_line() {
+        let event = LogEvent::Warn { message: "disk almost full".to_string(), module: None };
+        let line = format_log_line(&event, LogFormat::Text, UNIX_EPOCH + Duration::from_millis(1_705_322_096_789));
+        assert_eq!(line, "[2024-01-15T12:34:56.789Z] WARN: disk almost full\n");
+        assert!(is_iso_8601(&line[1..25]));
+        assert!(line.ends_with("] WARN: disk almost full\n"));
+        assert_eq!(format_log_line(&LogEvent::CloseConnection, LogFormat::Json, UNIX_EPOCH), "");
+    }
+
+    #[test]
+    fn test_log_info_macro_builds_fields() {
+        let (sender, receiver) = log_channel(10);
+        let peer_addr = "127.0.0.1:5000";
+        log_info!(sender, "Connection closed by client", peer_addr => peer_addr, op_count => 2).unwrap();
+        log_error!(&sender, format!("failed {}", 1)).unwrap();
+
+        match receiver.try_recv().unwrap() {
+            LogEvent::Info { message, fields, module } => {
+                assert_eq!(message, "Connection closed by client");
+                assert_eq!(module.as_deref(), Some("logger::tests"));
+                assert_eq!(
+                    fields,
+                    HashMap::from([
+                        ("peer_addr".to_string(), "127.0.0.1:5000".to_string()),
+                        ("op_count".to_string(), "2".to_string()),
+                    ])
+                );
+            }
+            _ => panic!("expected an Info event"),
+        }
+        assert!(matches!(receiver.try_recv().unwrap(), LogEvent::Error { message, fields, .. } if message == "failed 1" && fields.is_empty()));
+    }
+
+    #[test]
+    fn test_format_log_line_with_fields() {
+        let event = LogEvent::Info {
+            message: "Connection closed by client".to_string(),
+            fields: HashMap::from([
+                ("peer_addr".to_string(), "127.0.0.1:5000".to_string()),
+                ("op_count".to_string(), "2".to_string()),
+                ("message".to_string(), "ignored".to_string()),
+            ]),
+            module: None,
+        };
+
+        let text = format_log_line(&event, LogFormat::Text, SystemTime::now());
+        assert!(text.ends_with("] INFO: Connection closed by client message=ignored op_count=2 peer_addr=127.0.0.1:5000\n"));
+
+        let json: serde_json::Value = serde_json::from_str(&format_log_line(&event, LogFormat::Json, UNIX_EPOCH + Duration::from_millis(1_500))).unwrap();
+        assert_eq!(json["level"], "INFO");
+        assert_eq!(json["message"], "Connection closed by client");
+        assert_eq!(json["peer_addr"], "127.0.0.1:5000");
+        assert_eq!(json["op_count"], "2");
+        assert_eq!(json["timestamp"], "1970-01-01T00:00:01.500Z");
+    }
+
+    #[test]
+    fn test_module_filters_override_the_default_level() {
+        let log_path = "logs/server_filter_test_.log";
+        let config = LoggerConfig {
+            sink: LogSink::File(PathBuf::from(log_path)),
+            ..LoggerConfig::new().level(LogLevel::Info).filter("handle_client", LogLevel::Debug)
+        };
+        let debug = |message: &str, module: &str| LogEvent::Debug {
+            message: message.to_string(),
+            module: Some(module.to_string()),
+        };
+
+        let (sender, receiver) = log_channel(10);
+        sender.send(debug("from handle_client", "handle_client")).unwrap();
+        sender.send(debug("from main", "main")).unwrap();
+        sender.send(LogEvent::Debug { message: "without module".to_string(), module: None }).unwrap();
+        log_info!(sender, "info from tests").unwrap();
+        sender.send(LogEvent::CloseConnection).unwrap();
+        run_logger(config, receiver, &mut Vec::new(), Box::new(SystemTime::now));
+
+        let content = fs::read_to_string(log_path).unwrap();
+        assert!(content.contains("DEBUG: from handle_client"));
+        assert!(!content.contains("from main"));
+        assert!(!content.contains("without module"));
+        assert!(content.contains("INFO: info from tests"));
+        let _ = fs::remove_file(log_path);
+    }
+
+    #[test]
+    fn test_module_name_strips_the_binary_name() {
+        assert_eq!(module_name("server::handle_client"), "handle_client");
+        assert_eq!(module_name("server::logger::tests"), "logger::tests");
+        assert_eq!(module_name("server"), "main");
+        assert_eq!("WARN".parse::<LogLevel>(), Ok(LogLevel::Warn));
+        assert!("verbose".parse::<LogLevel>().is_err());
+    }
+
+    #[test]
+    fn test_dedup_collapses_identical_consecutive_messages() {
+        let log_path = "logs/server_dedup_test_.log";
+        let config = LoggerConfig {
+            dedup: true,
+            ..test_config(log_path, "logs/audit_unused_test_.log", "logs/metrics_unused_test_.log")
+        };
+
+        let (sender, receiver) = log_channel(200);
+        for _ in 0..100 {
+            log_error!(sender, "parsing error: unknown operation: X").unwrap();
+        }
+        sender.send(LogEvent::CloseConnection).unwrap();
+        run_logger(config.clone(), receiver, &mut Vec::new(), Box::new(SystemTime::now));
+
+        let content = fs::read_to_string(log_path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with("ERROR: parsing error: unknown operation: X"));
+        assert!(lines[1].ends_with("ERROR: (repeated 99 times)"));
+
+        let (sender, receiver) = log_channel(10);
+        for message in ["a", "a", "a", "b", "a"] {
+            log_info!(sender, message).unwrap();
+        }
+        sender.send(LogEvent::CloseConnection).unwrap();
+        run_logger(config, receiver, &mut Vec::new(), Box::new(SystemTime::now));
+
+        let content = fs::read_to_string(log_path).unwrap();
+        let messages: Vec<&str> = content.lines().map(|line| line.split_once("] ").unwrap().1).collect();
+        assert_eq!(messages, vec!["INFO: a", "INFO: (repeated 2 times)", "INFO: b", "INFO: a"]);
+        let _ = fs::remove_file(log_path);
+    }
+
+    #[test]
+    fn test_dated_path_puts_the_date_before_the_extension() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 14).unwrap();
+        assert_eq!(dated_path(Path::new("logs/server.log"), date), PathBuf::from("logs/server.2024-01-14.log"));
+        assert_eq!(dated_path(Path::new("server"), date), PathBuf::from("server.2024-01-14"));
+    }
+
+    #[test]
+    fn test_rolling_archives_the_log_when_the_day_changes() {
+        let log_path = "logs/server_rolling_test_.log";
+        let archived = "logs/server_rolling_test_.2024-01-14.log";
+        let _ = fs::remove_file(archived);
+        let config = LoggerConfig {
+            rolling: true,
+            compress_rotated: false,
+            ..test_config(log_path, "logs/audit_unused_test_.log", "logs/metrics_unused_test_.log")
+        };
+        // 2024-01-14T23:59:59Z; un segundo después es medianoche.
+        let clock = Arc::new(AtomicU64::new(1_705_276_799));
+        let now = {
+            let clock = clock.clone();
+            Box::new(move || UNIX_EPOCH + Duration::from_secs(clock.load(Ordering::SeqCst)))
+        };
+
+        let (sender, receiver) = log_channel(10);
+        let handle = thread::spawn(move || run_logger(config, receiver, &mut Vec::new(), now));
+        log_info!(sender, "last of the day").unwrap();
+        assert!(flush_logger(&sender));
+        clock.store(1_705_276_800, Ordering::SeqCst);
+        log_info!(sender, "first of the next day").unwrap();
+        sender.send(LogEvent::CloseConnection).unwrap();
+        handle.join().unwrap();
+
+        let old = fs::read_to_string(archived).unwrap();
+        assert_eq!(old, "[2024-01-14T23:59:59.000Z] INFO: last of the day\n");
+        let new = fs::read_to_string(log_path).unwrap();
+        assert_eq!(new, "[2024-01-15T00:00:00.000Z] INFO: first of the next day\n");
+        let _ = fs::remove_file(log_path);
+        let _ = fs::remove_file(archived);
+    }
+
+    #[test]
+    fn test_rotated_log_is_compressed_and_the_original_removed() {
+        let log_path = "logs/server_compress_test_.log";
+        let archived = "logs/server_compress_test_.2024-01-14.log";
+        let compressed = "logs/server_compress_test_.2024-01-14.log.gz";
+        let _ = fs::remove_file(compressed);
+        let config = LoggerConfig {
+            rolling: true,
+            ..test_config(log_path, "logs/audit_unused_test_.log", "logs/metrics_unused_test_.log")
+        };
+        let clock = Arc::new(AtomicU64::new(1_705_276_799));
+        let now = {
+            let clock = clock.clone();
+            Box::new(move || UNIX_EPOCH + Duration::from_secs(clock.load(Ordering::SeqCst)))
+        };
+
+        let (sender, receiver) = log_channel(10);
+        let handle = thread::spawn(move || run_logger(config, receiver, &mut Vec::new(), now));
+        log_info!(sender, "last of the day").unwrap();
+        assert!(flush_logger(&sender));
+        clock.store(1_705_276_800, Ordering::SeqCst);
+        log_info!(sender, "first of the next day").unwrap();
+        sender.send(LogEvent::CloseConnection).unwrap();
+        // El logger espera a que termine la compresión antes de cerrar.
+        handle.join().unwrap();
+
+        assert!(!Path::new(archived).exists());
+        let mut content = String::new();
+        GzDecoder::new(fs::File::open(compressed).unwrap()).read_to_string(&mut content).unwrap();
+        assert_eq!(content, "[2024-01-14T23:59:59.000Z] INFO: last of the day\n");
+        let _ = fs::remove_file(log_path);
+        let _ = fs::remove_file(compressed);
+    }
+
+    #[test]
+    fn test_failed_compression_keeps_no_partial_file() {
+        let missing = Path::new("logs/missing_rotated_test_.log");
+        assert!(compress_file(missing).is_err());
+        assert!(!Path::new("logs/missing_rotated_test_.log.gz").exists());
+    }
+
+    #[test]
+    fn test_both_sink_writes_the_same_lines_to_file_and_stderr() {
+        let log_path = "logs/server_both_test_.log";
+        let _ = fs::remove_file(log_path);
+        let config = LoggerConfig {
+            sink: LogSink::Both(PathBuf::from(log_path)),
+            ..test_config(log_path, "logs/audit_unused_test_.log", "logs/metrics_unused_test_.log")
+        };
+
+        let (sender, receiver) = log_channel(10);
+        log_info!(sender, "first").unwrap();
+        log_error!(sender, "second").unwrap();
+        sender.send(LogEvent::CloseConnection).unwrap();
+        let mut stderr = Vec::new();
+        run_logger(config, receiver, &mut stderr, Box::new(SystemTime::now));
+
+        let content = fs::read_to_string(log_path).unwrap();
+        assert_eq!(content.lines().count(), 2);
+        assert!(content.contains("INFO: first"));
+        assert_eq!(String::from_utf8(stderr).unwrap(), content);
+        let _ = fs::remove_file(log_path);
+    }
+
+    #[test]
+    fn test_file_sink_does_not_write_to_stderr() {
+        let log_path = "logs/server_file_only_test_.log";
+        let (sender, receiver) = log_channel(10);
+        log_info!(sender, "quiet").unwrap();
+        sender.send(LogEvent::CloseConnection).unwrap();
+        let mut stderr = Vec::new();
+        run_logger(test_config(log_path, "logs/audit_unused_test_.log", "logs/metrics_unused_test_.log"), receiver, &mut stderr, Box::new(SystemTime::now));
+
+        assert!(stderr.is_empty());
+        assert!(fs::read_to_string(log_path).unwrap().contains("INFO: quiet"));
+        let _ = fs::remove_file(log_path);
+    }
+
+    #[test]
+    fn test_full_channel_drops_events_instead_of_blocking() {
+        // Nadie lee del canal: a partir del tercer evento no hay lugar.
+        let (sender, _receiver) = log_channel(2);
+        let started = Instant::now();
+        let results: Vec<bool> = (0..5).map(|i| log_info!(sender, format!("message {}", i)).is_ok()).collect();
+
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(results, vec![true, true, false, false, false]);
+        assert_eq!(sender.dropped_events(), 3);
+        assert_eq!(sender.clone().dropped_events(), 3);
+    }
+}
@@ -1,112 +1,465 @@
-use std::{
-    net::{SocketAddr, TcpListener},
-    str::FromStr,
-    sync::{mpsc::{self, Sender}, Arc},
-    thread,
-};
+use std::{net::SocketAddr, str::FromStr, time::Duration};
 
+mod admin;
 mod calculator;
+mod calculator_error;
+mod config;
+mod connection_registry;
 mod handle_client;
 mod operation;
+#[cfg(test)]
+mod operation_proptest;
+mod peer_stream;
+mod pid_file;
+mod semaphore;
+mod server;
 mod server_error;
+mod server_state;
+mod server_stats;
+mod sessions;
+mod shared_calculator;
+mod snapshot_store;
+mod socket_options;
+mod subscribers;
+#[cfg(feature = "otel")]
+mod telemetry;
+mod thread_pool;
 mod logger;
-use crate::{handle_client::handle_connection, logger::LogEvent, server_error::ServerError};
-use calculator::Calculator;
-use logger::start_logger;
+mod namespaces;
+#[cfg(feature = "prometheus")]
+mod metrics;
+use crate::{config::Framing, logger::{LogFormat, LogLevel}, server::ServerBuilder, server_error::ServerError};
 
-fn main() -> Result<(), ServerError> {
-    let addr: SocketAddr = parse_arguments(std::env::args())?;
-    let log_path = "./logs/server.log";
-    run_server(addr, log_path)?;
-    Ok(())
+/// Argumentos de línea de comandos del servidor.
+#[derive(Debug, PartialEq, Eq)]
+struct ServerArgs {
+    /// Dirección en la que escucha el servidor (`IP:PUERTO`).
+    addr: SocketAddr,
+    /// Si es `true`, el log general se escribe también en stderr y desde el nivel DEBUG (`--verbose` / `-v`).
+    verbose: bool,
+    /// Archivo donde se escribe el PID del servidor (`--pid-file <path>`).
+    pid_file: Option<String>,
+}
+
+/// Lo que pide la línea de comandos: mostrar la ayuda, mostrar la versión o correr el servidor.
+#[derive(Debug, PartialEq, Eq)]
+enum ParseResult {
+    Help,
+    Version,
+    Run(ServerArgs),
 }
 
-fn parse_arguments<I: IntoIterator<Item = String>>(inputs: I) -> Result<SocketAddr, ServerError> {
-    let mut iter = inputs.into_iter();
-    iter.next();
-    let ip_str = iter.next().ok_or(ServerError::MissingArgument)?;
-    let addr = SocketAddr::from_str(&ip_str).map_err(|_| ServerError::InvalidArgument)?;
-    Ok(addr)
+/// Con `--help` imprime el uso y con `--version` la versión, y termina con código 0 sin arrancar el servidor.
+fn main() -> Result<(), ServerError> {
+    let env = |name: &str| std::env::var(name).ok();
+    let args = match parse_arguments(std::env::args(), env)? {
+        ParseResult::Help => {
+            print_usage(&std::env::args().next().unwrap_or_else(|| "server".to_string()));
+            return Ok(());
+        }
+        ParseResult::Version => {
+            println!("{}", version());
+            return Ok(());
+        }
+        ParseResult::Run(args) => args,
+    };
+    let mut builder = builder_from_env(args.addr, env)?;
+    if args.verbose {
+        builder = builder.log_level(LogLevel::Debug).log_stderr(true);
+    }
+    if let Some(path) = &args.pid_file {
+        builder = builder.pid_file(path);
+    }
+    builder.build()?.run()
 }
 
-fn run_server(address: SocketAddr, log_file: &str) -> Result<(), ServerError> {
-    let (sender, receiver) = mpsc::channel::<LogEvent>();
-    let logger_handle = start_logger(log_file, receiver);
-    
-    let listener: TcpListener = TcpListener::bind(address).map_err(|_| ServerError::BindFailed)?;
-
-    run_server_with_listener(listener,  sender.clone())?;
-    
-    let _ = sender.send(LogEvent::CloseConnection);
-    match logger_handle.join()  {
-            Ok(f) => f,
-            Err(e) => {
-                eprintln!("Failed to open log file: [{:?}] ", e);
-            }
+/// Parsea la dirección del servidor y sus flags, que pueden ir antes o después de la dirección.
+/// Si no se indica la dirección se usa `CALC_BIND_ADDR`, que `env` devuelve si está definida.
+/// Si aparece `--help` / `-h` o `--version` / `-V`, el resto de los argumentos se ignora;
+/// `--help` tiene prioridad.
+///
+/// #Errores
+/// `MissingArgument` si no se indica la dirección ni está definida `CALC_BIND_ADDR`, o si
+/// `--pid-file` no tiene su valor.
+/// `InvalidArgument` si la dirección no es válida o sobra algún argumento.
+fn parse_arguments<I, E>(inputs: I, env: E) -> Result<ParseResult, ServerError>
+where
+    I: IntoIterator<Item = String>,
+    E: Fn(&str) -> Option<String>,
+{
+    let args: Vec<String> = inputs.into_iter().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        return Ok(ParseResult::Help);
+    }
+    if args.iter().any(|arg| arg == "--version" || arg == "-V") {
+        return Ok(ParseResult::Version);
+    }
+    let mut addr = None;
+    let mut verbose = false;
+    let mut pid_file = None;
+    let mut iter = args.into_iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--verbose" | "-v" => verbose = true,
+            "--pid-file" => pid_file = Some(iter.next().ok_or(ServerError::MissingArgument)?),
+            _ if addr.is_none() => addr = Some(SocketAddr::from_str(&arg).map_err(|_| ServerError::InvalidArgument)?),
+            _ => return Err(ServerError::InvalidArgument),
+        }
+    }
+    let addr = match addr {
+        Some(addr) => addr,
+        None => {
+            let value = env("CALC_BIND_ADDR").ok_or(ServerError::MissingArgument)?;
+            SocketAddr::from_str(&value).map_err(|_| ServerError::InvalidArgument)?
+        }
     };
+    Ok(ParseResult::Run(ServerArgs { addr, verbose, pid_file }))
+}
+
+/// Texto de ayuda del servidor, con `program_name` como nombre del ejecutable.
+fn usage(program_name: &str) -> String {
+    format!(
+        "Usage: {program} [OPTIONS] [IP:PORT]
+
+Starts the calculator server listening on IP:PORT.
 
-    Ok(())
+Arguments:
+  [IP:PORT]               Address to listen on, e.g. 127.0.0.1:5000 or [::1]:5000
+                          (defaults to CALC_BIND_ADDR)
+
+Options:
+  -v, --verbose           Log from DEBUG level and also write the log to stderr
+      --pid-file <PATH>   Write the server PID to PATH and remove it on shutdown
+  -h, --help              Print this help and exit
+  -V, --version           Print the version and exit
+
+The rest of the configuration is read from CALC_* environment variables
+(CALC_LOG_FILE, CALC_LOG_LEVEL, CALC_MAX_CONNECTIONS, CALC_THREAD_POOL_SIZE, ...).
+Command line arguments take precedence over environment variables.
+
+Example:
+  {program} 127.0.0.1:5000 --verbose
+",
+        program = program_name
+    )
+}
+
+/// Línea que imprime `--version`, con la versión del crate embebida al compilar.
+fn version() -> String {
+    format!("distributed-calculator {}", env!("CARGO_PKG_VERSION"))
+}
 
+/// Imprime el texto de ayuda en stdout.
+fn print_usage(program_name: &str) {
+    print!("{}", usage(program_name));
 }
 
-fn run_server_with_listener(listener: TcpListener, sender : Sender<LogEvent> ) -> Result<(), ServerError> {
-    let calculator = Arc::new(std::sync::Mutex::new(Calculator::new()));
-
-    for stream in listener.incoming() {
-        match stream {
-            Ok(stream) => {
-                let calculator_pointer = Arc::clone(&calculator);
-                let sender_clone = sender.clone();
-                let peer_addr = stream.peer_addr().map_or("unknown".to_string(), |p| p.to_string());
-                let _ = sender_clone.send(LogEvent::Info(format!("New connection from {}", peer_addr)));
-
-                thread::spawn(move || {
-                    if let Err(e) = handle_connection(stream, calculator_pointer, sender_clone.clone()) {
-                        eprintln!("{}", e);
-                        let _ = sender_clone.send(LogEvent::Error(format!("Error: {}", e)));
-                    }
-
-                    let _ = sender_clone.send(LogEvent::Info(format!("Connection from {} closed", peer_addr)));
-                });
-            }
-            Err(_) => {
-                eprintln!("{}", ServerError::FailedConnection);
-                let _ = sender.send(LogEvent::Error(format!("{}", ServerError::FailedConnection)));
-                continue;
-            }
+/// Arma el builder del servidor a partir de las variables de entorno `CALC_*` que estén definidas.
+/// Las que no están conservan el valor por defecto de `ServerConfig`.
+/// `env` devuelve el valor de una variable de entorno, o `None` si no está definida.
+///
+/// #Errores
+/// `InvalidArgument` si una variable numérica o booleana no tiene un valor válido.
+fn builder_from_env(addr: SocketAddr, env: impl Fn(&str) -> Option<String>) -> Result<ServerBuilder, ServerError> {
+    let mut builder = ServerBuilder::new(addr);
+    if let Some(path) = env("CALC_LOG_FILE") {
+        builder = builder.log_file(&path);
+    }
+    if let Some(value) = env("CALC_LOG_STDERR") {
+        builder = builder.log_stderr(value.parse().map_err(|_| ServerError::InvalidArgument)?);
+    }
+    if let Some(format) = env("CALC_LOG_FORMAT") {
+        builder = builder.log_format(match format.as_str() {
+            "text" => LogFormat::Text,
+            "json" => LogFormat::Json,
+            _ => return Err(ServerError::InvalidArgument),
+        });
+    }
+    if let Some(level) = env("CALC_LOG_LEVEL") {
+        builder = builder.log_level(level.parse().map_err(|_| ServerError::InvalidArgument)?);
+    }
+    if let Some(filters) = env("CALC_LOG_FILTERS") {
+        for (module, level) in parse_log_filters(&filters)? {
+            builder = builder.log_filter(&module, level);
         }
     }
+    if let Some(value) = env("CALC_LOG_DEDUP") {
+        builder = builder.log_dedup(value.parse().map_err(|_| ServerError::InvalidArgument)?);
+    }
+    if let Some(value) = env("CALC_LOG_ROLLING") {
+        builder = builder.log_rolling(value.parse().map_err(|_| ServerError::InvalidArgument)?);
+    }
+    if let Some(value) = env("CALC_LOG_COMPRESS_ROTATED") {
+        builder = builder.log_compress_rotated(value.parse().map_err(|_| ServerError::InvalidArgument)?);
+    }
+    if let Some(path) = env("CALC_AUDIT_FILE") {
+        builder = builder.audit_file(&path);
+    }
+    if let Some(path) = env("CALC_METRICS_FILE") {
+        builder = builder.metrics_file(&path);
+    }
+    if let Some(token) = env("CALC_ADMIN_TOKEN") {
+        builder = builder.admin_token(&token);
+    }
+    if let Some(address) = env("CALC_ADMIN_ADDR") {
+        builder = builder.admin_address(address.parse().map_err(|_| ServerError::InvalidArgument)?);
+    }
+    if let Some(path) = env("CALC_STATE_FILE") {
+        builder = builder.state_file(&path);
+    }
+    if let Some(path) = env("CALC_PID_FILE") {
+        builder = builder.pid_file(&path);
+    }
+    if let Some(framing) = env("CALC_FRAMING") {
+        builder = builder.framing(match framing.as_str() {
+            "newline" => Framing::Newline,
+            "length" => Framing::LengthPrefixed,
+            _ => return Err(ServerError::InvalidArgument),
+        });
+    }
+    if let Some(version) = env("CALC_SERVER_VERSION_OVERRIDE") {
+        builder = builder.version_override(&version);
+    }
+    #[cfg(feature = "prometheus")]
+    {
+        builder = builder.metrics_address(match env("CALC_METRICS_ADDR") {
+            Some(address) => address.parse().map_err(|_| ServerError::InvalidArgument)?,
+            None => metrics::DEFAULT_METRICS_ADDRESS,
+        });
+    }
+    if let Some(secs) = env_number(&env, "CALC_TCP_KEEPALIVE_SECS")? {
+        builder = builder.tcp_keepalive(Duration::from_secs(secs as u64));
+    }
+    if let Some(secs) = env_number(&env, "CALC_IO_TIMEOUT_SECS")? {
+        builder = builder.io_timeout(Duration::from_secs(secs as u64));
+    }
+    if let Some(secs) = env_number(&env, "CALC_SESSION_TTL_SECS")? {
+        builder = builder.session_ttl(Duration::from_secs(secs as u64));
+    }
+    if let Some(secs) = env_number(&env, "CALC_HEARTBEAT_SECS")? {
+        builder = builder.heartbeat_interval((secs > 0).then(|| Duration::from_secs(secs as u64)));
+    }
+    if let Some(value) = env("CALC_INITIAL_ACCUMULATION") {
+        builder = builder.initial_accumulation(value.parse().map_err(|_| ServerError::InvalidArgument)?);
+    }
+    if let Some(value) = env("CALC_HISTORY") {
+        builder = builder.history(value.parse().map_err(|_| ServerError::InvalidArgument)?);
+    }
+    if let Some(value) = env("CALC_REGISTERS") {
+        builder = builder.registers(value.parse().map_err(|_| ServerError::InvalidArgument)?);
+    }
+    if let Some(value) = env("CALC_TCP_NODELAY") {
+        builder = builder.tcp_nodelay(value.parse().map_err(|_| ServerError::InvalidArgument)?);
+    }
+    if let Some(max) = env_number(&env, "CALC_MAX_CONNECTIONS")? {
+        builder = builder.max_connections(max);
+    }
+    if let Some(handlers) = env_number(&env, "CALC_MAX_IN_FLIGHT")? {
+        builder = builder.max_in_flight(handlers);
+    }
+    if let Some(size) = env_number(&env, "CALC_THREAD_POOL_SIZE")? {
+        builder = builder.thread_pool_size(size);
+    }
+    if let Some(depth) = env_number(&env, "CALC_PIPELINE_DEPTH")? {
+        builder = builder.pipeline_depth(depth);
+    }
+    if let Some(capacity) = env_number(&env, "CALC_LOG_CHANNEL_CAPACITY")? {
+        builder = builder.log_channel_capacity(capacity);
+    }
+    Ok(builder)
+}
 
-    Ok(())
+/// Parsea los filtros de log por módulo con formato `modulo=nivel,modulo=nivel`.
+///
+/// #Errores
+/// `InvalidArgument` si algún filtro no tiene `=` o su nivel no es válido.
+fn parse_log_filters(filters: &str) -> Result<Vec<(String, LogLevel)>, ServerError> {
+    filters
+        .split(',')
+        .filter(|filter| !filter.trim().is_empty())
+        .map(|filter| {
+            let (module, level) = filter.split_once('=').ok_or(ServerError::InvalidArgument)?;
+            let level = level.trim().parse().map_err(|_| ServerError::InvalidArgument)?;
+            Ok((module.trim().to_string(), level))
+        })
+        .collect()
 }
 
-#[cfg(test)]
+/// Lee una variable de entorno numérica con `env`. Devuelve `None` si no está definida.
+///
+/// #Errores
+/// `InvalidArgument` si la variable no es un número válido.
+fn env_number(env: &impl Fn(&str) -> Option<String>, name: &str) -> Result<Option<usize>, ServerError> {
+    match env(name) {
+        Some(value) => value.parse().map(Some).map_err(|_| ServerError::InvalidArgument),
+        None => Ok(None),
+    }
+}
 
+#[cfg(test)]
 mod tests {
-    use std::net::TcpListener;
+    use std::{collections::HashMap, time::Duration};
+
+    use crate::{
+        builder_from_env, logger::LogLevel, parse_arguments, parse_log_filters, server_error::ServerError, usage, version,
+        ParseResult, ServerArgs,
+    };
+
+    /// Entorno sin variables definidas.
+    fn no_env(_: &str) -> Option<String> {
+        None
+    }
 
-    use crate::{parse_arguments, run_server, server_error::ServerError};
+    /// Entorno con solo las variables de `vars`.
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        move |name| vars.get(name).cloned()
+    }
 
     #[test]
     fn parse_arguments_fails_with_missing_arguments() {
         let args = vec!["program_name".to_string()];
-        let result = parse_arguments(args);
+        let result = parse_arguments(args, no_env);
         assert!(matches!(result, Err(ServerError::MissingArgument)));
     }
 
     #[test]
     fn parse_arguments_fails_with_invalid_argument() {
         let args = vec!["program_name".to_string(), "not_an_ip".to_string()];
-        let result = parse_arguments(args);
+        let result = parse_arguments(args, no_env);
         assert!(matches!(result, Err(ServerError::InvalidArgument)));
     }
 
     #[test]
-    fn server_bind_fails() {
-        let addr = "127.0.0.1:54321".parse().unwrap();
-        let _listener = TcpListener::bind(addr).unwrap();
-        let log_path = "./logs/server.log";
-        let result = run_server(addr,log_path);
-        assert!(matches!(result, Err(ServerError::BindFailed)));
+    fn parse_arguments_reads_verbose_flag() {
+        let args = |values: &[&str]| values.iter().map(|v| v.to_string()).collect::<Vec<String>>();
+        let addr = "127.0.0.1:5000".parse().unwrap();
+
+        let run = |verbose| ParseResult::Run(ServerArgs { addr, verbose, pid_file: None });
+
+        assert_eq!(parse_arguments(args(&["server", "127.0.0.1:5000"]), no_env).unwrap(), run(false));
+        assert_eq!(parse_arguments(args(&["server", "127.0.0.1:5000", "--verbose"]), no_env).unwrap(), run(true));
+        assert_eq!(parse_arguments(args(&["server", "-v", "127.0.0.1:5000"]), no_env).unwrap(), run(true));
+        assert!(matches!(parse_arguments(args(&["server", "-v"]), no_env), Err(ServerError::MissingArgument)));
+        assert!(matches!(
+            parse_arguments(args(&["server", "127.0.0.1:5000", "127.0.0.1:5001"]), no_env),
+            Err(ServerError::InvalidArgument)
+        ));
+    }
+
+    #[test]
+    fn parse_arguments_reads_pid_file() {
+        let args = |values: &[&str]| values.iter().map(|v| v.to_string()).collect::<Vec<String>>();
+        let expected = ServerArgs {
+            addr: "127.0.0.1:5000".parse().unwrap(),
+            verbose: false,
+            pid_file: Some("/run/calc.pid".to_string()),
+        };
+
+        assert_eq!(
+            parse_arguments(args(&["server", "--pid-file", "/run/calc.pid", "127.0.0.1:5000"]), no_env).unwrap(),
+            ParseResult::Run(expected)
+        );
+        assert!(matches!(
+            parse_arguments(args(&["server", "127.0.0.1:5000", "--pid-file"]), no_env),
+            Err(ServerError::MissingArgument)
+        ));
+    }
+
+    #[test]
+    fn parse_arguments_detects_help() {
+        let args = |values: &[&str]| values.iter().map(|v| v.to_string()).collect::<Vec<String>>();
+
+        assert_eq!(parse_arguments(args(&["server", "--help"]), no_env).unwrap(), ParseResult::Help);
+        assert_eq!(parse_arguments(args(&["server", "127.0.0.1:5000", "-h"]), no_env).unwrap(), ParseResult::Help);
+        assert_eq!(parse_arguments(args(&["server", "not_an_ip", "--help"]), no_env).unwrap(), ParseResult::Help);
+    }
+
+    #[test]
+    fn parse_arguments_detects_version() {
+        let args = |values: &[&str]| values.iter().map(|v| v.to_string()).collect::<Vec<String>>();
+
+        assert_eq!(parse_arguments(args(&["server", "--version"]), no_env).unwrap(), ParseResult::Version);
+        assert_eq!(parse_arguments(args(&["server", "127.0.0.1:5000", "-V"]), no_env).unwrap(), ParseResult::Version);
+        assert_eq!(parse_arguments(args(&["server", "--version", "--help"]), no_env).unwrap(), ParseResult::Help);
+        assert_eq!(version(), format!("distributed-calculator {}", env!("CARGO_PKG_VERSION")));
+    }
+
+    #[test]
+    fn usage_documents_flags_and_example() {
+        let text = usage("server");
+        assert!(text.contains("[IP:PORT]"));
+        assert!(text.contains("--verbose"));
+        assert!(text.contains("--pid-file"));
+        assert!(text.contains("--help"));
+        assert!(text.contains("--version"));
+        assert!(text.contains("server 127.0.0.1:5000"));
+    }
+
+    #[test]
+    fn parse_arguments_falls_back_to_calc_bind_addr() {
+        let args = |values: &[&str]| values.iter().map(|v| v.to_string()).collect::<Vec<String>>();
+        let vars = env(&[("CALC_BIND_ADDR", "0.0.0.0:7000")]);
+        let run = |addr: &str, verbose| {
+            ParseResult::Run(ServerArgs { addr: addr.parse().unwrap(), verbose, pid_file: None })
+        };
+
+        assert_eq!(parse_arguments(args(&["server"]), &vars).unwrap(), run("0.0.0.0:7000", false));
+        assert_eq!(parse_arguments(args(&["server", "-v"]), &vars).unwrap(), run("0.0.0.0:7000", true));
+        assert_eq!(parse_arguments(args(&["server", "127.0.0.1:5000"]), &vars).unwrap(), run("127.0.0.1:5000", false));
+        let invalid = env(&[("CALC_BIND_ADDR", "not_an_ip")]);
+        assert!(matches!(parse_arguments(args(&["server"]), invalid), Err(ServerError::InvalidArgument)));
+    }
+
+    #[test]
+    fn builder_from_env_reads_overrides() {
+        let addr = "127.0.0.1:0".parse().unwrap();
+        let vars = env(&[
+            ("CALC_LOG_FILE", "/tmp/calc.log"),
+            ("CALC_MAX_CONNECTIONS", "10"),
+            ("CALC_THREAD_POOL_SIZE", "4"),
+            ("CALC_LOG_LEVEL", "warn"),
+            ("CALC_IO_TIMEOUT_SECS", "5"),
+            ("CALC_SESSION_TTL_SECS", "30"),
+        ]);
+        let builder = builder_from_env(addr, vars).unwrap();
+        let config = builder.config();
+        assert_eq!(config.io_timeout, Some(Duration::from_secs(5)));
+        assert_eq!(config.session_ttl, Duration::from_secs(30));
+        assert_eq!(config.log_file, "/tmp/calc.log");
+        assert_eq!(config.max_connections, Some(10));
+        assert_eq!(config.thread_pool_size, Some(4));
+        assert_eq!(config.log_level, LogLevel::Warn);
+
+        let defaults = builder_from_env(addr, no_env).unwrap();
+        assert_eq!(defaults.config().log_file, "./logs/server.log");
+        assert_eq!(defaults.config().max_connections, None);
+        assert!(matches!(
+            builder_from_env(addr, env(&[("CALC_MAX_CONNECTIONS", "many")])),
+            Err(ServerError::InvalidArgument)
+        ));
+    }
+
+    #[test]
+    fn parse_log_filters_reads_module_levels() {
+        let filters = parse_log_filters("handle_client=debug, main=INFO").unwrap();
+        assert_eq!(
+            filters,
+            vec![("handle_client".to_string(), LogLevel::Debug), ("main".to_string(), LogLevel::Info)]
+        );
+        assert!(parse_log_filters("").unwrap().is_empty());
+        assert!(matches!(parse_log_filters("handle_client"), Err(ServerError::InvalidArgument)));
+        assert!(matches!(parse_log_filters("main=loud"), Err(ServerError::InvalidArgument)));
+    }
+
+    #[test]
+    fn parse_arguments_accepts_ipv6_address() {
+        let args = vec!["program_name".to_string(), "[::1]:8080".to_string()];
+        let Ok(ParseResult::Run(ServerArgs { addr, .. })) = parse_arguments(args, no_env) else {
+            panic!("expected a run configuration");
+        };
+        assert!(addr.is_ipv6());
+        assert_eq!(addr.port(), 8080);
+        assert_eq!(addr.to_string(), "[::1]:8080");
     }
 }
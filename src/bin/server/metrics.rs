@@ -0,0 +1,168 @@
+//! Métricas del servidor en el formato de texto de Prometheus (feature `prometheus`).
+//! Se sirven por HTTP en `GET /metrics` desde un puerto aparte del de datos.
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    thread,
+};
+
+use prometheus::{Encoder, IntCounter, IntCounterVec, IntGauge, Opts, Registry, TextEncoder};
+
+use crate::{logger::{LogSender, log_error, log_warn}, server_error::ServerError, server_state::ServerState};
+
+/// Dirección en la que el binario expone las métricas si no se indica `CALC_METRICS_ADDR`.
+pub const DEFAULT_METRICS_ADDRESS: SocketAddr =
+    SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED), 9090);
+
+/// Métricas que el servidor actualiza mientras atiende conexiones.
+/// Clonarlo es barato: los contadores de `prometheus` comparten su valor entre clones.
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    connections_active: IntGauge,
+    connections_total: IntCounter,
+    operations_total: IntCounterVec,
+    accumulation_value: IntGauge,
+}
+
+impl Metrics {
+    /// Crea las métricas y las registra en un registro propio.
+    pub fn new() -> Self {
+        let registry = Registry::new();
+        let connections_active = IntGauge::new("calc_connections_active", "Connections currently open")
+            .expect("invalid metric");
+        let connections_total = IntCounter::new("calc_connections_total", "Connections accepted since start")
+            .expect("invalid metric");
+        let operations_total = IntCounterVec::new(
+            Opts::new("calc_operations_total", "Operations applied, by result"),
+            &["status"],
+        )
+        .expect("invalid metric");
+        let accumulation_value = IntGauge::new("calc_accumulation_value", "Current calculator value")
+            .expect("invalid metric");
+        for metric in [
+            Box::new(connections_active.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(connections_total.clone()),
+            Box::new(operations_total.clone()),
+            Box::new(accumulation_value.clone()),
+        ] {
+            registry.register(metric).expect("duplicated metric");
+        }
+        Self {
+            registry,
+            connections_active,
+            connections_total,
+            operations_total,
+            accumulation_value,
+        }
+    }
+
+    /// Cuenta una conexión aceptada.
+    pub fn connection_opened(&self) {
+        self.connections_total.inc();
+    }
+
+    /// Cuenta una operación según si se aplicó (`ok`) o el servidor respondió con un error.
+    pub fn operation_applied(&self, ok: bool) {
+        let status = if ok { "ok" } else { "error" };
+        self.operations_total.with_label_values(&[status]).inc();
+    }
+
+    /// Devuelve las métricas en el formato de texto de Prometheus.
+    /// Las conexiones activas y el valor de la calculadora se leen del estado en este momento.
+    ///
+    /// #Errores
+    /// `PoisonError` si se envenena el lock de la calculadora o del registro de conexiones.
+    pub fn render(&self, state: &ServerState) -> Result<String, ServerError> {
+        self.connections_active.set(state.registry.count()? as i64);
+        let accumulation = match &state.lock_free {
+            Some(calc) => calc.accumulation(),
+            None => state.calculator.accumulation()?,
+        };
+        self.accumulation_value.set(accumulation);
+        let mut out = Vec::new();
+        let _ = TextEncoder::new().encode(&self.registry.gather(), &mut out);
+        Ok(String::from_utf8_lossy(&out).into_owned())
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Acepta conexiones HTTP en el puerto de métricas y responde cada una en un hilo propio.
+/// Termina cuando se pide apagar el servidor.
+pub fn run_metrics_listener(listener: TcpListener, state: ServerState, sender: LogSender) {
+    for stream in listener.incoming() {
+        if state.is_shutting_down() {
+            break;
+        }
+        match stream {
+            Ok(stream) => {
+                let state = state.clone();
+                let sender = sender.clone();
+                thread::spawn(move || {
+                    if let Err(e) = handle_metrics_request(stream, &state) {
+                        let _ = log_warn!(sender, format!("Metrics request failed: {}", e));
+                    }
+                });
+            }
+            Err(_) => {
+                let _ = log_error!(sender, ServerError::FailedConnection);
+            }
+        }
+    }
+}
+
+/// Responde un pedido HTTP: `GET /metrics` devuelve las métricas y cualquier otro, 404.
+///
+/// # Errores
+/// - `ServerError::ReadFailed`: Si falla la lectura del pedido.
+/// - `ServerError::WriteFailed`: Si falla la escritura de la respuesta.
+fn handle_metrics_request(mut stream: TcpStream, state: &ServerState) -> Result<(), ServerError> {
+    let mut reader = BufReader::new(stream.try_clone().map_err(ServerError::ReadFailed)?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).map_err(ServerError::ReadFailed)?;
+    // Se descartan los encabezados hasta la línea vacía.
+    let mut header = String::new();
+    while reader.read_line(&mut header).map_err(ServerError::ReadFailed)? > 0 && !header.trim_end().is_empty() {
+        header.clear();
+    }
+
+    let (status, body) = match request_line.split_whitespace().take(2).collect::<Vec<_>>()[..] {
+        ["GET", "/metrics"] => ("200 OK", state.metrics.render(state)?),
+        _ => ("404 Not Found", "not found\n".to_string()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).map_err(ServerError::WriteFailed)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        calculator::Calculator, config::ServerConfig, server_state::ServerState, shared_calculator::SharedCalculator,
+    };
+
+    #[test]
+    fn render_includes_every_metric() {
+        let state = ServerState::new(SharedCalculator::new(Calculator::new()), ServerConfig::default());
+        state.metrics.connection_opened();
+        state.metrics.operation_applied(true);
+        state.metrics.operation_applied(false);
+
+        let text = state.metrics.render(&state).unwrap();
+
+        assert!(text.contains("calc_connections_active 0"));
+        assert!(text.contains("calc_connections_total 1"));
+        assert!(text.contains("calc_operations_total{status=\"ok\"} 1"));
+        assert!(text.contains("calc_operations_total{status=\"error\"} 1"));
+        assert!(text.contains("calc_accumulation_value 0"));
+    }
+}
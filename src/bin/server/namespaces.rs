@@ -0,0 +1,73 @@
+//! Calculadoras con nombre que cada conexión elige con `SELECT`.
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use crate::{calculator::Calculator, server_error::ServerError, shared_calculator::SharedCalculator};
+
+/// Nombre de la calculadora con la que arranca cada conexión: la que se persiste en el
+/// archivo de estado y sobre la que trabajan `SNAPSHOT`, `RESTORE` y `STATUS`.
+pub const DEFAULT_NAMESPACE: &str = "default";
+
+/// Registro de calculadoras por nombre, compartido entre todas las conexiones.
+/// Clonarlo es barato: solo se clona el `Arc` interno.
+#[derive(Clone)]
+pub struct Namespaces {
+    calculators: Arc<Mutex<HashMap<String, SharedCalculator>>>,
+}
+
+impl Namespaces {
+    /// Crea el registro con `default` como la calculadora de [`DEFAULT_NAMESPACE`].
+    pub fn new(default: SharedCalculator) -> Self {
+        let calculators = HashMap::from([(DEFAULT_NAMESPACE.to_string(), default)]);
+        Self {
+            calculators: Arc::new(Mutex::new(calculators)),
+        }
+    }
+
+    /// Devuelve la calculadora `name`, o `None` si nadie la creó.
+    ///
+    /// #Errores
+    /// `PoisonError` si se envenena el lock del registro.
+    pub fn get(&self, name: &str) -> Result<Option<SharedCalculator>, ServerError> {
+        let calculators = self.calculators.lock().map_err(|_| ServerError::PoisonError)?;
+        Ok(calculators.get(name).cloned())
+    }
+
+    /// Devuelve la calculadora `name`, creándola con la acumulación en 0 si no existe.
+    ///
+    /// #Errores
+    /// `PoisonError` si se envenena el lock del registro.
+    pub fn get_or_create(&self, name: &str) -> Result<SharedCalculator, ServerError> {
+        let mut calculators = self.calculators.lock().map_err(|_| ServerError::PoisonError)?;
+        Ok(calculators
+            .entry(name.to_string())
+            .or_insert_with(|| SharedCalculator::new(Calculator::new()))
+            .clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        calculator::Calculator,
+        namespaces::{DEFAULT_NAMESPACE, Namespaces},
+        operation::Operation,
+        shared_calculator::SharedCalculator,
+    };
+
+    #[test]
+    fn get_or_create_returns_the_same_calculator_for_a_name() {
+        let default = SharedCalculator::new(Calculator::with_initial(7));
+        let namespaces = Namespaces::new(default.clone());
+
+        namespaces.get_or_create("foo").unwrap().apply(Operation::Add(1)).unwrap();
+
+        assert_eq!(namespaces.get_or_create("foo").unwrap().accumulation().unwrap(), 1);
+        assert_eq!(namespaces.get_or_create("bar").unwrap().accumulation().unwrap(), 0);
+        assert_eq!(namespaces.get_or_create(DEFAULT_NAMESPACE).unwrap().accumulation().unwrap(), 7);
+        assert_eq!(default.accumulation().unwrap(), 7);
+        assert!(namespaces.get("missing").unwrap().is_none());
+    }
+}
@@ -0,0 +1,47 @@
+//! Propiedades de `Operation::from_str` y su `Display` sobre operaciones generadas al azar.
+use std::str::FromStr;
+
+use proptest::prelude::*;
+
+use crate::{calculator::Calculator, operation::Operation};
+
+/// Operación válida `<operador> <u8>`. La división empieza en 1 porque `/ 0` se rechaza al parsear.
+fn operation_string() -> impl Strategy<Value = String> {
+    prop_oneof![
+        (prop::sample::select(vec!["+", "-", "*"]), any::<u8>()),
+        (Just("/"), 1..=u8::MAX),
+    ]
+    .prop_map(|(operator, operand)| format!("{} {}", operator, operand))
+}
+
+/// Aplica `op` sobre una calculadora que arranca en `initial` y devuelve la acumulación.
+fn apply_from(initial: i64, op: Operation) -> i64 {
+    let mut calculator = Calculator::with_initial(initial);
+    calculator.apply(op).unwrap();
+    calculator.accumulation()
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(10_000))]
+
+    #[test]
+    fn display_inverts_from_str(s in operation_string()) {
+        let op = Operation::from_str(&s);
+        prop_assert!(op.is_ok());
+        let op = op.unwrap();
+        prop_assert_eq!(op.to_string(), s);
+        prop_assert_eq!(Operation::from_str(&op.to_string()), Ok(op));
+    }
+
+    #[test]
+    fn reparsed_operation_computes_the_same_result(s in operation_string(), initial in any::<i64>()) {
+        let op = Operation::from_str(&s).unwrap();
+        let reparsed = Operation::from_str(&op.to_string()).unwrap();
+        prop_assert_eq!(apply_from(initial, op), apply_from(initial, reparsed));
+    }
+
+    #[test]
+    fn from_str_never_panics(s in any::<String>()) {
+        let _ = Operation::from_str(&s);
+    }
+}
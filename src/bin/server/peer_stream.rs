@@ -0,0 +1,134 @@
+//! Stream de un cliente junto con su dirección, para que quien lo atiende pueda loguearla.
+use std::{
+    io::{self, Read, Write},
+    net::TcpStream,
+    sync::{Arc, Mutex},
+};
+
+/// Envuelve un stream de lectura/escritura y recuerda la dirección del cliente.
+/// `Read` y `Write` se delegan en el stream interno.
+pub struct PeerStream<RW> {
+    inner: RW,
+    peer_addr: String,
+    /// Copia del socket por la que otro hilo envía mensajes que el cliente no pidió (`SUBSCRIBE`).
+    push_stream: Option<TcpStream>,
+    /// Serializa los `write_all` de este stream con los de sus [`PushWriter`], para que los
+    /// mensajes de ambos hilos no se mezclen.
+    write_lock: Arc<Mutex<()>>,
+}
+
+impl<RW> PeerStream<RW> {
+    /// Envuelve `inner`, que pertenece al cliente con dirección `peer_addr`.
+    pub fn new(inner: RW, peer_addr: impl Into<String>) -> Self {
+        Self {
+            inner,
+            peer_addr: peer_addr.into(),
+            push_stream: None,
+            write_lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Habilita [`PeerStream::push_writer`] usando `stream`, una copia del socket de `inner`.
+    pub fn with_push_stream(mut self, stream: TcpStream) -> Self {
+        self.push_stream = Some(stream);
+        self
+    }
+
+    /// Devuelve la dirección del cliente.
+    pub fn peer_addr(&self) -> &str {
+        &self.peer_addr
+    }
+
+    /// Indica si [`PeerStream::push_writer`] puede devolver un escritor.
+    pub fn supports_push(&self) -> bool {
+        self.push_stream.is_some()
+    }
+
+    /// Devuelve un escritor para enviarle mensajes al cliente desde otro hilo, o `None` si el
+    /// stream no tiene una copia del socket.
+    pub fn push_writer(&self) -> Option<PushWriter> {
+        let stream = self.push_stream.as_ref()?.try_clone().ok()?;
+        Some(PushWriter {
+            stream,
+            write_lock: Arc::clone(&self.write_lock),
+        })
+    }
+}
+
+/// Escribe mensajes completos en el socket de un [`PeerStream`] desde otro hilo.
+pub struct PushWriter {
+    stream: TcpStream,
+    write_lock: Arc<Mutex<()>>,
+}
+
+impl PushWriter {
+    /// Escribe `bytes` sin que se intercalen con las respuestas de la conexión.
+    ///
+    /// #Errores
+    /// Si falla la escritura en el socket.
+    pub fn write_message(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        self.stream.write_all(bytes)
+    }
+}
+
+impl<RW: Read> Read for PeerStream<RW> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl<RW: Write> Write for PeerStream<RW> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        self.inner.write_all(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Cursor, Read, Write},
+        net::{TcpListener, TcpStream},
+    };
+
+    use crate::peer_stream::PeerStream;
+
+    #[test]
+    fn delegates_reads_and_writes() {
+        let mut stream = PeerStream::new(Cursor::new(b"GET\n".to_vec()), "[::1]:4000");
+        let mut buf = String::new();
+        stream.read_to_string(&mut buf).unwrap();
+        stream.write_all(b"VALUE 0\n").unwrap();
+
+        assert_eq!(buf, "GET\n");
+        assert_eq!(stream.peer_addr(), "[::1]:4000");
+        assert!(stream.push_writer().is_none());
+        assert!(!stream.supports_push());
+    }
+
+    #[test]
+    fn push_writer_writes_to_the_same_socket() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        let mut stream = PeerStream::new(server.try_clone().unwrap(), "peer").with_push_stream(server);
+
+        stream.write_all(b"OK\n").unwrap();
+        stream.push_writer().unwrap().write_message(b"VALUE 1\n").unwrap();
+
+        let mut reader = BufReader::new(client);
+        let mut lines = String::new();
+        reader.read_line(&mut lines).unwrap();
+        reader.read_line(&mut lines).unwrap();
+        assert_eq!(lines, "OK\nVALUE 1\n");
+    }
+}
@@ -0,0 +1,86 @@
+//! Archivo con el PID del servidor, para que los gestores de procesos (systemd, supervisord)
+//! sepan a qué proceso mandarle señales.
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::server_error::ServerError;
+
+/// Archivo de PID escrito al arrancar. Al descartarlo se borra, así un apagado limpio no deja
+/// un PID viejo.
+pub struct PidFile {
+    path: PathBuf,
+    previous_pid: Option<u32>,
+}
+
+impl PidFile {
+    /// Escribe el PID del proceso actual en `path`. Si el archivo ya existía con el PID de un
+    /// proceso vivo se sobrescribe igual; ese PID queda en [`PidFile::previous_pid`] para avisarlo.
+    ///
+    /// #Errores
+    /// `InvalidConfig` si no se puede escribir el archivo.
+    pub fn create(path: &Path) -> Result<Self, ServerError> {
+        let previous_pid = fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| contents.trim().parse::<u32>().ok())
+            .filter(|pid| is_alive(*pid));
+        fs::write(path, std::process::id().to_string())
+            .map_err(|e| ServerError::InvalidConfig(format!("pid file {}: {}", path.display(), e)))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            previous_pid,
+        })
+    }
+
+    /// PID de un proceso vivo que figuraba en el archivo antes de sobrescribirlo.
+    pub fn previous_pid(&self) -> Option<u32> {
+        self.previous_pid
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Indica si existe un proceso con `pid`. En sistemas sin `/proc` no se puede saber y se lo
+/// considera muerto.
+fn is_alive(pid: u32) -> bool {
+    Path::new("/proc").join(pid.to_string()).exists()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::pid_file::PidFile;
+
+    #[test]
+    fn pid_file_is_written_and_removed_on_drop() {
+        let path = std::env::temp_dir().join(format!("pid_file_{}.pid", std::process::id()));
+        let pid_file = PidFile::create(&path).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), std::process::id().to_string());
+        assert_eq!(pid_file.previous_pid(), None);
+        drop(pid_file);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn existing_pid_file_with_live_pid_is_overwritten() {
+        let path = std::env::temp_dir().join(format!("pid_file_live_{}.pid", std::process::id()));
+        // El propio proceso de los tests está vivo.
+        fs::write(&path, format!("{}\n", std::process::id())).unwrap();
+        let pid_file = PidFile::create(&path).unwrap();
+
+        assert_eq!(pid_file.previous_pid(), Some(std::process::id()));
+        assert_eq!(fs::read_to_string(&path).unwrap(), std::process::id().to_string());
+
+        fs::write(&path, "not a pid").unwrap();
+        drop(pid_file);
+        let pid_file = PidFile::create(&path).unwrap();
+        assert_eq!(pid_file.previous_pid(), None);
+    }
+}
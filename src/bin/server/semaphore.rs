@@ -0,0 +1,89 @@
+//! Semáforo contador para limitar cuántas conexiones se atienden a la vez.
+//! El ciclo de `accept` pide un permiso antes de aceptar; mientras no haya permisos libres,
+//! las conexiones nuevas esperan en el backlog del sistema operativo.
+use std::sync::{Arc, Condvar, Mutex};
+
+/// Semáforo con una cantidad fija de permisos.
+pub struct Semaphore {
+    available: Mutex<usize>,
+    released: Condvar,
+}
+
+impl Semaphore {
+    /// Crea el semáforo con `permits` permisos libres. `permits` debe ser mayor a 0.
+    pub fn new(permits: usize) -> Self {
+        Self {
+            available: Mutex::new(permits),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Toma un permiso, bloqueando hasta que haya uno libre.
+    /// Devuelve un guard que lo libera al salir de scope.
+    pub fn acquire(self: &Arc<Self>) -> SemaphorePermit {
+        let mut available = self.available.lock().unwrap_or_else(|e| e.into_inner());
+        while *available == 0 {
+            available = self.released.wait(available).unwrap_or_else(|e| e.into_inner());
+        }
+        *available -= 1;
+        SemaphorePermit {
+            semaphore: Arc::clone(self),
+        }
+    }
+
+    fn release(&self) {
+        let mut available = self.available.lock().unwrap_or_else(|e| e.into_inner());
+        *available += 1;
+        self.released.notify_one();
+    }
+}
+
+/// Permiso tomado de un `Semaphore`. Se devuelve al descartarlo.
+pub struct SemaphorePermit {
+    semaphore: Arc<Semaphore>,
+}
+
+impl Drop for SemaphorePermit {
+    fn drop(&mut self) {
+        self.semaphore.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        thread,
+        time::Duration,
+    };
+
+    use crate::semaphore::Semaphore;
+
+    #[test]
+    fn never_hands_out_more_permits_than_capacity() {
+        let semaphore = Arc::new(Semaphore::new(2));
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+
+        let handles: Vec<_> = (0..5)
+            .map(|_| {
+                let (semaphore, running, max_running) = (semaphore.clone(), running.clone(), max_running.clone());
+                thread::spawn(move || {
+                    let _permit = semaphore.acquire();
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_running.fetch_max(now, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(20));
+                    running.fetch_sub(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(max_running.load(Ordering::SeqCst), 2);
+    }
+}
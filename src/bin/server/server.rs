@@ -0,0 +1,1453 @@
+//! Construcción y ejecución del servidor.
+//! `ServerBuilder` arma la configuración con métodos encadenables, la valida y abre el socket;
+//! `Server::run` arranca el logger y el ciclo que acepta conexiones.
+use std::{
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        mpsc::{Receiver, channel},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use distributed_calculator::protocol::Protocol;
+
+use crate::{
+    admin::run_admin_listener, calculator::Calculator, config::{Framing, ServerConfig}, handle_client::{handle_connection, send_protocol},
+    logger::{LogEvent, LogFormat, LogLevel, LogSender, LogSink, LoggerConfig, flush_logger, log_error, log_info, log_warn, module_name, start_logger}, peer_stream::PeerStream, pid_file::PidFile, server_error::ServerError, server_state::ServerState,
+    semaphore::Semaphore, shared_calculator::SharedCalculator, socket_options, thread_pool::ThreadPool,
+};
+
+/// Arma un `Server` a partir de una dirección y opciones encadenables.
+///
+/// # Ejemplo
+/// let server = ServerBuilder::new(addr).log_file("server.log").max_connections(100).build()?;
+/// server.run()?;
+pub struct ServerBuilder {
+    address: SocketAddr,
+    listener: Option<TcpListener>,
+    admin_listener: Option<TcpListener>,
+    #[cfg(feature = "prometheus")]
+    metrics_listener: Option<TcpListener>,
+    config: ServerConfig,
+}
+
+impl ServerBuilder {
+    /// Crea el builder para escuchar en `address` con la configuración por defecto.
+    pub fn new(address: SocketAddr) -> Self {
+        Self {
+            address,
+            listener: None,
+            admin_listener: None,
+            #[cfg(feature = "prometheus")]
+            metrics_listener: None,
+            config: ServerConfig::default(),
+        }
+    }
+
+    /// Crea el builder sobre un listener ya abierto, para que los tests usen el puerto 0.
+    #[cfg(test)]
+    pub fn from_listener(listener: TcpListener) -> Self {
+        let address = listener.local_addr().expect("listener without local address");
+        Self {
+            listener: Some(listener),
+            ..Self::new(address)
+        }
+    }
+
+    /// Configuración que arma el builder hasta ahora.
+    #[cfg(test)]
+    pub fn config(&self) -> &ServerConfig {
+        &self.config
+    }
+
+    /// Ruta del archivo de log general.
+    pub fn log_file(mut self, path: &str) -> Self {
+        self.config.log_file = path.to_string();
+        self
+    }
+
+    /// Escribe el log general también en stderr, además del archivo.
+    pub fn log_stderr(mut self, enabled: bool) -> Self {
+        self.config.log_stderr = enabled;
+        self
+    }
+
+    /// Formato del log general.
+    pub fn log_format(mut self, format: LogFormat) -> Self {
+        self.config.log_format = format;
+        self
+    }
+
+    /// Nivel mínimo del log general para los módulos sin filtro propio.
+    pub fn log_level(mut self, level: LogLevel) -> Self {
+        self.config.log_level = level;
+        self
+    }
+
+    /// Nivel mínimo del log general para los eventos del módulo `module`.
+    pub fn log_filter(mut self, module: &str, level: LogLevel) -> Self {
+        self.config.log_module_filters.insert(module.to_string(), level);
+        self
+    }
+
+    /// Escribe una sola vez los mensajes idénticos consecutivos del log general.
+    pub fn log_dedup(mut self, enabled: bool) -> Self {
+        self.config.log_dedup = enabled;
+        self
+    }
+
+    /// Archiva el log general con la fecha en el nombre cada vez que cambia el día.
+    pub fn log_rolling(mut self, enabled: bool) -> Self {
+        self.config.log_rolling = enabled;
+        self
+    }
+
+    /// Comprime con gzip los logs archivados al cambiar el día.
+    pub fn log_compress_rotated(mut self, enabled: bool) -> Self {
+        self.config.log_compress_rotated = enabled;
+        self
+    }
+
+    /// Ruta del archivo de auditoría.
+    pub fn audit_file(mut self, path: &str) -> Self {
+        self.config.audit_file = path.to_string();
+        self
+    }
+
+    /// Ruta del archivo de métricas por conexión.
+    pub fn metrics_file(mut self, path: &str) -> Self {
+        self.config.metrics_file = path.to_string();
+        self
+    }
+
+    /// Cantidad máxima de eventos encolados para el logger antes de empezar a descartarlos.
+    pub fn log_channel_capacity(mut self, capacity: usize) -> Self {
+        self.config.log_channel_capacity = capacity;
+        self
+    }
+
+    /// Token que habilita los comandos de administración.
+    pub fn admin_token(mut self, token: &str) -> Self {
+        self.config.admin_token = Some(token.to_string());
+        self
+    }
+
+    /// Dirección del puerto de administración.
+    pub fn admin_address(mut self, address: SocketAddr) -> Self {
+        self.config.admin_address = Some(address);
+        self
+    }
+
+    /// Usa un listener ya abierto como puerto de administración, para que los tests usen el puerto 0.
+    #[cfg(test)]
+    pub fn admin_listener(mut self, listener: TcpListener) -> Self {
+        self.config.admin_address = listener.local_addr().ok();
+        self.admin_listener = Some(listener);
+        self
+    }
+
+    /// Dirección del endpoint HTTP de métricas de Prometheus.
+    #[cfg(feature = "prometheus")]
+    pub fn metrics_address(mut self, address: SocketAddr) -> Self {
+        self.config.metrics_address = Some(address);
+        self
+    }
+
+    /// Usa un listener ya abierto para las métricas, para que los tests usen el puerto 0.
+    #[cfg(all(test, feature = "prometheus"))]
+    pub fn metrics_listener(mut self, listener: TcpListener) -> Self {
+        self.config.metrics_address = listener.local_addr().ok();
+        self.metrics_listener = Some(listener);
+        self
+    }
+
+    /// Archivo donde se persiste el estado de la calculadora.
+    pub fn state_file(mut self, path: &str) -> Self {
+        self.config.state_file = Some(path.to_string());
+        self
+    }
+
+    /// Archivo donde se escribe el PID del servidor mientras corre.
+    pub fn pid_file(mut self, path: &str) -> Self {
+        self.config.pid_file = Some(PathBuf::from(path));
+        self
+    }
+
+    /// Cada cuánto se loguea que el servidor sigue vivo. Con `None` no se loguea.
+    pub fn heartbeat_interval(mut self, interval: Option<Duration>) -> Self {
+        self.config.heartbeat_interval = interval;
+        self
+    }
+
+    /// Tiempo máximo de espera de cada lectura o escritura en las conexiones de datos.
+    pub fn io_timeout(mut self, timeout: Duration) -> Self {
+        self.config.io_timeout = Some(timeout);
+        self
+    }
+
+    /// Tiempo durante el que se puede retomar la sesión de una conexión cerrada.
+    pub fn session_ttl(mut self, ttl: Duration) -> Self {
+        self.config.session_ttl = ttl;
+        self
+    }
+
+    /// Habilita TCP keepalive con el tiempo de inactividad indicado.
+    pub fn tcp_keepalive(mut self, idle: Duration) -> Self {
+        self.config.tcp_keepalive = Some(idle);
+        self
+    }
+
+    /// Activa o desactiva TCP_NODELAY en las conexiones aceptadas.
+    pub fn tcp_nodelay(mut self, enabled: bool) -> Self {
+        self.config.tcp_nodelay = enabled;
+        self
+    }
+
+    /// Cantidad máxima de conexiones simultáneas.
+    pub fn max_connections(mut self, max: usize) -> Self {
+        self.config.max_connections = Some(max);
+        self
+    }
+
+    /// Cantidad máxima de conexiones que se atienden a la vez antes de dejar de aceptar nuevas.
+    pub fn max_in_flight(mut self, handlers: usize) -> Self {
+        self.config.max_in_flight = Some(handlers);
+        self
+    }
+
+    /// Cantidad de hilos que atienden conexiones.
+    pub fn thread_pool_size(mut self, size: usize) -> Self {
+        self.config.thread_pool_size = Some(size);
+        self
+    }
+
+    /// Cantidad máxima de respuestas encoladas por conexión antes de enviarlas.
+    pub fn pipeline_depth(mut self, depth: usize) -> Self {
+        self.config.pipeline_depth = depth;
+        self
+    }
+
+    /// Framing de los mensajes en las conexiones de datos.
+    pub fn framing(mut self, framing: Framing) -> Self {
+        self.config.framing = framing;
+        self
+    }
+
+    /// Acumulación con la que arranca la calculadora cuando no se restaura un archivo de estado.
+    pub fn initial_accumulation(mut self, value: i64) -> Self {
+        self.config.initial_accumulation = value;
+        self
+    }
+
+    /// Habilita o deshabilita los comandos de historial.
+    pub fn history(mut self, enabled: bool) -> Self {
+        self.config.history = enabled;
+        self
+    }
+
+    /// Habilita o deshabilita los comandos de registros.
+    pub fn registers(mut self, enabled: bool) -> Self {
+        self.config.registers = enabled;
+        self
+    }
+
+    /// Reemplaza la versión que el servidor informa con `VERSION` (útil en tests).
+    pub fn version_override(mut self, version: &str) -> Self {
+        self.config.version = version.to_string();
+        self
+    }
+
+    /// Valida la configuración y abre el socket de datos y, si está configurado, el de administración.
+    ///
+    /// #Errores
+    /// `InvalidConfig` si `max_connections`, `max_in_flight`, `thread_pool_size`, `pipeline_depth`,
+    /// `log_channel_capacity`, `heartbeat_interval`, `io_timeout` o `session_ttl` es 0.
+    /// `BindFailed` si no se puede hacer bind a alguna de las direcciones.
+    pub fn build(self) -> Result<Server, ServerError> {
+        if self.config.max_connections == Some(0) {
+            return Err(ServerError::InvalidConfig("max_connections must be greater than 0".to_string()));
+        }
+        if self.config.max_in_flight == Some(0) {
+            return Err(ServerError::InvalidConfig("max_in_flight must be greater than 0".to_string()));
+        }
+        if self.config.thread_pool_size == Some(0) {
+            return Err(ServerError::InvalidConfig("thread_pool_size must be greater than 0".to_string()));
+        }
+        if self.config.pipeline_depth == 0 {
+            return Err(ServerError::InvalidConfig("pipeline_depth must be greater than 0".to_string()));
+        }
+        if self.config.log_channel_capacity == 0 {
+            return Err(ServerError::InvalidConfig("log_channel_capacity must be greater than 0".to_string()));
+        }
+        if self.config.heartbeat_interval == Some(Duration::ZERO) {
+            return Err(ServerError::InvalidConfig("heartbeat_interval must be greater than 0".to_string()));
+        }
+        if self.config.io_timeout == Some(Duration::ZERO) {
+            return Err(ServerError::InvalidConfig("io_timeout must be greater than 0".to_string()));
+        }
+        if self.config.session_ttl.is_zero() {
+            return Err(ServerError::InvalidConfig("session_ttl must be greater than 0".to_string()));
+        }
+        let listener = match self.listener {
+            Some(listener) => listener,
+            None => TcpListener::bind(self.address).map_err(ServerError::BindFailed)?,
+        };
+        let admin_listener = match (self.admin_listener, self.config.admin_address) {
+            (Some(listener), _) => Some(listener),
+            (None, Some(address)) => Some(TcpListener::bind(address).map_err(ServerError::BindFailed)?),
+            (None, None) => None,
+        };
+        #[cfg(feature = "prometheus")]
+        let metrics_listener = match (self.metrics_listener, self.config.metrics_address) {
+            (Some(listener), _) => Some(listener),
+            (None, Some(address)) => Some(TcpListener::bind(address).map_err(ServerError::BindFailed)?),
+            (None, None) => None,
+        };
+        Ok(Server {
+            listener,
+            admin_listener,
+            #[cfg(feature = "prometheus")]
+            metrics_listener,
+            config: self.config,
+        })
+    }
+}
+
+/// Servidor listo para aceptar conexiones.
+pub struct Server {
+    listener: TcpListener,
+    admin_listener: Option<TcpListener>,
+    #[cfg(feature = "prometheus")]
+    metrics_listener: Option<TcpListener>,
+    config: ServerConfig,
+}
+
+impl Server {
+    /// Dirección en la que quedó escuchando el socket de datos. Con puerto 0 es la que eligió el
+    /// sistema operativo, así los tests no dependen de un puerto libre fijo.
+    ///
+    /// #Errores
+    /// `BindFailed` si el sistema operativo no informa la dirección del socket.
+    pub fn local_addr(&self) -> Result<SocketAddr, ServerError> {
+        self.listener.local_addr().map_err(ServerError::BindFailed)
+    }
+
+    /// Arranca el logger (y, con la feature `otel`, el exportador de trazas) y acepta conexiones
+    /// hasta que un administrador pida `SHUTDOWN`. Con `pid_file` configurado, escribe el PID
+    /// mientras corre y lo borra al terminar.
+    ///
+    /// #Errores
+    /// `InvalidConfig` si no se puede crear el exportador de trazas o escribir el archivo de PID.
+    /// Los mismos que [`Server::run_with_sender`].
+    pub fn run(self) -> Result<(), ServerError> {
+        let pid_file = self.config.pid_file.as_deref().map(PidFile::create).transpose()?;
+        let mut logger_config = LoggerConfig {
+            sink: log_sink(&self.config),
+            format: self.config.log_format,
+            audit_file: self.config.audit_file.clone(),
+            metrics_file: self.config.metrics_file.clone(),
+            capacity: self.config.log_channel_capacity,
+            dedup: self.config.log_dedup,
+            rolling: self.config.log_rolling,
+            compress_rotated: self.config.log_compress_rotated,
+            ..LoggerConfig::new().level(self.config.log_level)
+        };
+        for (module, level) in &self.config.log_module_filters {
+            logger_config = logger_config.filter(module, *level);
+        }
+        let (sender, logger_handle) = start_logger(logger_config);
+        if let Some(pid) = pid_file.as_ref().and_then(PidFile::previous_pid) {
+            let _ = log_warn!(sender, format!("PID file belonged to running process {}; overwriting it", pid));
+        }
+        #[cfg(feature = "otel")]
+        let tracer_provider = crate::telemetry::init_tracer()?;
+
+        let result = self.run_with_sender(sender.clone());
+
+        #[cfg(feature = "otel")]
+        let _ = tracer_provider.shutdown();
+
+        let dropped = sender.dropped_events();
+        if dropped > 0 {
+            let _ = sender.send_blocking(LogEvent::Warn {
+                message: format!("{} log events dropped: logger channel was full", dropped),
+                module: Some(module_name(module_path!())),
+            });
+        }
+        // Lo logueado hasta el cierre queda en disco aunque el hilo del logger no termine bien.
+        flush_logger(&sender);
+        let _ = sender.send_blocking(LogEvent::CloseConnection);
+        if let Err(e) = logger_handle.join() {
+            eprintln!("Failed to open log file: [{:?}] ", e);
+        }
+        result
+    }
+
+    /// Acepta conexiones enviando los eventos de log por `sender` en lugar de arrancar un logger propio.
+    /// Solo se detiene cuando un administrador pide `SHUTDOWN`.
+    ///
+    /// #Errores
+    /// Los mismos que [`Server::run_with_shutdown_signal`].
+    pub fn run_with_sender(self, sender: LogSender) -> Result<(), ServerError> {
+        let (_signal, shutdown) = channel();
+        self.run_with_shutdown_signal(sender, shutdown)
+    }
+
+    /// Igual que [`Server::run_with_sender`], pero además deja de aceptar conexiones cuando llega
+    /// un mensaje por `shutdown`, como si un administrador hubiera pedido `SHUTDOWN`.
+    /// Si se cierra el otro extremo del canal sin enviar nada, el servidor sigue corriendo.
+    /// Con `max_in_flight` configurado, antes de cada `accept` espera a que se libere un permiso.
+    ///
+    /// #Errores
+    /// `StateFileFailed` si no se puede leer el archivo de estado.
+    /// `PoisonError` si se envenena el lock del registro de conexiones.
+    pub fn run_with_shutdown_signal(mut self, sender: LogSender, shutdown: Receiver<()>) -> Result<(), ServerError> {
+        self.config.start_time = Instant::now();
+        let calculator = match &self.config.state_file {
+            Some(path) if Path::new(path).exists() => {
+                Calculator::load(path).map_err(|_| ServerError::StateFileFailed)?
+            }
+            _ => Calculator::with_initial(self.config.initial_accumulation),
+        };
+        let pool = self.config.thread_pool_size.map(ThreadPool::new);
+        let semaphore = self.config.max_in_flight.map(|permits| Arc::new(Semaphore::new(permits)));
+        let local_addr = self.local_addr().ok();
+        let mut state = ServerState::new(SharedCalculator::new(calculator), self.config);
+        state.local_addr = local_addr;
+
+        let shutdown_state = state.clone();
+        thread::spawn(move || {
+            if shutdown.recv().is_ok() {
+                shutdown_state.request_shutdown();
+            }
+        });
+        if let Some(interval) = state.config.heartbeat_interval {
+            let heartbeat_state = state.clone();
+            let heartbeat_sender = sender.clone();
+            thread::spawn(move || run_heartbeat(interval, heartbeat_state, heartbeat_sender));
+        }
+        if let Some(admin_listener) = self.admin_listener {
+            let admin_state = state.clone();
+            let admin_sender = sender.clone();
+            thread::spawn(move || run_admin_listener(admin_listener, admin_state, admin_sender));
+        }
+        #[cfg(feature = "prometheus")]
+        if let Some(metrics_listener) = self.metrics_listener {
+            let metrics_state = state.clone();
+            let metrics_sender = sender.clone();
+            thread::spawn(move || crate::metrics::run_metrics_listener(metrics_listener, metrics_state, metrics_sender));
+        }
+
+        loop {
+            let permit = semaphore.as_ref().map(|semaphore| semaphore.acquire());
+            let stream = self.listener.accept().map(|(stream, _)| stream);
+            if state.is_shutting_down() {
+                let _ = log_info!(sender, "Server shutting down");
+                break;
+            }
+            match stream {
+                Ok(stream) => {
+                    let sender_clone = sender.clone();
+                    let peer_addr = stream.peer_addr().map_or("unknown".to_string(), |p| p.to_string());
+                    let _ = log_info!(sender_clone, format!("New connection from {}", peer_addr));
+                    match admit_connection(&state) {
+                        Ok(()) => {}
+                        Err(e @ ServerError::TooManyConnections { .. }) => {
+                            reject_connection(stream, &sender_clone, &peer_addr, &e);
+                            continue;
+                        }
+                        Err(e) => return Err(e),
+                    }
+                    let connection_id = state.registry.register(&peer_addr)?;
+                    #[cfg(feature = "prometheus")]
+                    state.metrics.connection_opened();
+                    if let Ok(clone) = stream.try_clone() {
+                        state.registry.attach_stream(connection_id, clone)?;
+                    }
+                    socket_options::configure_stream(&stream, &state.config, &sender_clone, &peer_addr);
+
+                    let state_clone = state.clone();
+                    let job = move || {
+                        let _permit = permit;
+                        let push_stream = stream.try_clone();
+                        let mut peer_stream = PeerStream::new(stream, peer_addr.clone());
+                        if let Ok(push_stream) = push_stream {
+                            peer_stream = peer_stream.with_push_stream(push_stream);
+                        }
+                        if let Err(e) = handle_connection(peer_stream, state_clone.clone(), sender_clone.clone(), connection_id) {
+                            eprintln!("{}", e);
+                        }
+
+                        let _ = state_clone.subscribers.unsubscribe(connection_id);
+                        let _ = state_clone.registry.remove(connection_id);
+                        let _ = log_info!(sender_clone, format!("Connection from {} closed", peer_addr));
+                    };
+                    match &pool {
+                        Some(pool) => pool.execute(job),
+                        None => {
+                            thread::spawn(job);
+                        }
+                    }
+                }
+                Err(_) => {
+                    eprintln!("{}", ServerError::FailedConnection);
+                    let _ = log_error!(sender, ServerError::FailedConnection);
+                    continue;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Loguea `heartbeat: server alive` cada `interval` hasta que se pida apagar el servidor.
+fn run_heartbeat(interval: Duration, state: ServerState, sender: LogSender) {
+    loop {
+        thread::sleep(interval);
+        if state.is_shutting_down() {
+            break;
+        }
+        let _ = log_info!(sender, "heartbeat: server alive");
+    }
+}
+
+/// Elige el destino del log general: sin archivo se escribe en stderr y, con `log_stderr`, en ambos.
+fn log_sink(config: &ServerConfig) -> LogSink {
+    match (config.log_file.is_empty(), config.log_stderr) {
+        (true, _) => LogSink::Stderr,
+        (false, true) => LogSink::Both(PathBuf::from(&config.log_file)),
+        (false, false) => LogSink::File(PathBuf::from(&config.log_file)),
+    }
+}
+
+/// Decide si se puede atender una conexión más sin superar `max_connections`.
+///
+/// #Errores
+/// `TooManyConnections` si ya hay `max_connections` conexiones abiertas.
+/// `PoisonError` si se envenena el lock del registro de conexiones.
+fn admit_connection(state: &ServerState) -> Result<(), ServerError> {
+    if let Some(limit) = state.config.max_connections {
+        let current = state.registry.count()?;
+        if current >= limit {
+            return Err(ServerError::TooManyConnections { limit, current });
+        }
+    }
+    Ok(())
+}
+
+/// Responde con un error y cierra una conexión que supera `max_connections`.
+/// Se loguea como advertencia: bajo carga es esperable y no es una falla del servidor.
+fn reject_connection(mut stream: TcpStream, sender: &LogSender, peer_addr: &str, error: &ServerError) {
+    let _ = log_warn!(sender, format!("[{}] Rejected: {}", peer_addr, error));
+    let _ = send_protocol(Protocol::ErrorOperation(error.message().to_string()), &mut stream);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::{SocketAddr, TcpListener, TcpStream},
+        path::PathBuf,
+        sync::mpsc::channel,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use crate::{
+        config::ServerConfig,
+        logger::{DEFAULT_LOG_CAPACITY, LogEvent, LogFormat, LogLevel, LogSender, LogSink, log_channel},
+        server::{ServerBuilder, admit_connection, log_sink},
+        server_error::ServerError,
+        server_state::ServerState,
+        shared_calculator::SharedCalculator,
+    };
+
+    fn start(builder: ServerBuilder, sender: LogSender) -> Result<(), ServerError> {
+        builder.build()?.run_with_sender(sender)
+    }
+
+    /// Se conecta al servidor en `addr` y devuelve la conexión junto con el `HELLO` con el que
+    /// el servidor la abre.
+    fn connect_with_greeting(addr: SocketAddr) -> (TcpStream, String) {
+        let client = TcpStream::connect(addr).unwrap();
+        let mut greeting = String::new();
+        // Todavía no se envió ningún pedido: el buffer no puede leer más que el `HELLO`.
+        BufReader::new(client.try_clone().unwrap()).read_line(&mut greeting).unwrap();
+        (client, greeting)
+    }
+
+    /// Se conecta al servidor en `addr` y descarta el `HELLO` con el que el servidor la abre.
+    fn connect(addr: SocketAddr) -> TcpStream {
+        let (client, greeting) = connect_with_greeting(addr);
+        assert!(greeting.starts_with("HELLO "), "unexpected greeting: {}", greeting);
+        client
+    }
+
+    fn round_trip(client: TcpStream, message: &[u8]) -> String {
+        let mut writer = client.try_clone().unwrap();
+        writer.write_all(message).unwrap();
+        let mut reader = BufReader::new(client);
+        let mut buf = String::new();
+        reader.read_line(&mut buf).unwrap();
+        buf
+    }
+
+    /// Envía `messages` por `client` y lee `responses` líneas de respuesta.
+    fn exchange(client: &TcpStream, messages: &[u8], responses: usize) -> Vec<String> {
+        let mut writer = client.try_clone().unwrap();
+        writer.write_all(messages).unwrap();
+        let mut reader = BufReader::new(client.try_clone().unwrap());
+        (0..responses)
+            .map(|_| {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                line
+            })
+            .collect()
+    }
+
+    #[test]
+    fn builder_methods_set_config() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let builder = ServerBuilder::from_listener(listener)
+            .log_file("a.log")
+            .log_stderr(true)
+            .log_format(LogFormat::Json)
+            .log_level(LogLevel::Warn)
+            .log_filter("handle_client", LogLevel::Debug)
+            .log_dedup(true)
+            .log_rolling(true)
+            .log_compress_rotated(false)
+            .audit_file("b.log")
+            .metrics_file("c.log")
+            .log_channel_capacity(10)
+            .admin_token("secret")
+            .state_file("state.json")
+            .pid_file("server.pid")
+            .tcp_keepalive(Duration::from_secs(5))
+            .heartbeat_interval(Some(Duration::from_secs(30)))
+            .io_timeout(Duration::from_secs(10))
+            .session_ttl(Duration::from_secs(20))
+            .tcp_nodelay(false)
+            .max_connections(100)
+            .thread_pool_size(8)
+            .pipeline_depth(4);
+
+        let config = &builder.config;
+        assert_eq!(config.log_file, "a.log");
+        assert!(config.log_stderr);
+        assert_eq!(config.log_format, LogFormat::Json);
+        assert_eq!(config.log_level, LogLevel::Warn);
+        assert_eq!(config.log_module_filters.get("handle_client"), Some(&LogLevel::Debug));
+        assert!(config.log_dedup);
+        assert!(config.log_rolling);
+        assert!(!config.log_compress_rotated);
+        assert_eq!(config.audit_file, "b.log");
+        assert_eq!(config.metrics_file, "c.log");
+        assert_eq!(config.log_channel_capacity, 10);
+        assert_eq!(config.admin_token.as_deref(), Some("secret"));
+        assert_eq!(config.state_file.as_deref(), Some("state.json"));
+        assert_eq!(config.pid_file, Some(PathBuf::from("server.pid")));
+        assert_eq!(config.tcp_keepalive, Some(Duration::from_secs(5)));
+        assert_eq!(config.heartbeat_interval, Some(Duration::from_secs(30)));
+        assert_eq!(config.io_timeout, Some(Duration::from_secs(10)));
+        assert_eq!(config.session_ttl, Duration::from_secs(20));
+        assert!(!config.tcp_nodelay);
+        assert_eq!(config.max_connections, Some(100));
+        assert_eq!(config.thread_pool_size, Some(8));
+        assert_eq!(config.pipeline_depth, 4);
+        assert!(builder.build().is_ok());
+    }
+
+    #[test]
+    fn build_fails_with_zero_max_connections() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let result = ServerBuilder::from_listener(listener).max_connections(0).build();
+        assert!(matches!(result, Err(ServerError::InvalidConfig(msg)) if msg.contains("max_connections")));
+    }
+
+    #[test]
+    fn build_fails_with_zero_thread_pool_size() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let result = ServerBuilder::from_listener(listener).thread_pool_size(0).build();
+        assert!(matches!(result, Err(ServerError::InvalidConfig(msg)) if msg.contains("thread_pool_size")));
+    }
+
+    #[test]
+    fn build_fails_with_zero_pipeline_depth() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let result = ServerBuilder::from_listener(listener).pipeline_depth(0).build();
+        assert!(matches!(result, Err(ServerError::InvalidConfig(msg)) if msg.contains("pipeline_depth")));
+    }
+
+    #[test]
+    fn log_sink_follows_log_file_and_log_stderr() {
+        let mut config = ServerConfig::default();
+        assert_eq!(log_sink(&config), LogSink::File(PathBuf::from("./logs/server.log")));
+        config.log_stderr = true;
+        assert_eq!(log_sink(&config), LogSink::Both(PathBuf::from("./logs/server.log")));
+        config.log_file = String::new();
+        assert_eq!(log_sink(&config), LogSink::Stderr);
+    }
+
+    #[test]
+    fn build_fails_with_zero_log_channel_capacity() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let result = ServerBuilder::from_listener(listener).log_channel_capacity(0).build();
+        assert!(matches!(result, Err(ServerError::InvalidConfig(msg)) if msg.contains("log_channel_capacity")));
+    }
+
+    #[test]
+    fn server_rejects_connections_over_max() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sender, receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let builder = ServerBuilder::from_listener(listener).max_connections(1);
+        thread::spawn(move || start(builder, sender));
+
+        let first = connect(addr);
+        assert_eq!(round_trip(first.try_clone().unwrap(), b"GET\n"), "VALUE 0\n");
+        let second = TcpStream::connect(addr).unwrap();
+
+        assert_eq!(round_trip(second, b"GET\n"), "ERROR \"max connections reached\"\n");
+        drop(first);
+        let expected = "Rejected: ERROR \"max connections reached (1 open, limit 1)\"";
+        let rejected = receiver
+            .try_iter()
+            .any(|event| matches!(event, LogEvent::Warn { message, .. } if message.ends_with(expected)));
+        assert!(rejected);
+    }
+
+    #[test]
+    fn admit_connection_fails_at_the_limit() {
+        let config = ServerConfig {
+            max_connections: Some(1),
+            ..ServerConfig::default()
+        };
+        let state = ServerState::new(SharedCalculator::default(), config);
+
+        assert!(admit_connection(&state).is_ok());
+        state.registry.register("10.0.0.1:1").unwrap();
+
+        let result = admit_connection(&state);
+        assert!(matches!(result, Err(ServerError::TooManyConnections { limit: 1, current: 1 })));
+    }
+
+    #[test]
+    fn server_serves_clients_with_thread_pool() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let builder = ServerBuilder::from_listener(listener).thread_pool_size(2);
+        thread::spawn(move || start(builder, sender));
+
+        for _ in 0..3 {
+            let client = connect(addr);
+            assert_eq!(round_trip(client, b"OP + 1\n"), "OK\n");
+        }
+        let client = connect(addr);
+        assert_eq!(round_trip(client, b"GET\n"), "VALUE 3\n");
+    }
+
+    #[test]
+    fn server_without_history_or_registers_uses_lock_free_calculator() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let builder = ServerBuilder::from_listener(listener).history(false).registers(false);
+        thread::spawn(move || start(builder, sender));
+
+        for op in [&b"OP + 4\n"[..], b"OP * 5\n", b"OP - 2\n"] {
+            assert_eq!(round_trip(connect(addr), op), "OK\n");
+        }
+        assert_eq!(round_trip(connect(addr), b"GET\n"), "VALUE 18\n");
+        assert_eq!(
+            round_trip(connect(addr), b"HISTORY\n"),
+            "ERROR \"history is disabled\"\n"
+        );
+        assert_eq!(
+            round_trip(connect(addr), b"GET A\n"),
+            "ERROR \"registers are disabled\"\n"
+        );
+        // AUTH, BATCH y SUBSCRIBE: sin historial ni registros no se ofrecen esas capacidades.
+        assert_eq!(connect_with_greeting(addr).1, "HELLO 1 13\n");
+    }
+
+    #[test]
+    fn server_limits_in_flight_handlers() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let builder = ServerBuilder::from_listener(listener).max_in_flight(2);
+        thread::spawn(move || start(builder, sender));
+
+        let clients: Vec<_> = (0..5)
+            .map(|_| {
+                thread::spawn(move || {
+                    let client = connect(addr);
+                    let status = round_trip(client.try_clone().unwrap(), b"STATUS\n");
+                    thread::sleep(Duration::from_millis(50));
+                    status
+                })
+            })
+            .collect();
+        let statuses: Vec<String> = clients.into_iter().map(|client| client.join().unwrap()).collect();
+
+        for status in statuses {
+            let connections: usize = status
+                .split_whitespace()
+                .find_map(|field| field.strip_prefix("connections="))
+                .unwrap()
+                .parse()
+                .unwrap();
+            assert!(connections <= 2, "{}", status);
+        }
+    }
+
+    #[test]
+    fn build_fails_with_zero_max_in_flight() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let result = ServerBuilder::from_listener(listener).max_in_flight(0).build();
+        assert!(matches!(result, Err(ServerError::InvalidConfig(msg)) if msg.contains("max_in_flight")));
+    }
+
+    #[test]
+    fn server_bind_fails() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let result = ServerBuilder::new(listener.local_addr().unwrap()).build();
+        assert!(matches!(result, Err(ServerError::BindFailed(_))));
+    }
+
+    #[test]
+    fn local_addr_reports_the_port_chosen_by_the_os() {
+        let server = ServerBuilder::new("127.0.0.1:0".parse().unwrap()).build().unwrap();
+        let addr = server.local_addr().unwrap();
+        assert_ne!(addr.port(), 0);
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let (signal, shutdown) = channel();
+        let handle = thread::spawn(move || server.run_with_shutdown_signal(sender, shutdown));
+
+        let client = connect(addr);
+        assert_eq!(round_trip(client, b"OP + 3\nGET\n"), "OK\n");
+        signal.send(()).unwrap();
+        assert!(handle.join().unwrap().is_ok());
+    }
+
+    #[test]
+    fn heartbeat_is_logged_periodically() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let (sender, receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let (signal, shutdown) = channel();
+        let server = ServerBuilder::from_listener(listener)
+            .heartbeat_interval(Some(Duration::from_millis(10)))
+            .build()
+            .unwrap();
+        let handle = thread::spawn(move || server.run_with_shutdown_signal(sender, shutdown));
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let heartbeat = loop {
+            match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(LogEvent::Info { message, .. }) if message == "heartbeat: server alive" => break true,
+                Ok(_) => continue,
+                Err(_) => break false,
+            }
+        };
+        signal.send(()).unwrap();
+
+        assert!(heartbeat);
+        assert!(handle.join().unwrap().is_ok());
+    }
+
+    #[test]
+    fn build_fails_with_zero_io_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let result = ServerBuilder::from_listener(listener).io_timeout(Duration::ZERO).build();
+        assert!(matches!(result, Err(ServerError::InvalidConfig(msg)) if msg.contains("io_timeout")));
+    }
+
+    #[test]
+    fn build_fails_with_zero_session_ttl() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let result = ServerBuilder::from_listener(listener).session_ttl(Duration::ZERO).build();
+        assert!(matches!(result, Err(ServerError::InvalidConfig(msg)) if msg.contains("session_ttl")));
+    }
+
+    #[test]
+    fn build_fails_with_zero_heartbeat_interval() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let result = ServerBuilder::from_listener(listener).heartbeat_interval(Some(Duration::ZERO)).build();
+        assert!(matches!(result, Err(ServerError::InvalidConfig(msg)) if msg.contains("heartbeat_interval")));
+    }
+
+    #[test]
+    fn shutdown_signal_stops_the_accept_loop() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sender, receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let (signal, shutdown) = channel();
+        let server = ServerBuilder::from_listener(listener).build().unwrap();
+        let handle = thread::spawn(move || server.run_with_shutdown_signal(sender, shutdown));
+
+        let client = connect(addr);
+        assert_eq!(exchange(&client, b"OP + 2\nGET\n", 2), vec!["OK\n", "VALUE 2\n"]);
+        signal.send(()).unwrap();
+
+        assert!(handle.join().unwrap().is_ok());
+        let messages: Vec<String> = receiver
+            .try_iter()
+            .filter_map(|event| match event {
+                LogEvent::Info { message, .. } => Some(message),
+                _ => None,
+            })
+            .collect();
+        assert!(messages.contains(&"Server shutting down".to_string()));
+    }
+
+    #[test]
+    fn status_reports_uptime() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+
+        thread::spawn(move || start(ServerBuilder::from_listener(listener), sender));
+        thread::sleep(Duration::from_millis(10));
+
+        let mut client = connect(addr);
+        client.write_all(b"STATUS\n").unwrap();
+
+        let mut reader = BufReader::new(client);
+        let mut buf = String::new();
+        reader.read_line(&mut buf).unwrap();
+
+        assert!(buf.starts_with("STATUS uptime="));
+        assert!(buf.contains("connections=1"));
+    }
+
+    #[test]
+    fn named_calculators_are_isolated() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        thread::spawn(move || start(ServerBuilder::from_listener(listener), sender));
+
+        let first = connect(addr);
+        let responses = exchange(&first, b"OP + 1\nSELECT foo\nOP + 10\nGET\n", 4);
+        assert_eq!(responses, vec!["OK\n", "OK\n", "OK\n", "VALUE 10\n"]);
+
+        // Otra conexión arranca en `default` y ve la misma `foo`.
+        let second = connect(addr);
+        let responses = exchange(&second, b"GET\nSELECT bar\nGET\nSELECT foo\nOP * 3\nGET\n", 6);
+        assert_eq!(responses, vec!["VALUE 1\n", "OK\n", "VALUE 0\n", "OK\n", "OK\n", "VALUE 30\n"]);
+
+        let responses = exchange(&first, b"GET\nSELECT default\nGET\n", 3);
+        assert_eq!(responses, vec!["VALUE 30\n", "OK\n", "VALUE 1\n"]);
+    }
+
+    #[test]
+    fn copy_duplicates_a_named_calculator() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        thread::spawn(move || start(ServerBuilder::from_listener(listener), sender));
+
+        let client = connect(addr);
+        let responses = exchange(
+            &client,
+            b"SELECT A\nOP + 4\nOP * 2\nCOPY A B\nOP + 100\nSELECT B\nGET\nHISTORY\n",
+            8,
+        );
+        assert_eq!(&responses[3], "OK\n");
+        assert_eq!(&responses[6], "VALUE 8\n");
+        assert_eq!(&responses[7], "HISTORY_VALUE + 4; * 2\n");
+
+        let responses = exchange(&client, b"COPY B B\nGET\nCOPY missing B\nSELECT A\nGET\n", 5);
+        assert_eq!(
+            responses,
+            vec!["OK\n", "VALUE 8\n", "ERROR \"source not found\"\n", "OK\n", "VALUE 108\n"]
+        );
+    }
+
+    #[test]
+    fn transaction_is_discarded_on_error_and_rollback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        thread::spawn(move || start(ServerBuilder::from_listener(listener), sender));
+
+        let client = connect(addr);
+        let responses = exchange(&client, b"OP + 1\nBEGIN\nOP + 5\nOP * 2\nOP << 64\nOP + 3\nGET\nCOMMIT\nGET\n", 9);
+        assert_eq!(
+            responses,
+            vec![
+                "OK\n",
+                "OK\n",
+                "OK\n",
+                "OK\n",
+                "OK\n",
+                "OK\n",
+                "VALUE 1\n",
+                "ERROR \"shift overflow: shift amount must be at most 63\"\n",
+                "VALUE 1\n"
+            ]
+        );
+
+        let responses = exchange(&client, b"BEGIN\nOP + 5\nROLLBACK\nGET\nCOMMIT\nBEGIN\nOP * 7\nCOMMIT\nGET\n", 9);
+        assert_eq!(
+            responses,
+            vec![
+                "OK\n",
+                "OK\n",
+                "OK\n",
+                "VALUE 1\n",
+                "ERROR \"no transaction open\"\n",
+                "OK\n",
+                "OK\n",
+                "OK\n",
+                "VALUE 7\n"
+            ]
+        );
+    }
+
+    #[test]
+    fn concurrent_commits_are_applied_atomically() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        thread::spawn(move || start(ServerBuilder::from_listener(listener), sender));
+
+        let committers: Vec<_> = ["+ 2", "- 1"]
+            .into_iter()
+            .map(|op| {
+                thread::spawn(move || {
+                    let client = connect(addr);
+                    let transaction = format!("BEGIN\nOP {op}\nOP {op}\nOP {op}\nCOMMIT\n");
+                    for _ in 0..50 {
+                        assert_eq!(exchange(&client, transaction.as_bytes(), 5)[4], "OK\n");
+                    }
+                })
+            })
+            .collect();
+        for committer in committers {
+            committer.join().unwrap();
+        }
+
+        let client = connect(addr);
+        let responses = exchange(&client, b"GET\nHISTORY\n", 2);
+        assert_eq!(responses[0], "VALUE 150\n");
+        let history: Vec<&str> = responses[1].trim_end().strip_prefix("HISTORY_VALUE ").unwrap().split("; ").collect();
+        assert_eq!(history.len(), 300);
+        // Las operaciones de cada transacción quedan juntas en el historial.
+        assert!(history.chunks(3).all(|chunk| chunk.iter().all(|op| *op == chunk[0])));
+    }
+
+    #[test]
+    fn subscribe_pushes_values_from_other_connections() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        thread::spawn(move || start(ServerBuilder::from_listener(listener), sender));
+
+        let subscriber = connect(addr);
+        assert_eq!(exchange(&subscriber, b"SUBSCRIBE\n", 1), vec!["OK\n"]);
+        let mut pushes = BufReader::new(subscriber.try_clone().unwrap());
+
+        let client = connect(addr);
+        let responses = exchange(&client, b"OP + 5\nOP / 0\nSELECT foo\nOP + 1\nSELECT default\nOP * 3\n", 6);
+        assert_eq!(&responses[1], "ERROR \"division by zero\"\n");
+
+        let mut line = String::new();
+        pushes.read_line(&mut line).unwrap();
+        assert_eq!(line, "VALUE 5\n");
+        line.clear();
+        pushes.read_line(&mut line).unwrap();
+        assert_eq!(line, "VALUE 15\n");
+    }
+
+    #[test]
+    fn subscribe_receives_every_concurrent_operation() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        thread::spawn(move || start(ServerBuilder::from_listener(listener), sender));
+
+        let subscriber = connect(addr);
+        assert_eq!(exchange(&subscriber, b"SUBSCRIBE\n", 1), vec!["OK\n"]);
+        let mut pushes = BufReader::new(subscriber.try_clone().unwrap());
+
+        let clients: Vec<_> = (0..4)
+            .map(|_| {
+                thread::spawn(move || {
+                    let client = connect(addr);
+                    exchange(&client, "OP + 1\n".repeat(10).as_bytes(), 10);
+                })
+            })
+            .collect();
+        for client in clients {
+            client.join().unwrap();
+        }
+
+        let values: Vec<i64> = (0..40)
+            .map(|_| {
+                let mut line = String::new();
+                pushes.read_line(&mut line).unwrap();
+                line.trim_end().strip_prefix("VALUE ").unwrap().parse().unwrap()
+            })
+            .collect();
+        assert_eq!(values.iter().max(), Some(&40));
+    }
+
+    #[test]
+    fn unsubscribe_stops_the_pushes() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        thread::spawn(move || start(ServerBuilder::from_listener(listener), sender));
+
+        let subscriber = connect(addr);
+        let mut writer = subscriber.try_clone().unwrap();
+        let mut reader = BufReader::new(subscriber.try_clone().unwrap());
+        let client = connect(addr);
+        let mut line = String::new();
+
+        writer.write_all(b"SUBSCRIBE\n").unwrap();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "OK\n");
+        exchange(&client, b"OP + 2\n", 1);
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "VALUE 2\n");
+
+        writer.write_all(b"UNSUBSCRIBE\n").unwrap();
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "OK\n");
+        exchange(&client, b"OP + 2\n", 1);
+
+        subscriber.set_read_timeout(Some(Duration::from_millis(200))).unwrap();
+        line.clear();
+        assert!(reader.read_line(&mut line).is_err());
+    }
+
+    #[test]
+    fn lock_free_server_only_uses_atomic_accumulation_for_default() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let builder = ServerBuilder::from_listener(listener).history(false).registers(false);
+        thread::spawn(move || start(builder, sender));
+
+        let client = connect(addr);
+        let responses = exchange(&client, b"OP + 2\nSELECT foo\nOP + 5\nGET\nSELECT default\nGET\n", 6);
+        assert_eq!(responses, vec!["OK\n", "OK\n", "OK\n", "VALUE 5\n", "OK\n", "VALUE 2\n"]);
+    }
+
+    #[test]
+    fn server_starts_from_initial_accumulation() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let builder = ServerBuilder::from_listener(listener).initial_accumulation(-40);
+        thread::spawn(move || start(builder, sender));
+
+        assert_eq!(round_trip(connect(addr), b"GET\n"), "VALUE -40\n");
+        assert_eq!(round_trip(connect(addr), b"OP + 2\n"), "OK\n");
+        assert_eq!(round_trip(connect(addr), b"GET\n"), "VALUE -38\n");
+    }
+
+    #[test]
+    fn status_reports_calculator_activity() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        thread::spawn(move || start(ServerBuilder::from_listener(listener), sender));
+
+        let status = round_trip(connect(addr), b"STATUS\n");
+        assert!(status.trim_end().ends_with("total_ops=0 last_op=never"), "{}", status);
+
+        assert_eq!(round_trip(connect(addr), b"OP + 1\n"), "OK\n");
+        assert_eq!(round_trip(connect(addr), b"OP * 4\n"), "OK\n");
+        let status = round_trip(connect(addr), b"STATUS\n");
+        assert!(status.contains("total_ops=2 last_op="), "{}", status);
+        assert!(!status.contains("never"), "{}", status);
+    }
+
+    #[test]
+    fn server_restores_persisted_state() {
+        let state_file = "logs/server_state_test_.json";
+        let _ = std::fs::remove_file(state_file);
+        let first = TcpListener::bind("127.0.0.1:0").unwrap();
+        let first_addr = first.local_addr().unwrap();
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let builder = ServerBuilder::from_listener(first).state_file(state_file);
+        thread::spawn(move || start(builder, sender));
+
+        let mut client = connect(first_addr);
+        client.write_all(b"OP + 5\nOP * 3\n").unwrap();
+        let mut reader = BufReader::new(client);
+        let mut buf = String::new();
+        reader.read_line(&mut buf).unwrap();
+        reader.read_line(&mut buf).unwrap();
+
+        let second = TcpListener::bind("127.0.0.1:0").unwrap();
+        let second_addr = second.local_addr().unwrap();
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let builder = ServerBuilder::from_listener(second).state_file(state_file);
+        thread::spawn(move || start(builder, sender));
+
+        let mut client = connect(second_addr);
+        client.write_all(b"GET\nHISTORY\n").unwrap();
+        let mut reader = BufReader::new(client);
+        buf.clear();
+        reader.read_line(&mut buf).unwrap();
+        reader.read_line(&mut buf).unwrap();
+        let _ = std::fs::remove_file(state_file);
+
+        assert_eq!(buf, "VALUE 15\nHISTORY_VALUE + 5; * 3\n");
+    }
+
+    #[test]
+    fn server_accepts_connections_with_keepalive() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sender, receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let builder = ServerBuilder::from_listener(listener).tcp_keepalive(Duration::from_secs(30));
+        thread::spawn(move || start(builder, sender));
+
+        let mut client = connect(addr);
+        client.write_all(b"GET\n").unwrap();
+        let mut reader = BufReader::new(client);
+        let mut buf = String::new();
+        reader.read_line(&mut buf).unwrap();
+
+        assert_eq!(buf, "VALUE 0\n");
+        let debug = receiver.iter().find_map(|event| match event {
+            LogEvent::Debug { message, .. } => Some(message),
+            _ => None,
+        });
+        assert!(debug.unwrap().contains("TCP keepalive set to 30s"));
+    }
+
+    fn average_round_trip(tcp_nodelay: bool, iterations: u32) -> Duration {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sender, receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let builder = ServerBuilder::from_listener(listener).tcp_nodelay(tcp_nodelay);
+        thread::spawn(move || start(builder, sender));
+        thread::spawn(move || for _ in receiver {});
+
+        let client = connect(addr);
+        client.set_nodelay(tcp_nodelay).unwrap();
+        let mut writer = client.try_clone().unwrap();
+        let mut reader = BufReader::new(client);
+        let mut buf = String::new();
+        let start = Instant::now();
+        for _ in 0..iterations {
+            writer.write_all(b"OP + 1\n").unwrap();
+            buf.clear();
+            reader.read_line(&mut buf).unwrap();
+        }
+        start.elapsed() / iterations
+    }
+
+    /// Micro-benchmark de latencia ida y vuelta de una operación, con y sin TCP_NODELAY.
+    /// Se corre con `cargo test --bin server -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_single_operation_round_trip() {
+        let with_nodelay = average_round_trip(true, 500);
+        let without_nodelay = average_round_trip(false, 500);
+        println!("round trip with TCP_NODELAY: {:?}", with_nodelay);
+        println!("round trip without TCP_NODELAY: {:?}", without_nodelay);
+    }
+
+    #[cfg(feature = "prometheus")]
+    #[test]
+    fn metrics_endpoint_exposes_prometheus_text() {
+        use std::io::Read;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let metrics = TcpListener::bind("127.0.0.1:0").unwrap();
+        let metrics_addr = metrics.local_addr().unwrap();
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let builder = ServerBuilder::from_listener(listener).metrics_listener(metrics);
+        thread::spawn(move || start(builder, sender));
+
+        let client = connect(addr);
+        assert_eq!(round_trip(client, b"OP + 5\n"), "OK\n");
+
+        let mut http = TcpStream::connect(metrics_addr).unwrap();
+        http.write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        http.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("calc_connections_total 1"));
+        assert!(response.contains("calc_operations_total{status=\"ok\"} 1"));
+        assert!(response.contains("calc_accumulation_value 5"));
+    }
+
+    #[test]
+    fn admin_port_manages_data_connections() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let admin_listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let admin_addr = admin_listener.local_addr().unwrap();
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let builder = ServerBuilder::from_listener(listener)
+            .admin_token("secret")
+            .admin_listener(admin_listener);
+        let server = thread::spawn(move || start(builder, sender));
+
+        let data = connect(addr);
+        let mut data_reader = BufReader::new(data.try_clone().unwrap());
+        let mut data_writer = data.try_clone().unwrap();
+        data_writer.write_all(b"OP + 1\nSHUTDOWN\nAUTH secret\nSTATUS\nLIST_CLIENTS\n").unwrap();
+        let mut buf = String::new();
+        for _ in 0..5 {
+            data_reader.read_line(&mut buf).unwrap();
+        }
+        assert_eq!(
+            buf,
+            "OK\nERROR \"unexpected message: SHUTDOWN\"\nOK\nERROR \"unexpected message: STATUS\"\nERROR \"unexpected message: ADMIN LIST_CLIENTS\"\n"
+        );
+
+        let admin = TcpStream::connect(admin_addr).unwrap();
+        let mut admin_reader = BufReader::new(admin.try_clone().unwrap());
+        let mut admin_writer = admin;
+        let mut admin_request = |message: &[u8]| {
+            admin_writer.write_all(message).unwrap();
+            let mut line = String::new();
+            admin_reader.read_line(&mut line).unwrap();
+            line
+        };
+
+        assert_eq!(admin_request(b"STATUS\n"), "ERROR \"admin authentication required\"\n");
+        assert_eq!(admin_request(b"AUTH secret\n"), "OK\n");
+        assert!(admin_request(b"STATUS\n").contains("connections=1"));
+        let clients = admin_request(b"LIST_CLIENTS\n");
+        assert!(clients.starts_with("CLIENTS id=1 "));
+        assert!(clients.contains("ops=1"));
+        assert_eq!(admin_request(b"KILL 7\n"), "ERROR \"unknown connection: 7\"\n");
+        assert_eq!(admin_request(b"KILL 1\n"), "OK\n");
+
+        buf.clear();
+        assert_eq!(data_reader.read_line(&mut buf).unwrap(), 0);
+
+        assert_eq!(admin_request(b"SHUTDOWN\n"), "OK\n");
+        assert!(server.join().unwrap().is_ok());
+    }
+
+    #[test]
+    fn server_serves_ipv6_clients_and_logs_bracketed_address() {
+        let listener = TcpListener::bind("[::1]:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sender, receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let builder = ServerBuilder::from_listener(listener).admin_token("secret");
+        thread::spawn(move || start(builder, sender));
+
+        let client = connect(addr);
+        let client_addr = client.local_addr().unwrap().to_string();
+        assert!(client_addr.starts_with("[::1]:"));
+        assert_eq!(round_trip(client.try_clone().unwrap(), b"OP + 1\n"), "OK\n");
+        assert_eq!(round_trip(client.try_clone().unwrap(), b"AUTH secret\n"), "OK\n");
+        let clients = round_trip(client, b"ADMIN LIST_CLIENTS\n");
+        assert!(clients.contains(&format!("peer={} ", client_addr)));
+
+        let connected = receiver.iter().find_map(|event| match event {
+            LogEvent::Info { message, .. } if message.starts_with("New connection from") => Some(message),
+            _ => None,
+        });
+        assert_eq!(connected.unwrap(), format!("New connection from {}", client_addr));
+    }
+
+    #[test]
+    fn version_reports_crate_and_protocol_version() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        thread::spawn(move || start(ServerBuilder::from_listener(listener), sender));
+
+        let response = round_trip(connect(addr), b"VERSION\n");
+
+        assert!(response.starts_with("VERSION_INFO"));
+        assert_eq!(
+            response,
+            format!("VERSION_INFO crate={} protocol=1\n", env!("CARGO_PKG_VERSION"))
+        );
+    }
+
+    #[test]
+    fn version_override_is_reported() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let builder = ServerBuilder::from_listener(listener).version_override("9.9.9-test");
+        thread::spawn(move || start(builder, sender));
+
+        let response = round_trip(connect(addr), b"VERSION\n");
+
+        assert_eq!(response, "VERSION_INFO crate=9.9.9-test protocol=1\n");
+    }
+
+    #[test]
+    fn pipelined_requests_get_responses_in_order() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let builder = ServerBuilder::from_listener(listener).pipeline_depth(2);
+        thread::spawn(move || start(builder, sender));
+
+        let mut client = connect(addr);
+        client.write_all(b"OP + 1\nOP / 0\nOP * 5\nGET\nVERSION\n").unwrap();
+        let mut reader = BufReader::new(client);
+        let mut lines = Vec::new();
+        for _ in 0..5 {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            lines.push(line);
+        }
+
+        assert_eq!(lines[0], "OK\n");
+        assert_eq!(lines[1], "ERROR \"division by zero\"\n");
+        assert_eq!(lines[2], "OK\n");
+        assert_eq!(lines[3], "VALUE 5\n");
+        assert!(lines[4].starts_with("VERSION_INFO"));
+    }
+
+    fn operations_per_second(depth: usize, operations: usize) -> f64 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sender, receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        thread::spawn(move || start(ServerBuilder::from_listener(listener), sender));
+        thread::spawn(move || for _ in receiver {});
+
+        let mut writer = connect(addr);
+        let mut reader = BufReader::new(writer.try_clone().unwrap());
+        let mut line = String::new();
+        let start = Instant::now();
+        for _ in 0..operations / depth {
+            writer.write_all(&b"OP + 1\n".repeat(depth)).unwrap();
+            for _ in 0..depth {
+                line.clear();
+                reader.read_line(&mut line).unwrap();
+            }
+        }
+        operations as f64 / start.elapsed().as_secs_f64()
+    }
+
+    /// Benchmark de throughput de 1000 operaciones, secuencial contra pipelining.
+    /// Se corre con `cargo test --bin server -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_pipelined_throughput() {
+        let sequential = operations_per_second(1, 1000);
+        let pipelined = operations_per_second(50, 1000);
+        println!("sequential: {:.0} ops/s", sequential);
+        println!("pipelined (depth 50): {:.0} ops/s", pipelined);
+    }
+}
@@ -0,0 +1,95 @@
+//! Estado compartido entre todas las conexiones del servidor.
+use std::{
+    net::{SocketAddr, TcpStream},
+    sync::{
+        Arc, Mutex, RwLock,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use crate::{
+    calculator::LockFreeCalculator, config::ServerConfig, connection_registry::ConnectionRegistry,
+    namespaces::Namespaces,
+    sessions::SessionStore, shared_calculator::SharedCalculator, snapshot_store::SnapshotStore,
+    subscribers::Subscribers,
+};
+#[cfg(feature = "prometheus")]
+use crate::metrics::Metrics;
+
+/// Agrupa lo que comparten los hilos que atienden clientes.
+/// Clonarlo es barato: solo se clonan los `Arc` internos.
+#[derive(Clone)]
+pub struct ServerState {
+    /// Calculadora compartida. Los `GET` toman el lock de lectura y las operaciones, el de escritura.
+    pub calculator: SharedCalculator,
+    /// Acumulación sin locks que reemplaza a `calculator` en `OP` y `GET` cuando la
+    /// configuración no habilita historial ni registros
+    pub lock_free: Option<Arc<LockFreeCalculator>>,
+    /// Calculadoras con nombre que se eligen con `SELECT`; `calculator` es la de `default`
+    pub namespaces: Namespaces,
+    /// Conexiones activas
+    pub registry: ConnectionRegistry,
+    /// Conexiones suscriptas con `SUBSCRIBE`
+    pub subscribers: Subscribers,
+    /// Fotos del estado de la calculadora tomadas con `SNAPSHOT`
+    pub snapshots: SnapshotStore,
+    /// Sesiones de conexiones cerradas que se pueden retomar con `RESUME`
+    pub sessions: SessionStore,
+    /// Cada mensaje que modifica el estado toma el lock de lectura mientras se procesa; `DRAIN`
+    /// toma el de escritura, así espera a los que están en curso y frena a los nuevos hasta `RELEASE`.
+    pub drain: Arc<RwLock<()>>,
+    /// Se toma mientras se escribe el archivo de estado, para que dos conexiones no lo
+    /// reescriban a la vez
+    pub persist: Arc<Mutex<()>>,
+    /// Configuración con la que corre el servidor
+    pub config: Arc<ServerConfig>,
+    /// Dirección del puerto de datos, usada para despertar el ciclo de `accept` al apagar
+    pub local_addr: Option<SocketAddr>,
+    /// Métricas que se exponen en `/metrics`
+    #[cfg(feature = "prometheus")]
+    pub metrics: Metrics,
+    /// Se activa cuando un administrador pide `SHUTDOWN`
+    shutdown: Arc<AtomicBool>,
+}
+
+impl ServerState {
+    /// Crea el estado compartido a partir de la calculadora y la configuración.
+    /// Si la configuración no usa historial ni registros, arranca además una `LockFreeCalculator`
+    /// con la acumulación de `calculator`.
+    pub fn new(calculator: SharedCalculator, config: ServerConfig) -> Self {
+        let lock_free = config.lock_free().then(|| {
+            let accumulation = calculator.accumulation().unwrap_or_default();
+            Arc::new(LockFreeCalculator::new(accumulation))
+        });
+        Self {
+            namespaces: Namespaces::new(calculator.clone()),
+            calculator,
+            lock_free,
+            registry: ConnectionRegistry::new(),
+            subscribers: Subscribers::new(),
+            snapshots: SnapshotStore::new(),
+            sessions: SessionStore::new(),
+            drain: Arc::new(RwLock::new(())),
+            persist: Arc::new(Mutex::new(())),
+            config: Arc::new(config),
+            local_addr: None,
+            #[cfg(feature = "prometheus")]
+            metrics: Metrics::new(),
+            shutdown: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Pide que el servidor deje de aceptar conexiones.
+    /// Se conecta al puerto de datos para que el ciclo de `accept` vea el pedido.
+    pub fn request_shutdown(&self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        if let Some(addr) = self.local_addr {
+            let _ = TcpStream::connect(addr);
+        }
+    }
+
+    /// Indica si se pidió apagar el servidor.
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst)
+    }
+}
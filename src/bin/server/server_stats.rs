@@ -0,0 +1,90 @@
+//! Estadísticas del servidor que se informan con el comando `STATUS`.
+use std::{
+    fmt,
+    time::{Instant, UNIX_EPOCH},
+};
+
+use crate::calculator::CalculatorStats;
+
+/// Foto de las estadísticas del servidor en un momento dado.
+pub struct ServerStats {
+    /// Momento en que arrancó el servidor
+    start_time: Instant,
+    /// Cantidad de conexiones activas
+    active_connections: usize,
+    /// Actividad de la calculadora
+    calculator: CalculatorStats,
+}
+
+impl ServerStats {
+    /// Crea las estadísticas a partir del momento de arranque, las conexiones activas y la
+    /// actividad de la calculadora.
+    pub fn new(start_time: Instant, active_connections: usize, calculator: CalculatorStats) -> Self {
+        Self {
+            start_time,
+            active_connections,
+            calculator,
+        }
+    }
+
+    /// Devuelve los segundos transcurridos desde que arrancó el servidor.
+    pub fn uptime_secs(&self) -> u64 {
+        self.start_time.elapsed().as_secs()
+    }
+
+    /// Devuelve la cantidad de conexiones activas.
+    pub fn active_connections(&self) -> usize {
+        self.active_connections
+    }
+}
+
+impl fmt::Display for ServerStats {
+    /// Imprime las estadísticas como pares `clave=valor`, el formato que viaja en `STATUS`.
+    /// `last_op` son los segundos Unix de la última operación, o `never` si no hubo ninguna.
+    /// Ejemplo: `uptime=12 connections=3 total_ops=40 last_op=1760400000`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "uptime={} connections={} total_ops={} last_op=",
+            self.uptime_secs(),
+            self.active_connections(),
+            self.calculator.total_operations
+        )?;
+        match self.calculator.last_operation_at {
+            Some(at) => write!(f, "{}", at.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)),
+            None => write!(f, "never"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant, UNIX_EPOCH};
+
+    use crate::{calculator::CalculatorStats, server_stats::ServerStats};
+
+    #[test]
+    fn uptime_counts_from_start_time() {
+        let start_time = Instant::now() - Duration::from_secs(5);
+        let stats = ServerStats::new(start_time, 2, CalculatorStats::default());
+
+        assert!(stats.uptime_secs() >= 5);
+        assert_eq!(stats.active_connections(), 2);
+    }
+
+    #[test]
+    fn display_uses_key_value_format() {
+        let stats = ServerStats::new(Instant::now(), 1, CalculatorStats::default());
+        assert_eq!(stats.to_string(), "uptime=0 connections=1 total_ops=0 last_op=never");
+    }
+
+    #[test]
+    fn display_includes_last_operation_time() {
+        let calculator = CalculatorStats {
+            total_operations: 7,
+            last_operation_at: Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+        };
+        let stats = ServerStats::new(Instant::now(), 0, calculator);
+        assert_eq!(stats.to_string(), "uptime=0 connections=0 total_ops=7 last_op=1700000000");
+    }
+}
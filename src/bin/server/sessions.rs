@@ -0,0 +1,112 @@
+//! Sesiones de las conexiones que hicieron `HELLO`, para poder retomarlas con `RESUME` después
+//! de una desconexión. Se guardan en memoria al cerrarse la conexión y vencen pasado
+//! `ServerConfig::session_ttl`.
+use std::{
+    collections::HashMap,
+    hash::{BuildHasher, RandomState},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use distributed_calculator::capabilities::ServerCapabilities;
+
+use crate::{operation::Operation, server_error::ServerError};
+
+/// Estado propio de una conexión que se recupera con `RESUME`.
+/// La autenticación de administrador no se guarda: hay que volver a enviar `AUTH`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SessionState {
+    /// Calculadora con nombre elegida con `SELECT`
+    pub namespace: String,
+    /// Operaciones encoladas desde un `BEGIN` sin `COMMIT` ni `ROLLBACK`
+    pub transaction: Option<Vec<Operation>>,
+    /// Capacidades acordadas en `HELLO`
+    pub negotiated: Option<ServerCapabilities>,
+}
+
+/// Almacén compartido de sesiones. Clonarlo es barato: todas las copias comparten el mismo mapa.
+#[derive(Clone, Default)]
+pub struct SessionStore {
+    sessions: Arc<Mutex<HashMap<String, (SessionState, Instant)>>>,
+}
+
+impl SessionStore {
+    /// Crea un almacén vacío.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Devuelve un identificador nuevo de 32 dígitos hexadecimales, difícil de adivinar para
+    /// que otra conexión no pueda retomar una sesión ajena.
+    pub fn new_id() -> String {
+        let state = RandomState::new();
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+        format!("{:016x}{:016x}", state.hash_one(nanos), state.hash_one(nanos.rotate_left(64)))
+    }
+
+    /// Guarda la sesión `id` al cerrarse su conexión y descarta las que vencieron hace más de `ttl`.
+    ///
+    /// #Errores
+    /// `Error::PosionError` - En el caso de que se envenene el lock.
+    pub fn save(&self, id: String, state: SessionState, ttl: Duration) -> Result<(), ServerError> {
+        let mut sessions = self.sessions.lock().map_err(|_| ServerError::PoisonError)?;
+        sessions.retain(|_, (_, saved_at)| saved_at.elapsed() < ttl);
+        sessions.insert(id, (state, Instant::now()));
+        Ok(())
+    }
+
+    /// Saca la sesión `id` del almacén para que la retome una conexión. Devuelve `None` si no
+    /// existe o si se guardó hace más de `ttl`.
+    ///
+    /// #Errores
+    /// `Error::PosionError` - En el caso de que se envenene el lock.
+    pub fn resume(&self, id: &str, ttl: Duration) -> Result<Option<SessionState>, ServerError> {
+        let mut sessions = self.sessions.lock().map_err(|_| ServerError::PoisonError)?;
+        Ok(sessions
+            .remove(id)
+            .filter(|(_, saved_at)| saved_at.elapsed() < ttl)
+            .map(|(state, _)| state))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::sessions::{SessionState, SessionStore};
+
+    fn session(namespace: &str) -> SessionState {
+        SessionState {
+            namespace: namespace.to_string(),
+            transaction: None,
+            negotiated: None,
+        }
+    }
+
+    #[test]
+    fn new_ids_are_distinct_hex_strings() {
+        let first = SessionStore::new_id();
+        assert_eq!(first.len(), 32);
+        assert!(first.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(first, SessionStore::new_id());
+    }
+
+    #[test]
+    fn resume_takes_the_session_once() {
+        let store = SessionStore::new();
+        store.save("a".to_string(), session("foo"), Duration::from_secs(60)).unwrap();
+
+        assert_eq!(store.clone().resume("a", Duration::from_secs(60)).unwrap(), Some(session("foo")));
+        assert_eq!(store.resume("a", Duration::from_secs(60)).unwrap(), None);
+        assert_eq!(store.resume("b", Duration::from_secs(60)).unwrap(), None);
+    }
+
+    #[test]
+    fn expired_sessions_cannot_be_resumed() {
+        let store = SessionStore::new();
+        store.save("a".to_string(), session("foo"), Duration::from_secs(60)).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+
+        assert_eq!(store.resume("a", Duration::from_millis(10)).unwrap(), None);
+    }
+}
@@ -0,0 +1,106 @@
+//! Calculadora compartida entre las conexiones, con el manejo del lock encapsulado.
+use std::sync::{Arc, RwLock};
+
+use crate::{calculator::Calculator, operation::Operation, server_error::ServerError};
+
+/// Envuelve un `Arc<RwLock<Calculator>>` y convierte el lock envenenado en `ServerError::PoisonError`.
+/// Clonarla es barato: solo se clona el `Arc` interno.
+#[derive(Clone, Default)]
+pub struct SharedCalculator {
+    inner: Arc<RwLock<Calculator>>,
+}
+
+impl SharedCalculator {
+    /// Comparte `calculator` entre las conexiones.
+    pub fn new(calculator: Calculator) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(calculator)),
+        }
+    }
+
+    /// Aplica una operación y devuelve la acumulación que dejó, sin soltar el lock entre ambas.
+    ///
+    /// #Errores
+    /// `PoisonError` si se envenena el lock.
+    /// `OperationFailed` si la calculadora rechaza la operación.
+    pub fn apply(&self, op: Operation) -> Result<i64, ServerError> {
+        self.write(|calc| {
+            calc.apply(op).map_err(ServerError::OperationFailed)?;
+            Ok(calc.accumulation())
+        })?
+    }
+
+    /// Aplica `ops` en orden sin soltar el lock y devuelve la acumulación que dejaron.
+    /// Si alguna falla no se aplica ninguna.
+    ///
+    /// #Errores
+    /// `PoisonError` si se envenena el lock.
+    /// `OperationFailed` si la calculadora rechaza alguna de las operaciones.
+    pub fn apply_all(&self, ops: &[Operation]) -> Result<i64, ServerError> {
+        self.write(|calc| {
+            calc.apply_all(ops).map_err(ServerError::OperationFailed)?;
+            Ok(calc.accumulation())
+        })?
+    }
+
+    /// Devuelve el valor actual de la acumulación.
+    ///
+    /// #Errores
+    /// `PoisonError` si se envenena el lock.
+    pub fn accumulation(&self) -> Result<i64, ServerError> {
+        self.read(Calculator::accumulation)
+    }
+
+    /// Corre `f` con el lock de lectura tomado y devuelve su resultado.
+    ///
+    /// #Errores
+    /// `PoisonError` si se envenena el lock.
+    pub fn read<T>(&self, f: impl FnOnce(&Calculator) -> T) -> Result<T, ServerError> {
+        let calc = self.inner.read().map_err(|_| ServerError::PoisonError)?;
+        Ok(f(&calc))
+    }
+
+    /// Corre `f` con el lock de escritura tomado y devuelve su resultado.
+    ///
+    /// #Errores
+    /// `PoisonError` si se envenena el lock.
+    pub fn write<T>(&self, f: impl FnOnce(&mut Calculator) -> T) -> Result<T, ServerError> {
+        let mut calc = self.inner.write().map_err(|_| ServerError::PoisonError)?;
+        Ok(f(&mut calc))
+    }
+
+    /// Saca la marca de envenenado del lock, para que los próximos `read` y `write` vuelvan a
+    /// funcionar. La calculadora queda como la dejó el hilo que entró en pánico: `apply` y
+    /// `apply_all` solo la modifican después de calcular el resultado, así que no queda a medias.
+    pub fn clear_poison(&self) {
+        self.inner.clear_poison();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        calculator::Calculator, calculator_error::CalculatorError, operation::Operation, server_error::ServerError,
+        shared_calculator::SharedCalculator,
+    };
+
+    #[test]
+    fn clones_share_the_same_calculator() {
+        let calculator = SharedCalculator::new(Calculator::new());
+        let clone = calculator.clone();
+
+        assert_eq!(clone.apply(Operation::Add(5)).unwrap(), 5);
+        assert_eq!(calculator.apply(Operation::Mul(3)).unwrap(), 15);
+        assert_eq!(clone.accumulation().unwrap(), 15);
+        assert_eq!(calculator.read(|calc| calc.history().len()).unwrap(), 2);
+    }
+
+    #[test]
+    fn apply_reports_rejected_operations() {
+        let calculator = SharedCalculator::default();
+        let result = calculator.apply(Operation::Shl(64));
+
+        assert!(matches!(result, Err(ServerError::OperationFailed(CalculatorError::ShiftOverflow))));
+        assert_eq!(calculator.accumulation().unwrap(), 0);
+    }
+}
@@ -0,0 +1,70 @@
+//! Fotos en memoria del estado de la calculadora, creadas con `SNAPSHOT` y recuperadas con `RESTORE`.
+//! No se persisten: se pierden al reiniciar el servidor.
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use crate::{calculator::CalculatorState, server_error::ServerError};
+
+/// Almacén compartido de fotos. Clonarlo es barato: todas las copias comparten el mismo mapa.
+#[derive(Clone, Default)]
+pub struct SnapshotStore {
+    snapshots: Arc<Mutex<HashMap<String, CalculatorState>>>,
+    next_id: Arc<Mutex<u64>>,
+}
+
+impl SnapshotStore {
+    /// Crea un almacén vacío.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Guarda la foto y devuelve el identificador asignado (`snap-1`, `snap-2`, ...).
+    ///
+    /// #Errores
+    /// `Error::PosionError` - En el caso de que se envenene el lock.
+    pub fn save(&self, state: CalculatorState) -> Result<String, ServerError> {
+        let id = {
+            let mut next_id = self.next_id.lock().map_err(|_| ServerError::PoisonError)?;
+            *next_id += 1;
+            format!("snap-{}", *next_id)
+        };
+        self.snapshots
+            .lock()
+            .map_err(|_| ServerError::PoisonError)?
+            .insert(id.clone(), state);
+        Ok(id)
+    }
+
+    /// Devuelve la foto con el identificador `id`, si existe.
+    ///
+    /// #Errores
+    /// `Error::PosionError` - En el caso de que se envenene el lock.
+    pub fn get(&self, id: &str) -> Result<Option<CalculatorState>, ServerError> {
+        let snapshots = self.snapshots.lock().map_err(|_| ServerError::PoisonError)?;
+        Ok(snapshots.get(id).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{calculator::CalculatorState, snapshot_store::SnapshotStore};
+
+    #[test]
+    fn save_assigns_distinct_ids() {
+        let store = SnapshotStore::new();
+        let state = CalculatorState {
+            accumulation: 1,
+            base: 1,
+            history: Vec::new(),
+        };
+
+        let first = store.save(state.clone()).unwrap();
+        let second = store.clone().save(state.clone()).unwrap();
+
+        assert_ne!(first, second);
+        assert_eq!(store.get(&first).unwrap(), Some(state));
+        assert_eq!(store.get("snap-99").unwrap(), None);
+    }
+}
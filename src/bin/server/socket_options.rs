@@ -0,0 +1,112 @@
+//! Opciones de socket que el servidor aplica a cada conexión aceptada.
+use std::{io, net::TcpStream, time::Duration};
+
+use socket2::{SockRef, TcpKeepalive};
+
+use crate::{config::ServerConfig, logger::{LogSender, log_debug, log_warn}};
+
+/// Aplica al stream aceptado las opciones de socket de la configuración.
+/// Las fallas no cortan la conexión: se registran como advertencia en el log.
+pub fn configure_stream(stream: &TcpStream, config: &ServerConfig, sender: &LogSender, peer_addr: &str) {
+    if let Err(e) = stream.set_nodelay(config.tcp_nodelay) {
+        let _ = log_warn!(sender, format!("[{}] Could not set TCP_NODELAY: {}", peer_addr, e));
+    }
+    if let Some(idle) = config.tcp_keepalive {
+        match apply_keepalive(stream, idle) {
+            Ok(()) => {
+                let _ = log_debug!(sender, format!("[{}] TCP keepalive set to {:?}", peer_addr, idle));
+            }
+            Err(e) => {
+                let _ = log_warn!(sender, format!("[{}] Could not set TCP keepalive: {}", peer_addr, e));
+            }
+        }
+    }
+    if let Some(timeout) = config.io_timeout
+        && let Err(e) = stream.set_read_timeout(Some(timeout)).and_then(|()| stream.set_write_timeout(Some(timeout)))
+    {
+        let _ = log_warn!(sender, format!("[{}] Could not set the I/O timeout: {}", peer_addr, e));
+    }
+}
+
+/// Habilita TCP keepalive en el stream, enviando sondas después de `idle` sin tráfico.
+/// Evita que firewalls intermedios descarten en silencio las conexiones inactivas.
+///
+/// #Errores
+/// Si el sistema operativo no permite configurar keepalive en el socket.
+pub fn apply_keepalive(stream: &TcpStream, idle: Duration) -> io::Result<()> {
+    let keepalive = TcpKeepalive::new().with_time(idle);
+    SockRef::from(stream).set_tcp_keepalive(&keepalive)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{TcpListener, TcpStream},
+        time::Duration,
+    };
+
+    use socket2::SockRef;
+
+    use crate::{
+        config::ServerConfig,
+        logger::{DEFAULT_LOG_CAPACITY, log_channel},
+        socket_options::{apply_keepalive, configure_stream},
+    };
+
+    fn connected_pair() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        (client, stream)
+    }
+
+    #[test]
+    fn keepalive_is_enabled_on_stream() {
+        let (_client, stream) = connected_pair();
+
+        apply_keepalive(&stream, Duration::from_secs(30)).unwrap();
+
+        assert!(SockRef::from(&stream).keepalive().unwrap());
+    }
+
+    #[test]
+    fn configure_stream_sets_nodelay_by_default() {
+        let (_client, stream) = connected_pair();
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+
+        configure_stream(&stream, &ServerConfig::default(), &sender, "test");
+
+        assert!(stream.nodelay().unwrap());
+        assert!(!SockRef::from(&stream).keepalive().unwrap());
+    }
+
+    #[test]
+    fn configure_stream_respects_disabled_nodelay() {
+        let (_client, stream) = connected_pair();
+        stream.set_nodelay(true).unwrap();
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let config = ServerConfig {
+            tcp_nodelay: false,
+            ..ServerConfig::default()
+        };
+
+        configure_stream(&stream, &config, &sender, "test");
+
+        assert!(!stream.nodelay().unwrap());
+    }
+
+    #[test]
+    fn configure_stream_sets_io_timeout() {
+        let (_client, stream) = connected_pair();
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        let config = ServerConfig {
+            io_timeout: Some(Duration::from_secs(3)),
+            ..ServerConfig::default()
+        };
+
+        configure_stream(&stream, &config, &sender, "test");
+
+        assert_eq!(stream.read_timeout().unwrap(), Some(Duration::from_secs(3)));
+        assert_eq!(stream.write_timeout().unwrap(), Some(Duration::from_secs(3)));
+    }
+}
@@ -0,0 +1,130 @@
+//! Conexiones suscriptas con `SUBSCRIBE` a los cambios de una calculadora.
+//! Cada suscripción tiene un hilo propio que le escribe al cliente los `VALUE` que se publican.
+use std::{
+    sync::{
+        Arc, Mutex,
+        mpsc::{Receiver, Sender},
+    },
+    thread,
+};
+
+use distributed_calculator::protocol::{Protocol, write_frame};
+
+use crate::{config::Framing, peer_stream::PushWriter, server_error::ServerError};
+
+/// Una conexión suscripta y el canal por el que recibe los mensajes a enviar.
+struct Subscriber {
+    connection_id: u64,
+    namespace: String,
+    sender: Sender<Protocol>,
+}
+
+/// Registro de suscripciones, compartido entre todas las conexiones.
+/// Clonarlo es barato: solo se clona el `Arc` interno.
+#[derive(Clone, Default)]
+pub struct Subscribers {
+    subscribers: Arc<Mutex<Vec<Subscriber>>>,
+}
+
+impl Subscribers {
+    /// Crea el registro sin suscripciones.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Suscribe la conexión `connection_id` a los cambios de la calculadora `namespace`.
+    /// Si ya estaba suscripta, la suscripción anterior se reemplaza.
+    ///
+    /// #Errores
+    /// `PoisonError` si se envenena el lock del registro.
+    pub fn subscribe(&self, connection_id: u64, namespace: &str, sender: Sender<Protocol>) -> Result<(), ServerError> {
+        let mut subscribers = self.subscribers.lock().map_err(|_| ServerError::PoisonError)?;
+        subscribers.retain(|s| s.connection_id != connection_id);
+        subscribers.push(Subscriber {
+            connection_id,
+            namespace: namespace.to_string(),
+            sender,
+        });
+        Ok(())
+    }
+
+    /// Da de baja la suscripción de `connection_id`, si la tenía.
+    /// Al soltar el canal termina el hilo que le escribía al cliente.
+    ///
+    /// #Errores
+    /// `PoisonError` si se envenena el lock del registro.
+    pub fn unsubscribe(&self, connection_id: u64) -> Result<(), ServerError> {
+        let mut subscribers = self.subscribers.lock().map_err(|_| ServerError::PoisonError)?;
+        subscribers.retain(|s| s.connection_id != connection_id);
+        Ok(())
+    }
+
+    /// Envía `VALUE value` a los suscriptos a `namespace`.
+    /// Descarta las suscripciones cuyo hilo ya terminó.
+    ///
+    /// #Errores
+    /// `PoisonError` si se envenena el lock del registro.
+    pub fn publish(&self, namespace: &str, value: i64) -> Result<(), ServerError> {
+        let mut subscribers = self.subscribers.lock().map_err(|_| ServerError::PoisonError)?;
+        subscribers.retain(|s| s.namespace != namespace || s.sender.send(Protocol::Value(value.to_string())).is_ok());
+        Ok(())
+    }
+}
+
+/// Arranca el hilo que escribe en `writer` cada mensaje que llega por `receiver`, con el
+/// framing de la conexión. Termina cuando se da de baja la suscripción o falla la escritura.
+pub fn spawn_push_thread(mut writer: PushWriter, framing: Framing, receiver: Receiver<Protocol>) {
+    thread::spawn(move || {
+        for protocol in receiver {
+            let bytes = protocol.to_bytes();
+            let message = match framing {
+                Framing::Newline => bytes,
+                Framing::LengthPrefixed => {
+                    let mut frame = Vec::with_capacity(bytes.len() + 4);
+                    if write_frame(&mut frame, bytes.strip_suffix(b"\n").unwrap_or(&bytes)).is_err() {
+                        break;
+                    }
+                    frame
+                }
+            };
+            if writer.write_message(&message).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+
+    use distributed_calculator::protocol::Protocol;
+
+    use crate::subscribers::Subscribers;
+
+    #[test]
+    fn publish_reaches_only_subscribers_of_the_namespace() {
+        let subscribers = Subscribers::new();
+        let (foo_sender, foo) = channel();
+        let (default_sender, default) = channel();
+        subscribers.subscribe(1, "foo", foo_sender).unwrap();
+        subscribers.subscribe(2, "default", default_sender).unwrap();
+
+        subscribers.publish("foo", 5).unwrap();
+
+        assert_eq!(foo.try_recv().unwrap(), Protocol::Value("5".to_string()));
+        assert!(default.try_recv().is_err());
+    }
+
+    #[test]
+    fn unsubscribe_closes_the_channel() {
+        let subscribers = Subscribers::new();
+        let (sender, receiver) = channel();
+        subscribers.subscribe(1, "default", sender).unwrap();
+
+        subscribers.unsubscribe(1).unwrap();
+        subscribers.publish("default", 1).unwrap();
+
+        assert!(receiver.recv().is_err());
+    }
+}
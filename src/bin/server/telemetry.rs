@@ -0,0 +1,159 @@
+//! Trazas de OpenTelemetry para las conexiones de datos (feature `otel`).
+//! Cada conexión abre un span raíz `calc.connection` y cada operación o `GET` un span hijo.
+//! Las trazas se exportan por OTLP/HTTP al endpoint de `OTEL_EXPORTER_OTLP_ENDPOINT`.
+use opentelemetry::{
+    Context, KeyValue, global,
+    trace::{Span, Status, TraceContextExt, Tracer},
+};
+use opentelemetry_sdk::trace::SdkTracerProvider;
+
+use distributed_calculator::protocol::Protocol;
+
+use crate::server_error::ServerError;
+
+/// Nombre con el que el servidor pide su tracer al proveedor global.
+const TRACER_NAME: &str = "distributed_calculator";
+
+/// Crea el exportador OTLP y lo instala como proveedor global de trazas.
+/// Devuelve el proveedor para poder cerrarlo (y vaciar los spans pendientes) al terminar.
+///
+/// #Errores
+/// `InvalidConfig` si no se puede crear el exportador.
+pub fn init_tracer() -> Result<SdkTracerProvider, ServerError> {
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .build()
+        .map_err(|e| ServerError::InvalidConfig(format!("otlp exporter: {}", e)))?;
+    let provider = SdkTracerProvider::builder().with_batch_exporter(exporter).build();
+    global::set_tracer_provider(provider.clone());
+    Ok(provider)
+}
+
+/// Span raíz de una conexión. Al descartarlo se cierra el span.
+pub struct ConnectionTrace {
+    context: Context,
+}
+
+impl ConnectionTrace {
+    /// Abre el span `calc.connection` para el cliente con dirección `peer_addr`.
+    pub fn start(peer_addr: &str) -> Self {
+        let tracer = global::tracer(TRACER_NAME);
+        let span = tracer
+            .span_builder("calc.connection")
+            .with_attributes([KeyValue::new("net.peer.address", peer_addr.to_string())])
+            .start(&tracer);
+        Self {
+            context: Context::current_with_span(span),
+        }
+    }
+
+    /// Abre el span hijo que corresponde a un mensaje, si se traza:
+    /// `calc.operation` (con `calc.op` y `calc.operand`) para `OP` y `calc.get` para `GET`.
+    pub fn message_span(&self, protocol: &Protocol) -> Option<global::BoxedSpan> {
+        let tracer = global::tracer(TRACER_NAME);
+        let builder = match protocol {
+            Protocol::Operation(args) => {
+                let (operator, operand) = args.split_once(' ').unwrap_or((args.as_str(), ""));
+                tracer.span_builder("calc.operation").with_attributes([
+                    KeyValue::new("calc.op", operator.to_string()),
+                    KeyValue::new("calc.operand", operand.trim().to_string()),
+                ])
+            }
+            Protocol::Get => tracer.span_builder("calc.get"),
+            _ => return None,
+        };
+        Some(builder.start_with_context(&tracer, &self.context))
+    }
+}
+
+/// Completa un span de mensaje con la respuesta que se va a enviar y lo cierra.
+/// Un `VALUE` se registra como `calc.result`; un `ERROR`, como estado de error del span.
+pub fn finish_message_span(mut span: global::BoxedSpan, response: &[u8]) {
+    match Protocol::from_bytes(response.strip_suffix(b"\n").unwrap_or(response)) {
+        Ok(Protocol::Value(value)) => span.set_attribute(KeyValue::new("calc.result", value)),
+        Ok(Protocol::ErrorOperation(message)) => span.set_status(Status::error(message)),
+        _ => {}
+    }
+    span.end();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use opentelemetry::global;
+    use opentelemetry_sdk::{
+        error::OTelSdkResult,
+        trace::{SdkTracerProvider, SpanData, SpanExporter},
+    };
+
+    use crate::{
+        calculator::Calculator, config::ServerConfig, handle_client::handle_connection, logger::{DEFAULT_LOG_CAPACITY, log_channel},
+        peer_stream::PeerStream, server_state::ServerState, shared_calculator::SharedCalculator,
+    };
+
+    /// Guarda en memoria los spans exportados.
+    #[derive(Clone, Debug, Default)]
+    struct RecordingExporter(Arc<Mutex<Vec<SpanData>>>);
+
+    impl SpanExporter for RecordingExporter {
+        async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+            self.0.lock().unwrap().extend(batch);
+            Ok(())
+        }
+    }
+
+    /// Stream que lee de un buffer fijo y descarta lo que se le escribe.
+    struct ScriptedStream(std::io::Cursor<Vec<u8>>);
+
+    impl std::io::Read for ScriptedStream {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl std::io::Write for ScriptedStream {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn handle_connection_creates_spans() {
+        let exporter = RecordingExporter::default();
+        let provider = SdkTracerProvider::builder().with_simple_exporter(exporter.clone()).build();
+        global::set_tracer_provider(provider.clone());
+
+        let stream = ScriptedStream(std::io::Cursor::new(b"OP + 5\nGET\n".to_vec()));
+        let calculator = SharedCalculator::new(Calculator::new());
+        let (sender, _receiver) = log_channel(DEFAULT_LOG_CAPACITY);
+        handle_connection(
+            PeerStream::new(stream, "10.9.9.9:1234"),
+            ServerState::new(calculator, ServerConfig::default()),
+            sender,
+            0,
+        )
+        .unwrap();
+        provider.force_flush().unwrap();
+
+        let spans = exporter.0.lock().unwrap();
+        let connection = spans
+            .iter()
+            .find(|span| {
+                span.name == "calc.connection"
+                    && span.attributes.iter().any(|kv| kv.value.as_str() == "10.9.9.9:1234")
+            })
+            .expect("missing connection span");
+        let children: Vec<&SpanData> = spans
+            .iter()
+            .filter(|span| span.parent_span_id == connection.span_context.span_id())
+            .collect();
+        let names: Vec<&str> = children.iter().map(|span| span.name.as_ref()).collect();
+        assert_eq!(names, vec!["calc.operation", "calc.get"]);
+        assert!(children[1].attributes.iter().any(|kv| kv.key.as_str() == "calc.result" && kv.value.as_str() == "5"));
+    }
+}
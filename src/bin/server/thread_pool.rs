@@ -0,0 +1,69 @@
+//! Pool de hilos de tamaño fijo para atender conexiones.
+//! Si el servidor se configura sin pool, cada conexión se atiende en un hilo propio.
+use std::{
+    sync::{
+        Arc, Mutex,
+        mpsc::{self, Sender},
+    },
+    thread,
+};
+
+/// Tarea a ejecutar por alguno de los hilos del pool.
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// Reparte tareas entre una cantidad fija de hilos.
+/// Las tareas que llegan con todos los hilos ocupados esperan en una cola.
+pub struct ThreadPool {
+    sender: Sender<Job>,
+}
+
+impl ThreadPool {
+    /// Crea el pool con `size` hilos. `size` debe ser mayor a 0.
+    pub fn new(size: usize) -> Self {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..size {
+            let receiver = Arc::clone(&receiver);
+            thread::spawn(move || {
+                loop {
+                    let job = match receiver.lock() {
+                        Ok(receiver) => receiver.recv(),
+                        Err(_) => break,
+                    };
+                    match job {
+                        Ok(job) => job(),
+                        Err(_) => break,
+                    }
+                }
+            });
+        }
+        Self { sender }
+    }
+
+    /// Encola una tarea para que la ejecute el primer hilo libre.
+    pub fn execute<F: FnOnce() + Send + 'static>(&self, job: F) {
+        let _ = self.sender.send(Box::new(job));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+
+    use crate::thread_pool::ThreadPool;
+
+    #[test]
+    fn pool_runs_every_job() {
+        let pool = ThreadPool::new(2);
+        let (sender, receiver) = channel();
+        for i in 0..5 {
+            let sender = sender.clone();
+            pool.execute(move || sender.send(i).unwrap());
+        }
+        drop(sender);
+
+        let mut results: Vec<i32> = receiver.iter().collect();
+        results.sort();
+        assert_eq!(results, vec![0, 1, 2, 3, 4]);
+    }
+}
@@ -0,0 +1,27 @@
+//! Representa los errores que pueden ocurrir al aplicar una operación a la calculadora.
+//!
+/// Cada variante del enum representa un caso especifico por el que la calculadora
+/// rechaza una operación. En ese caso la acumulación no se modifica.
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CalculatorError {
+    ///Error por desplazar más bits de los que tiene la acumulación
+    ShiftOverflow,
+}
+
+impl CalculatorError {
+    /// Devuelve un mensaje de error descriptivo para cada variante del CalculatorError Enum.
+    pub fn message(&self) -> &str {
+        match self {
+            CalculatorError::ShiftOverflow => "shift overflow: shift amount must be at most 63",
+        }
+    }
+}
+
+impl std::fmt::Display for CalculatorError {
+    /// Imprime el error en un formato legible.
+    /// Ejemplo: ERROR "shift overflow: shift amount must be at most 63"
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ERROR \"{}\"", self.message())
+    }
+}
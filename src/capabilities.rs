@@ -0,0 +1,198 @@
+//! Capacidades que se negocian en el handshake `HELLO`.
+//! Viajan como una máscara de bits en hexadecimal (`HELLO 1 d` es AUTH, HISTORY y REGISTERS);
+//! los nombres separados por comas (`AUTH,HISTORY,REGISTERS`) se usan en los mensajes para el usuario.
+use std::{
+    convert::Infallible,
+    fmt,
+    ops::{BitAnd, BitOr},
+    str::FromStr,
+};
+
+use crate::protocol::Protocol;
+
+/// Conjunto de capacidades representado como flags de bits.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ServerCapabilities(u32);
+
+/// Nombre de cada capacidad en el protocolo, en el orden en que se imprimen.
+const NAMES: [(ServerCapabilities, &str); 5] = [
+    (ServerCapabilities::AUTH, "AUTH"),
+    (ServerCapabilities::BATCH, "BATCH"),
+    (ServerCapabilities::HISTORY, "HISTORY"),
+    (ServerCapabilities::REGISTERS, "REGISTERS"),
+    (ServerCapabilities::SUBSCRIBE, "SUBSCRIBE"),
+];
+
+impl ServerCapabilities {
+    /// Autenticación y comandos de administración (`AUTH`, `ADMIN ...`)
+    pub const AUTH: Self = Self(1);
+    /// Envío de varias operaciones en un mismo mensaje
+    pub const BATCH: Self = Self(1 << 1);
+    /// Historial de operaciones (`HISTORY`, `CLEAR_HISTORY`)
+    pub const HISTORY: Self = Self(1 << 2);
+    /// Registros con nombre (`SET_REGISTER`, `GET <nombre>`, `SWAP`)
+    pub const REGISTERS: Self = Self(1 << 3);
+    /// Suscripción a los cambios de la calculadora (`SUBSCRIBE`, `UNSUBSCRIBE`)
+    pub const SUBSCRIBE: Self = Self(1 << 4);
+
+    /// Devuelve el conjunto vacío.
+    pub fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Devuelve todas las capacidades conocidas.
+    pub fn all() -> Self {
+        NAMES.iter().fold(Self::empty(), |acc, (capability, _)| acc | *capability)
+    }
+
+    /// Crea el conjunto a partir de la máscara que viaja en `HELLO`.
+    /// Los bits que no corresponden a ninguna capacidad conocida se descartan.
+    pub fn from_bits(bits: u32) -> Self {
+        Self(bits) & Self::all()
+    }
+
+    /// Devuelve la máscara de bits que se envía en `HELLO`.
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// Devuelve la capacidad que necesita un mensaje, si necesita alguna.
+    /// Una operación con varios operandos (`OP + 1 2 3`) necesita `BATCH`.
+    pub fn required_by(protocol: &Protocol) -> Option<Self> {
+        match protocol {
+            Protocol::History | Protocol::ClearHistory | Protocol::GetHistory | Protocol::RollbackTo(_) => {
+                Some(Self::HISTORY)
+            }
+            Protocol::SetRegister(_, _)
+            | Protocol::GetRegister(_)
+            | Protocol::Swap(_, _)
+            | Protocol::Rename { .. }
+            | Protocol::ListRegisters
+            | Protocol::MultiGet(_) => Some(Self::REGISTERS),
+            Protocol::Auth(_) | Protocol::ListClients => Some(Self::AUTH),
+            Protocol::Subscribe | Protocol::Unsubscribe => Some(Self::SUBSCRIBE),
+            Protocol::Operation(args) if args.split_whitespace().count() > 2 => Some(Self::BATCH),
+            _ => None,
+        }
+    }
+
+    /// Indica si están todas las capacidades de `other`.
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl FromStr for ServerCapabilities {
+    type Err = Infallible;
+
+    /// Parsea una lista de capacidades como `AUTH,HISTORY` o `caps=AUTH,HISTORY`.
+    /// Los nombres no distinguen mayúsculas y los desconocidos se ignoran, para que un cliente
+    /// viejo pueda hablar con un servidor que anuncia capacidades nuevas.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let list = s.trim().strip_prefix("caps=").unwrap_or(s.trim());
+        let capabilities = list
+            .split(',')
+            .map(str::trim)
+            .filter_map(|name| {
+                NAMES
+                    .iter()
+                    .find(|(_, known)| known.eq_ignore_ascii_case(name))
+                    .map(|(capability, _)| *capability)
+            })
+            .fold(Self::empty(), |acc, capability| acc | capability);
+        Ok(capabilities)
+    }
+}
+
+impl BitOr for ServerCapabilities {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitAnd for ServerCapabilities {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        Self(self.0 & rhs.0)
+    }
+}
+
+impl fmt::Display for ServerCapabilities {
+    /// Imprime las capacidades separadas por comas, con el formato que acepta `from_str`.
+    /// Ejemplo: `AUTH,HISTORY`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = NAMES
+            .iter()
+            .filter(|(capability, _)| self.contains(*capability))
+            .map(|(_, name)| *name)
+            .collect();
+        write!(f, "{}", names.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::{capabilities::ServerCapabilities, protocol::Protocol};
+
+    #[test]
+    fn from_str_parses_every_capability() {
+        let caps = ServerCapabilities::from_str("AUTH,BATCH,HISTORY,REGISTERS").unwrap();
+        assert!(caps.contains(ServerCapabilities::AUTH));
+        assert!(caps.contains(ServerCapabilities::BATCH));
+        assert!(caps.contains(ServerCapabilities::HISTORY));
+        assert!(caps.contains(ServerCapabilities::REGISTERS));
+    }
+
+    #[test]
+    fn from_str_accepts_prefix_case_and_spaces() {
+        let caps = ServerCapabilities::from_str("caps=history, Registers").unwrap();
+        assert_eq!(caps, ServerCapabilities::HISTORY | ServerCapabilities::REGISTERS);
+        assert!(!caps.contains(ServerCapabilities::AUTH));
+    }
+
+    #[test]
+    fn from_str_ignores_unknown_and_empty_names() {
+        assert_eq!(ServerCapabilities::from_str("").unwrap(), ServerCapabilities::empty());
+        assert_eq!(ServerCapabilities::from_str("caps=").unwrap(), ServerCapabilities::empty());
+        assert_eq!(
+            ServerCapabilities::from_str("TELEPORT,AUTH,").unwrap(),
+            ServerCapabilities::AUTH
+        );
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let caps = ServerCapabilities::AUTH | ServerCapabilities::REGISTERS;
+        assert_eq!(caps.to_string(), "AUTH,REGISTERS");
+        assert_eq!(ServerCapabilities::from_str(&caps.to_string()).unwrap(), caps);
+    }
+
+    #[test]
+    fn bits_follow_the_protocol_positions() {
+        assert_eq!(ServerCapabilities::AUTH.bits(), 1);
+        assert_eq!(ServerCapabilities::SUBSCRIBE.bits(), 1 << 4);
+        assert_eq!(ServerCapabilities::all().bits(), 0x1f);
+        assert_eq!(ServerCapabilities::from_bits(0xff), ServerCapabilities::all());
+        assert_eq!(
+            ServerCapabilities::all() & ServerCapabilities::from_bits(0b0101),
+            ServerCapabilities::AUTH | ServerCapabilities::HISTORY
+        );
+    }
+
+    #[test]
+    fn required_by_maps_messages_to_capabilities() {
+        let required = |message: &[u8]| ServerCapabilities::required_by(&Protocol::from_bytes(message).unwrap());
+        assert_eq!(required(b"HISTORY"), Some(ServerCapabilities::HISTORY));
+        assert_eq!(required(b"SWAP A B"), Some(ServerCapabilities::REGISTERS));
+        assert_eq!(required(b"MGET A B"), Some(ServerCapabilities::REGISTERS));
+        assert_eq!(required(b"SUBSCRIBE"), Some(ServerCapabilities::SUBSCRIBE));
+        assert_eq!(required(b"OP + 1 2 3"), Some(ServerCapabilities::BATCH));
+        assert_eq!(required(b"OP + 1"), None);
+        assert_eq!(required(b"GET"), None);
+    }
+}
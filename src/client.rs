@@ -0,0 +1,436 @@
+//! Cliente de la calculadora distribuida para usar desde código.
+//! Mantiene una única conexión con el servidor y expone cada comando del protocolo como un método.
+//!
+//! ```no_run
+//! use distributed_calculator::{client::CalculatorClient, operation::Operation};
+//!
+//! let addr = "127.0.0.1:12345".parse().unwrap();
+//! let mut client = CalculatorClient::connect(addr)?;
+//! client.apply(Operation::Add(10))?;
+//! client.apply(Operation::Mul(3))?;
+//! assert_eq!(client.get()?, 30);
+//! client.reset()?;
+//! assert_eq!(client.get()?, 0);
+//! # Ok::<(), distributed_calculator::error::AppError>(())
+//! ```
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpStream},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    capabilities::ServerCapabilities,
+    client_error::ClientError,
+    error::AppError,
+    operation::Operation,
+    protocol::{PROTOCOL_VERSION, Protocol},
+};
+
+/// Conexión con el servidor de la calculadora.
+/// Por defecto usa un `TcpStream`, pero acepta cualquier stream que implemente `Read` y `Write`.
+/// Los métodos devuelven `AppError`; los errores que se listan en cada uno son variantes de
+/// `ClientError` envueltas en `AppError::Client`, salvo que se indique otra cosa.
+pub struct CalculatorClient<S: Read + Write = TcpStream> {
+    reader: BufReader<S>,
+    /// Capacidades que ofreció el servidor en el `HELLO` con el que abre la conexión, una vez leído
+    offered: Option<ServerCapabilities>,
+    /// Identificador que envía el servidor después de `HELLO`, para retomar la sesión con `RESUME`
+    session_id: Option<String>,
+}
+
+impl CalculatorClient<TcpStream> {
+    /// Se conecta al servidor en `addr`.
+    ///
+    /// #Errores
+    /// 'FailedConnection' si no se puede conectar al servidor.
+    pub fn connect(addr: SocketAddr) -> Result<Self, AppError> {
+        let stream = TcpStream::connect(addr).map_err(ClientError::FailedConnection)?;
+        stream.set_nodelay(true).map_err(ClientError::FailedConnection)?;
+        Ok(Self::from_stream(stream))
+    }
+}
+
+impl<S: Read + Write> CalculatorClient<S> {
+    /// Crea un cliente sobre un stream ya conectado.
+    pub fn from_stream(stream: S) -> Self {
+        Self {
+            reader: BufReader::new(stream),
+            offered: None,
+            session_id: None,
+        }
+    }
+
+    /// Devuelve el identificador de la sesión recibido en el último `HELLO`, si hubo alguno.
+    pub fn session_id(&self) -> Option<&str> {
+        self.session_id.as_deref()
+    }
+
+    /// Responde el `HELLO` con el que el servidor abre la conexión eligiendo, de las capacidades
+    /// que ofreció, todas las que conoce el cliente, y las devuelve. A partir de ahí el servidor
+    /// rechaza los comandos de las demás.
+    /// Guarda el identificador de sesión con el que el servidor responde.
+    ///
+    /// #Errores
+    /// 'ProtocolError' si el servidor no abrió la conexión con `HELLO` o no responde con un `SESSION_ID`.
+    /// 'ServerErrorMessage' si el servidor responde con un error.
+    pub fn hello(&mut self) -> Result<ServerCapabilities, AppError> {
+        let chosen = self.offered_capabilities()? & ServerCapabilities::all();
+        match self.request(&Protocol::Hello { version: PROTOCOL_VERSION, capabilities: chosen.bits() })? {
+            Protocol::SessionId(id) => {
+                self.session_id = Some(id);
+                Ok(chosen)
+            }
+            Protocol::ErrorOperation(message) => Err(ClientError::ServerErrorMessage(message).into()),
+            other => Err(ClientError::ProtocolError {
+                expected: "SESSION_ID".to_string(),
+                got: other.to_string().trim_end().to_string(),
+            }
+            .into()),
+        }
+    }
+
+    /// Devuelve las capacidades que ofreció el servidor en el `HELLO` con el que abre la
+    /// conexión. La primera vez lee ese `HELLO`.
+    ///
+    /// #Errores
+    /// 'ProtocolError' si el primer mensaje del servidor no es un `HELLO`.
+    /// 'FailedConnection' si no se puede leer o el servidor cierra la conexión.
+    pub fn offered_capabilities(&mut self) -> Result<ServerCapabilities, AppError> {
+        if let Some(offered) = self.offered {
+            return Ok(offered);
+        }
+        let offered = match self.read_response()? {
+            Protocol::Hello { capabilities, .. } => ServerCapabilities::from_bits(capabilities),
+            other => {
+                return Err(ClientError::ProtocolError {
+                    expected: "HELLO".to_string(),
+                    got: other.to_string().trim_end().to_string(),
+                }
+                .into());
+            }
+        };
+        self.offered = Some(offered);
+        Ok(offered)
+    }
+
+    /// Envía una operación para que el servidor la aplique a la acumulación.
+    ///
+    /// #Errores
+    /// 'ServerErrorMessage' si el servidor rechaza la operación.
+    /// 'FailedWrite', 'FailedConnection' o 'ErrorMessage' si falla la comunicación.
+    pub fn apply(&mut self, op: Operation) -> Result<(), AppError> {
+        match self.request(&Protocol::Operation(op.to_string()))? {
+            Protocol::Ok => Ok(()),
+            Protocol::ErrorOperation(message) => Err(ClientError::ServerErrorMessage(message).into()),
+            _ => Err(ClientError::ErrorMessage.into()),
+        }
+    }
+
+    /// Pide el valor actual de la acumulación.
+    ///
+    /// #Errores
+    /// 'ServerErrorMessage' si el servidor responde con un error.
+    /// 'ErrorMessage' si la respuesta no es un valor entero.
+    pub fn get(&mut self) -> Result<i64, AppError> {
+        match self.request(&Protocol::Get)? {
+            Protocol::Value(value) => Ok(value.parse().map_err(|_| ClientError::ErrorMessage)?),
+            Protocol::ErrorOperation(message) => Err(ClientError::ServerErrorMessage(message).into()),
+            _ => Err(ClientError::ErrorMessage.into()),
+        }
+    }
+
+    /// Pide los valores de varios registros con nombre en un único mensaje (`MGET`), en lugar de
+    /// un `GET` por registro. Devuelve el valor de cada registro por nombre.
+    ///
+    /// #Errores
+    /// 'ServerErrorMessage' si algún registro no existe o el servidor no tiene registros.
+    /// 'ErrorMessage' si la respuesta no es un `MVALUE` con valores enteros.
+    pub fn get_multiple(&mut self, names: &[&str]) -> Result<HashMap<String, i64>, AppError> {
+        let names = names.iter().map(|name| name.to_string()).collect();
+        match self.request(&Protocol::MultiGet(names))? {
+            Protocol::MultiValue(values) => values
+                .into_iter()
+                .map(|(name, value)| Ok((name, value.parse().map_err(|_| ClientError::ErrorMessage)?)))
+                .collect(),
+            Protocol::ErrorOperation(message) => Err(ClientError::ServerErrorMessage(message).into()),
+            _ => Err(ClientError::ErrorMessage.into()),
+        }
+    }
+
+    /// Pide los nombres de todos los registros, en orden lexicográfico.
+    ///
+    /// #Errores
+    /// 'ServerErrorMessage' si el servidor no tiene registros.
+    /// 'ErrorMessage' si la respuesta no es un `REGISTERS`.
+    pub fn list_registers(&mut self) -> Result<Vec<String>, AppError> {
+        match self.request(&Protocol::ListRegisters)? {
+            Protocol::RegisterList(names) => Ok(names),
+            Protocol::ErrorOperation(message) => Err(ClientError::ServerErrorMessage(message).into()),
+            _ => Err(ClientError::ErrorMessage.into()),
+        }
+    }
+
+    /// Vuelve la acumulación a 0.
+    ///
+    /// #Errores
+    /// Los mismos que [`CalculatorClient::apply`].
+    pub fn reset(&mut self) -> Result<(), AppError> {
+        self.apply(Operation::Set(0))
+    }
+
+    /// Retoma en esta conexión la sesión `id`, recibida con [`CalculatorClient::session_id`] en una
+    /// conexión anterior: recupera la calculadora elegida, la transacción abierta y las capacidades
+    /// acordadas.
+    ///
+    /// #Errores
+    /// 'ServerErrorMessage' si la sesión no existe o venció.
+    /// 'FailedWrite', 'FailedConnection' o 'ErrorMessage' si falla la comunicación.
+    pub fn resume(&mut self, id: &str) -> Result<(), AppError> {
+        match self.request(&Protocol::Resume(id.to_string()))? {
+            Protocol::Ok => {
+                self.session_id = Some(id.to_string());
+                Ok(())
+            }
+            Protocol::ErrorOperation(message) => Err(ClientError::ServerErrorMessage(message).into()),
+            _ => Err(ClientError::ErrorMessage.into()),
+        }
+    }
+
+    /// Mide el tiempo de ida y vuelta de un `ECHO`, que el servidor responde sin tocar la
+    /// calculadora. Sirve como latencia base para comparar con la de las operaciones.
+    /// El texto enviado es la hora actual en nanosegundos, para reconocer su respuesta.
+    ///
+    /// #Errores
+    /// 'ServerErrorMessage' si el servidor no entiende `ECHO`.
+    /// 'ProtocolError' si la respuesta no es el `ECHO_REPLY` del texto enviado.
+    /// 'FailedWrite' o 'FailedConnection' si falla la comunicación.
+    pub fn ping_latency(&mut self) -> Result<Duration, AppError> {
+        let payload = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos()
+            .to_string();
+        let started = Instant::now();
+        let response = self.request(&Protocol::Echo(payload.clone()))?;
+        let elapsed = started.elapsed();
+        match response {
+            Protocol::EchoReply(reply) if reply == payload => Ok(elapsed),
+            Protocol::ErrorOperation(message) => Err(ClientError::ServerErrorMessage(message).into()),
+            other => Err(ClientError::ProtocolError {
+                expected: format!("ECHO_REPLY {}", payload),
+                got: other.to_string().trim_end().to_string(),
+            }
+            .into()),
+        }
+    }
+
+    /// Envía un mensaje y espera la línea de respuesta del servidor. Si todavía no se leyó el
+    /// `HELLO` con el que el servidor abre la conexión, lo lee antes de la respuesta.
+    ///
+    /// #Errores
+    /// 'FailedWrite' si no se puede enviar el mensaje.
+    /// 'ProtocolError' si el servidor no abrió la conexión con `HELLO`.
+    /// 'FailedConnection' si no se puede leer la respuesta o el servidor cierra la conexión.
+    /// `AppError::Protocol` si la respuesta no es un mensaje válido del protocolo.
+    fn request(&mut self, protocol: &Protocol) -> Result<Protocol, AppError> {
+        let stream = self.reader.get_mut();
+        stream
+            .write_all(&protocol.to_bytes())
+            .map_err(ClientError::FailedWrite)?;
+        stream.flush().map_err(ClientError::FailedWrite)?;
+        self.offered_capabilities()?;
+        self.read_response()
+    }
+
+    /// Lee la próxima línea que envía el servidor.
+    ///
+    /// #Errores
+    /// 'FailedConnection' si no se puede leer la línea o el servidor cierra la conexión.
+    /// `AppError::Protocol` si la línea no es un mensaje válido del protocolo.
+    fn read_response(&mut self) -> Result<Protocol, AppError> {
+        let mut response = String::new();
+        match self.reader.read_line(&mut response) {
+            Ok(0) => Err(ClientError::connection_closed().into()),
+            Err(e) => Err(ClientError::FailedConnection(e).into()),
+            Ok(_) => Ok(Protocol::from_bytes(response.trim_end().as_bytes())?),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read, Write};
+
+    use crate::{
+        capabilities::ServerCapabilities, client::CalculatorClient, client_error::ClientError, error::AppError,
+        operation::Operation,
+    };
+
+    struct FakeStream {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl FakeStream {
+        /// Un servidor que abre la conexión ofreciendo todas las capacidades y después responde `responses`.
+        fn new(responses: &str) -> Self {
+            Self::without_greeting(&format!("HELLO 1 1f\n{}", responses))
+        }
+
+        fn without_greeting(responses: &str) -> Self {
+            Self {
+                input: Cursor::new(responses.as_bytes().to_vec()),
+                output: Vec::new(),
+            }
+        }
+    }
+
+    impl Read for FakeStream {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for FakeStream {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn apply_get_and_reset_send_expected_messages() {
+        let mut stream = FakeStream::new("OK\nVALUE 10\nOK\n");
+        let mut client = CalculatorClient::from_stream(&mut stream);
+
+        client.apply(Operation::Add(10)).unwrap();
+        assert_eq!(client.get().unwrap(), 10);
+        client.reset().unwrap();
+
+        assert_eq!(String::from_utf8(stream.output).unwrap(), "OP + 10\nGET\nOP = 0\n");
+    }
+
+    #[test]
+    fn apply_returns_server_error() {
+        let mut stream = FakeStream::new("ERROR \"division by zero\"\n");
+        let mut client = CalculatorClient::from_stream(&mut stream);
+
+        let result = client.apply(Operation::Div(0));
+
+        assert!(
+            matches!(result, Err(AppError::Client(ClientError::ServerErrorMessage(m))) if m.contains("division by zero"))
+        );
+    }
+
+    #[test]
+    fn get_multiple_reads_every_register_in_one_round_trip() {
+        let mut stream = FakeStream::new("MVALUE A=10 B=-2\n");
+        let mut client = CalculatorClient::from_stream(&mut stream);
+
+        let values = client.get_multiple(&["A", "B"]).unwrap();
+
+        assert_eq!(values.len(), 2);
+        assert_eq!((values["A"], values["B"]), (10, -2));
+        assert_eq!(String::from_utf8(stream.output).unwrap(), "MGET A B\n");
+    }
+
+    #[test]
+    fn get_multiple_returns_unknown_registers_as_server_errors() {
+        let mut client = CalculatorClient::from_stream(FakeStream::new("ERROR \"unknown register: C\"\n"));
+
+        let result = client.get_multiple(&["A", "C"]);
+
+        assert!(
+            matches!(result, Err(AppError::Client(ClientError::ServerErrorMessage(m))) if m == "unknown register: C")
+        );
+    }
+
+    #[test]
+    fn list_registers_reads_the_names() {
+        let mut stream = FakeStream::new("REGISTERS\nREGISTERS A total\n");
+        let mut client = CalculatorClient::from_stream(&mut stream);
+
+        assert!(client.list_registers().unwrap().is_empty());
+        assert_eq!(client.list_registers().unwrap(), vec!["A", "total"]);
+        assert_eq!(String::from_utf8(stream.output).unwrap(), "LIST_REGISTERS\nLIST_REGISTERS\n");
+    }
+
+    #[test]
+    fn get_fails_when_server_closes_connection() {
+        let mut client = CalculatorClient::from_stream(FakeStream::new(""));
+
+        assert!(matches!(client.get(), Err(AppError::Client(ClientError::FailedConnection(_)))));
+    }
+
+    #[test]
+    fn hello_answers_with_the_capabilities_offered_by_the_server() {
+        let mut stream = FakeStream::without_greeting("HELLO 1 5\nSESSION_ID 3fa2\n");
+        let mut client = CalculatorClient::from_stream(&mut stream);
+
+        let caps = client.hello().unwrap();
+
+        assert_eq!(caps, ServerCapabilities::AUTH | ServerCapabilities::HISTORY);
+        assert_eq!(client.offered_capabilities().unwrap(), caps);
+        assert_eq!(client.session_id(), Some("3fa2"));
+        assert_eq!(String::from_utf8(stream.output).unwrap(), "HELLO 1 5\n");
+    }
+
+    #[test]
+    fn requests_fail_when_the_server_does_not_open_with_hello() {
+        let mut client = CalculatorClient::from_stream(FakeStream::without_greeting("VALUE 1\n"));
+
+        let result = client.get();
+
+        assert!(matches!(result, Err(AppError::Client(ClientError::ProtocolError { expected, got })) if expected == "HELLO" && got == "VALUE 1"));
+    }
+
+    #[test]
+    fn resume_keeps_the_session_id_only_when_accepted() {
+        let mut stream = FakeStream::new("ERROR \"unknown session\"\nOK\n");
+        let mut client = CalculatorClient::from_stream(&mut stream);
+
+        assert!(matches!(client.resume("old"), Err(AppError::Client(ClientError::ServerErrorMessage(_)))));
+        assert_eq!(client.session_id(), None);
+        client.resume("3fa2").unwrap();
+        assert_eq!(client.session_id(), Some("3fa2"));
+
+        assert_eq!(String::from_utf8(stream.output).unwrap(), "RESUME old\nRESUME 3fa2\n");
+    }
+
+    #[test]
+    fn ping_latency_sends_echo_and_checks_the_reply() {
+        struct Echo(Vec<u8>);
+
+        impl Read for Echo {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let reply = String::from_utf8(std::mem::take(&mut self.0)).unwrap().replacen("ECHO", "ECHO_REPLY", 1);
+                buf[..reply.len()].copy_from_slice(reply.as_bytes());
+                Ok(reply.len())
+            }
+        }
+
+        impl Write for Echo {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.write(buf)
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut client = CalculatorClient::from_stream(Echo(b"HELLO 1 1f\n".to_vec()));
+        client.ping_latency().unwrap();
+
+        let mut stream = FakeStream::new("ECHO_REPLY 1\n");
+        let mut client = CalculatorClient::from_stream(&mut stream);
+        let result = client.ping_latency();
+        assert!(matches!(result, Err(AppError::Client(ClientError::ProtocolError { got, .. })) if got == "ECHO_REPLY 1"));
+        assert!(String::from_utf8(stream.output).unwrap().starts_with("ECHO "));
+    }
+}
@@ -0,0 +1,176 @@
+//! Representa los distintos errores que pueden ocurrir en el programa.
+//!
+use std::{io, time::Duration};
+
+/// Cada variante del enum representa un caso de especifico de error que puede
+/// ocurrir durante la ejecución.
+
+#[derive(Debug)]
+
+pub enum ClientError {
+    ///Error por falta de un argumento
+    MissingArgument,
+    ///Error por argumento invalido
+    InvalidArgument,
+    ///Error al conectar con el servidor o al leer su respuesta, con el error de E/S original
+    FailedConnection(io::Error),
+    ///Error al leer
+    FailToReadLine,
+    ///Error al escribir, con el error de E/S original
+    FailedWrite(io::Error),
+    ///Error al recibir un mensaje incorrectamente del servidor
+    ErrorMessage,
+    ///Mensaje de error recibido del servidor
+    ServerErrorMessage(String),
+    ///El usuario interrumpió el procesamiento con Ctrl-C
+    Interrupted,
+    ///El servidor no respondió dentro del tiempo configurado (`after`)
+    Timeout { after: Duration },
+    ///El servidor respondió con otro mensaje (`got`) en lugar del esperado (`expected`)
+    ProtocolError { expected: String, got: String },
+    ///El servidor respondió con un error a algunas líneas del archivo de entrada
+    LineErrors(Vec<LineError>),
+}
+
+/// Línea del archivo de entrada a la que el servidor respondió con un error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineError {
+    /// Número de línea en el archivo, desde 1
+    pub line: usize,
+    /// Mensaje enviado por esa línea, sin el salto de línea final
+    pub content: String,
+    /// Mensaje de error del servidor
+    pub error: String,
+}
+
+impl std::fmt::Display for LineError {
+    /// Ejemplo: Line 42: ERROR "division by zero"
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Line {}: ERROR \"{}\"", self.line, self.error)
+    }
+}
+
+impl ClientError {
+    /// Error que se usa cuando el servidor cierra la conexión sin responder.
+    pub fn connection_closed() -> ClientError {
+        ClientError::FailedConnection(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed by the server"))
+    }
+
+    /// Devuelve un mensaje de error descriptivo para cada variante del ClientError Enum.
+    pub fn message(&self) -> &str {
+        match self {
+            ClientError::MissingArgument => "A required argument is missing.",
+            ClientError::InvalidArgument => "An argument provided is invalid.",
+            ClientError::FailedConnection(_) => "Incoming connection failed.",
+            ClientError::FailToReadLine => "Failed to read a line from the input.",
+            ClientError::FailedWrite(_) => "Failed to write to the server.",
+            ClientError::ErrorMessage => "Received a message incorrectly from the server.",
+            ClientError::ServerErrorMessage(msg) => msg,
+            ClientError::Interrupted => "Interrupted by the user.",
+            ClientError::Timeout { .. } => "Timed out waiting for the server.",
+            ClientError::ProtocolError { .. } => "Unexpected response from the server.",
+            ClientError::LineErrors(_) => "The server rejected some lines of the input.",
+        }
+    }
+
+    /// Convierte un `FailedConnection` o `FailedWrite` causado por vencerse el tiempo de espera
+    /// del socket en `Timeout` con el tiempo configurado. Según la plataforma, el sistema
+    /// operativo informa el vencimiento como `TimedOut` o como `WouldBlock`.
+    /// Sin `timeout` configurado, o para cualquier otro error, lo devuelve sin cambios.
+    pub fn with_timeout(self, timeout: Option<Duration>) -> ClientError {
+        match (&self, timeout) {
+            (ClientError::FailedConnection(e) | ClientError::FailedWrite(e), Some(after))
+                if matches!(e.kind(), io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock) =>
+            {
+                ClientError::Timeout { after }
+            }
+            _ => self,
+        }
+    }
+}
+
+impl std::fmt::Display for ClientError {
+    /// Imprime el error en un formato legible. Los errores de E/S incluyen su tipo.
+    /// Ejemplo: ERROR "A required argument is missing."
+    /// Ejemplo: ERROR "Incoming connection failed. (connection refused)"
+    /// Ejemplo: ERROR "timed out after 5s waiting for the server"
+    /// Ejemplo: ERROR "protocol error: expected VALUE, got OK"
+    /// Ejemplo: ERROR "server errors in 2 lines, first at line 42"
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClientError::LineErrors(errors) if !errors.is_empty() => {
+                write!(f, "ERROR \"server errors in {} lines, first at line {}\"", errors.len(), errors[0].line)
+            }
+            ClientError::ProtocolError { expected, got } => {
+                write!(f, "ERROR \"protocol error: expected {}, got {}\"", expected, got)
+            }
+            ClientError::Timeout { after } => write!(f, "ERROR \"timed out after {:?} waiting for the server\"", after),
+            ClientError::FailedConnection(e) | ClientError::FailedWrite(e) => {
+                write!(f, "ERROR \"{} ({})\"", self.message(), e.kind())
+            }
+            _ => write!(f, "ERROR \"{}\"", self.message()),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {
+    /// Devuelve el error de E/S que causó `FailedConnection` o `FailedWrite`.
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ClientError::FailedConnection(e) | ClientError::FailedWrite(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{error::Error, io, time::Duration};
+
+    use crate::client_error::{ClientError, LineError};
+
+    #[test]
+    fn io_errors_keep_their_kind_and_source() {
+        let error = ClientError::FailedConnection(io::Error::from(io::ErrorKind::ConnectionRefused));
+
+        assert_eq!(error.to_string(), "ERROR \"Incoming connection failed. (connection refused)\"");
+        let source = error.source().and_then(|source| source.downcast_ref::<io::Error>()).unwrap();
+        assert_eq!(source.kind(), io::ErrorKind::ConnectionRefused);
+    }
+
+    #[test]
+    fn line_errors_show_the_line_number() {
+        let error = LineError { line: 42, content: "OP / 0".to_string(), error: "division by zero".to_string() };
+        assert_eq!(error.to_string(), "Line 42: ERROR \"division by zero\"");
+
+        let errors = ClientError::LineErrors(vec![error.clone(), LineError { line: 50, ..error }]);
+        assert_eq!(errors.to_string(), "ERROR \"server errors in 2 lines, first at line 42\"");
+    }
+
+    #[test]
+    fn other_errors_have_no_source() {
+        assert_eq!(ClientError::MissingArgument.to_string(), "ERROR \"A required argument is missing.\"");
+        assert!(ClientError::MissingArgument.source().is_none());
+    }
+
+    #[test]
+    fn with_timeout_converts_only_timed_out_io_errors() {
+        let after = Duration::from_secs(5);
+        let timed_out = ClientError::FailedConnection(io::Error::from(io::ErrorKind::WouldBlock)).with_timeout(Some(after));
+
+        assert!(matches!(timed_out, ClientError::Timeout { after: a } if a == after));
+        assert_eq!(timed_out.to_string(), "ERROR \"timed out after 5s waiting for the server\"");
+        let refused = ClientError::FailedConnection(io::Error::from(io::ErrorKind::ConnectionRefused));
+        assert!(matches!(refused.with_timeout(Some(after)), ClientError::FailedConnection(_)));
+        let unconfigured = ClientError::FailedWrite(io::Error::from(io::ErrorKind::TimedOut)).with_timeout(None);
+        assert!(matches!(unconfigured, ClientError::FailedWrite(_)));
+    }
+
+    #[test]
+    fn protocol_error_shows_expected_and_received_messages() {
+        let error = ClientError::ProtocolError { expected: "VALUE".to_string(), got: "OK".to_string() };
+
+        assert_eq!(error.to_string(), "ERROR \"protocol error: expected VALUE, got OK\"");
+        assert!(error.source().is_none());
+    }
+}
@@ -0,0 +1,87 @@
+//! Error único para quien usa la biblioteca: envuelve los errores del servidor, del cliente y
+//! del protocolo para poder propagar cualquiera de ellos con `?`.
+use crate::{client_error::ClientError, protocol_error::ProtocolError, server_error::ServerError};
+
+/// Cada variante envuelve el error original de una de las partes del programa.
+#[derive(Debug)]
+pub enum AppError {
+    ///Error del servidor
+    Server(ServerError),
+    ///Error del cliente
+    Client(ClientError),
+    ///Mensaje que no es válido en el protocolo
+    Protocol(ProtocolError),
+}
+
+impl From<ServerError> for AppError {
+    fn from(e: ServerError) -> Self {
+        AppError::Server(e)
+    }
+}
+
+impl From<ClientError> for AppError {
+    fn from(e: ClientError) -> Self {
+        AppError::Client(e)
+    }
+}
+
+impl From<ProtocolError> for AppError {
+    fn from(e: ProtocolError) -> Self {
+        AppError::Protocol(e)
+    }
+}
+
+impl std::fmt::Display for AppError {
+    /// Imprime el error envuelto tal como lo imprime su propio `Display`.
+    /// Ejemplo: ERROR "Incoming connection failed. (connection refused)"
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AppError::Server(e) => write!(f, "{}", e),
+            AppError::Client(e) => write!(f, "{}", e),
+            AppError::Protocol(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for AppError {
+    /// Devuelve el error envuelto.
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AppError::Server(e) => Some(e),
+            AppError::Client(e) => Some(e),
+            AppError::Protocol(e) => Some(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use crate::{
+        client_error::ClientError, error::AppError, protocol_error::ProtocolError, server_error::ServerError,
+    };
+
+    #[test]
+    fn question_mark_converts_every_error() {
+        fn fails(which: u8) -> Result<(), AppError> {
+            match which {
+                0 => Err(ServerError::PoisonError)?,
+                1 => Err(ClientError::MissingArgument)?,
+                _ => Err(ProtocolError::InvalidUtf8)?,
+            }
+        }
+
+        assert!(matches!(fails(0), Err(AppError::Server(ServerError::PoisonError))));
+        assert!(matches!(fails(1), Err(AppError::Client(ClientError::MissingArgument))));
+        assert!(matches!(fails(2), Err(AppError::Protocol(ProtocolError::InvalidUtf8))));
+    }
+
+    #[test]
+    fn display_and_source_come_from_the_wrapped_error() {
+        let error = AppError::from(ClientError::MissingArgument);
+
+        assert_eq!(error.to_string(), "ERROR \"A required argument is missing.\"");
+        assert_eq!(error.source().unwrap().to_string(), error.to_string());
+    }
+}
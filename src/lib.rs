@@ -1 +1,26 @@
+pub mod calculator_error;
+pub mod capabilities;
+pub mod client;
+pub mod client_error;
+pub mod error;
+pub mod operation;
 pub mod protocol;
+pub mod protocol_error;
+pub mod server_error;
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::{operation::Operation, protocol::Protocol};
+
+    #[test]
+    fn operation_parsed_from_protocol_message() {
+        let protocol = Protocol::from_bytes(b"OP + 10\n").unwrap();
+        let Protocol::Operation(args) = protocol else {
+            panic!("expected an operation, got {}", protocol);
+        };
+
+        assert_eq!(Operation::from_str(&args), Ok(Operation::Add(10)));
+    }
+}
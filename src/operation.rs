@@ -0,0 +1,366 @@
+//! Módulo que define operaciones aritméticas y su parsing desde strings.
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
+
+/// Operaciones soportadas por la calculadora
+pub enum Operation {
+    /// Suma de un valor `i64`
+    Add(i64),
+    /// Resta de un valor `i64`    
+    Sub(i64),
+    /// Multiplicación por un valor `i64`
+    Mul(i64),
+    /// División por un valor `i64` (no permite dividir por cero)
+    Div(i64),
+    /// AND a nivel de bits con un valor `i64`
+    And(i64),
+    /// OR a nivel de bits con un valor `i64`
+    Or(i64),
+    /// XOR a nivel de bits con un valor `i64`
+    Xor(i64),
+    /// Desplazamiento a izquierda de `u32` bits
+    Shl(u32),
+    /// Desplazamiento a derecha de `u32` bits
+    Shr(u32),
+    /// Asigna directamente un valor `i64` a la acumulación
+    Set(i64),
+}
+
+impl FromStr for Operation {
+    type Err = String;
+    /// Convierte un string en una operación
+    ///
+    /// # Formato esperado
+    /// <operaor> <valor> [<valor> ...]
+    ///
+    /// Con varios valores, `+` y `-` aplican la suma de todos ellos y `*` su producto
+    /// (`+ 1 2 3` equivale a `+ 6`). El resto de los operadores acepta un único valor.
+    ///
+    /// El valor es un entero `i64` y puede ser negativo (`- -5` resta -5).
+    /// Acepta los prefijos `0x` (hexadecimal), `0b` (binario) y `0o` (octal).
+    ///
+    /// Operadores válidos: `+`, `-`, `*`, `/`, `&`, `|`, `^`, `<<`, `>>`, `=`.
+    /// También se aceptan los alias `ADD`, `SUB`, `MUL` y `DIV` (sin distinguir mayúsculas).
+    ///     
+    /// # Ejemplo
+    /// let op = Operation::from_str("+ 10").unwrap();
+    ///
+    /// # Errores
+    /// - Si el string no tiene al menos un valor → `"expected 1 or more operands"`.
+    /// - Si un operador de un único valor recibe varios → `"expected 1 operand"`.
+    /// - Si el segundo token no es un número válido → `"parsing error: invalid integer"`.
+    /// - División por cero → `"division by zero"`.
+    /// - Desplazamiento negativo o mayor a `u32` → `"parsing error: invalid shift amount"`.
+    /// - Operador desconocido → `"parsing error: unknown operation"`.
+    ///
+    fn from_str(tokens: &str) -> Result<Self, Self::Err> {
+        let vector: Vec<&str> = tokens.split_whitespace().collect();
+
+        let (operation, operands) = match vector.split_first() {
+            Some((operation, operands)) if !operands.is_empty() => (operation, operands),
+            _ => return Err("expected 1 or more operands".to_string()),
+        };
+
+        let operands = operands
+            .iter()
+            .map(|operand| parse_operand(operand))
+            .collect::<Result<Vec<i64>, String>>()?;
+
+        match normalize_operator(operation) {
+            "+" => Ok(Operation::Add(sum(&operands))),
+            "-" => Ok(Operation::Sub(sum(&operands))),
+            "*" => Ok(Operation::Mul(product(&operands))),
+            "/" => {
+                let operand = single_operand(&operands)?;
+                if operand == 0 {
+                    Err("division by zero".to_string())
+                } else {
+                    Ok(Operation::Div(operand))
+                }
+            }
+            "&" => Ok(Operation::And(single_operand(&operands)?)),
+            "|" => Ok(Operation::Or(single_operand(&operands)?)),
+            "^" => Ok(Operation::Xor(single_operand(&operands)?)),
+            "<<" => Ok(Operation::Shl(parse_shift_amount(single_operand(&operands)?)?)),
+            ">>" => Ok(Operation::Shr(parse_shift_amount(single_operand(&operands)?)?)),
+            "=" => Ok(Operation::Set(single_operand(&operands)?)),
+            _ => Err(format!("parsing error: unknown operation: {}", operation)),
+        }
+    }
+}
+
+/// Suma los operandos de una operación con varios valores.
+fn sum(operands: &[i64]) -> i64 {
+    operands.iter().fold(0, |acc, operand| acc.wrapping_add(*operand))
+}
+
+/// Multiplica los operandos de una operación con varios valores.
+fn product(operands: &[i64]) -> i64 {
+    operands.iter().fold(1, |acc, operand| acc.wrapping_mul(*operand))
+}
+
+/// Devuelve el operando de una operación que admite un único valor.
+///
+/// # Errores
+/// `"expected 1 operand"` si se recibió más de un valor.
+fn single_operand(operands: &[i64]) -> Result<i64, String> {
+    match operands {
+        [operand] => Ok(*operand),
+        _ => Err("expected 1 operand".to_string()),
+    }
+}
+
+/// Traduce los alias `ADD`, `SUB`, `MUL` y `DIV` (sin distinguir mayúsculas) a su símbolo.
+/// Cualquier otro operador se devuelve sin modificar.
+fn normalize_operator(operation: &str) -> &str {
+    match operation.to_ascii_uppercase().as_str() {
+        "ADD" => "+",
+        "SUB" => "-",
+        "MUL" => "*",
+        "DIV" => "/",
+        _ => operation,
+    }
+}
+
+/// Parsea el operando de una operación como un entero `i64`.
+/// Detecta los prefijos `0x`/`0X`, `0b`/`0B` y `0o`/`0O` (después del signo, si lo hay)
+/// y parsea el resto en la base correspondiente. Sin prefijo se parsea en base 10.
+///
+/// # Errores
+/// `"parsing error: invalid integer: <detalle>"` si el operando no es un entero válido.
+pub fn parse_operand(token: &str) -> Result<i64, String> {
+    let (sign, unsigned) = match token.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", token),
+    };
+
+    let (radix, digits) = match unsigned.get(..2) {
+        Some("0x") | Some("0X") => (16, &unsigned[2..]),
+        Some("0b") | Some("0B") => (2, &unsigned[2..]),
+        Some("0o") | Some("0O") => (8, &unsigned[2..]),
+        _ => (10, unsigned),
+    };
+
+    let result = if radix == 10 {
+        token.parse::<i64>()
+    } else {
+        i64::from_str_radix(&format!("{}{}", sign, digits), radix)
+    };
+    result.map_err(|e| format!("parsing error: invalid integer: {}", e))
+}
+
+/// Convierte el operando de un desplazamiento a `u32`.
+/// El límite de 63 bits lo valida la calculadora al aplicar la operación.
+///
+/// # Errores
+/// `"parsing error: invalid shift amount: <detalle>"` si el operando es negativo o no entra en un `u32`.
+fn parse_shift_amount(operand: i64) -> Result<u32, String> {
+    u32::try_from(operand).map_err(|e| format!("parsing error: invalid shift amount: {}", e))
+}
+
+impl fmt::Display for Operation {
+    /// Imprime la operación con el mismo formato que acepta `from_str`.
+    /// Ejemplo: `+ 10`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operation::Add(operand) => write!(f, "+ {}", operand),
+            Operation::Sub(operand) => write!(f, "- {}", operand),
+            Operation::Mul(operand) => write!(f, "* {}", operand),
+            Operation::Div(operand) => write!(f, "/ {}", operand),
+            Operation::And(operand) => write!(f, "& {}", operand),
+            Operation::Or(operand) => write!(f, "| {}", operand),
+            Operation::Xor(operand) => write!(f, "^ {}", operand),
+            Operation::Shl(amount) => write!(f, "<< {}", amount),
+            Operation::Shr(amount) => write!(f, ">> {}", amount),
+            Operation::Set(value) => write!(f, "= {}", value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Operation;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_correct_parsing() {
+        assert_eq!(Operation::from_str("+ 10"), Ok(Operation::Add(10)));
+        assert_eq!(Operation::from_str("- 20"), Ok(Operation::Sub(20)));
+        assert_eq!(Operation::from_str("* 30"), Ok(Operation::Mul(30)));
+        assert_eq!(Operation::from_str("/ 40"), Ok(Operation::Div(40)));
+    }
+
+    #[test]
+    fn test_incorrect_quantity_of_arguments() {
+        assert_eq!(
+            Operation::from_str("+"),
+            Err("expected 1 or more operands".to_string())
+        );
+        assert_eq!(
+            Operation::from_str(""),
+            Err("expected 1 or more operands".to_string())
+        );
+    }
+
+    #[test]
+    fn test_multiple_operands() {
+        assert_eq!(Operation::from_str("+ 1 2 3"), Ok(Operation::Add(6)));
+        assert_eq!(Operation::from_str("- 1 2 3"), Ok(Operation::Sub(6)));
+        assert_eq!(Operation::from_str("* 2 3"), Ok(Operation::Mul(6)));
+        assert_eq!(Operation::from_str("+ 10 20"), Ok(Operation::Add(30)));
+    }
+
+    #[test]
+    fn test_multiple_operands_rejected_for_single_operand_operators() {
+        assert_eq!(
+            Operation::from_str("/ 2 3"),
+            Err("expected 1 operand".to_string())
+        );
+        assert_eq!(
+            Operation::from_str("= 1 2"),
+            Err("expected 1 operand".to_string())
+        );
+    }
+
+    #[test]
+    fn test_not_an_integer_operand() {
+        assert_eq!(
+            Operation::from_str("+ ten"),
+            Err("parsing error: invalid integer: invalid digit found in string".to_string())
+        );
+    }
+
+    #[test]
+    fn test_too_large_integer() {
+        assert_eq!(
+            Operation::from_str("+ 9223372036854775808"),
+            Err(
+                "parsing error: invalid integer: number too large to fit in target type"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_unknown_operation() {
+        assert_eq!(
+            Operation::from_str("% 10"),
+            Err("parsing error: unknown operation: %".to_string())
+        );
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        assert_eq!(
+            Operation::from_str("/ 0"),
+            Err("division by zero".to_string())
+        );
+    }
+
+    #[test]
+    fn test_negative_operands() {
+        assert_eq!(Operation::from_str("+ -10"), Ok(Operation::Add(-10)));
+        assert_eq!(Operation::from_str("- -5"), Ok(Operation::Sub(-5)));
+        assert_eq!(Operation::from_str("* -2"), Ok(Operation::Mul(-2)));
+        assert_eq!(Operation::from_str("/ -1"), Ok(Operation::Div(-1)));
+    }
+
+    #[test]
+    fn test_division_by_negative_zero() {
+        assert_eq!(
+            Operation::from_str("/ -0"),
+            Err("division by zero".to_string())
+        );
+    }
+
+    #[test]
+    fn test_hexadecimal_operand() {
+        assert_eq!(Operation::from_str("+ 0xFF"), Ok(Operation::Add(255)));
+        assert_eq!(Operation::from_str("+ 0Xff"), Ok(Operation::Add(255)));
+        assert_eq!(Operation::from_str("- -0x10"), Ok(Operation::Sub(-16)));
+    }
+
+    #[test]
+    fn test_binary_operand() {
+        assert_eq!(Operation::from_str("+ 0b1010"), Ok(Operation::Add(10)));
+        assert_eq!(Operation::from_str("* 0B11"), Ok(Operation::Mul(3)));
+    }
+
+    #[test]
+    fn test_octal_operand() {
+        assert_eq!(Operation::from_str("+ 0o17"), Ok(Operation::Add(15)));
+        assert_eq!(Operation::from_str("/ 0O10"), Ok(Operation::Div(8)));
+    }
+
+    #[test]
+    fn test_invalid_hexadecimal_digit() {
+        assert_eq!(
+            Operation::from_str("+ 0xFG"),
+            Err("parsing error: invalid integer: invalid digit found in string".to_string())
+        );
+        assert_eq!(
+            Operation::from_str("+ 0b102"),
+            Err("parsing error: invalid integer: invalid digit found in string".to_string())
+        );
+    }
+
+    #[test]
+    fn test_prefixed_zero_is_division_by_zero() {
+        assert_eq!(
+            Operation::from_str("/ 0x0"),
+            Err("division by zero".to_string())
+        );
+    }
+
+    #[test]
+    fn test_bitwise_parsing() {
+        assert_eq!(Operation::from_str("& 0xFF"), Ok(Operation::And(255)));
+        assert_eq!(Operation::from_str("| 0b1010"), Ok(Operation::Or(10)));
+        assert_eq!(Operation::from_str("^ 3"), Ok(Operation::Xor(3)));
+        assert_eq!(Operation::from_str("<< 4"), Ok(Operation::Shl(4)));
+        assert_eq!(Operation::from_str(">> 64"), Ok(Operation::Shr(64)));
+    }
+
+    #[test]
+    fn test_set_parsing() {
+        assert_eq!(Operation::from_str("= 42"), Ok(Operation::Set(42)));
+        assert_eq!(Operation::from_str("= -7"), Ok(Operation::Set(-7)));
+    }
+
+    #[test]
+    fn test_keyword_aliases() {
+        assert_eq!(Operation::from_str("ADD 5"), Ok(Operation::Add(5)));
+        assert_eq!(Operation::from_str("sub 3"), Ok(Operation::Sub(3)));
+        assert_eq!(Operation::from_str("Mul 2"), Ok(Operation::Mul(2)));
+        assert_eq!(Operation::from_str("div 4"), Ok(Operation::Div(4)));
+        assert_eq!(
+            Operation::from_str("DIV 0"),
+            Err("division by zero".to_string())
+        );
+    }
+
+    #[test]
+    fn test_unknown_keyword() {
+        assert_eq!(
+            Operation::from_str("SUBTRACT 3"),
+            Err("parsing error: unknown operation: SUBTRACT".to_string())
+        );
+    }
+
+    #[test]
+    fn test_negative_shift_amount() {
+        assert_eq!(
+            Operation::from_str("<< -1"),
+            Err("parsing error: invalid shift amount: out of range integral type conversion attempted".to_string())
+        );
+    }
+
+    #[test]
+    fn test_display_matches_parsing_format() {
+        assert_eq!(Operation::Add(10).to_string(), "+ 10");
+        assert_eq!(Operation::from_str(&Operation::Div(4).to_string()), Ok(Operation::Div(4)));
+    }
+}
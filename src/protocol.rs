@@ -1,9 +1,27 @@
 //! Representa el protocolo de comunicación con el que cumplen el servidor y el cliente 
 //!
 
-use std::fmt;
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{self, Read, Write},
+};
 
-#[derive(Debug)]
+use crate::protocol_error::ProtocolError;
+
+/// Tamaño máximo del payload de un mensaje con framing por longitud.
+/// Evita reservar memoria sin límite si el prefijo de longitud llega corrupto.
+pub const MAX_FRAME_LEN: u32 = 1024 * 1024;
+
+/// Versión del protocolo que informa el servidor en `VERSION_INFO`.
+/// Se incrementa cuando cambia el formato de algún mensaje.
+pub const PROTOCOL_VERSION: u8 = 1;
+
+/// Prefijo de los mensajes de `ERROR` por fallas transitorias del servidor, que pueden no
+/// repetirse si se reenvía el mensaje.
+pub const TRANSIENT_ERROR_PREFIX: &str = "internal error";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 
 pub enum Protocol {
     ///Operación aritmetica
@@ -16,6 +34,89 @@ pub enum Protocol {
     ErrorOperation(String),
     ///Valor actual 
     Value(String),
+    ///Pide el historial de operaciones aplicadas
+    History,
+    ///Historial de operaciones aplicadas, en orden
+    HistoryValue(Vec<String>),
+    ///Vacía el historial sin modificar el valor actual
+    ClearHistory,
+    ///Pide el historial de operaciones aplicadas, una por línea
+    GetHistory,
+    ///Historial de operaciones aplicadas, en orden: una por línea entre `HISTORY_START` y `HISTORY_END`
+    HistoryResponse(Vec<String>),
+    ///Asigna un valor a un registro con nombre
+    SetRegister(String, String),
+    ///Pide el valor de un registro con nombre
+    GetRegister(String),
+    ///Intercambia los valores de dos registros con nombre
+    Swap(String, String),
+    ///Cambia el nombre de un registro sin modificar su valor
+    Rename { from: String, to: String },
+    ///Pide los nombres de todos los registros
+    ListRegisters,
+    ///Nombres de los registros, ordenados
+    RegisterList(Vec<String>),
+    ///Pide los valores de varios registros con nombre en un único mensaje
+    MultiGet(Vec<String>),
+    ///Valores de los registros pedidos en `MGET`, por nombre
+    MultiValue(HashMap<String, String>),
+    ///Autentica la conexión como administrador
+    Auth(String),
+    ///Comando de administración: pide la lista de clientes conectados
+    ListClients,
+    ///Clientes conectados, uno por elemento
+    ClientList(Vec<String>),
+    ///Pide las estadísticas del servidor
+    Status,
+    ///Estadísticas del servidor como pares `clave=valor`
+    StatusInfo(String),
+    ///Guarda una foto del estado de la calculadora
+    Snapshot,
+    ///Identificador de la foto creada
+    SnapshotId(String),
+    ///Vuelve la calculadora al estado de la foto indicada
+    Restore(String),
+    ///Handshake: versión del protocolo y máscara de capacidades (ver `ServerCapabilities`).
+    ///El servidor abre la conexión con las que ofrece y el cliente responde con las que elige de entre ellas.
+    Hello { version: u8, capabilities: u32 },
+    ///Identificador de la sesión, con el que el servidor responde el `HELLO` del cliente
+    SessionId(String),
+    ///Retoma la sesión con el identificador indicado, recibido en `SESSION_ID` en una conexión anterior
+    Resume(String),
+    ///Pide la versión del servidor
+    Version,
+    ///Versión del servidor como pares `clave=valor`
+    VersionInfo(String),
+    ///Cierra la conexión de datos con el identificador indicado (puerto de administración)
+    Kill(String),
+    ///Detiene el servidor (puerto de administración)
+    Shutdown,
+    ///Cambia la calculadora con nombre sobre la que opera la conexión, creándola si no existe
+    Select(String),
+    ///Copia la acumulación y el historial de una calculadora con nombre en otra
+    Copy { from: String, to: String },
+    ///Pide que el servidor envíe un `VALUE` cada vez que cambia la calculadora seleccionada
+    Subscribe,
+    ///Da de baja la suscripción hecha con `SUBSCRIBE`
+    Unsubscribe,
+    ///Abre una transacción: las operaciones siguientes se encolan hasta `COMMIT` o `ROLLBACK`
+    Begin,
+    ///Aplica juntas las operaciones encoladas desde `BEGIN`
+    Commit,
+    ///Descarta las operaciones encoladas desde `BEGIN`
+    Rollback,
+    ///Vuelve la calculadora al paso indicado del historial (desde 0) y descarta los siguientes
+    RollbackTo(usize),
+    ///No hace nada: mantiene viva la conexión sin tocar la calculadora
+    Noop,
+    ///Espera a que terminen las operaciones en curso de todas las conexiones y frena las nuevas hasta `RELEASE`
+    Drain,
+    ///Termina el `DRAIN` de la conexión y deja seguir a las operaciones frenadas
+    Release,
+    ///Pide que el servidor devuelva el texto tal cual, para medir la latencia de ida y vuelta
+    Echo(String),
+    ///Texto recibido en `ECHO`, sin cambios
+    EchoReply(String),
     ///Se usa para catalogar los mensajes que no son validos
     SynthaxError(String),
 }
@@ -25,36 +126,81 @@ impl Protocol {
     ///
     /// Intenta interpretar los bytes como UTF-8.  
     /// - Si es válido, se parsea el string según las reglas del protocolo (`OP`, `GET`, `OK`, `ERROR`, `VALUE`).  
-    /// - Si no es válido UTF-8, se devuelve `Protocol::SynthaxError` con el mensaje de error.
+    ///   Un mensaje que no corresponde a ningún comando se devuelve como `Protocol::SynthaxError`.
+    /// - El único mensaje de varias líneas es `HISTORY_START\n<op>\n...\nHISTORY_END`, que se
+    ///   devuelve como `Protocol::HistoryResponse` con una operación por línea.
     ///
     /// # Ejemplo
     /// 
     /// let proto = Protocol::from_bytes(b"GET\n");
-    /// assert!(matches!(proto, Protocol::Get));
+    /// assert!(matches!(proto, Ok(Protocol::Get)));
     /// 
-    pub fn from_bytes(bytes: &[u8]) -> Protocol {
-        match std::str::from_utf8(bytes) {
-            Ok(message) => {
-                let vector: Vec<&str> = message.split_whitespace().collect();
-                Protocol::from_str(vector)
-            }
-            Err(message) => Protocol::SynthaxError(message.to_string()),
+    /// #Errores
+    /// `ProtocolError::InvalidUtf8` - Si los bytes no son UTF-8 válido.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Protocol, ProtocolError> {
+        let message = std::str::from_utf8(bytes).map_err(|_| ProtocolError::InvalidUtf8)?;
+        if let Some(ops) = history_lines(message) {
+            return Ok(Protocol::HistoryResponse(ops));
         }
+        let vector: Vec<&str> = message.split_whitespace().collect();
+        Ok(Protocol::from_str(vector))
     }
 
     /// Parser interno: convierte un vector de tokens (`Vec<&str>`) en la variante correspondiente.
     ///
-    /// - `["OP", arg1, arg2]` → `Protocol::Operation` con `"arg1 arg2"`.  
+    /// - `["OP", arg1, arg2, ...]` → `Protocol::Operation` con `"arg1 arg2 ..."`.  
     /// - `["GET"]` → `Protocol::Get`
     /// - `["OK"]` → `Protocol::Ok`
-    /// - `["ERROR", ...]` → `Protocol::ErrorOperation` con los argumentos concatenados.  
+    /// - `["ERROR", ...]` → `Protocol::ErrorOperation` con los argumentos concatenados, sin las comillas
+    ///   que agrega [`to_bytes`].  
     /// - `["VALUE", val]` → `Protocol::Value` con el valor.  
+    /// - `["HISTORY"]` → `Protocol::History`
+    /// - `["HISTORY_VALUE", ...]` → `Protocol::HistoryValue` con las operaciones separadas por `;`.  
+    /// - `["CLEAR_HISTORY"]` → `Protocol::ClearHistory`
+    /// - `["GET_HISTORY"]` → `Protocol::GetHistory`
+    /// - `["SET_REGISTER", name, val]` → `Protocol::SetRegister` con el nombre y el valor.  
+    /// - `["GET", name]` → `Protocol::GetRegister` con el nombre del registro.  
+    /// - `["SWAP", a, b]` → `Protocol::Swap` con los nombres de ambos registros.  
+    /// - `["RENAME", from, to]` → `Protocol::Rename` con el nombre actual y el nuevo.  
+    /// - `["LIST_REGISTERS"]` → `Protocol::ListRegisters`
+    /// - `["REGISTERS", names...]` → `Protocol::RegisterList` con los nombres, que pueden no ser ninguno.  
+    /// - `["MGET", names...]` → `Protocol::MultiGet` con al menos un nombre de registro.  
+    /// - `["MVALUE", pairs...]` → `Protocol::MultiValue` con al menos un par `nombre=valor`.  
+    /// - `["AUTH", token]` → `Protocol::Auth` con el token.  
+    /// - `["ADMIN", "LIST_CLIENTS"]` → `Protocol::ListClients`
+    /// - `["CLIENTS", ...]` → `Protocol::ClientList` con los clientes separados por `;`.  
+    /// - `["STATUS"]` → `Protocol::Status`
+    /// - `["STATUS", ...]` → `Protocol::StatusInfo` con los pares `clave=valor`.  
+    /// - `["LIST_CLIENTS"]` → `Protocol::ListClients` (forma usada en el puerto de administración)
+    /// - `["KILL", id]` → `Protocol::Kill` con el identificador de la conexión.  
+    /// - `["SHUTDOWN"]` → `Protocol::Shutdown`
+    /// - `["SNAPSHOT"]` → `Protocol::Snapshot`
+    /// - `["SNAPSHOT_ID", id]` → `Protocol::SnapshotId` con el identificador de la foto.  
+    /// - `["RESTORE", id]` → `Protocol::Restore` con el identificador de la foto.  
+    /// - `["HELLO", version, caps]` → `Protocol::Hello` con la versión y la máscara de capacidades en hexadecimal.  
+    /// - `["SESSION_ID", id]` → `Protocol::SessionId` con el identificador de la sesión.  
+    /// - `["RESUME", id]` → `Protocol::Resume` con el identificador de la sesión.  
+    /// - `["VERSION"]` → `Protocol::Version`
+    /// - `["VERSION_INFO", ...]` → `Protocol::VersionInfo` con los pares `clave=valor`.  
+    /// - `["SELECT", name]` → `Protocol::Select` con el nombre de la calculadora.  
+    /// - `["COPY", from, to]` → `Protocol::Copy` con los nombres de origen y destino.  
+    /// - `["SUBSCRIBE"]` → `Protocol::Subscribe`
+    /// - `["UNSUBSCRIBE"]` → `Protocol::Unsubscribe`
+    /// - `["BEGIN"]` → `Protocol::Begin`
+    /// - `["COMMIT"]` → `Protocol::Commit`
+    /// - `["ROLLBACK"]` → `Protocol::Rollback`
+    /// - `["ROLLBACK_TO", step]` → `Protocol::RollbackTo` con el paso del historial.  
+    /// - `["NOOP"]` → `Protocol::Noop`
+    /// - `["DRAIN"]` → `Protocol::Drain`
+    /// - `["RELEASE"]` → `Protocol::Release`
+    /// - `["ECHO", ...]` → `Protocol::Echo` con el texto a devolver.  
+    /// - `["ECHO_REPLY", ...]` → `Protocol::EchoReply` con el texto devuelto.  
     /// - Otro caso → `Protocol::SynthaxError` con el string original.
     ///
     /// Este método está marcado como `fn` porque se usa solo desde [`from_bytes`].    
     fn from_str(message: Vec<&str>) -> Protocol {
         match message.as_slice() {
-            ["OP", rest @ ..] if rest.len() == 2 => {
+            ["OP", rest @ ..] if rest.len() >= 2 => {
                 let args = rest.join(" ");
                 Protocol::Operation(args)
             }
@@ -62,9 +208,67 @@ impl Protocol {
             ["OK"] => Protocol::Ok,
             ["ERROR", rest @ ..] => {
                 let args = rest.join(" ");
-                Protocol::ErrorOperation(args)
+                let unquoted = args
+                    .strip_prefix('"')
+                    .and_then(|a| a.strip_suffix('"'))
+                    .map(str::to_string);
+                Protocol::ErrorOperation(unquoted.unwrap_or(args))
             }
             ["VALUE", only] => Protocol::Value((*only).to_string()),
+            ["HISTORY"] => Protocol::History,
+            ["HISTORY_VALUE", rest @ ..] => Protocol::HistoryValue(split_list(rest)),
+            ["CLEAR_HISTORY"] => Protocol::ClearHistory,
+            ["GET_HISTORY"] => Protocol::GetHistory,
+            ["SET_REGISTER", name, value] => {
+                Protocol::SetRegister((*name).to_string(), (*value).to_string())
+            }
+            ["GET", name] => Protocol::GetRegister((*name).to_string()),
+            ["SWAP", a, b] => Protocol::Swap((*a).to_string(), (*b).to_string()),
+            ["RENAME", from, to] => Protocol::Rename { from: (*from).to_string(), to: (*to).to_string() },
+            ["LIST_REGISTERS"] => Protocol::ListRegisters,
+            ["REGISTERS", names @ ..] => Protocol::RegisterList(names.iter().map(|name| (*name).to_string()).collect()),
+            ["MGET", names @ ..] if !names.is_empty() => {
+                Protocol::MultiGet(names.iter().map(|name| (*name).to_string()).collect())
+            }
+            ["MVALUE", rest @ ..] if !rest.is_empty() => match parse_pairs(rest) {
+                Some(values) => Protocol::MultiValue(values),
+                None => Protocol::SynthaxError(message.join(" ")),
+            },
+            ["AUTH", token] => Protocol::Auth((*token).to_string()),
+            ["ADMIN", "LIST_CLIENTS"] => Protocol::ListClients,
+            ["CLIENTS", rest @ ..] => Protocol::ClientList(split_list(rest)),
+            ["STATUS"] => Protocol::Status,
+            ["STATUS", rest @ ..] => Protocol::StatusInfo(rest.join(" ")),
+            ["LIST_CLIENTS"] => Protocol::ListClients,
+            ["KILL", id] => Protocol::Kill((*id).to_string()),
+            ["SHUTDOWN"] => Protocol::Shutdown,
+            ["SNAPSHOT"] => Protocol::Snapshot,
+            ["SNAPSHOT_ID", id] => Protocol::SnapshotId((*id).to_string()),
+            ["RESTORE", id] => Protocol::Restore((*id).to_string()),
+            ["HELLO", version, caps] => match (version.parse(), u32::from_str_radix(caps, 16)) {
+                (Ok(version), Ok(capabilities)) => Protocol::Hello { version, capabilities },
+                _ => Protocol::SynthaxError(message.join(" ")),
+            },
+            ["SESSION_ID", id] => Protocol::SessionId((*id).to_string()),
+            ["RESUME", id] => Protocol::Resume((*id).to_string()),
+            ["VERSION"] => Protocol::Version,
+            ["VERSION_INFO", rest @ ..] if !rest.is_empty() => Protocol::VersionInfo(rest.join(" ")),
+            ["SELECT", name] => Protocol::Select((*name).to_string()),
+            ["COPY", from, to] => Protocol::Copy { from: (*from).to_string(), to: (*to).to_string() },
+            ["SUBSCRIBE"] => Protocol::Subscribe,
+            ["UNSUBSCRIBE"] => Protocol::Unsubscribe,
+            ["BEGIN"] => Protocol::Begin,
+            ["COMMIT"] => Protocol::Commit,
+            ["ROLLBACK"] => Protocol::Rollback,
+            ["ROLLBACK_TO", step] => match step.parse() {
+                Ok(step) => Protocol::RollbackTo(step),
+                Err(_) => Protocol::SynthaxError(message.join(" ")),
+            },
+            ["NOOP"] => Protocol::Noop,
+            ["DRAIN"] => Protocol::Drain,
+            ["RELEASE"] => Protocol::Release,
+            ["ECHO", rest @ ..] if !rest.is_empty() => Protocol::Echo(rest.join(" ")),
+            ["ECHO_REPLY", rest @ ..] if !rest.is_empty() => Protocol::EchoReply(rest.join(" ")),
             _ => Protocol::SynthaxError(message.join(" ")),
         }
     }
@@ -85,9 +289,231 @@ impl Protocol {
             Protocol::Ok => b"OK\n".to_vec(),
             Protocol::ErrorOperation(args) => format!("ERROR \"{}\"\n", args).into_bytes(),
             Protocol::Value(val) => format!("VALUE {}\n", val).into_bytes(),
+            Protocol::History => b"HISTORY\n".to_vec(),
+            Protocol::HistoryValue(ops) => format!("HISTORY_VALUE {}\n", ops.join("; ")).into_bytes(),
+            Protocol::ClearHistory => b"CLEAR_HISTORY\n".to_vec(),
+            Protocol::GetHistory => b"GET_HISTORY\n".to_vec(),
+            Protocol::HistoryResponse(ops) => history_response(ops).into_bytes(),
+            Protocol::SetRegister(name, value) => {
+                format!("SET_REGISTER {} {}\n", name, value).into_bytes()
+            }
+            Protocol::GetRegister(name) => format!("GET {}\n", name).into_bytes(),
+            Protocol::Swap(a, b) => format!("SWAP {} {}\n", a, b).into_bytes(),
+            Protocol::Rename { from, to } => format!("RENAME {} {}\n", from, to).into_bytes(),
+            Protocol::ListRegisters => b"LIST_REGISTERS\n".to_vec(),
+            Protocol::RegisterList(names) => register_list(names).into_bytes(),
+            Protocol::MultiGet(names) => format!("MGET {}\n", names.join(" ")).into_bytes(),
+            Protocol::MultiValue(values) => format!("MVALUE {}\n", join_pairs(values)).into_bytes(),
+            Protocol::Auth(token) => format!("AUTH {}\n", token).into_bytes(),
+            Protocol::ListClients => b"ADMIN LIST_CLIENTS\n".to_vec(),
+            Protocol::ClientList(clients) => format!("CLIENTS {}\n", clients.join("; ")).into_bytes(),
+            Protocol::Status => b"STATUS\n".to_vec(),
+            Protocol::StatusInfo(info) => format!("STATUS {}\n", info).into_bytes(),
+            Protocol::Kill(id) => format!("KILL {}\n", id).into_bytes(),
+            Protocol::Shutdown => b"SHUTDOWN\n".to_vec(),
+            Protocol::Snapshot => b"SNAPSHOT\n".to_vec(),
+            Protocol::SnapshotId(id) => format!("SNAPSHOT_ID {}\n", id).into_bytes(),
+            Protocol::Restore(id) => format!("RESTORE {}\n", id).into_bytes(),
+            Protocol::Hello { version, capabilities } => format!("HELLO {} {:x}\n", version, capabilities).into_bytes(),
+            Protocol::SessionId(id) => format!("SESSION_ID {}\n", id).into_bytes(),
+            Protocol::Resume(id) => format!("RESUME {}\n", id).into_bytes(),
+            Protocol::Version => b"VERSION\n".to_vec(),
+            Protocol::VersionInfo(info) => format!("VERSION_INFO {}\n", info).into_bytes(),
+            Protocol::Select(name) => format!("SELECT {}\n", name).into_bytes(),
+            Protocol::Copy { from, to } => format!("COPY {} {}\n", from, to).into_bytes(),
+            Protocol::Subscribe => b"SUBSCRIBE\n".to_vec(),
+            Protocol::Unsubscribe => b"UNSUBSCRIBE\n".to_vec(),
+            Protocol::Begin => b"BEGIN\n".to_vec(),
+            Protocol::Commit => b"COMMIT\n".to_vec(),
+            Protocol::Rollback => b"ROLLBACK\n".to_vec(),
+            Protocol::RollbackTo(step) => format!("ROLLBACK_TO {}\n", step).into_bytes(),
+            Protocol::Noop => b"NOOP\n".to_vec(),
+            Protocol::Drain => b"DRAIN\n".to_vec(),
+            Protocol::Release => b"RELEASE\n".to_vec(),
+            Protocol::Echo(payload) => format!("ECHO {}\n", payload).into_bytes(),
+            Protocol::EchoReply(payload) => format!("ECHO_REPLY {}\n", payload).into_bytes(),
             Protocol::SynthaxError(val) => val.as_bytes().to_vec(),
         }
     }
+
+    /// Igual que [`Protocol::to_bytes`], pero devuelve `bytes::Bytes` (feature `bytes`).
+    /// Los mensajes sin contenido variable (`GET`, `OK`, ...) no reservan memoria: apuntan a
+    /// un slice estático. El resto se escribe en un `BytesMut` que se congela al final.
+    #[cfg(feature = "bytes")]
+    pub fn to_bytes_copy_free(&self) -> bytes::Bytes {
+        use std::fmt::Write as _;
+
+        let fixed: &'static [u8] = match self {
+            Protocol::Get => b"GET\n",
+            Protocol::Ok => b"OK\n",
+            Protocol::History => b"HISTORY\n",
+            Protocol::ClearHistory => b"CLEAR_HISTORY\n",
+            Protocol::GetHistory => b"GET_HISTORY\n",
+            Protocol::ListClients => b"ADMIN LIST_CLIENTS\n",
+            Protocol::Status => b"STATUS\n",
+            Protocol::Shutdown => b"SHUTDOWN\n",
+            Protocol::Snapshot => b"SNAPSHOT\n",
+            Protocol::Version => b"VERSION\n",
+            Protocol::Subscribe => b"SUBSCRIBE\n",
+            Protocol::Unsubscribe => b"UNSUBSCRIBE\n",
+            Protocol::Begin => b"BEGIN\n",
+            Protocol::Commit => b"COMMIT\n",
+            Protocol::Rollback => b"ROLLBACK\n",
+            Protocol::ListRegisters => b"LIST_REGISTERS\n",
+            Protocol::Noop => b"NOOP\n",
+            Protocol::Drain => b"DRAIN\n",
+            Protocol::Release => b"RELEASE\n",
+            // Las respuestas más frecuentes se arman copiando sus partes, sin pasar por `format!`.
+            Protocol::Operation(args) => return concat_bytes(&[b"OP ", args.as_bytes(), b"\n"]),
+            Protocol::Value(val) => return concat_bytes(&[b"VALUE ", val.as_bytes(), b"\n"]),
+            Protocol::ErrorOperation(args) => return concat_bytes(&[b"ERROR \"", args.as_bytes(), b"\"\n"]),
+            _ => {
+                let mut buf = bytes::BytesMut::with_capacity(32);
+                // Escribir en un `BytesMut` no falla: crece lo que haga falta.
+                let _ = write!(buf, "{}", self);
+                return buf.freeze();
+            }
+        };
+        bytes::Bytes::from_static(fixed)
+    }
+
+    /// Lee un mensaje con framing por longitud: 4 bytes big-endian con el largo del payload
+    /// seguidos del payload. Permite mensajes cuyo contenido incluya saltos de línea.
+    ///
+    /// #Errores
+    /// - `UnexpectedEof` si el stream termina antes de completar el mensaje.
+    /// - `InvalidData` si el largo supera [`MAX_FRAME_LEN`].
+    pub fn read_framed<R: Read>(reader: &mut R) -> io::Result<Protocol> {
+        let payload = read_frame(reader)?;
+        Protocol::from_bytes(&payload).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Escribe el mensaje con framing por longitud. El payload es el mismo que el de
+    /// [`Protocol::to_bytes`] sin el `\n` final.
+    ///
+    /// #Errores
+    /// Si falla la escritura en el stream.
+    pub fn write_framed<W: Write>(writer: &mut W, p: &Protocol) -> io::Result<()> {
+        let bytes = p.to_bytes();
+        write_frame(writer, bytes.strip_suffix(b"\n").unwrap_or(&bytes))
+    }
+}
+
+/// Lee el payload de un frame escrito con [`write_frame`], sin interpretarlo.
+///
+/// #Errores
+/// - `UnexpectedEof` si el stream termina antes de completar el frame.
+/// - `InvalidData` si el largo supera [`MAX_FRAME_LEN`].
+pub fn read_frame<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut len_bytes = [0u8; 4];
+    reader.read_exact(&mut len_bytes)?;
+    let len = u32::from_be_bytes(len_bytes);
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame too large: {} bytes", len),
+        ));
+    }
+    let mut payload = vec![0u8; len as usize];
+    reader.read_exact(&mut payload)?;
+    Ok(payload)
+}
+
+/// Escribe `payload` precedido por su largo como `u32` big-endian.
+///
+/// #Errores
+/// - `InvalidInput` si el payload supera [`MAX_FRAME_LEN`].
+/// - Si falla la escritura en el stream.
+pub fn write_frame<W: Write>(writer: &mut W, payload: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(payload.len())
+        .ok()
+        .filter(|len| *len <= MAX_FRAME_LEN)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "frame too large"))?;
+    writer.write_all(&len.to_be_bytes())?;
+    writer.write_all(payload)
+}
+
+/// Arma el `ERROR` de una falla transitoria: `TRANSIENT_ERROR_PREFIX` seguido de `detail`.
+pub fn transient_error(detail: &str) -> Protocol {
+    Protocol::ErrorOperation(format!("{}: {}", TRANSIENT_ERROR_PREFIX, detail))
+}
+
+/// Indica si un mensaje de `ERROR` corresponde a una falla transitoria, que puede no repetirse
+/// si se reenvía la operación. El protocolo no tiene códigos de error, así que se reconoce por
+/// [`TRANSIENT_ERROR_PREFIX`]; el resto (operaciones desconocidas o mal formadas, divisiones
+/// por cero, desbordes) son permanentes y reenviarlas daría el mismo error.
+pub fn is_transient_error(message: &str) -> bool {
+    message.starts_with(TRANSIENT_ERROR_PREFIX)
+}
+
+/// Copia `parts` una detrás de otra en un único `BytesMut` del tamaño justo y lo congela.
+#[cfg(feature = "bytes")]
+fn concat_bytes(parts: &[&[u8]]) -> bytes::Bytes {
+    let mut buf = bytes::BytesMut::with_capacity(parts.iter().map(|part| part.len()).sum());
+    for part in parts {
+        buf.extend_from_slice(part);
+    }
+    buf.freeze()
+}
+
+/// Arma la respuesta de `LIST_REGISTERS`. Sin registros es solo `REGISTERS`, sin espacio final.
+/// Los nombres no se encierran entre comillas: un nombre no puede tener espacios, porque se
+/// parsea como un único token de `SET_REGISTER`.
+fn register_list(names: &[String]) -> String {
+    if names.is_empty() {
+        "REGISTERS\n".to_string()
+    } else {
+        format!("REGISTERS {}\n", names.join(" "))
+    }
+}
+
+/// Arma la respuesta de `GET_HISTORY`: `HISTORY_START`, una operación por línea y `HISTORY_END`.
+fn history_response(ops: &[String]) -> String {
+    let mut response = String::from("HISTORY_START\n");
+    for op in ops {
+        response.push_str(op);
+        response.push('\n');
+    }
+    response.push_str("HISTORY_END\n");
+    response
+}
+
+/// Si `message` es una respuesta de `GET_HISTORY`, devuelve sus operaciones, una por línea.
+/// Las líneas vacías se descartan.
+fn history_lines(message: &str) -> Option<Vec<String>> {
+    let lines: Vec<&str> = message.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+    match lines.as_slice() {
+        ["HISTORY_START", ops @ .., "HISTORY_END"] => Some(ops.iter().map(|op| op.to_string()).collect()),
+        _ => None,
+    }
+}
+
+/// Parsea pares `nombre=valor`, uno por token. Devuelve `None` si algún token no tiene `=`.
+fn parse_pairs(tokens: &[&str]) -> Option<HashMap<String, String>> {
+    tokens
+        .iter()
+        .map(|token| token.split_once('=').map(|(name, value)| (name.to_string(), value.to_string())))
+        .collect()
+}
+
+/// Une los pares como `nombre=valor` separados por espacios, ordenados por nombre para que el
+/// mismo mapa se envíe siempre igual.
+fn join_pairs(values: &HashMap<String, String>) -> String {
+    let mut pairs: Vec<String> = values.iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+    pairs.sort();
+    pairs.join(" ")
+}
+
+/// Reconstruye una lista de elementos separados por `;` a partir de los tokens del mensaje.
+/// Los elementos vacíos se descartan, de modo que una lista vacía se parsea como `Vec` vacío.
+fn split_list(tokens: &[&str]) -> Vec<String> {
+    tokens
+        .join(" ")
+        .split(';')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
 }
 
 impl fmt::Display for Protocol {
@@ -102,6 +528,47 @@ impl fmt::Display for Protocol {
             Protocol::Ok => "OK\n".to_string(),
             Protocol::ErrorOperation(args) => format!("ERROR \"{}\"\n", args),
             Protocol::Value(val) => format!("VALUE {}\n", val),
+            Protocol::History => "HISTORY\n".to_string(),
+            Protocol::HistoryValue(ops) => format!("HISTORY_VALUE {}\n", ops.join("; ")),
+            Protocol::ClearHistory => "CLEAR_HISTORY\n".to_string(),
+            Protocol::GetHistory => "GET_HISTORY\n".to_string(),
+            Protocol::HistoryResponse(ops) => history_response(ops),
+            Protocol::SetRegister(name, value) => format!("SET_REGISTER {} {}\n", name, value),
+            Protocol::GetRegister(name) => format!("GET {}\n", name),
+            Protocol::Swap(a, b) => format!("SWAP {} {}\n", a, b),
+            Protocol::Rename { from, to } => format!("RENAME {} {}\n", from, to),
+            Protocol::ListRegisters => "LIST_REGISTERS\n".to_string(),
+            Protocol::RegisterList(names) => register_list(names),
+            Protocol::MultiGet(names) => format!("MGET {}\n", names.join(" ")),
+            Protocol::MultiValue(values) => format!("MVALUE {}\n", join_pairs(values)),
+            Protocol::Auth(token) => format!("AUTH {}\n", token),
+            Protocol::ListClients => "ADMIN LIST_CLIENTS\n".to_string(),
+            Protocol::ClientList(clients) => format!("CLIENTS {}\n", clients.join("; ")),
+            Protocol::Status => "STATUS\n".to_string(),
+            Protocol::StatusInfo(info) => format!("STATUS {}\n", info),
+            Protocol::Kill(id) => format!("KILL {}\n", id),
+            Protocol::Shutdown => "SHUTDOWN\n".to_string(),
+            Protocol::Snapshot => "SNAPSHOT\n".to_string(),
+            Protocol::SnapshotId(id) => format!("SNAPSHOT_ID {}\n", id),
+            Protocol::Restore(id) => format!("RESTORE {}\n", id),
+            Protocol::Hello { version, capabilities } => format!("HELLO {} {:x}\n", version, capabilities),
+            Protocol::SessionId(id) => format!("SESSION_ID {}\n", id),
+            Protocol::Resume(id) => format!("RESUME {}\n", id),
+            Protocol::Version => "VERSION\n".to_string(),
+            Protocol::VersionInfo(info) => format!("VERSION_INFO {}\n", info),
+            Protocol::Select(name) => format!("SELECT {}\n", name),
+            Protocol::Copy { from, to } => format!("COPY {} {}\n", from, to),
+            Protocol::Subscribe => "SUBSCRIBE\n".to_string(),
+            Protocol::Unsubscribe => "UNSUBSCRIBE\n".to_string(),
+            Protocol::Begin => "BEGIN\n".to_string(),
+            Protocol::Commit => "COMMIT\n".to_string(),
+            Protocol::Rollback => "ROLLBACK\n".to_string(),
+            Protocol::RollbackTo(step) => format!("ROLLBACK_TO {}\n", step),
+            Protocol::Noop => "NOOP\n".to_string(),
+            Protocol::Drain => "DRAIN\n".to_string(),
+            Protocol::Release => "RELEASE\n".to_string(),
+            Protocol::Echo(payload) => format!("ECHO {}\n", payload),
+            Protocol::EchoReply(payload) => format!("ECHO_REPLY {}\n", payload),
             Protocol::SynthaxError(args) => args.to_string(),
         };
         write!(f, "{}", s)
@@ -109,13 +576,17 @@ impl fmt::Display for Protocol {
 }
 
 #[cfg(test)]
-
 mod tests {
-    use crate::protocol::Protocol;
+    use std::collections::HashMap;
+
+    use crate::{
+        protocol::{Protocol, is_transient_error, transient_error},
+        protocol_error::ProtocolError,
+    };
  
     #[test]
     fn from_bytes_operation() {
-        let proto = Protocol::from_bytes(b"OP ADD 5\n");
+        let proto = Protocol::from_bytes(b"OP ADD 5\n").unwrap();
         match proto {
             Protocol::Operation(args) => assert_eq!(args, "ADD 5"),
             _ =>  assert_eq!(proto.to_string(), "OP ADD 5\n")
@@ -123,18 +594,455 @@ mod tests {
     }
 
     #[test]
-    fn test_from_bytes_invalid_utf8() {
-        let proto = Protocol::from_bytes(&[0xFF, 0xFF, 0xFF]); // bytes no válidos UTF-8
-        match proto {
-            Protocol::SynthaxError(msg) => assert!(msg.contains("invalid utf-8")),
-            _ => assert_eq!(proto.to_string(), "invalid utf-8"),
+    fn from_bytes_operation_with_multiple_operands() {
+        match Protocol::from_bytes(b"OP + 1 2 3\n").unwrap() {
+            Protocol::Operation(args) => assert_eq!(args, "+ 1 2 3"),
+            other => panic!("unexpected protocol: {}", other),
         }
+    }
+
+    #[test]
+    fn test_from_bytes_invalid_utf8() {
+        let result = Protocol::from_bytes(&[0xFF, 0xFF, 0xFF]); // bytes no válidos UTF-8
+        assert_eq!(result, Err(ProtocolError::InvalidUtf8));
+        assert_eq!(ProtocolError::InvalidUtf8.to_string(), "invalid utf-8");
     }    
 
+    #[test]
+    fn test_from_bytes_unknown_command_is_not_an_error() {
+        assert_eq!(Protocol::from_bytes(b"FOO 1\n"), Ok(Protocol::SynthaxError("FOO 1".to_string())));
+        assert_eq!(ProtocolError::UnknownCommand("FOO".to_string()).to_string(), "unknown command: FOO");
+    }
+
     #[test]
     fn test_operation_to_bytes_and_display() {
         let proto = Protocol::Operation("ADD 5".to_string());
         assert_eq!(proto.to_string(), "OP ADD 5\n");
         assert_eq!(proto.to_bytes(), b"OP ADD 5\n".to_vec());
 }
+
+    #[test]
+    fn history_messages_from_bytes() {
+        assert!(matches!(Protocol::from_bytes(b"HISTORY\n").unwrap(), Protocol::History));
+        assert!(matches!(Protocol::from_bytes(b"CLEAR_HISTORY\n").unwrap(), Protocol::ClearHistory));
+    }
+
+    #[test]
+    fn history_value_roundtrip() {
+        let proto = Protocol::HistoryValue(vec!["+ 5".to_string(), "* 3".to_string()]);
+        assert_eq!(proto.to_string(), "HISTORY_VALUE + 5; * 3\n");
+        match Protocol::from_bytes(&proto.to_bytes()).unwrap() {
+            Protocol::HistoryValue(ops) => assert_eq!(ops, vec!["+ 5", "* 3"]),
+            other => panic!("unexpected protocol: {}", other),
+        }
+    }
+
+    #[test]
+    fn empty_history_value_roundtrip() {
+        match Protocol::from_bytes(&Protocol::HistoryValue(Vec::new()).to_bytes()).unwrap() {
+            Protocol::HistoryValue(ops) => assert!(ops.is_empty()),
+            other => panic!("unexpected protocol: {}", other),
+        }
+    }
+
+    #[test]
+    fn register_messages_from_bytes() {
+        match Protocol::from_bytes(b"SWAP A B\n").unwrap() {
+            Protocol::Swap(a, b) => assert_eq!((a.as_str(), b.as_str()), ("A", "B")),
+            other => panic!("unexpected protocol: {}", other),
+        }
+        match Protocol::from_bytes(b"SET_REGISTER A 10\n").unwrap() {
+            Protocol::SetRegister(name, value) => assert_eq!((name.as_str(), value.as_str()), ("A", "10")),
+            other => panic!("unexpected protocol: {}", other),
+        }
+        assert!(matches!(Protocol::from_bytes(b"GET A\n").unwrap(), Protocol::GetRegister(name) if name == "A"));
+        assert_eq!(
+            Protocol::from_bytes(b"RENAME A B\n").unwrap(),
+            Protocol::Rename { from: "A".to_string(), to: "B".to_string() }
+        );
+        assert!(matches!(Protocol::from_bytes(b"RENAME A\n").unwrap(), Protocol::SynthaxError(_)));
+        assert!(matches!(Protocol::from_bytes(b"GET\n").unwrap(), Protocol::Get));
+    }
+
+    #[test]
+    fn get_history_messages_from_bytes() {
+        assert_eq!(Protocol::from_bytes(b"GET_HISTORY\n").unwrap(), Protocol::GetHistory);
+
+        let response = Protocol::HistoryResponse(vec!["+ 5".to_string(), "* 3".to_string()]);
+        assert_eq!(response.to_string(), "HISTORY_START\n+ 5\n* 3\nHISTORY_END\n");
+        assert_eq!(Protocol::from_bytes(&response.to_bytes()).unwrap(), response);
+        assert_eq!(
+            Protocol::from_bytes(b"HISTORY_START\nHISTORY_END\n").unwrap(),
+            Protocol::HistoryResponse(Vec::new())
+        );
+        assert!(matches!(Protocol::from_bytes(b"HISTORY_START\n+ 5\n").unwrap(), Protocol::SynthaxError(_)));
+    }
+
+    #[test]
+    fn list_registers_messages_from_bytes() {
+        assert_eq!(Protocol::from_bytes(b"LIST_REGISTERS\n").unwrap(), Protocol::ListRegisters);
+        assert_eq!(Protocol::RegisterList(Vec::new()).to_bytes(), b"REGISTERS\n".to_vec());
+        assert_eq!(Protocol::from_bytes(b"REGISTERS\n").unwrap(), Protocol::RegisterList(Vec::new()));
+        let names = vec!["A".to_string(), "total".to_string()];
+        assert_eq!(Protocol::RegisterList(names.clone()).to_string(), "REGISTERS A total\n");
+        assert_eq!(Protocol::from_bytes(b"REGISTERS A total\n").unwrap(), Protocol::RegisterList(names));
+    }
+
+    #[test]
+    fn rollback_to_messages_from_bytes() {
+        assert_eq!(Protocol::from_bytes(b"ROLLBACK_TO 3\n").unwrap(), Protocol::RollbackTo(3));
+        assert_eq!(Protocol::RollbackTo(0).to_bytes(), b"ROLLBACK_TO 0\n".to_vec());
+        assert!(matches!(Protocol::from_bytes(b"ROLLBACK_TO -1\n").unwrap(), Protocol::SynthaxError(_)));
+        assert!(matches!(Protocol::from_bytes(b"ROLLBACK_TO\n").unwrap(), Protocol::SynthaxError(_)));
+    }
+
+    #[test]
+    fn multi_get_messages_from_bytes() {
+        let names = vec!["A".to_string(), "B".to_string()];
+        assert_eq!(Protocol::from_bytes(b"MGET A B\n").unwrap(), Protocol::MultiGet(names.clone()));
+        assert_eq!(Protocol::MultiGet(names).to_bytes(), b"MGET A B\n".to_vec());
+        assert!(matches!(Protocol::from_bytes(b"MGET\n").unwrap(), Protocol::SynthaxError(_)));
+
+        let values: HashMap<String, String> = [("B", "-2"), ("A", "10")]
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        assert_eq!(Protocol::from_bytes(b"MVALUE A=10 B=-2\n").unwrap(), Protocol::MultiValue(values.clone()));
+        assert_eq!(Protocol::MultiValue(values).to_string(), "MVALUE A=10 B=-2\n");
+        assert!(matches!(Protocol::from_bytes(b"MVALUE A=1 B\n").unwrap(), Protocol::SynthaxError(_)));
+    }
+
+    #[test]
+    fn admin_messages_from_bytes() {
+        assert!(matches!(Protocol::from_bytes(b"AUTH secret\n").unwrap(), Protocol::Auth(token) if token == "secret"));
+        assert!(matches!(Protocol::from_bytes(b"ADMIN LIST_CLIENTS\n").unwrap(), Protocol::ListClients));
+        match Protocol::from_bytes(b"CLIENTS id=1 ops=0; id=2 ops=3\n").unwrap() {
+            Protocol::ClientList(clients) => assert_eq!(clients, vec!["id=1 ops=0", "id=2 ops=3"]),
+            other => panic!("unexpected protocol: {}", other),
+        }
+    }
+
+    #[test]
+    fn status_messages_from_bytes() {
+        assert!(matches!(Protocol::from_bytes(b"STATUS\n").unwrap(), Protocol::Status));
+        match Protocol::from_bytes(b"STATUS uptime=3 connections=1\n").unwrap() {
+            Protocol::StatusInfo(info) => assert_eq!(info, "uptime=3 connections=1"),
+            other => panic!("unexpected protocol: {}", other),
+        }
+    }
+
+    #[test]
+    fn admin_port_messages_from_bytes() {
+        assert!(matches!(Protocol::from_bytes(b"LIST_CLIENTS\n").unwrap(), Protocol::ListClients));
+        assert!(matches!(Protocol::from_bytes(b"SHUTDOWN\n").unwrap(), Protocol::Shutdown));
+        match Protocol::from_bytes(b"KILL 3\n").unwrap() {
+            Protocol::Kill(id) => assert_eq!(id, "3"),
+            other => panic!("unexpected protocol: {}", other),
+        }
+        assert_eq!(Protocol::Kill("3".to_string()).to_bytes(), b"KILL 3\n".to_vec());
+    }
+
+    #[test]
+    fn version_messages_from_bytes() {
+        assert!(matches!(Protocol::from_bytes(b"VERSION\n").unwrap(), Protocol::Version));
+        match Protocol::from_bytes(b"VERSION_INFO crate=0.1.0 protocol=1\n").unwrap() {
+            Protocol::VersionInfo(info) => assert_eq!(info, "crate=0.1.0 protocol=1"),
+            other => panic!("unexpected protocol: {}", other),
+        }
+        assert_eq!(
+            Protocol::VersionInfo("crate=0.1.0 protocol=1".to_string()).to_bytes(),
+            b"VERSION_INFO crate=0.1.0 protocol=1\n".to_vec()
+        );
+    }
+
+    #[test]
+    fn snapshot_messages_from_bytes() {
+        assert!(matches!(Protocol::from_bytes(b"SNAPSHOT\n").unwrap(), Protocol::Snapshot));
+        match Protocol::from_bytes(b"SNAPSHOT_ID snap-1\n").unwrap() {
+            Protocol::SnapshotId(id) => assert_eq!(id, "snap-1"),
+            other => panic!("unexpected protocol: {}", other),
+        }
+        match Protocol::from_bytes(b"RESTORE snap-1\n").unwrap() {
+            Protocol::Restore(id) => assert_eq!(id, "snap-1"),
+            other => panic!("unexpected protocol: {}", other),
+        }
+    }
+
+    #[test]
+    fn hello_messages_from_bytes() {
+        assert_eq!(Protocol::from_bytes(b"HELLO 1 1f\n").unwrap(), Protocol::Hello { version: 1, capabilities: 0x1f });
+        assert!(matches!(Protocol::from_bytes(b"HELLO\n").unwrap(), Protocol::SynthaxError(_)));
+        assert!(matches!(Protocol::from_bytes(b"HELLO 1\n").unwrap(), Protocol::SynthaxError(_)));
+        assert!(matches!(Protocol::from_bytes(b"HELLO 1 caps=AUTH\n").unwrap(), Protocol::SynthaxError(_)));
+        assert!(matches!(Protocol::from_bytes(b"HELLO 300 1\n").unwrap(), Protocol::SynthaxError(_)));
+        assert_eq!(Protocol::Hello { version: 1, capabilities: 0xd }.to_bytes(), b"HELLO 1 d\n".to_vec());
+    }
+
+    #[test]
+    fn session_messages_from_bytes() {
+        assert_eq!(Protocol::from_bytes(b"SESSION_ID 3fa2\n").unwrap(), Protocol::SessionId("3fa2".to_string()));
+        assert_eq!(Protocol::from_bytes(b"RESUME 3fa2\n").unwrap(), Protocol::Resume("3fa2".to_string()));
+        assert!(matches!(Protocol::from_bytes(b"RESUME\n").unwrap(), Protocol::SynthaxError(_)));
+        assert_eq!(Protocol::Resume("3fa2".to_string()).to_bytes(), b"RESUME 3fa2\n".to_vec());
+    }
+
+    #[test]
+    fn select_messages_from_bytes() {
+        assert!(matches!(Protocol::from_bytes(b"SELECT foo\n").unwrap(), Protocol::Select(name) if name == "foo"));
+        assert!(matches!(Protocol::from_bytes(b"SELECT\n").unwrap(), Protocol::SynthaxError(_)));
+        assert_eq!(Protocol::Select("foo".to_string()).to_bytes(), b"SELECT foo\n".to_vec());
+    }
+
+    #[test]
+    fn subscribe_messages_from_bytes() {
+        assert!(matches!(Protocol::from_bytes(b"SUBSCRIBE\n").unwrap(), Protocol::Subscribe));
+        assert!(matches!(Protocol::from_bytes(b"UNSUBSCRIBE\n").unwrap(), Protocol::Unsubscribe));
+    }
+
+    #[test]
+    fn transaction_messages_from_bytes() {
+        assert!(matches!(Protocol::from_bytes(b"BEGIN\n").unwrap(), Protocol::Begin));
+        assert!(matches!(Protocol::from_bytes(b"COMMIT\n").unwrap(), Protocol::Commit));
+        assert!(matches!(Protocol::from_bytes(b"ROLLBACK\n").unwrap(), Protocol::Rollback));
+    }
+
+    #[test]
+    fn noop_messages_from_bytes() {
+        assert!(matches!(Protocol::from_bytes(b"NOOP\n").unwrap(), Protocol::Noop));
+        assert!(matches!(Protocol::from_bytes(b"NOOP 1\n").unwrap(), Protocol::SynthaxError(_)));
+        assert_eq!(Protocol::Noop.to_bytes(), b"NOOP\n".to_vec());
+    }
+
+    #[test]
+    fn drain_messages_from_bytes() {
+        assert!(matches!(Protocol::from_bytes(b"DRAIN\n").unwrap(), Protocol::Drain));
+        assert!(matches!(Protocol::from_bytes(b"RELEASE\n").unwrap(), Protocol::Release));
+    }
+
+    #[test]
+    fn echo_messages_from_bytes() {
+        assert!(matches!(Protocol::from_bytes(b"ECHO 1234\n").unwrap(), Protocol::Echo(p) if p == "1234"));
+        assert!(matches!(Protocol::from_bytes(b"ECHO_REPLY a b\n").unwrap(), Protocol::EchoReply(p) if p == "a b"));
+        assert!(matches!(Protocol::from_bytes(b"ECHO\n").unwrap(), Protocol::SynthaxError(_)));
+        assert_eq!(Protocol::EchoReply("1234".to_string()).to_bytes(), b"ECHO_REPLY 1234\n".to_vec());
+    }
+
+    #[test]
+    fn copy_messages_from_bytes() {
+        match Protocol::from_bytes(b"COPY A B\n").unwrap() {
+            Protocol::Copy { from, to } => assert_eq!((from.as_str(), to.as_str()), ("A", "B")),
+            other => panic!("unexpected protocol: {}", other),
+        }
+        assert!(matches!(Protocol::from_bytes(b"COPY A\n").unwrap(), Protocol::SynthaxError(_)));
+    }
+
+    fn framed_round_trip(protocol: Protocol) -> String {
+        let mut buf = Vec::new();
+        Protocol::write_framed(&mut buf, &protocol).unwrap();
+        let payload_len = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize;
+        assert_eq!(payload_len, buf.len() - 4);
+        let decoded = Protocol::read_framed(&mut buf.as_slice()).unwrap();
+        assert_eq!(decoded.to_string(), protocol.to_string());
+        decoded.to_string()
+    }
+
+    #[test]
+    fn framed_round_trip_every_variant() {
+        let variants = vec![
+            Protocol::Operation("+ 5".to_string()),
+            Protocol::Get,
+            Protocol::Ok,
+            Protocol::ErrorOperation("division by zero".to_string()),
+            Protocol::Value("42".to_string()),
+            Protocol::History,
+            Protocol::HistoryValue(vec!["+ 1".to_string(), "* 2".to_string()]),
+            Protocol::ClearHistory,
+            Protocol::GetHistory,
+            Protocol::HistoryResponse(vec!["+ 1".to_string(), "* 2".to_string()]),
+            Protocol::SetRegister("A".to_string(), "1".to_string()),
+            Protocol::GetRegister("A".to_string()),
+            Protocol::Swap("A".to_string(), "B".to_string()),
+            Protocol::Rename { from: "A".to_string(), to: "B".to_string() },
+            Protocol::ListRegisters,
+            Protocol::RegisterList(vec!["A".to_string(), "B".to_string()]),
+            Protocol::MultiGet(vec!["A".to_string(), "B".to_string()]),
+            Protocol::MultiValue([("A".to_string(), "1".to_string())].into_iter().collect()),
+            Protocol::Auth("secret".to_string()),
+            Protocol::ListClients,
+            Protocol::ClientList(vec!["id=1 peer=x".to_string()]),
+            Protocol::Status,
+            Protocol::StatusInfo("uptime=1 connections=1".to_string()),
+            Protocol::Snapshot,
+            Protocol::SnapshotId("snap-1".to_string()),
+            Protocol::Restore("snap-1".to_string()),
+            Protocol::Hello { version: 1, capabilities: 0x1 },
+            Protocol::SessionId("3fa2".to_string()),
+            Protocol::Resume("3fa2".to_string()),
+            Protocol::Version,
+            Protocol::VersionInfo("crate=0.1.0 protocol=1".to_string()),
+            Protocol::Kill("1".to_string()),
+            Protocol::Select("foo".to_string()),
+            Protocol::Copy { from: "A".to_string(), to: "B".to_string() },
+            Protocol::Subscribe,
+            Protocol::Unsubscribe,
+            Protocol::Begin,
+            Protocol::Commit,
+            Protocol::Rollback,
+            Protocol::RollbackTo(2),
+            Protocol::Noop,
+            Protocol::Drain,
+            Protocol::Release,
+            Protocol::Echo("1234".to_string()),
+            Protocol::EchoReply("1234".to_string()),
+            Protocol::Shutdown,
+        ];
+        for protocol in variants {
+            framed_round_trip(protocol);
+        }
+    }
+
+    #[test]
+    fn framed_payload_may_contain_newlines() {
+        let mut buf = Vec::new();
+        crate::protocol::write_frame(&mut buf, b"OP +\n5").unwrap();
+        match Protocol::read_framed(&mut buf.as_slice()).unwrap() {
+            Protocol::Operation(args) => assert_eq!(args, "+ 5"),
+            other => panic!("unexpected protocol: {}", other),
+        }
+    }
+
+    #[test]
+    fn read_framed_rejects_truncated_and_oversized_frames() {
+        let truncated = [0u8, 0, 0, 10, b'G'];
+        let err = Protocol::read_framed(&mut truncated.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+
+        let oversized = (crate::protocol::MAX_FRAME_LEN + 1).to_be_bytes();
+        let err = Protocol::read_framed(&mut oversized.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        let invalid_utf8 = [0u8, 0, 0, 2, 0xFF, 0xFF];
+        let err = Protocol::read_framed(&mut invalid_utf8.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "invalid utf-8");
+    }
+
+    #[test]
+    fn transient_errors_are_recognized_by_their_prefix() {
+        let Protocol::ErrorOperation(message) = transient_error("busy") else {
+            panic!("expected an error");
+        };
+        assert_eq!(message, "internal error: busy");
+        assert!(is_transient_error(&message));
+        assert!(!is_transient_error("division by zero"));
+    }
+}
+
+#[cfg(test)]
+mod protocol_proptest {
+    use proptest::{
+        collection::{hash_map, vec},
+        prelude::*,
+    };
+
+    use crate::protocol::Protocol;
+
+    /// Palabra sin espacios, `;` ni comillas: lo que el parser trata como un único token.
+    fn token() -> impl Strategy<Value = String> {
+        "[A-Za-z0-9=+*/_.:,-]{1,8}"
+    }
+
+    /// Varios tokens separados por un único espacio. El parser colapsa los espacios repetidos,
+    /// así que un texto con dos espacios seguidos no sobrevive el viaje de ida y vuelta.
+    fn words(min: usize) -> impl Strategy<Value = String> {
+        vec(token(), min..5).prop_map(|tokens| tokens.join(" "))
+    }
+
+    /// Nombre de registro: un token sin `=`, que separa el nombre del valor en `MVALUE`.
+    fn name() -> impl Strategy<Value = String> {
+        "[A-Za-z0-9_]{1,8}"
+    }
+
+    /// Elementos de una lista separada por `;`; cada uno no vacío.
+    fn list() -> impl Strategy<Value = Vec<String>> {
+        vec(words(1), 0..4)
+    }
+
+    /// Variantes válidas del protocolo. `SynthaxError` queda afuera porque se envía sin cambios:
+    /// su texto puede parsearse como cualquier otra variante.
+    /// `StatusInfo` y `VersionInfo` llevan al menos un par: `STATUS` solo es `Status`.
+    fn protocol() -> impl Strategy<Value = Protocol> {
+        prop_oneof![
+            (token(), words(1)).prop_map(|(op, rest)| Protocol::Operation(format!("{} {}", op, rest))),
+            Just(Protocol::Get),
+            Just(Protocol::Ok),
+            words(0).prop_map(Protocol::ErrorOperation),
+            token().prop_map(Protocol::Value),
+            Just(Protocol::History),
+            list().prop_map(Protocol::HistoryValue),
+            Just(Protocol::ClearHistory),
+            Just(Protocol::GetHistory),
+            vec(words(1), 0..4).prop_map(Protocol::HistoryResponse),
+            (token(), token()).prop_map(|(name, value)| Protocol::SetRegister(name, value)),
+            token().prop_map(Protocol::GetRegister),
+            (token(), token()).prop_map(|(a, b)| Protocol::Swap(a, b)),
+            (token(), token()).prop_map(|(from, to)| Protocol::Rename { from, to }),
+            Just(Protocol::ListRegisters),
+            vec(token(), 0..4).prop_map(Protocol::RegisterList),
+            vec(token(), 1..4).prop_map(Protocol::MultiGet),
+            hash_map(name(), token(), 1..4).prop_map(Protocol::MultiValue),
+            token().prop_map(Protocol::Auth),
+            Just(Protocol::ListClients),
+            list().prop_map(Protocol::ClientList),
+            Just(Protocol::Status),
+            words(1).prop_map(Protocol::StatusInfo),
+            Just(Protocol::Snapshot),
+            token().prop_map(Protocol::SnapshotId),
+            token().prop_map(Protocol::Restore),
+            (any::<u8>(), any::<u32>()).prop_map(|(version, capabilities)| Protocol::Hello { version, capabilities }),
+            token().prop_map(Protocol::SessionId),
+            token().prop_map(Protocol::Resume),
+            Just(Protocol::Version),
+            words(1).prop_map(Protocol::VersionInfo),
+            token().prop_map(Protocol::Kill),
+            token().prop_map(Protocol::Select),
+            (token(), token()).prop_map(|(from, to)| Protocol::Copy { from, to }),
+            Just(Protocol::Subscribe),
+            Just(Protocol::Unsubscribe),
+            Just(Protocol::Begin),
+            Just(Protocol::Commit),
+            Just(Protocol::Rollback),
+            any::<usize>().prop_map(Protocol::RollbackTo),
+            Just(Protocol::Noop),
+            Just(Protocol::Drain),
+            Just(Protocol::Release),
+            words(1).prop_map(Protocol::Echo),
+            words(1).prop_map(Protocol::EchoReply),
+            Just(Protocol::Shutdown),
+        ]
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(10_000))]
+
+        #[cfg(feature = "bytes")]
+        #[test]
+        fn to_bytes_copy_free_matches_to_bytes(p in protocol()) {
+            prop_assert_eq!(p.to_bytes_copy_free(), bytes::Bytes::from(p.to_bytes()));
+        }
+
+        #[test]
+        fn from_bytes_inverts_to_bytes(p in protocol()) {
+            prop_assert_eq!(Protocol::from_bytes(&p.to_bytes()), Ok(p));
+        }
+
+        #[test]
+        fn from_bytes_never_panics(bytes in vec(any::<u8>(), 0..256)) {
+            let result = Protocol::from_bytes(&bytes);
+            prop_assert_eq!(result.is_err(), std::str::from_utf8(&bytes).is_err());
+            if let Ok(protocol) = result {
+                let _ = protocol.to_string();
+            }
+        }
+    }
 }
@@ -0,0 +1,25 @@
+//! Errores al interpretar los bytes recibidos como un mensaje del protocolo.
+//!
+/// Cada variante representa un motivo por el que unos bytes no forman un `Protocol`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProtocolError {
+    ///Los bytes recibidos no son UTF-8 válido
+    InvalidUtf8,
+    ///El mensaje es texto válido pero no corresponde a ningún comando.
+    ///`Protocol::from_bytes` lo sigue devolviendo como `Protocol::SynthaxError`; la variante
+    ///queda para quien necesite tratarlo como un error.
+    UnknownCommand(String),
+}
+
+impl std::fmt::Display for ProtocolError {
+    /// Imprime el error en un formato legible.
+    /// Ejemplo: unknown command: FOO
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProtocolError::InvalidUtf8 => write!(f, "invalid utf-8"),
+            ProtocolError::UnknownCommand(command) => write!(f, "unknown command: {}", command),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
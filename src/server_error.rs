@@ -0,0 +1,162 @@
+//! Representa los distintos errores que pueden ocurrir en el programa.
+//!
+use std::{io, time::Duration};
+
+use crate::{calculator_error::CalculatorError, protocol_error::ProtocolError};
+
+/// Cada variante del enum representa un caso de especifico de error que puede
+/// ocurrir durante la ejecución.
+
+#[derive(Debug)]
+
+pub enum ServerError {
+    ///Error por falta de un argumento
+    MissingArgument,
+    ///Error por argumento invalido   
+    InvalidArgument,
+    ///Error al conectar con el cliente
+    FailedConnection,
+    ///Error al conectar el socket, con el error de E/S original
+    BindFailed(io::Error),
+    ///Error al escribir, con el error de E/S original
+    WriteFailed(io::Error),
+    ///Error de lock envenenando
+    PoisonError,
+    ///Error de lectura, con el error de E/S original
+    ReadFailed(io::Error),
+    ///La calculadora rechazó la operación
+    OperationFailed(CalculatorError),
+    ///Error al leer o escribir el archivo de estado de la calculadora
+    StateFileFailed,
+    ///Configuración del servidor inválida
+    InvalidConfig(String),
+    ///Una lectura o escritura (`operation`) no terminó dentro del tiempo configurado (`after`)
+    Timeout { operation: &'static str, after: Duration },
+    ///Se rechazó una conexión porque ya hay `current` abiertas y el máximo es `limit`
+    TooManyConnections { limit: usize, current: usize },
+}
+
+impl ServerError {
+    /// `ReadFailed` para un mensaje recibido que no es válido en el protocolo.
+    pub fn invalid_message(e: ProtocolError) -> ServerError {
+        ServerError::ReadFailed(io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Devuelve un mensaje de error descriptivo para cada variante del ServerError Enum.
+    pub fn message(&self) -> &str {
+        match self {
+            ServerError::MissingArgument => "A required argument is missing.",
+            ServerError::InvalidArgument => "An argument provided is invalid.",
+            ServerError::FailedConnection => "Incoming connection failed.",
+            ServerError::BindFailed(_) => "Failed to bind to the specified address.",
+            ServerError::WriteFailed(_) => "Failed to write to the stream.",
+            ServerError::PoisonError => "Failed to acquire lock on the calculator -> poisoned.",
+            ServerError::ReadFailed(_) => "Failed to read from the stream.",
+            ServerError::OperationFailed(e) => e.message(),
+            ServerError::StateFileFailed => "Failed to read or write the calculator state file.",
+            ServerError::InvalidConfig(msg) => msg,
+            ServerError::Timeout { .. } => "Timed out waiting for the stream.",
+            ServerError::TooManyConnections { .. } => "max connections reached",
+        }
+    }
+
+    /// Convierte un `ReadFailed` o `WriteFailed` causado por vencerse el tiempo de espera del
+    /// socket en `Timeout` con el tiempo configurado. Según la plataforma, el sistema operativo
+    /// informa el vencimiento como `TimedOut` o como `WouldBlock`.
+    /// Sin `timeout` configurado, o para cualquier otro error, lo devuelve sin cambios.
+    pub fn with_timeout(self, timeout: Option<Duration>) -> ServerError {
+        let Some(after) = timeout else {
+            return self;
+        };
+        match &self {
+            ServerError::ReadFailed(e) if is_timeout(e) => ServerError::Timeout { operation: "read", after },
+            ServerError::WriteFailed(e) if is_timeout(e) => ServerError::Timeout { operation: "write", after },
+            _ => self,
+        }
+    }
+}
+
+/// Indica si el error de E/S corresponde a un tiempo de espera vencido.
+fn is_timeout(e: &io::Error) -> bool {
+    matches!(e.kind(), io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock)
+}
+
+impl std::fmt::Display for ServerError {
+    /// Imprime el error en un formato legible. Los errores de E/S incluyen su tipo.
+    /// Ejemplo: ERROR "A required argument is missing."
+    /// Ejemplo: ERROR "Failed to bind to the specified address. (address in use)"
+    /// Ejemplo: ERROR "timed out after 5s waiting for read"
+    /// Ejemplo: ERROR "max connections reached (2 open, limit 2)"
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ServerError::TooManyConnections { limit, current } => {
+                write!(f, "ERROR \"{} ({} open, limit {})\"", self.message(), current, limit)
+            }
+            ServerError::Timeout { operation, after } => {
+                write!(f, "ERROR \"timed out after {:?} waiting for {}\"", after, operation)
+            }
+            ServerError::BindFailed(e) | ServerError::WriteFailed(e) | ServerError::ReadFailed(e) => {
+                write!(f, "ERROR \"{} ({})\"", self.message(), e.kind())
+            }
+            _ => write!(f, "ERROR \"{}\"", self.message()),
+        }
+    }
+}
+
+impl std::error::Error for ServerError {
+    /// Devuelve el error de E/S de `BindFailed`, `WriteFailed` o `ReadFailed`.
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ServerError::BindFailed(e) | ServerError::WriteFailed(e) | ServerError::ReadFailed(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{error::Error, io, time::Duration};
+
+    use crate::{protocol_error::ProtocolError, server_error::ServerError};
+
+    #[test]
+    fn io_errors_keep_their_kind_and_source() {
+        let error = ServerError::BindFailed(io::Error::from(io::ErrorKind::AddrInUse));
+
+        assert_eq!(error.to_string(), "ERROR \"Failed to bind to the specified address. (address in use)\"");
+        let source = error.source().and_then(|source| source.downcast_ref::<io::Error>()).unwrap();
+        assert_eq!(source.kind(), io::ErrorKind::AddrInUse);
+    }
+
+    #[test]
+    fn invalid_messages_are_read_failures_with_invalid_data() {
+        let error = ServerError::invalid_message(ProtocolError::InvalidUtf8);
+
+        assert!(matches!(&error, ServerError::ReadFailed(e) if e.kind() == io::ErrorKind::InvalidData));
+        assert_eq!(error.source().unwrap().to_string(), "invalid utf-8");
+        assert!(ServerError::PoisonError.source().is_none());
+    }
+
+    #[test]
+    fn with_timeout_converts_only_timed_out_io_errors() {
+        let after = Duration::from_secs(5);
+        let read = ServerError::ReadFailed(io::Error::from(io::ErrorKind::WouldBlock)).with_timeout(Some(after));
+        let write = ServerError::WriteFailed(io::Error::from(io::ErrorKind::TimedOut)).with_timeout(Some(after));
+
+        assert!(matches!(read, ServerError::Timeout { operation: "read", after: a } if a == after));
+        assert_eq!(read.to_string(), "ERROR \"timed out after 5s waiting for read\"");
+        assert!(matches!(write, ServerError::Timeout { operation: "write", .. }));
+        let reset = ServerError::ReadFailed(io::Error::from(io::ErrorKind::ConnectionReset)).with_timeout(Some(after));
+        assert!(matches!(reset, ServerError::ReadFailed(_)));
+        let unconfigured = ServerError::ReadFailed(io::Error::from(io::ErrorKind::WouldBlock)).with_timeout(None);
+        assert!(matches!(unconfigured, ServerError::ReadFailed(_)));
+    }
+
+    #[test]
+    fn too_many_connections_shows_limit_and_current() {
+        let error = ServerError::TooManyConnections { limit: 2, current: 2 };
+
+        assert_eq!(error.message(), "max connections reached");
+        assert_eq!(error.to_string(), "ERROR \"max connections reached (2 open, limit 2)\"");
+    }
+}
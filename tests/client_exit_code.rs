@@ -0,0 +1,124 @@
+//! Verifica el código de salida del binario del cliente contra un servidor falso.
+
+mod mock_server;
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+    thread,
+    time::Duration,
+};
+
+use mock_server::MockServer;
+
+/// Corre el cliente sobre `contents` contra un servidor falso que responde `ERROR` a las
+/// divisiones por cero. Devuelve la salida del cliente y los mensajes que recibió el servidor.
+fn run_client(name: &str, contents: &str, flags: &[&str]) -> (Output, Vec<String>) {
+    let path: PathBuf = std::env::temp_dir().join(format!("client_exit_code_{}_{}.calc", name, std::process::id()));
+    std::fs::write(&path, contents).unwrap();
+    let server = MockServer::new();
+    server.respond_to("OP / 0", "ERROR \"division by zero\"");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_client"))
+        .arg(server.addr().to_string())
+        .arg(&path)
+        .args(flags)
+        .output()
+        .unwrap();
+
+    std::fs::remove_file(&path).unwrap();
+    (output, server.received())
+}
+
+#[test]
+fn exits_with_zero_without_errors() {
+    let (output, _) = run_client("ok", "+ 1\n", &[]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "42\n");
+}
+
+#[test]
+fn exits_with_one_after_processing_everything_when_a_response_is_an_error() {
+    let (output, received) = run_client("errors", "/ 0\n+ 1\n", &[]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(received, vec!["HELLO 1 1f", "OP / 0", "OP + 1", "GET"]);
+}
+
+#[test]
+fn strict_exits_with_one_on_first_error() {
+    let (output, received) = run_client("strict", "/ 0\n+ 1\n", &["--strict"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(received, vec!["HELLO 1 1f", "OP / 0"]);
+}
+
+#[test]
+fn dry_run_reports_errors_without_connecting() {
+    let path = std::env::temp_dir().join(format!("client_dry_run_{}.calc", std::process::id()));
+    std::fs::write(&path, "+ 1\n/ 0\n").unwrap();
+    // Nadie escucha en esta dirección: si el cliente intentara conectarse fallaría.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    drop(listener);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_client"))
+        .arg(addr.to_string())
+        .arg(&path)
+        .arg("--dry-run")
+        .output()
+        .unwrap();
+
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "valid operations: 1, errors: 1\nline 2: \"/ 0\": division by zero\n"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn ctrl_c_requests_the_value_and_exits_with_130() {
+    let path = std::env::temp_dir().join(format!("client_ctrl_c_{}.calc", std::process::id()));
+    std::fs::write(&path, "+ 1\n+ 2\n+ 3\n").unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let client = Command::new(env!("CARGO_BIN_EXE_client"))
+        .arg(addr.to_string())
+        .arg(&path)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let (stream, _) = listener.accept().unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut writer = stream;
+    writeln!(writer, "HELLO 1 0").unwrap();
+    let mut received = Vec::new();
+    let mut line = String::new();
+    while reader.read_line(&mut line).unwrap() > 0 {
+        let message = line.trim_end().to_string();
+        line.clear();
+        let response = match message.as_str() {
+            "HELLO 1 0" => "SESSION_ID 3fa2",
+            "OP + 1" => {
+                // El cliente queda esperando la respuesta: se lo interrumpe antes de contestar.
+                let status = Command::new("kill").arg("-INT").arg(client.id().to_string()).status().unwrap();
+                assert!(status.success());
+                thread::sleep(Duration::from_millis(200));
+                "OK"
+            }
+            "GET" => "VALUE 1",
+            _ => "OK",
+        };
+        received.push(message);
+        writeln!(writer, "{}", response).unwrap();
+    }
+    let output = client.wait_with_output().unwrap();
+
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(output.status.code(), Some(130));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+    assert_eq!(received, vec!["HELLO 1 0", "OP + 1", "GET"]);
+}
@@ -0,0 +1,99 @@
+//! Verifica lo que el binario del cliente envía y muestra usando el servidor falso.
+
+mod mock_server;
+
+use std::process::{Command, Output};
+
+use mock_server::MockServer;
+
+fn run_client(server: &MockServer, name: &str, contents: &str, flags: &[&str]) -> Output {
+    let path = std::env::temp_dir().join(format!("client_mock_server_{}_{}.calc", name, std::process::id()));
+    std::fs::write(&path, contents).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_client"))
+        .arg(server.addr().to_string())
+        .arg(&path)
+        .args(flags)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    output
+}
+
+#[test]
+fn client_sends_every_operation_in_order() {
+    let server = MockServer::new();
+    server.expect_operation("+ 1").expect_operation("* 3").expect_operation("- 2");
+
+    let output = run_client(&server, "order", "+ 1\n* 3\n- 2\n", &[]);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "42\n");
+    server.verify();
+}
+
+#[test]
+fn pipelined_client_sends_the_same_operations() {
+    let server = MockServer::new();
+    for op in ["+ 1", "+ 2", "+ 3", "+ 4", "+ 5"] {
+        server.expect_operation(op);
+    }
+
+    let output = run_client(&server, "pipeline", "+ 1\n+ 2\n+ 3\n+ 4\n+ 5\n", &["--pipeline", "3"]);
+
+    assert_eq!(output.status.code(), Some(0));
+    server.verify();
+    assert_eq!(server.received().last().map(String::as_str), Some("GET"));
+}
+
+#[test]
+fn json_format_reports_mocked_value() {
+    let server = MockServer::new();
+    server.respond_to("GET", "VALUE -7");
+
+    let output = run_client(&server, "json", "+ 1\n- 8\n", &["--format", "json"]);
+
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "{\"value\": -7, \"operations_sent\": 2}\n"
+    );
+}
+
+#[test]
+fn output_flag_writes_the_value_to_a_file() {
+    let server = MockServer::new();
+    let path = std::env::temp_dir().join(format!("client_mock_server_output_{}.txt", std::process::id()));
+
+    let output = run_client(&server, "output", "+ 1\n", &["--output", path.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "42");
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn count_reports_sent_operations_and_errors() {
+    let server = MockServer::new();
+    server.respond_to("OP / 0", "ERROR \"division by zero\"");
+
+    let output = run_client(&server, "count", "+ 1\n/ 0\n* 2\n", &["--count"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "42\nSent: 3 operations, 1 errors\n");
+
+    let output = run_client(&server, "count_quiet", "+ 1\n/ 0\n", &["--count", "--quiet"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "Sent: 2 operations, 1 errors\n");
+}
+
+#[test]
+fn parallel_sends_every_operation_without_the_final_get() {
+    let server = MockServer::new();
+
+    let output = run_client(&server, "parallel", "+ 1\n+ 2\n+ 3\n+ 4\n", &["--parallel", "2"]);
+
+    assert_eq!(output.status.code(), Some(0));
+    let received = server.received();
+    assert_eq!(received.iter().filter(|message| *message == "HELLO 1 1f").count(), 2);
+    let mut operations: Vec<&str> = received.iter().filter_map(|message| message.strip_prefix("OP ")).collect();
+    operations.sort();
+    assert_eq!(operations, vec!["+ 1", "+ 2", "+ 3", "+ 4"]);
+    assert!(!received.iter().any(|message| message == "GET"));
+}
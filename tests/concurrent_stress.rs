@@ -0,0 +1,65 @@
+//! Carga concurrente sobre el binario del servidor: muchos clientes sumando a la vez sobre la
+//! misma calculadora. Detecta carreras, deadlocks y locks envenenados.
+//!
+//! Conviene correrlo solo para que el servidor no compita con otros tests:
+//! `RUST_TEST_THREADS=1 cargo test --test concurrent_stress`.
+
+mod server_process;
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpStream},
+    thread,
+};
+
+use server_process::ServerProcess;
+
+const CLIENTS: usize = 100;
+const OPERATIONS_PER_CLIENT: usize = 1000;
+
+/// Se conecta al servidor y lee el `HELLO` con el que abre la conexión.
+fn connect(addr: SocketAddr) -> BufReader<TcpStream> {
+    let mut reader = BufReader::new(TcpStream::connect(addr).unwrap());
+    let mut greeting = String::new();
+    reader.read_line(&mut greeting).unwrap();
+    assert!(greeting.starts_with("HELLO "), "unexpected greeting: {:?}", greeting);
+    reader
+}
+
+/// Envía `OPERATIONS_PER_CLIENT` veces `OP + 1` seguidas de un `GET` y devuelve cuántos `OK` recibió.
+fn add_ones(addr: SocketAddr) -> usize {
+    let mut reader = connect(addr);
+    let mut writer = reader.get_ref().try_clone().unwrap();
+    writer.write_all("OP + 1\n".repeat(OPERATIONS_PER_CLIENT).as_bytes()).unwrap();
+    writer.write_all(b"GET\n").unwrap();
+
+    let mut ok = 0;
+    for _ in 0..OPERATIONS_PER_CLIENT {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        if line == "OK\n" {
+            ok += 1;
+        }
+    }
+    let mut value = String::new();
+    reader.read_line(&mut value).unwrap();
+    assert!(value.starts_with("VALUE "), "unexpected GET response: {:?}", value);
+    ok
+}
+
+#[test]
+fn concurrent_clients_do_not_lose_operations() {
+    let server = ServerProcess::start("concurrent_stress");
+    let addr = server.addr();
+
+    let handles: Vec<_> = (0..CLIENTS).map(|_| thread::spawn(move || add_ones(addr))).collect();
+    for handle in handles {
+        assert_eq!(handle.join().unwrap(), OPERATIONS_PER_CLIENT);
+    }
+
+    let mut reader = connect(addr);
+    reader.get_ref().try_clone().unwrap().write_all(b"GET\n").unwrap();
+    let mut value = String::new();
+    reader.read_line(&mut value).unwrap();
+    assert_eq!(value, format!("VALUE {}\n", CLIENTS * OPERATIONS_PER_CLIENT));
+}
@@ -0,0 +1,38 @@
+//! Prueba de punta a punta: levanta el binario del servidor y le envía operaciones con el
+//! binario del cliente.
+
+mod server_process;
+
+use std::process::{Command, Output};
+
+use server_process::ServerProcess;
+
+/// Corre el cliente contra `server` con un archivo que tiene `contents`.
+fn run_client(server: &ServerProcess, name: &str, contents: &str) -> Output {
+    let path = server.dir().join(format!("{}.calc", name));
+    std::fs::write(&path, contents).unwrap();
+    Command::new(env!("CARGO_BIN_EXE_client"))
+        .arg(server.addr().to_string())
+        .arg(&path)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn client_prints_the_value_computed_by_the_server() {
+    let server = ServerProcess::start("e2e_success");
+    let output = run_client(&server, "ops", "+ 5\n* 3\n- 1\n");
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "14\n");
+}
+
+#[test]
+fn client_exits_with_one_when_the_server_reports_errors() {
+    let server = ServerProcess::start("e2e_errors");
+    let output = run_client(&server, "ops", "+ 10\n/ 0\n- 4\n");
+
+    assert_eq!(output.status.code(), Some(1));
+    // La división rechazada no cambia la acumulación y el resto de las operaciones se aplica igual.
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "6\n");
+}
@@ -0,0 +1,107 @@
+//! Servidor falso para probar el binario del cliente sobre una conexión TCP real.
+//!
+//! Abre cada conexión con un `HELLO` que ofrece todas las capacidades y responde el `HELLO` del
+//! cliente con un `SESSION_ID`, `OK` a cada `OP` y `VALUE 42` a `GET`.
+//! Las respuestas se pueden reemplazar por mensaje con [`MockServer::respond_to`], y las
+//! operaciones que el cliente debe enviar se programan con [`MockServer::expect_operation`].
+// Cada archivo de tests compila su propia copia y no todos usan todos los métodos.
+#![allow(dead_code)]
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+#[derive(Default)]
+struct MockState {
+    responses: HashMap<String, String>,
+    expected: Vec<String>,
+    received: Vec<String>,
+}
+
+/// Servidor que atiende, una detrás de otra, las conexiones que recibe en un puerto libre.
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockServer {
+    /// Abre un puerto libre en loopback y empieza a atender conexiones en un hilo propio.
+    pub fn new() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let state = Arc::new(Mutex::new(MockState::default()));
+        let thread_state = Arc::clone(&state);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                serve(stream, &thread_state);
+            }
+        });
+        Self { addr, state }
+    }
+
+    /// Dirección en la que escucha.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Reemplaza la respuesta a un mensaje exacto, por ejemplo `"OP / 0"`.
+    pub fn respond_to(&self, message: &str, response: &str) -> &Self {
+        let mut state = self.state.lock().unwrap();
+        state.responses.insert(message.to_string(), response.to_string());
+        self
+    }
+
+    /// Agrega una operación a la secuencia que el cliente debe enviar, sin el `OP` inicial.
+    pub fn expect_operation(&self, op: &str) -> &Self {
+        self.state.lock().unwrap().expected.push(op.to_string());
+        self
+    }
+
+    /// Mensajes recibidos hasta ahora, en orden.
+    pub fn received(&self) -> Vec<String> {
+        self.state.lock().unwrap().received.clone()
+    }
+
+    /// Verifica que las operaciones recibidas sean exactamente las esperadas, en orden.
+    pub fn verify(&self) {
+        let state = self.state.lock().unwrap();
+        let operations: Vec<&str> = state
+            .received
+            .iter()
+            .filter_map(|message| message.strip_prefix("OP "))
+            .collect();
+        assert_eq!(operations, state.expected, "unexpected operations");
+    }
+}
+
+/// Atiende una conexión hasta que el cliente la cierra.
+fn serve(stream: TcpStream, state: &Mutex<MockState>) {
+    let mut writer = stream.try_clone().unwrap();
+    if writer.write_all(b"HELLO 1 1f\n").is_err() {
+        return;
+    }
+    for line in BufReader::new(stream).lines() {
+        let Ok(message) = line else { break };
+        let response = {
+            let mut state = state.lock().unwrap();
+            state.received.push(message.clone());
+            state.responses.get(&message).cloned().unwrap_or_else(|| default_response(&message))
+        };
+        if writer.write_all(format!("{}\n", response).as_bytes()).is_err() {
+            break;
+        }
+    }
+}
+
+fn default_response(message: &str) -> String {
+    match message.split_whitespace().next() {
+        Some("HELLO") => "SESSION_ID 3fa2".to_string(),
+        Some("OP") => "OK".to_string(),
+        Some("GET") => "VALUE 42".to_string(),
+        _ => format!("ERROR \"unexpected message: {}\"", message),
+    }
+}
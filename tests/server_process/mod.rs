@@ -0,0 +1,67 @@
+//! Proceso real del binario del servidor, escuchando en un puerto libre, para los tests de
+//! punta a punta.
+// Cada archivo de tests compila su propia copia y no todos usan todos los métodos.
+#![allow(dead_code)]
+
+use std::{
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    process::{Child, Command},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Proceso del servidor con sus logs en un directorio temporal. Al descartarlo se mata el
+/// proceso y se borra el directorio.
+pub struct ServerProcess {
+    child: Child,
+    addr: SocketAddr,
+    dir: PathBuf,
+}
+
+impl ServerProcess {
+    /// Arranca el servidor y espera a que acepte conexiones. `name` distingue el directorio
+    /// temporal de cada test.
+    pub fn start(name: &str) -> Self {
+        // El puerto queda libre al soltar el listener y el servidor lo vuelve a tomar.
+        let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let dir = std::env::temp_dir().join(format!("server_process_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let child = Command::new(env!("CARGO_BIN_EXE_server"))
+            .arg(addr.to_string())
+            .env("CALC_LOG_FILE", dir.join("server.log"))
+            .env("CALC_AUDIT_FILE", dir.join("audit.log"))
+            .env("CALC_METRICS_FILE", dir.join("metrics.log"))
+            .spawn()
+            .unwrap();
+        let server = Self { child, addr, dir };
+        server.wait_until_ready();
+        server
+    }
+
+    /// Dirección en la que escucha el servidor.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Directorio temporal del servidor, donde los tests pueden dejar sus archivos de entrada.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn wait_until_ready(&self) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while TcpStream::connect(self.addr).is_err() {
+            assert!(Instant::now() < deadline, "server did not start listening on {}", self.addr);
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+}
+
+impl Drop for ServerProcess {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}